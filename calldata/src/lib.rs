@@ -0,0 +1,124 @@
+//! ABI encoding of calldata to invoke the verify function of `Halo2Verifier` rendered by
+//! `halo2_solidity_verifier`, from proof and instances already serialized into big-endian words.
+//!
+//! It's `no_std` and only depends on `alloc`, so embedded or WASM targets that can't link
+//! `halo2_proofs` can prepare calldata. `halo2_solidity_verifier` encodes calldata by it as well.
+
+#![no_std]
+#![deny(missing_docs)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+/// Function signature of `verifyProof(bytes,uint256[])`.
+pub const FN_SIG_VERIFY_PROOF: [u8; 4] = [0x1e, 0x8e, 0x1e, 0x13];
+
+/// Function signature of `verifyProof(address,bytes,uint256[])`.
+pub const FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS: [u8; 4] = [0xaf, 0x83, 0xa1, 0x8d];
+
+/// Function signature of `verifyProof(bytes,bytes32)`, which is rendered with
+/// `SolidityGeneratorBuilder::hashed_instances`.
+pub const FN_SIG_VERIFY_PROOF_HASHED: [u8; 4] = [0x18, 0x58, 0xcb, 0x5b];
+
+/// Function signature of `verifyProof(address,bytes,bytes32)`, which is rendered with
+/// `SolidityGeneratorBuilder::hashed_instances`.
+pub const FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS: [u8; 4] = [0x9e, 0xf8, 0x43, 0x15];
+
+/// Encode proof into calldata to invoke `Halo2Verifier.verifyProof`, same as `encode_calldata`
+/// but with instances already serialized into big-endian words, e.g. by [`fr_repr_to_word`].
+///
+/// For `vk_address`, pass the address of the deployed verifying key contract, or `None` if the
+/// verifying key is embedded in the verifier. For `instances`, pass them flattened column by
+/// column.
+pub fn encode_calldata_words(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances: &[[u8; 32]],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF
+    };
+    encode_calldata_words_with_fn_sig(fn_sig, vk_address, proof, instances)
+}
+
+/// Encode proof into calldata to invoke `Halo2Verifier.verifyProof` rendered with
+/// `SolidityGeneratorBuilder::hashed_instances`, same as `encode_calldata_hashed`.
+pub fn encode_calldata_hashed_words(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances_hash: [u8; 32],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF_HASHED
+    };
+    encode_calldata_hashed_words_with_fn_sig(fn_sig, vk_address, proof, instances_hash)
+}
+
+/// Convert little-endian representation of a scalar, e.g. `PrimeField::to_repr` of
+/// `bn256::Fr`, into the big-endian word it's encoded as in calldata.
+pub fn fr_repr_to_word(mut repr: [u8; 32]) -> [u8; 32] {
+    repr.reverse();
+    repr
+}
+
+/// Encode proof into calldata same as [`encode_calldata_words`] but with given function
+/// signature, e.g. of verify function renamed by `SolidityGeneratorBuilder::fn_name`.
+pub fn encode_calldata_words_with_fn_sig(
+    fn_sig: [u8; 4],
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances: &[[u8; 32]],
+) -> Vec<u8> {
+    let offset = if vk_address.is_some() { 0x60 } else { 0x40 };
+    let len = 4 + offset + 0x40 + proof.len() + instances.len() * 0x20;
+    let mut calldata = Vec::with_capacity(len);
+    calldata.extend_from_slice(&fn_sig); // function signature
+    if let Some(vk_address) = vk_address {
+        calldata.extend_from_slice(&address_to_word(vk_address)); // verifying key address
+    }
+    calldata.extend_from_slice(&usize_to_word(offset)); // offset of proof
+    calldata.extend_from_slice(&usize_to_word(offset + 0x20 + proof.len())); // offset of instances
+    calldata.extend_from_slice(&usize_to_word(proof.len())); // length of proof
+    calldata.extend_from_slice(proof); // proof
+    calldata.extend_from_slice(&usize_to_word(instances.len())); // length of instances
+    calldata.extend(instances.iter().flatten()); // instances
+    calldata
+}
+
+/// Encode proof into calldata same as [`encode_calldata_hashed_words`] but with given function
+/// signature.
+pub fn encode_calldata_hashed_words_with_fn_sig(
+    fn_sig: [u8; 4],
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances_hash: [u8; 32],
+) -> Vec<u8> {
+    let offset = if vk_address.is_some() { 0x60 } else { 0x40 };
+    let mut calldata = Vec::with_capacity(4 + offset + 0x20 + proof.len());
+    calldata.extend_from_slice(&fn_sig); // function signature
+    if let Some(vk_address) = vk_address {
+        calldata.extend_from_slice(&address_to_word(vk_address)); // verifying key address
+    }
+    calldata.extend_from_slice(&usize_to_word(offset)); // offset of proof
+    calldata.extend_from_slice(&instances_hash); // instances hash
+    calldata.extend_from_slice(&usize_to_word(proof.len())); // length of proof
+    calldata.extend_from_slice(proof); // proof
+    calldata
+}
+
+fn usize_to_word(value: usize) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[24..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+fn address_to_word(address: [u8; 20]) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[12..].copy_from_slice(&address);
+    word
+}
@@ -14,7 +14,11 @@ fn main() {
     let params = setup(K_RANGE, &mut rng);
 
     let vk = keygen_vk(&params[&K_RANGE.start], &StandardPlonk::default()).unwrap();
-    let generator = SolidityGenerator::new(&params[&K_RANGE.start], &vk, Bdfg21, 0);
+    let generator = SolidityGenerator::builder(&params[&K_RANGE.start], &vk)
+        .scheme(Bdfg21)
+        .num_instances(&[0])
+        .build()
+        .unwrap();
     let (verifier_solidity, _) = generator.render_separately().unwrap();
     save_solidity("Halo2Verifier.sol", &verifier_solidity);
 
@@ -33,7 +37,11 @@ fn main() {
 
         let vk = keygen_vk(&params[&k], &circuit).unwrap();
         let pk = keygen_pk(&params[&k], vk, &circuit).unwrap();
-        let generator = SolidityGenerator::new(&params[&k], pk.get_vk(), Bdfg21, num_instances);
+        let generator = SolidityGenerator::builder(&params[&k], pk.get_vk())
+            .scheme(Bdfg21)
+            .num_instances(&[num_instances])
+            .build()
+            .unwrap();
         let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
         save_solidity(format!("Halo2VerifyingKey-{k}.sol"), &vk_solidity);
 
@@ -45,7 +53,7 @@ fn main() {
         let calldata = {
             let instances = circuit.instances();
             let proof = create_proof_checked(&params[&k], &pk, circuit, &instances, &mut rng);
-            encode_calldata(Some(vk_address.into()), &proof, &instances)
+            encode_calldata(Some(vk_address.into()), &proof, &[instances])
         };
         let (gas_cost, output) = evm.call(verifier_address, calldata);
         assert_eq!(output, [vec![0; 31], vec![1]].concat());
@@ -114,116 +122,13 @@ fn create_proof_checked(
     proof
 }
 
-mod application {
-    use crate::prelude::*;
-
-    #[derive(Clone)]
-    pub struct StandardPlonkConfig {
-        selectors: [Column<Fixed>; 5],
-        wires: [Column<Advice>; 3],
-    }
-
-    impl StandardPlonkConfig {
-        fn configure(meta: &mut ConstraintSystem<impl PrimeField>) -> Self {
-            let [w_l, w_r, w_o] = [(); 3].map(|_| meta.advice_column());
-            let [q_l, q_r, q_o, q_m, q_c] = [(); 5].map(|_| meta.fixed_column());
-            let pi = meta.instance_column();
-            [w_l, w_r, w_o].map(|column| meta.enable_equality(column));
-            meta.create_gate(
-                "q_l·w_l + q_r·w_r + q_o·w_o + q_m·w_l·w_r + q_c + pi = 0",
-                |meta| {
-                    let [w_l, w_r, w_o] =
-                        [w_l, w_r, w_o].map(|column| meta.query_advice(column, Rotation::cur()));
-                    let [q_l, q_r, q_o, q_m, q_c] = [q_l, q_r, q_o, q_m, q_c]
-                        .map(|column| meta.query_fixed(column, Rotation::cur()));
-                    let pi = meta.query_instance(pi, Rotation::cur());
-                    Some(
-                        q_l * w_l.clone()
-                            + q_r * w_r.clone()
-                            + q_o * w_o
-                            + q_m * w_l * w_r
-                            + q_c
-                            + pi,
-                    )
-                },
-            );
-            StandardPlonkConfig {
-                selectors: [q_l, q_r, q_o, q_m, q_c],
-                wires: [w_l, w_r, w_o],
-            }
-        }
-    }
-
-    #[derive(Clone, Debug, Default)]
-    pub struct StandardPlonk<F>(Vec<F>);
-
-    impl<F: PrimeField> StandardPlonk<F> {
-        pub fn rand<R: RngCore>(num_instances: usize, mut rng: R) -> Self {
-            Self((0..num_instances).map(|_| F::random(&mut rng)).collect())
-        }
-
-        pub fn instances(&self) -> Vec<F> {
-            self.0.clone()
-        }
-    }
-
-    impl<F: PrimeField> Circuit<F> for StandardPlonk<F> {
-        type Config = StandardPlonkConfig;
-        type FloorPlanner = SimpleFloorPlanner;
-
-        fn without_witnesses(&self) -> Self {
-            unimplemented!()
-        }
-
-        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-            meta.set_minimum_degree(5);
-            StandardPlonkConfig::configure(meta)
-        }
-
-        fn synthesize(
-            &self,
-            config: Self::Config,
-            mut layouter: impl Layouter<F>,
-        ) -> Result<(), Error> {
-            let [q_l, q_r, q_o, q_m, q_c] = config.selectors;
-            let [w_l, w_r, w_o] = config.wires;
-            layouter.assign_region(
-                || "",
-                |mut region| {
-                    for (offset, instance) in self.0.iter().enumerate() {
-                        region.assign_advice(|| "", w_l, offset, || Value::known(*instance))?;
-                        region.assign_fixed(|| "", q_l, offset, || Value::known(-F::ONE))?;
-                    }
-                    let offset = self.0.len();
-                    let a = region.assign_advice(|| "", w_l, offset, || Value::known(F::ONE))?;
-                    a.copy_advice(|| "", &mut region, w_r, offset)?;
-                    a.copy_advice(|| "", &mut region, w_o, offset)?;
-                    let offset = offset + 1;
-                    region.assign_advice(|| "", w_l, offset, || Value::known(-F::from(5)))?;
-                    for (column, idx) in [q_l, q_r, q_o, q_m, q_c].iter().zip(1..) {
-                        region.assign_fixed(
-                            || "",
-                            *column,
-                            offset,
-                            || Value::known(F::from(idx)),
-                        )?;
-                    }
-                    Ok(())
-                },
-            )
-        }
-    }
-}
+mod application;
 
 mod prelude {
     pub use halo2_proofs::{
-        circuit::{Layouter, SimpleFloorPlanner, Value},
-        halo2curves::{
-            bn256::{Bn256, Fr, G1Affine},
-            ff::PrimeField,
-        },
+        halo2curves::bn256::{Bn256, Fr, G1Affine},
         plonk::*,
-        poly::{kzg::commitment::ParamsKZG, Rotation},
+        poly::kzg::commitment::ParamsKZG,
     };
     pub use rand::{
         rngs::{OsRng, StdRng},
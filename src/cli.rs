@@ -0,0 +1,98 @@
+//! Command line entry point [`main`], which renders the verifier of a circuit from its serialized
+//! verifying key, so shell scripts can generate verifiers without Rust glue code.
+//!
+//! `halo2_proofs` recovers the `ConstraintSystem` of a serialized verifying key from the shape of
+//! the circuit, so the crate doesn't ship a binary. Declare a one-line `main` in the crate of the
+//! circuit instead, e.g. as `src/bin/gen-verifier.rs`:
+//!
+//! ```ignore
+//! fn main() {
+//!     halo2_solidity_verifier::cli::main::<MyCircuit>()
+//! }
+//! ```
+
+use crate::{BatchOpenScheme, SolidityGenerator, VerifierParams};
+use clap::Parser;
+use halo2_proofs::{halo2curves::bn256, plonk::Circuit, SerdeFormat};
+use serde::Serialize;
+use std::{error::Error, fs, path::PathBuf, process};
+
+/// Arguments of [`main`].
+#[derive(Clone, Debug, Parser)]
+#[command(about = "Generate Solidity verifier of halo2 circuit")]
+pub struct Args {
+    /// Path of verifier params written by `VerifierParams::write`.
+    #[arg(long)]
+    pub params: PathBuf,
+    /// Path of verifying key written by `VerifyingKey::write` in `SerdeFormat::RawBytes`.
+    #[arg(long)]
+    pub vk: PathBuf,
+    /// Batch open scheme the proof is created with.
+    #[arg(long, value_enum, default_value = "bdfg21")]
+    pub scheme: BatchOpenScheme,
+    /// Number of instances of each instance column, separated by comma.
+    #[arg(long, value_delimiter = ',')]
+    pub num_instances: Vec<usize>,
+    /// Print verifier, verifying key and gas estimate as JSON, instead of the verifier with
+    /// verifying key embedded.
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Output of [`main`] with `--json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonOutput {
+    /// Verifier rendered separately from verifying key.
+    pub verifier: String,
+    /// Verifying key contract.
+    pub vk: String,
+    /// Gas cost of `verifyProof` estimated by `SolidityGenerator::estimate_gas_statically`.
+    pub gas_estimate: u64,
+}
+
+/// Render verifier as `args` specify, and return what [`main`] prints.
+pub fn run<ConcreteCircuit: Circuit<bn256::Fr>>(
+    args: &Args,
+    #[cfg(feature = "circuit-params")] circuit_params: ConcreteCircuit::Params,
+) -> Result<String, Box<dyn Error>> {
+    let params = VerifierParams::read(&mut fs::File::open(&args.params)?)?;
+    let vk_bytes = fs::read(&args.vk)?;
+    let generator = SolidityGenerator::from_vk_bytes::<ConcreteCircuit>(
+        params,
+        &vk_bytes,
+        SerdeFormat::RawBytes,
+        #[cfg(feature = "circuit-params")]
+        circuit_params,
+        args.scheme,
+        &args.num_instances,
+    )?;
+    if !args.json {
+        return Ok(generator.render()?);
+    }
+
+    let (verifier, vk) = generator.render_separately()?;
+    let output = JsonOutput {
+        verifier,
+        vk,
+        gas_estimate: generator.estimate_gas_statically(),
+    };
+    Ok(serde_json::to_string_pretty(&output)?)
+}
+
+/// Parse [`Args`] from command line and print what [`run`] returns, or exit with the error.
+///
+/// With feature `circuit-params`, the default params of `ConcreteCircuit` are used.
+pub fn main<ConcreteCircuit: Circuit<bn256::Fr>>() {
+    let args = Args::parse();
+    match run::<ConcreteCircuit>(
+        &args,
+        #[cfg(feature = "circuit-params")]
+        Default::default(),
+    ) {
+        Ok(output) => println!("{output}"),
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+}
@@ -1,18 +1,48 @@
-use crate::codegen::{
-    evaluator::Evaluator,
-    template::{Halo2Verifier, Halo2VerifyingKey},
-    util::{fr_to_u256, g1_to_u256s, g2_to_u256s, ConstraintSystemMeta, Data, Ptr},
+use crate::{
+    codegen::{
+        evaluator::{column_eval_var, Evaluator},
+        template::{
+            render, FoundryTest, Halo2PairingAggregator, Halo2Verifier, Halo2VerifierRegistry,
+            Halo2VerifierYul, Halo2VerifyingKey, HardhatConfig, HardhatDeployScript,
+            IHalo2Verifier,
+        },
+        util::{
+            for_loop, fr_to_u256, g1_to_u256s, g2_to_u256s, load_instance, ConstraintSystemMeta,
+            Data, Ptr,
+        },
+    },
+    evm::{
+        encode_calldata_hashed_with_fn_sig, encode_calldata_nested_with_fn_sig,
+        encode_calldata_packed_with_fn_sig, encode_calldata_v2_with_byte_order,
+        encode_calldata_with_fn_sig, hash_from_instances, pack_instances, InstanceByteOrder,
+        InstanceEncoder,
+    },
 };
 use halo2_proofs::{
-    halo2curves::{bn256, ff::Field},
-    plonk::VerifyingKey,
-    poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG, Rotation},
+    halo2curves::{
+        bn256,
+        ff::{Field, PrimeField},
+        group::GroupEncoding,
+        CurveAffine,
+    },
+    plonk::{Circuit, ConstraintSystem, VerifyingKey},
+    poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+    SerdeFormat,
 };
-use itertools::{chain, Itertools};
+use itertools::{chain, izip, Itertools};
 use ruint::aliases::U256;
-use std::fmt::{self, Debug};
+use sha3::Digest;
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    io, iter, slice,
+};
 
-mod evaluator;
+pub(crate) mod evaluator;
+#[cfg(feature = "serde")]
+mod json;
 mod pcs;
 mod template;
 pub(crate) mod util;
@@ -22,14 +52,63 @@ pub use pcs::BatchOpenScheme;
 /// Solidity verifier generator for [`halo2`] proof with KZG polynomial commitment scheme on BN254.
 #[derive(Debug)]
 pub struct SolidityGenerator<'a> {
-    params: &'a ParamsKZG<bn256::Bn256>,
-    vk: &'a VerifyingKey<bn256::G1Affine>,
-    scheme: BatchOpenScheme,
-    num_instances: usize,
-    acc_encoding: Option<AccumulatorEncoding>,
+    params: VerifierParams,
+    vk: Cow<'a, VerifyingKey<bn256::G1Affine>>,
+    options: Options,
+    cse: bool,
     meta: ConstraintSystemMeta,
 }
 
+/// Points of KZG parameters needed by verifier, which are `g1`, `g2` and `s_g2`.
+///
+/// It can be taken from `ParamsKZG` once and stored separately, so rendering doesn't require
+/// the full SRS to be loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifierParams {
+    /// Generator of G1.
+    pub g1: bn256::G1Affine,
+    /// Generator of G2.
+    pub g2: bn256::G2Affine,
+    /// Generator of G2 multiplied by secret `s`.
+    pub s_g2: bn256::G2Affine,
+}
+
+impl VerifierParams {
+    /// Return a new `VerifierParams`.
+    pub fn new(g1: bn256::G1Affine, g2: bn256::G2Affine, s_g2: bn256::G2Affine) -> Self {
+        Self { g1, g2, s_g2 }
+    }
+
+    /// Write points in compressed form.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_all(self.g1.to_bytes().as_ref())?;
+        writer.write_all(self.g2.to_bytes().as_ref())?;
+        writer.write_all(self.s_g2.to_bytes().as_ref())
+    }
+
+    /// Read points in compressed form written by [`VerifierParams::write`].
+    pub fn read(reader: &mut impl io::Read) -> io::Result<Self> {
+        fn read_point<C: GroupEncoding>(reader: &mut impl io::Read) -> io::Result<C> {
+            let mut repr = C::Repr::default();
+            reader.read_exact(repr.as_mut())?;
+            Option::from(C::from_bytes(&repr))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid point"))
+        }
+
+        Ok(Self {
+            g1: read_point(reader)?,
+            g2: read_point(reader)?,
+            s_g2: read_point(reader)?,
+        })
+    }
+}
+
+impl From<&ParamsKZG<bn256::Bn256>> for VerifierParams {
+    fn from(params: &ParamsKZG<bn256::Bn256>) -> Self {
+        Self::new(params.get_g()[0], params.g2(), params.s_g2())
+    }
+}
+
 /// KZG accumulator encoding information.
 /// Limbs of each field element are assumed to be least significant limb first.
 ///
@@ -64,6 +143,7 @@ pub struct SolidityGenerator<'a> {
 /// In the end of `verifyProof`, the accumulator will be used to do batched pairing with the
 /// pairing input of incoming proof.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccumulatorEncoding {
     /// Offset of accumulator limbs in instances.
     pub offset: usize,
@@ -73,59 +153,1895 @@ pub struct AccumulatorEncoding {
     pub num_limb_bits: usize,
 }
 
-impl AccumulatorEncoding {
-    /// Return a new `AccumulatorEncoding`.
-    pub fn new(offset: usize, num_limbs: usize, num_limb_bits: usize) -> Self {
-        Self {
-            offset,
-            num_limbs,
-            num_limb_bits,
-        }
+impl AccumulatorEncoding {
+    /// Return a new `AccumulatorEncoding`.
+    pub fn new(offset: usize, num_limbs: usize, num_limb_bits: usize) -> Self {
+        Self {
+            offset,
+            num_limbs,
+            num_limb_bits,
+        }
+    }
+}
+
+/// Behavior of `verifyProof` when any check fails, e.g. invalid EC point in proof, mismatched
+/// number of instances, or failed pairing check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OnFailure {
+    /// Revert without any return data, which is the default.
+    Revert,
+    /// Return `false`, so callers can try several proofs without `try`/`catch`.
+    ReturnFalse,
+    /// Revert with custom error `ProofInvalid(uint256 stage)`, where `stage` is the
+    /// [`FailureStage`] whose check fails. Custom errors require `solc` 0.8.4 or later.
+    CustomError,
+}
+
+/// Stage of `verifyProof` reported by `ProofInvalid(uint256 stage)` with
+/// [`OnFailure::CustomError`].
+///
+/// Note that a proof of unsatisfied gates or lookups is only detected by the final pairing check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureStage {
+    /// Length of proof or instances is wrong, or any instance is not in scalar field.
+    Instances = 0,
+    /// Any EC point or evaluation in proof, or accumulator in instances, is invalid.
+    Transcript = 1,
+    /// Lagrange evaluations for quotient evaluation can't be computed.
+    Quotient = 2,
+    /// Any EC operation of batch opening fails.
+    Pcs = 3,
+    /// Final pairing check fails.
+    Pairing = 4,
+}
+
+impl FailureStage {
+    /// Return `FailureStage` of the `stage` in `ProofInvalid(uint256 stage)`.
+    pub fn from_stage(stage: u64) -> Option<Self> {
+        [
+            Self::Instances,
+            Self::Transcript,
+            Self::Quotient,
+            Self::Pcs,
+            Self::Pairing,
+        ]
+        .into_iter()
+        .find(|failure_stage| *failure_stage as u64 == stage)
+    }
+}
+
+/// Where the verifier rendered by `render_separately` reads verifying key address from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VkAddressSource {
+    /// Read from the first parameter of the verify function.
+    Calldata,
+    /// Read from an immutable set by constructor argument.
+    Immutable,
+    /// Read from storage set by constructor argument, which can be updated by owner with
+    /// `setVkAddress(address)` for circuit upgrades.
+    Ownable,
+}
+
+/// Layout of proof and calldata expected by rendered verifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifierMeta {
+    /// Length of proof in bytes.
+    pub proof_len: usize,
+    /// Number of evaluations in proof.
+    pub num_evals: usize,
+    /// Number of EC points in proof, including the ones of batch opening proof.
+    pub num_commitments: usize,
+    /// Length of calldata in bytes, which is the length of [`encode_calldata`] output.
+    ///
+    /// [`encode_calldata`]: crate::encode_calldata
+    pub calldata_len: usize,
+    /// Total number of instances of all instance columns.
+    pub num_instances: usize,
+}
+
+/// Metrics of circuit extracted from verifying key alone, without building `SolidityGenerator`,
+/// e.g. for dashboards or CI gates tracking circuit complexity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitMetrics {
+    /// Number of advice columns.
+    pub num_advice_columns: usize,
+    /// Number of fixed columns, including the ones converted from selectors.
+    pub num_fixed_columns: usize,
+    /// Number of instance columns.
+    pub num_instance_columns: usize,
+    /// Number of lookup arguments.
+    pub num_lookups: usize,
+    /// Number of shuffle arguments.
+    pub num_shuffles: usize,
+    /// Number of chunks the permutation argument is split into, each with its own grand product.
+    pub num_permutation_chunks: usize,
+    /// Degree of constraint system.
+    pub degree: usize,
+    /// Length of proof in bytes with `BatchOpenScheme::Bdfg21` (SHPLONK).
+    pub proof_size_bytes: usize,
+}
+
+impl CircuitMetrics {
+    /// Extract `CircuitMetrics` from verifying key.
+    pub fn from_vk(vk: &VerifyingKey<bn256::G1Affine>) -> Self {
+        let cs = vk.cs();
+        let meta = ConstraintSystemMeta::new(cs);
+        Self {
+            num_advice_columns: cs.num_advice_columns(),
+            num_fixed_columns: cs.num_fixed_columns(),
+            num_instance_columns: cs.num_instance_columns(),
+            num_lookups: cs.lookups().len(),
+            num_shuffles: cs.shuffles().len(),
+            num_permutation_chunks: meta.num_permutation_zs,
+            degree: cs.degree(),
+            proof_size_bytes: meta.proof_len(BatchOpenScheme::Bdfg21),
+        }
+    }
+}
+
+/// Error returned when `SolidityGenerator` can't be built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GeneratorError {
+    /// Circuit uses a feature which can't be encoded into verifier yet, with the name of the
+    /// feature and details about where it's used.
+    Unsupported(&'static str, String),
+    /// Number of instance columns in circuit mismatches the given one.
+    InstanceColumnsMismatch {
+        /// Number of instance columns in circuit.
+        expected: usize,
+        /// Number of instance columns given.
+        actual: usize,
+    },
+    /// Verifying key can't be deserialized.
+    InvalidVerifyingKey(String),
+    /// Serialized `SolidityGenerator` can't be deserialized from JSON.
+    InvalidJson(String),
+    /// Option set by [`SolidityGeneratorBuilder`] is malformed or can't be used together with
+    /// other options, with the name of its setter and the reason.
+    InvalidOption {
+        /// Name of the setter of the option.
+        option: &'static str,
+        /// Why the option is rejected.
+        reason: String,
+    },
+    /// Accumulator limbs are out of range of instances.
+    AccumulatorOutOfRange {
+        /// Offset of accumulator limbs in instances.
+        offset: usize,
+        /// Number of accumulator limbs.
+        num_limbs: usize,
+        /// Number of instances.
+        num_instances: usize,
+    },
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(feature, details) => write!(f, "Unsupported {feature}: {details}"),
+            Self::InstanceColumnsMismatch { expected, actual } => write!(
+                f,
+                "Number of instance columns mismatches, expected {expected} but got {actual}"
+            ),
+            Self::InvalidVerifyingKey(err) => write!(f, "Invalid verifying key: {err}"),
+            Self::InvalidJson(err) => write!(f, "Invalid JSON: {err}"),
+            Self::InvalidOption { option, reason } => {
+                write!(f, "Invalid option `{option}`: {reason}")
+            }
+            Self::AccumulatorOutOfRange {
+                offset,
+                num_limbs,
+                num_instances,
+            } => write!(
+                f,
+                "Accumulator limbs {offset}..{} are out of range of {num_instances} instances",
+                offset + num_limbs
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+/// Error returned by [`SolidityGenerator::check_bytecode_size`] when runtime bytecode of the
+/// verifier exceeds the limit of EIP-170.
+#[cfg(feature = "evm")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BytecodeTooLargeError {
+    /// Size of runtime bytecode in bytes.
+    pub size: usize,
+}
+
+#[cfg(feature = "evm")]
+impl BytecodeTooLargeError {
+    /// Maximum size of runtime bytecode in bytes set by EIP-170.
+    pub const LIMIT: usize = 24576;
+}
+
+#[cfg(feature = "evm")]
+impl fmt::Display for BytecodeTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Verifier runtime bytecode is {} bytes, exceeding EIP-170 limit of {} bytes, \
+            consider `render_separately` to move verifying key out of verifier",
+            self.size,
+            Self::LIMIT
+        )
+    }
+}
+
+#[cfg(feature = "evm")]
+impl std::error::Error for BytecodeTooLargeError {}
+
+/// Reason why a verifying key can't be verified by the verifier rendered by `render_separately`,
+/// returned by [`SolidityGenerator::is_compatible_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IncompatibilityReason {
+    /// Number of columns or challenges mismatches, with the kind of them.
+    NumColumns {
+        /// Kind of columns, e.g. `advice` or `challenge`.
+        kind: &'static str,
+        /// Number in verifier.
+        expected: usize,
+        /// Number in verifying key.
+        actual: usize,
+    },
+    /// Phases of advice columns or challenges mismatch.
+    Phases(&'static str),
+    /// Degree of constraint system mismatches, which determines number of quotient chunks and
+    /// permutation chunks.
+    Degree {
+        /// Degree in verifier.
+        expected: usize,
+        /// Degree in verifying key.
+        actual: usize,
+    },
+    /// Number of blinding factors mismatches, which determines rotation of last row.
+    BlindingFactors {
+        /// Number in verifier.
+        expected: usize,
+        /// Number in verifying key.
+        actual: usize,
+    },
+    /// Queried columns or rotations mismatch, with the kind of columns.
+    Queries(&'static str),
+    /// Columns with equality enabled mismatch.
+    PermutationColumns,
+    /// Gate mismatches, with its name.
+    Gate(String),
+    /// Lookup mismatches, with its index.
+    Lookup(usize),
+    /// Shuffle mismatches, with its index.
+    Shuffle(usize),
+    /// Rendered verifier mismatches for other reason.
+    Verifier,
+}
+
+impl fmt::Display for IncompatibilityReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NumColumns {
+                kind,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Number of {kind} columns mismatches, expected {expected} but got {actual}"
+            ),
+            Self::Phases(kind) => write!(f, "Phases of {kind} mismatch"),
+            Self::Degree { expected, actual } => {
+                write!(f, "Degree mismatches, expected {expected} but got {actual}")
+            }
+            Self::BlindingFactors { expected, actual } => write!(
+                f,
+                "Number of blinding factors mismatches, expected {expected} but got {actual}"
+            ),
+            Self::Queries(kind) => write!(f, "Queries of {kind} columns mismatch"),
+            Self::PermutationColumns => write!(f, "Columns with equality enabled mismatch"),
+            Self::Gate(name) => write!(f, "Gate `{name}` mismatches"),
+            Self::Lookup(idx) => write!(f, "Lookup #{idx} mismatches"),
+            Self::Shuffle(idx) => write!(f, "Shuffle #{idx} mismatches"),
+            Self::Verifier => write!(f, "Rendered verifier mismatches"),
+        }
+    }
+}
+
+impl std::error::Error for IncompatibilityReason {}
+
+/// Error returned by [`SolidityGenerator::verify_vk_consistency`] when constants embedded in
+/// rendered `Halo2VerifyingKey.sol` don't match the `VerifyingKey` and `VerifierParams`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InconsistencyError {
+    /// Line of rendered verifying key can't be parsed, or is stored at unexpected offset.
+    Malformed(String),
+    /// Constant is missing in rendered verifying key, with its name.
+    Missing(String),
+    /// Constant can't be deserialized into field element or curve point, with its name.
+    InvalidValue(String),
+    /// Constant mismatches the one derived from `VerifyingKey`, with its name.
+    Mismatch(String),
+    /// Number of commitments mismatches, with the kind of them.
+    NumCommitments {
+        /// Kind of commitments, e.g. `fixed` or `permutation`.
+        kind: &'static str,
+        /// Number in `VerifyingKey`.
+        expected: usize,
+        /// Number in rendered verifying key.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for InconsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(line) => write!(f, "Malformed verifying key line `{line}`"),
+            Self::Missing(name) => write!(f, "Constant `{name}` is missing"),
+            Self::InvalidValue(name) => write!(f, "Constant `{name}` has invalid value"),
+            Self::Mismatch(name) => write!(f, "Constant `{name}` mismatches"),
+            Self::NumCommitments {
+                kind,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Number of {kind} commitments mismatches, expected {expected} but got {actual}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InconsistencyError {}
+
+impl<'a> SolidityGenerator<'a> {
+    /// Return a new `SolidityGenerator`.
+    ///
+    /// `num_instances` is the number of instances of each instance column, in the same order as
+    /// the instance columns are created in the circuit.
+    ///
+    /// # Panics
+    /// Panics if the circuit is not supported, see [`GeneratorError`].
+    pub fn new(
+        params: impl Into<VerifierParams>,
+        vk: &'a VerifyingKey<bn256::G1Affine>,
+        scheme: BatchOpenScheme,
+        num_instances: &[usize],
+    ) -> Self {
+        Self::builder(params, vk)
+            .scheme(scheme)
+            .num_instances(num_instances)
+            .build()
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Return a new `SolidityGenerator` for an aggregation circuit of `snark_verifier`, whose KZG
+    /// accumulator in instances is accumulated into the pairing check of the verifier.
+    ///
+    /// `accumulator_indices` are `(column, row)` of limbs like the ones returned by
+    /// `AggregationCircuit::accumulator_indices`, see
+    /// [`SolidityGeneratorBuilder::accumulator_indices`]. The number of bits of each limb follows
+    /// the ones `snark_verifier` uses, which is 88 for 3 limbs and 68 for 4 limbs.
+    ///
+    /// # Panics
+    /// Panics if the circuit is not supported or `accumulator_indices` is invalid, see
+    /// [`GeneratorError`].
+    pub fn for_aggregation(
+        params: impl Into<VerifierParams>,
+        vk: &'a VerifyingKey<bn256::G1Affine>,
+        scheme: BatchOpenScheme,
+        num_instances: &[usize],
+        accumulator_indices: &[(usize, usize)],
+    ) -> Self {
+        snark_verifier_limb_bits(accumulator_indices)
+            .and_then(|num_limb_bits| {
+                Self::builder(params, vk)
+                    .scheme(scheme)
+                    .num_instances(num_instances)
+                    .accumulator_indices(accumulator_indices, num_limb_bits)
+                    .build()
+            })
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Return a new `SolidityGenerator` with number of instances of each instance column derived
+    /// from `instances`, which could be the ones used to create a proof.
+    ///
+    /// Number of instances is not recorded in `VerifyingKey`, only number of instance columns
+    /// is, so it returns [`GeneratorError::InstanceColumnsMismatch`] if `instances` has different
+    /// number of columns than the circuit.
+    pub fn new_auto(
+        params: impl Into<VerifierParams>,
+        vk: &'a VerifyingKey<bn256::G1Affine>,
+        scheme: BatchOpenScheme,
+        instances: &[Vec<bn256::Fr>],
+    ) -> Result<Self, GeneratorError> {
+        let num_instances = instances.iter().map(Vec::len).collect_vec();
+        Self::builder(params, vk)
+            .scheme(scheme)
+            .num_instances(&num_instances)
+            .build()
+    }
+
+    /// Return a new `SolidityGenerator` with verifying key deserialized from `vk_bytes`, which
+    /// is serialized by `VerifyingKey::to_bytes` in given `format`.
+    ///
+    /// Only the shape of `ConcreteCircuit` is used to recover the `ConstraintSystem`, so the
+    /// circuit itself and the full SRS are not needed.
+    pub fn from_vk_bytes<ConcreteCircuit: Circuit<bn256::Fr>>(
+        params: impl Into<VerifierParams>,
+        vk_bytes: &[u8],
+        format: SerdeFormat,
+        #[cfg(feature = "circuit-params")] circuit_params: ConcreteCircuit::Params,
+        scheme: BatchOpenScheme,
+        num_instances: &[usize],
+    ) -> Result<Self, GeneratorError> {
+        let vk = VerifyingKey::from_bytes::<ConcreteCircuit>(
+            vk_bytes,
+            format,
+            #[cfg(feature = "circuit-params")]
+            circuit_params,
+        )
+        .map_err(|err| GeneratorError::InvalidVerifyingKey(err.to_string()))?;
+        let mut builder = Self::builder_with_vk(params.into(), Cow::Owned(vk));
+        builder.scheme(scheme).num_instances(num_instances);
+        // Take the owned verifying key over instead of cloning it as `build` does
+        builder.into_generator()
+    }
+
+    /// Return a new `SolidityGeneratorBuilder` with [`BatchOpenScheme::Bdfg21`], no instances,
+    /// no `AccumulatorEncoding`, pragma version `^0.8.0`, license `MIT` and contract names
+    /// `Halo2Verifier` and `Halo2VerifyingKey` by default.
+    pub fn builder(
+        params: impl Into<VerifierParams>,
+        vk: &'a VerifyingKey<bn256::G1Affine>,
+    ) -> SolidityGeneratorBuilder<'a> {
+        Self::builder_with_vk(params.into(), Cow::Borrowed(vk))
+    }
+
+    fn builder_with_vk(
+        params: VerifierParams,
+        vk: Cow<'a, VerifyingKey<bn256::G1Affine>>,
+    ) -> SolidityGeneratorBuilder<'a> {
+        SolidityGeneratorBuilder {
+            params,
+            vk,
+            options: Options::default(),
+        }
+    }
+
+    /// Set `AccumulatorEncoding`.
+    ///
+    /// # Panics
+    /// Panics if accumulator limbs are out of range of instances, see [`GeneratorError`].
+    pub fn set_acc_encoding(mut self, acc_encoding: Option<AccumulatorEncoding>) -> Self {
+        self.options.acc_encoding = acc_encoding;
+        self.options.acc_indices = None;
+        if let Err(err) = self.options.validate(&self.meta) {
+            panic!("{err}");
+        }
+        self
+    }
+
+    /// Set whether common subexpressions of gates are computed once into memory, which is only
+    /// turned off to compare against in tests.
+    #[cfg(test)]
+    pub(crate) fn with_cse(mut self, cse: bool) -> Self {
+        self.cse = cse;
+        self
+    }
+
+    /// Return function signature of the verify function, with `vk` address as the first
+    /// parameter if `separate`.
+    pub fn fn_sig(&self, separate: bool) -> [u8; 4] {
+        self.fn_sig_with_name(&self.options.fn_name, separate)
+    }
+
+    /// Return function signature of function `name` taking same parameters as the verify
+    /// function.
+    fn fn_sig_with_name(&self, name: &str, separate: bool) -> [u8; 4] {
+        let hash = sha3::Keccak256::digest(self.fn_signature_with_name(name, separate));
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Return `name(params)` of function `name` taking same parameters as the verify function.
+    fn fn_signature_with_name(&self, name: &str, separate: bool) -> String {
+        let instances = if self.options.hashed_instances {
+            "bytes32"
+        } else {
+            "uint256[]"
+        };
+        if separate {
+            format!("{name}(address,bytes,{instances})")
+        } else {
+            format!("{name}(bytes,{instances})")
+        }
+    }
+
+    /// Encode proof into calldata to invoke the verify function, which is same as
+    /// [`encode_calldata`] but with the custom function name set by
+    /// [`SolidityGeneratorBuilder::fn_name`], and instances encoded by the encoder set by
+    /// [`SolidityGeneratorBuilder::instance_encoder`].
+    ///
+    /// With [`SolidityGeneratorBuilder::hashed_instances`], `instances` are the 2 instances of the
+    /// circuit, which are recomposed into `instancesHash` same as [`encode_calldata_hashed`].
+    ///
+    /// [`encode_calldata`]: crate::encode_calldata
+    /// [`encode_calldata_hashed`]: crate::encode_calldata_hashed
+    pub fn encode_calldata(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> Vec<u8> {
+        let fn_sig = self.fn_sig(vk_address.is_some());
+        if self.options.hashed_instances {
+            let instances_hash = hash_from_instances(instances);
+            encode_calldata_hashed_with_fn_sig(fn_sig, vk_address, proof, instances_hash)
+        } else {
+            let instances = self.options.instance_byte_order.encode(instances);
+            encode_calldata_with_fn_sig(fn_sig, vk_address, proof, &instances)
+        }
+    }
+
+    /// Encode proof into calldata to invoke `verifyAndStore` rendered with
+    /// [`SolidityGeneratorBuilder::verify_and_store`], which is same as [`Self::encode_calldata`]
+    /// but with function signature of `verifyAndStore`, since it takes the same parameters.
+    pub fn encode_calldata_verify_and_store(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> Vec<u8> {
+        let mut calldata = self.encode_calldata(vk_address, proof, instances);
+        let fn_sig = self.fn_sig_with_name("verifyAndStore", vk_address.is_some());
+        calldata[..4].copy_from_slice(&fn_sig);
+        calldata
+    }
+
+    /// Return digest of `proof` and `instances` recorded by `verifyAndStore` rendered with
+    /// [`SolidityGeneratorBuilder::verify_and_store`] once the proof is valid, to be passed to
+    /// `isVerified`.
+    ///
+    /// It's keccak256 of `vk_address` if given, `proof`, and instances encoded same as
+    /// [`Self::encode_calldata`], which are `instancesHash` with
+    /// [`SolidityGeneratorBuilder::hashed_instances`].
+    pub fn proof_digest(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> [u8; 32] {
+        let instances = if self.options.hashed_instances {
+            hash_from_instances(instances).to_vec()
+        } else {
+            self.options.instance_byte_order.encode(instances)
+        };
+        let mut hasher = sha3::Keccak256::new();
+        if let Some(vk_address) = vk_address {
+            hasher.update(vk_address);
+        }
+        hasher.update(proof);
+        hasher.update(instances);
+        hasher.finalize().into()
+    }
+
+    /// Encode proof of many circuits proven together into calldata to invoke the verify function
+    /// rendered with [`SolidityGeneratorBuilder::num_circuits`], which is same as
+    /// [`Self::encode_calldata`] with `instances` of each circuit concatenated circuit by circuit.
+    ///
+    /// # Panics
+    /// Panics if number of circuits mismatches the one set by
+    /// [`SolidityGeneratorBuilder::num_circuits`].
+    pub fn encode_calldata_multi(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proof: &[u8],
+        instances: &[&[impl AsRef<[bn256::Fr]>]],
+    ) -> Vec<u8> {
+        assert_eq!(
+            instances.len(),
+            self.options.num_circuits,
+            "Expected instances of {} circuits",
+            self.options.num_circuits
+        );
+        let instances = instances
+            .iter()
+            .flat_map(|instances| instances.iter().map(|column| column.as_ref()))
+            .collect::<Vec<&[bn256::Fr]>>();
+        self.encode_calldata(vk_address, proof, &instances)
+    }
+
+    /// Encode proof into calldata to invoke `verifyProofPacked` rendered with
+    /// [`SolidityGeneratorBuilder::packed_instances`], which is same as [`encode_calldata_packed`]
+    /// but with the custom function name set by [`SolidityGeneratorBuilder::fn_name`] followed by
+    /// `Packed`.
+    ///
+    /// # Panics
+    /// Panics if instances are not packed, or any instance doesn't fit in its bit width.
+    ///
+    /// [`encode_calldata_packed`]: crate::encode_calldata_packed
+    pub fn encode_calldata_packed(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> Vec<u8> {
+        let bit_widths = self
+            .options
+            .instance_bit_widths
+            .as_ref()
+            .expect("Instances are not packed");
+        let params = if vk_address.is_some() {
+            "address,bytes,bytes"
+        } else {
+            "bytes,bytes"
+        };
+        let hash = sha3::Keccak256::digest(format!("{}Packed({params})", self.options.fn_name));
+        let fn_sig = [hash[0], hash[1], hash[2], hash[3]];
+        let packed_instances = pack_instances(instances, bit_widths);
+        encode_calldata_packed_with_fn_sig(fn_sig, vk_address, proof, &packed_instances)
+    }
+
+    /// Encode proof into calldata to invoke `verifyProofNested` rendered with
+    /// [`SolidityGeneratorBuilder::nested_instances`], which is same as [`encode_calldata_nested`]
+    /// but with the custom function name set by [`SolidityGeneratorBuilder::fn_name`] followed by
+    /// `Nested`, and instances encoded by the encoder set by
+    /// [`SolidityGeneratorBuilder::instance_encoder`].
+    ///
+    /// For many circuits set by [`SolidityGeneratorBuilder::num_circuits`], pass instance columns
+    /// of all circuits concatenated circuit by circuit.
+    ///
+    /// # Panics
+    /// Panics if instances are not nested.
+    ///
+    /// [`encode_calldata_nested`]: crate::encode_calldata_nested
+    pub fn encode_calldata_nested(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> Vec<u8> {
+        assert!(self.options.nested_instances, "Instances are not nested");
+        let params = if vk_address.is_some() {
+            "address,bytes,uint256[][]"
+        } else {
+            "bytes,uint256[][]"
+        };
+        let hash = sha3::Keccak256::digest(format!("{}Nested({params})", self.options.fn_name));
+        let fn_sig = [hash[0], hash[1], hash[2], hash[3]];
+        let columns = instances
+            .iter()
+            .map(|column| {
+                self.options
+                    .instance_byte_order
+                    .encode(slice::from_ref(column))
+            })
+            .collect_vec();
+        encode_calldata_nested_with_fn_sig(fn_sig, vk_address, proof, &columns)
+    }
+
+    /// Encode proofs into calldata to invoke `verifyProofsV2` rendered with
+    /// [`SolidityGeneratorBuilder::num_proofs`], which is same as [`encode_calldata_v2`] but with
+    /// instances encoded by the encoder set by [`SolidityGeneratorBuilder::instance_encoder`].
+    ///
+    /// [`encode_calldata_v2`]: crate::encode_calldata_v2
+    pub fn encode_calldata_v2(
+        &self,
+        vk_address: Option<[u8; 20]>,
+        proofs: &[(&[u8], &[impl AsRef<[bn256::Fr]>])],
+    ) -> Vec<u8> {
+        encode_calldata_v2_with_byte_order(vk_address, proofs, self.options.instance_byte_order)
+    }
+
+    /// Check if `other_vk` can be verified by the verifier rendered by `render_separately`, which
+    /// requires the constraint system to have the same shape, including columns, gates, lookups,
+    /// shuffles, rotations and number of quotient chunks.
+    ///
+    /// Domain size and number of instances of each instance column are stored in verifying key,
+    /// so they can differ.
+    pub fn is_compatible_with(
+        &self,
+        other_vk: &VerifyingKey<bn256::G1Affine>,
+    ) -> Result<(), IncompatibilityReason> {
+        let (cs, other_cs) = (self.vk.cs(), other_vk.cs());
+
+        for (kind, expected, actual) in [
+            (
+                "instance",
+                cs.num_instance_columns(),
+                other_cs.num_instance_columns(),
+            ),
+            (
+                "advice",
+                cs.num_advice_columns(),
+                other_cs.num_advice_columns(),
+            ),
+            (
+                "fixed",
+                cs.num_fixed_columns(),
+                other_cs.num_fixed_columns(),
+            ),
+            ("challenge", cs.num_challenges(), other_cs.num_challenges()),
+        ] {
+            if expected != actual {
+                return Err(IncompatibilityReason::NumColumns {
+                    kind,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        if cs.advice_column_phase() != other_cs.advice_column_phase() {
+            return Err(IncompatibilityReason::Phases("advice columns"));
+        }
+        if cs.challenge_phase() != other_cs.challenge_phase() {
+            return Err(IncompatibilityReason::Phases("challenges"));
+        }
+        if cs.degree() != other_cs.degree() {
+            return Err(IncompatibilityReason::Degree {
+                expected: cs.degree(),
+                actual: other_cs.degree(),
+            });
+        }
+        if cs.blinding_factors() != other_cs.blinding_factors() {
+            return Err(IncompatibilityReason::BlindingFactors {
+                expected: cs.blinding_factors(),
+                actual: other_cs.blinding_factors(),
+            });
+        }
+        if cs.advice_queries() != other_cs.advice_queries() {
+            return Err(IncompatibilityReason::Queries("advice"));
+        }
+        if cs.fixed_queries() != other_cs.fixed_queries() {
+            return Err(IncompatibilityReason::Queries("fixed"));
+        }
+        if cs.instance_queries() != other_cs.instance_queries() {
+            return Err(IncompatibilityReason::Queries("instance"));
+        }
+        if cs.permutation().get_columns() != other_cs.permutation().get_columns() {
+            return Err(IncompatibilityReason::PermutationColumns);
+        }
+
+        // Shapes are same, so expressions are evaluated with same memory and calldata layout
+        let other_meta = self.options.meta(other_cs);
+        let vk = self.generate_vk();
+        let data = Data::new(&self.meta, &vk, Ptr::memory(0x100000), Ptr::calldata(0x84));
+        let other_data = Data::new(&other_meta, &vk, Ptr::memory(0x100000), Ptr::calldata(0x84));
+        let evaluator = Evaluator::new(cs, &self.meta, &data);
+        let other_evaluator = Evaluator::new(other_cs, &other_meta, &other_data);
+
+        let gate_computations = evaluator.gate_computations();
+        let other_gate_computations = other_evaluator.gate_computations();
+        let mut offset = 0;
+        for (idx, gate) in other_cs.gates().iter().enumerate() {
+            let num_polys = gate.polynomials().len();
+            let num_expected_polys = cs.gates().get(idx).map(|gate| gate.polynomials().len());
+            let range = offset..offset + num_polys;
+            if num_expected_polys != Some(num_polys)
+                || gate_computations.get(range.clone()) != other_gate_computations.get(range)
+            {
+                return Err(IncompatibilityReason::Gate(gate.name().to_string()));
+            }
+            offset += num_polys;
+        }
+        if let Some(gate) = cs.gates().get(other_cs.gates().len()) {
+            return Err(IncompatibilityReason::Gate(gate.name().to_string()));
+        }
+
+        if cs.lookups().len() != other_cs.lookups().len() {
+            let idx = cs.lookups().len().min(other_cs.lookups().len());
+            return Err(IncompatibilityReason::Lookup(idx));
+        }
+        if !cs.lookups().is_empty() {
+            let lookup_computations = evaluator.lookup_computations();
+            let other_lookup_computations = other_evaluator.lookup_computations();
+            let chunk_size = lookup_computations.len() / cs.lookups().len();
+            if let Some(idx) = izip!(
+                lookup_computations.chunks(chunk_size),
+                other_lookup_computations.chunks(chunk_size)
+            )
+            .position(|(lhs, rhs)| lhs != rhs)
+            {
+                return Err(IncompatibilityReason::Lookup(idx));
+            }
+        }
+
+        if cs.shuffles().len() != other_cs.shuffles().len() {
+            let idx = cs.shuffles().len().min(other_cs.shuffles().len());
+            return Err(IncompatibilityReason::Shuffle(idx));
+        }
+        if !cs.shuffles().is_empty() {
+            let shuffle_computations = evaluator.shuffle_computations();
+            let other_shuffle_computations = other_evaluator.shuffle_computations();
+            let chunk_size = shuffle_computations.len() / cs.shuffles().len();
+            if let Some(idx) = izip!(
+                shuffle_computations.chunks(chunk_size),
+                other_shuffle_computations.chunks(chunk_size)
+            )
+            .position(|(lhs, rhs)| lhs != rhs)
+            {
+                return Err(IncompatibilityReason::Shuffle(idx));
+            }
+        }
+
+        // Compare rendered verifiers in the end in case anything else is missed above
+        let other = SolidityGeneratorBuilder {
+            params: self.params,
+            vk: Cow::Borrowed(other_vk),
+            options: self.options.clone(),
+        }
+        .build()
+        .map_err(|_| IncompatibilityReason::Verifier)?;
+        let [verifier, other_verifier] = [self, &other].map(|generator| {
+            // NatSpec describes domain size and number of instances, which are allowed to differ
+            let mut template = generator.generate_verifier(true);
+            template.natspec = None;
+            template.vk_codehash = None;
+            let mut verifier = String::new();
+            render(&template, &mut verifier).map(|_| verifier)
+        });
+        if verifier.is_err() || verifier != other_verifier {
+            return Err(IncompatibilityReason::Verifier);
+        }
+
+        Ok(())
+    }
+
+    /// Return suggested filename of rendered verifier, which is the contract name with extension
+    /// `.sol`, or suffixed by `Base` with [`SolidityGeneratorBuilder::on_verified_hook`].
+    pub fn suggested_filename(&self) -> String {
+        if self.options.on_verified_hook {
+            format!("{}Base.sol", self.options.contract_name)
+        } else {
+            format!("{}.sol", self.options.contract_name)
+        }
+    }
+
+    /// Return suggested filename of verifying key rendered by `render_separately`, which is the
+    /// verifying key contract name with extension `.sol`.
+    pub fn suggested_vk_filename(&self) -> String {
+        format!("{}.sol", self.options.vk_contract_name)
+    }
+
+    /// Return number of advice columns, which is the number of advice commitments in proof.
+    pub fn num_advice_columns(&self) -> usize {
+        self.vk.cs().num_advice_columns()
+    }
+
+    /// Return number of fixed columns, including the ones converted from selectors (compressed
+    /// or not, depending on `compress_selectors` of keygen), which is the number of fixed
+    /// commitments in verifying key.
+    pub fn num_fixed_columns(&self) -> usize {
+        self.vk.fixed_commitments().len()
+    }
+
+    /// Return digest of verifying key in big-endian, which is its `transcript_repr` absorbed first
+    /// by transcript, so off-chain tooling can tell which circuit a verifier corresponds to.
+    ///
+    /// It's returned by `VERIFYING_KEY_DIGEST()` of the verifier with verifying key embedded or
+    /// read from state, or by `VERIFYING_KEY_DIGEST(address vk)` when verifying key address is
+    /// passed in calldata, and stored as the first word of runtime code of the verifying key
+    /// contract.
+    pub fn vk_digest(&self) -> [u8; 32] {
+        fr_to_u256(self.vk.transcript_repr()).to_be_bytes()
+    }
+}
+
+/// Builder of [`SolidityGenerator`].
+///
+/// Options are set by name instead of position, and validated all together by
+/// [`SolidityGeneratorBuilder::build`]. Setters take and return `&mut Self`, so they can be
+/// chained on a temporary builder, or applied conditionally to one held in a variable, e.g.
+/// `if let Some(name) = name { builder.contract_name(name); }`.
+#[derive(Clone, Debug)]
+pub struct SolidityGeneratorBuilder<'a> {
+    params: VerifierParams,
+    vk: Cow<'a, VerifyingKey<bn256::G1Affine>>,
+    options: Options,
+}
+
+impl<'a> SolidityGeneratorBuilder<'a> {
+    /// Set `VerifierParams`, replacing the ones given to [`SolidityGenerator::builder`].
+    pub fn params(&mut self, params: impl Into<VerifierParams>) -> &mut Self {
+        self.params = params.into();
+        self
+    }
+
+    /// Set `VerifyingKey` of the circuit, replacing the one given to
+    /// [`SolidityGenerator::builder`].
+    pub fn vk(&mut self, vk: &'a VerifyingKey<bn256::G1Affine>) -> &mut Self {
+        self.vk = Cow::Borrowed(vk);
+        self
+    }
+
+    /// Set `BatchOpenScheme`.
+    pub fn scheme(&mut self, scheme: BatchOpenScheme) -> &mut Self {
+        self.options.scheme = scheme;
+        self
+    }
+
+    /// Set number of instances of each instance column, in the same order as the instance
+    /// columns are created in the circuit.
+    pub fn num_instances(&mut self, num_instances: &[usize]) -> &mut Self {
+        self.options.num_instances = num_instances.to_vec();
+        self
+    }
+
+    /// Set `AccumulatorEncoding`.
+    pub fn acc_encoding(&mut self, acc_encoding: Option<AccumulatorEncoding>) -> &mut Self {
+        self.options.acc_encoding = acc_encoding;
+        self.options.acc_indices = None;
+        self
+    }
+
+    /// Set accumulator limbs by `(column, row)` of instances, like `AccumulatorIndices` of
+    /// `snark_verifier`, which lists limbs of `lhs.x`, `lhs.y`, `rhs.x` and `rhs.y` in order with
+    /// least significant limb first, and each limb has `num_limb_bits` bits.
+    ///
+    /// Unlike `AccumulatorEncoding`, limbs don't need to be contiguous in instances, and the
+    /// verifier reads each limb from its own position before accumulating it into the pairing.
+    pub fn accumulator_indices(
+        &mut self,
+        indices: &[(usize, usize)],
+        num_limb_bits: usize,
+    ) -> &mut Self {
+        let num_limbs = indices.len() / 4;
+        self.options.acc_encoding = Some(AccumulatorEncoding::new(0, num_limbs, num_limb_bits));
+        self.options.acc_indices = Some(indices.to_vec());
+        self
+    }
+
+    /// Set `OnFailure`, which decides what `verifyProof` does when any check fails.
+    ///
+    /// `OnFailure::ReturnFalse` can't be used with [`Self::deferred_pairing`].
+    pub fn on_failure(&mut self, on_failure: OnFailure) -> &mut Self {
+        self.options.on_failure = on_failure;
+        self
+    }
+
+    /// Set whether to emit NatSpec comments describing the circuit, e.g. degree, number of
+    /// columns and batch open scheme, on generated contracts and functions.
+    pub fn natspec(&mut self, natspec: bool) -> &mut Self {
+        self.options.natspec = natspec;
+        self
+    }
+
+    /// Set whether `verifyProof` is declared as `view`, which defaults to `true` so it can be
+    /// called by `staticcall` or from `view` functions. Pass `false` to keep it non-payable.
+    ///
+    /// It can't be set to `true` with [`Self::emit_event`] or [`Self::on_verified_hook`], which
+    /// modify state.
+    pub fn view(&mut self, view: bool) -> &mut Self {
+        self.options.view = view;
+        self
+    }
+
+    /// Set whether the verify function emits `Verified(bool success)` with its result whenever it
+    /// returns, so indexers can track verifications without tracing calls. It's not emitted when
+    /// the verify function reverts, e.g. with `OnFailure::Revert`.
+    ///
+    /// It requires [`Self::view`] set to `false`, since `view` functions can't emit events.
+    pub fn emit_event(&mut self, emit_event: bool) -> &mut Self {
+        self.options.emit_event = emit_event;
+        self
+    }
+
+    /// Set whether the verify function returns pairing lhs and rhs as `uint256[4]` instead of
+    /// performing pairing, so pairings of many verifications can be checked at once later by the
+    /// aggregator rendered by [`SolidityGenerator::render_pairing_aggregator`], e.g. on L1 for
+    /// verifications on L2 where `ecPairing` is expensive.
+    ///
+    /// It can't be used with `OnFailure::ReturnFalse`, whose zero return data would be points at
+    /// infinity satisfying any pairing, or with [`Self::batch_verify`],
+    /// [`Self::packed_instances`], [`Self::nested_instances`] and [`Self::emit_event`], which all
+    /// expect the verify function to return a result.
+    pub fn deferred_pairing(&mut self, deferred_pairing: bool) -> &mut Self {
+        self.options.deferred_pairing = deferred_pairing;
+        self
+    }
+
+    /// Set whether to render `verifyAndStore` along with the verify function, which takes the
+    /// same parameters, calls the verify function by itself, and records
+    /// `keccak256(proof ‖ instances)` in storage once the proof is valid, so it can be re-checked
+    /// cheaply by the `view` function `isVerified(bytes32 digest)` in later steps of a workflow.
+    ///
+    /// The verify function is left untouched and stays `view` if [`Self::view`] is `true`. With
+    /// [`Self::hashed_instances`], `instancesHash` is hashed in place of instances, and when
+    /// verifying key address is passed in calldata, it's prepended to the digest so a proof
+    /// verified against another verifying key is never taken as verified. Use
+    /// [`SolidityGenerator::encode_calldata_verify_and_store`] and
+    /// [`SolidityGenerator::proof_digest`] to encode calldata and compute the digest.
+    ///
+    /// It can't be used with [`Self::deferred_pairing`] or [`Self::batch_verify`], which make the
+    /// verify function return pairing instead of result.
+    pub fn verify_and_store(&mut self, verify_and_store: bool) -> &mut Self {
+        self.options.verify_and_store = verify_and_store;
+        self
+    }
+
+    /// Set whether to render the verifier as `abstract contract {contract_name}Base`, whose verify
+    /// function calls the internal `_onVerified(uint256[] memory instances)` once the proof is
+    /// valid, so applications can inherit it and override the hook to act on verified instances,
+    /// e.g. mint or record something, instead of wrapping the verifier.
+    ///
+    /// Since the verifier takes over memory and returns directly, the verify function calls
+    /// itself to verify before calling the hook, and `verifyProofPacked`, `verifyProofNested` and
+    /// `verifyAndStore` call the hook after their own calls. Calls from the contract itself, e.g.
+    /// `this.verifyProof(...)`, skip the hook.
+    ///
+    /// It requires [`Self::view`] set to `false` since the hook is expected to modify state, and
+    /// can't be used with [`Self::hashed_instances`], [`Self::deferred_pairing`] or
+    /// [`Self::batch_verify`], which leave no instances or result to pass to the hook.
+    pub fn on_verified_hook(&mut self, on_verified_hook: bool) -> &mut Self {
+        self.options.on_verified_hook = on_verified_hook;
+        self
+    }
+
+    /// Set whether to render `verifyProofs(bytes[],uint256[][])` along with `verifyProof`, which
+    /// verifies many proofs in one call and combines their pairings by random linear combination,
+    /// so only one `ecPairing` is performed. Use [`encode_calldata_batch`] to encode its calldata.
+    ///
+    /// It can't be used with [`Self::packed_instances`], [`Self::nested_instances`],
+    /// [`Self::deferred_pairing`], [`Self::verify_and_store`] or [`Self::on_verified_hook`],
+    /// which expect the verify function to return a result.
+    ///
+    /// [`encode_calldata_batch`]: crate::encode_calldata_batch
+    pub fn batch_verify(&mut self, batch_verify: bool) -> &mut Self {
+        self.options.batch_verify = batch_verify;
+        self
+    }
+
+    /// Set number of proofs to render `verifyProofsV2(bytes proofs)` along with `verifyProofs`,
+    /// which takes exactly `num_proofs` proofs concatenated in one `bytes`, each prefixed by its
+    /// length and followed by its instances, and verifies them by `verifyProofs`. Use
+    /// [`encode_calldata_v2`] or [`SolidityGenerator::encode_calldata_v2`] to encode calldata.
+    ///
+    /// It requires [`Self::batch_verify`], and can't be used with [`Self::hashed_instances`].
+    ///
+    /// [`encode_calldata_v2`]: crate::encode_calldata_v2
+    pub fn num_proofs(&mut self, num_proofs: Option<usize>) -> &mut Self {
+        self.options.num_proofs = num_proofs;
+        self
+    }
+
+    /// Set number of circuits sharing the verifying key proven together in one proof, i.e.
+    /// `create_proof` with `num_circuits` circuits, which defaults to 1.
+    ///
+    /// The verifier reads advice commitments, grand products and their evaluations of each
+    /// circuit, and expects instances of all circuits concatenated circuit by circuit, each
+    /// flattened column by column with the same `num_instances`. Use
+    /// [`SolidityGenerator::encode_calldata_multi`] to encode calldata.
+    ///
+    /// It can't be used with [`Self::hashed_instances`], [`Self::packed_instances`], accumulator
+    /// or instance queries with rotation, which all assume a single circuit.
+    pub fn num_circuits(&mut self, num_circuits: usize) -> &mut Self {
+        self.options.num_circuits = num_circuits;
+        self
+    }
+
+    /// Set `VkAddressSource`, which decides where the verifier rendered by `render_separately`
+    /// reads verifying key address from, and defaults to `VkAddressSource::Calldata`.
+    ///
+    /// With `VkAddressSource::Immutable` or `VkAddressSource::Ownable`, the verify function
+    /// doesn't take verifying key address, so calldata is encoded with `vk_address` as `None`.
+    ///
+    /// `VkAddressSource::Ownable` can't be used with [`Self::vk_codehash_check`], which would
+    /// reject any verifying key the owner updates to.
+    pub fn vk_address_source(&mut self, vk_address_source: VkAddressSource) -> &mut Self {
+        self.options.vk_address_source = vk_address_source;
+        self
+    }
+
+    /// Set whether the verifier rendered by `render_separately` embeds [`vk_codehash`] and checks
+    /// `extcodehash` of verifying key address against it before copying verifying key, which
+    /// reverts with `VkCodehashMismatch()` on mismatch.
+    ///
+    /// It can't be used with `VkAddressSource::Ownable`, whose verifying key address is expected
+    /// to be updated to the verifying key of another circuit.
+    ///
+    /// [`vk_codehash`]: SolidityGenerator::vk_codehash
+    pub fn vk_codehash_check(&mut self, vk_codehash_check: bool) -> &mut Self {
+        self.options.vk_codehash_check = vk_codehash_check;
+        self
+    }
+
+    /// Set whether the verify function takes `bytes32 instancesHash` instead of `uint256[]
+    /// instances`, which saves calldata and hashing of many instances.
+    ///
+    /// The circuit is expected to constrain `keccak256` of its actual public inputs, and expose
+    /// the digest as exactly 2 instances, the higher and lower 128 bits, which the verifier splits
+    /// `instancesHash` into. Use [`hash_instances`] and [`instances_from_hash`] to compute them,
+    /// and [`encode_calldata_hashed`] to encode calldata.
+    ///
+    /// [`hash_instances`]: crate::hash_instances
+    /// [`instances_from_hash`]: crate::instances_from_hash
+    /// [`encode_calldata_hashed`]: crate::encode_calldata_hashed
+    pub fn hashed_instances(&mut self, hashed_instances: bool) -> &mut Self {
+        self.options.hashed_instances = hashed_instances;
+        self
+    }
+
+    /// Set bit width of each instance flattened column by column, which renders
+    /// `verifyProofPacked(bytes proof, bytes packedInstances)` along with the verify function to
+    /// save calldata of small instances, e.g. 8 bytes instead of 32 bytes for a 64 bits instance.
+    ///
+    /// Each instance is packed big-endian into `(bit_width + 7) / 8` bytes, and the verifier
+    /// unpacks and checks it's less than `2^bit_width` before calling the verify function by
+    /// itself. Use [`encode_calldata_packed`] to encode calldata.
+    ///
+    /// It can't be used with [`Self::hashed_instances`], or [`Self::batch_verify`] which makes
+    /// the verify function return pairing when called by itself.
+    ///
+    /// [`encode_calldata_packed`]: crate::encode_calldata_packed
+    pub fn packed_instances(&mut self, bit_widths: &[usize]) -> &mut Self {
+        self.options.instance_bit_widths = Some(bit_widths.to_vec());
+        self
+    }
+
+    /// Set whether to render `verifyProofNested(bytes proof, uint256[][] nestedInstances)` along
+    /// with the verify function, which takes instances of each instance column as a separate
+    /// array, so callers holding instances per column don't need to flatten them.
+    ///
+    /// The canonical layout of instances is always flattened column by column. The verifier
+    /// checks there are exactly as many arrays as instance columns (times number of circuits set
+    /// by [`Self::num_circuits`]), each of the length in `num_instances`, and copies them one
+    /// after another before calling the verify function by itself. Use [`encode_calldata_nested`]
+    /// or [`SolidityGenerator::encode_calldata_nested`] to encode calldata.
+    ///
+    /// It can't be used with [`Self::hashed_instances`], or [`Self::batch_verify`] which makes
+    /// the verify function return pairing when called by itself.
+    ///
+    /// [`encode_calldata_nested`]: crate::encode_calldata_nested
+    pub fn nested_instances(&mut self, nested_instances: bool) -> &mut Self {
+        self.options.nested_instances = nested_instances;
+        self
+    }
+
+    /// Set [`InstanceEncoder`] of instances in calldata, which defaults to [`BigEndianEncoder`]
+    /// same as ABI encoding of `uint256[]`, so the verifier decodes instances in the matching
+    /// byte order, e.g. reverses bytes of each instance for [`LittleEndianEncoder`]. Use
+    /// [`SolidityGenerator::encode_calldata`] or [`encode_calldata_with_encoder`] to encode
+    /// calldata.
+    ///
+    /// It can't be used with [`Self::hashed_instances`] or [`Self::packed_instances`] unless the
+    /// encoder is big-endian, since they don't take instances as words.
+    ///
+    /// [`InstanceEncoder`]: crate::InstanceEncoder
+    /// [`BigEndianEncoder`]: crate::BigEndianEncoder
+    /// [`LittleEndianEncoder`]: crate::LittleEndianEncoder
+    /// [`encode_calldata_with_encoder`]: crate::encode_calldata_with_encoder
+    pub fn instance_encoder<E: InstanceEncoder>(&mut self) -> &mut Self {
+        self.options.instance_byte_order = E::BYTE_ORDER;
+        self
+    }
+
+    /// Set pragma version of generated contracts, e.g. `^0.8.20` or `>=0.8.0 <0.9.0`.
+    pub fn pragma_version(&mut self, version: &str) -> &mut Self {
+        self.options.pragma = version.to_string();
+        self
+    }
+
+    /// Set SPDX license identifier of generated contracts, e.g. `MIT` or `BUSL-1.1`.
+    pub fn license(&mut self, license: &str) -> &mut Self {
+        self.options.license = license.to_string();
+        self
+    }
+
+    /// Set contract name of verifier.
+    pub fn contract_name(&mut self, name: &str) -> &mut Self {
+        self.options.contract_name = name.to_string();
+        self
+    }
+
+    /// Set contract name of verifying key, which is only used by `render_separately`.
+    pub fn vk_contract_name(&mut self, name: &str) -> &mut Self {
+        self.options.vk_contract_name = name.to_string();
+        self
+    }
+
+    /// Set name of the verify function, which defaults to `verifyProof`.
+    ///
+    /// The parameters are `(bytes proof, uint256[] instances)` when verifying key is embedded,
+    /// and `(address vk, bytes proof, uint256[] instances)` when rendered separately. Use
+    /// [`SolidityGenerator::encode_calldata`] to encode calldata with the custom function name.
+    pub fn fn_name(&mut self, name: &str) -> &mut Self {
+        self.options.fn_name = name.to_string();
+        self
+    }
+
+    /// Set whether memory slots of subexpressions shared by gates are reused once their last use
+    /// has been evaluated, which keeps the scratch memory small on circuits with many gates.
+    ///
+    /// It defaults to `true`, and can be turned off for debugging, so each shared subexpression
+    /// stays in its own slot through the quotient evaluation.
+    pub fn scratch_reuse(&mut self, scratch_reuse: bool) -> &mut Self {
+        self.options.scratch_reuse = scratch_reuse;
+        self
+    }
+
+    /// Validate options against the circuit and return a `SolidityGenerator`.
+    ///
+    /// The builder is left untouched, so it can go on to build generators with other options.
+    pub fn build(&self) -> Result<SolidityGenerator<'a>, GeneratorError> {
+        self.clone().into_generator()
+    }
+
+    fn into_generator(mut self) -> Result<SolidityGenerator<'a>, GeneratorError> {
+        let cs = self.vk.cs();
+        check_supported(cs)?;
+        if cs.num_instance_columns() != self.options.num_instances.len() {
+            return Err(GeneratorError::InstanceColumnsMismatch {
+                expected: cs.num_instance_columns(),
+                actual: self.options.num_instances.len(),
+            });
+        }
+        let options = &mut self.options;
+        if let (Some(acc_encoding), Some(indices)) =
+            (&mut options.acc_encoding, &options.acc_indices)
+        {
+            acc_encoding.offset = accumulator_offsets(&options.num_instances, indices)?[0];
+        }
+        let meta = self.options.meta(cs);
+        self.options.validate(&meta)?;
+
+        Ok(SolidityGenerator {
+            params: self.params,
+            vk: self.vk,
+            options: self.options,
+            cse: true,
+            meta,
+        })
+    }
+}
+
+/// Options of [`SolidityGenerator`] set by its builder, and validated together by
+/// [`SolidityGeneratorBuilder::build`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Options {
+    scheme: BatchOpenScheme,
+    num_instances: Vec<usize>,
+    acc_encoding: Option<AccumulatorEncoding>,
+    acc_indices: Option<Vec<(usize, usize)>>,
+    on_failure: OnFailure,
+    natspec: bool,
+    view: bool,
+    emit_event: bool,
+    deferred_pairing: bool,
+    verify_and_store: bool,
+    on_verified_hook: bool,
+    batch_verify: bool,
+    num_proofs: Option<usize>,
+    num_circuits: usize,
+    vk_address_source: VkAddressSource,
+    vk_codehash_check: bool,
+    hashed_instances: bool,
+    instance_bit_widths: Option<Vec<usize>>,
+    nested_instances: bool,
+    instance_byte_order: InstanceByteOrder,
+    pragma: String,
+    license: String,
+    contract_name: String,
+    vk_contract_name: String,
+    fn_name: String,
+    scratch_reuse: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scheme: BatchOpenScheme::Bdfg21,
+            num_instances: Vec::new(),
+            acc_encoding: None,
+            acc_indices: None,
+            on_failure: OnFailure::Revert,
+            natspec: false,
+            view: true,
+            emit_event: false,
+            deferred_pairing: false,
+            verify_and_store: false,
+            on_verified_hook: false,
+            batch_verify: false,
+            num_proofs: None,
+            num_circuits: 1,
+            vk_address_source: VkAddressSource::Calldata,
+            vk_codehash_check: false,
+            hashed_instances: false,
+            instance_bit_widths: None,
+            nested_instances: false,
+            instance_byte_order: InstanceByteOrder::BigEndian,
+            pragma: DEFAULT_PRAGMA.to_string(),
+            license: DEFAULT_LICENSE.to_string(),
+            contract_name: DEFAULT_CONTRACT_NAME.to_string(),
+            vk_contract_name: DEFAULT_VK_CONTRACT_NAME.to_string(),
+            fn_name: DEFAULT_FN_NAME.to_string(),
+            scratch_reuse: true,
+        }
+    }
+}
+
+impl Options {
+    /// Return `ConstraintSystemMeta` of `cs` for the number of circuits set, which is what
+    /// [`SolidityGeneratorBuilder::build`] validates against and the generator renders from.
+    fn meta(&self, cs: &ConstraintSystem<bn256::Fr>) -> ConstraintSystemMeta {
+        ConstraintSystemMeta::new(cs).with_num_circuits(self.num_circuits)
+    }
+
+    fn validate(&self, meta: &ConstraintSystemMeta) -> Result<(), GeneratorError> {
+        if let Some(acc_encoding) = self.acc_encoding {
+            let num_instances: usize = self.num_instances.iter().sum();
+            let num_limbs = 4 * acc_encoding.num_limbs;
+            if acc_encoding.offset + num_limbs > num_instances {
+                return Err(GeneratorError::AccumulatorOutOfRange {
+                    offset: acc_encoding.offset,
+                    num_limbs,
+                    num_instances,
+                });
+            }
+        }
+        if self.hashed_instances {
+            validate_hashed_instances(&self.num_instances, self.acc_encoding)?;
+        }
+        if let Some(bit_widths) = &self.instance_bit_widths {
+            validate_packed_instances(
+                &self.num_instances,
+                bit_widths,
+                self.hashed_instances,
+                self.batch_verify,
+            )?;
+        }
+        validate_instance_byte_order(
+            self.instance_byte_order,
+            self.hashed_instances,
+            self.instance_bit_widths.is_some(),
+        )?;
+        if let Some(num_proofs) = self.num_proofs {
+            validate_num_proofs(num_proofs, self.batch_verify, self.hashed_instances)?;
+        }
+        if self.nested_instances {
+            validate_nested_instances(self.hashed_instances, self.batch_verify)?;
+        }
+        if self.emit_event {
+            validate_emit_event(self.view)?;
+        }
+        if self.deferred_pairing {
+            validate_deferred_pairing(
+                self.on_failure,
+                self.batch_verify,
+                self.instance_bit_widths.is_some(),
+                self.nested_instances,
+                self.emit_event,
+            )?;
+        }
+        if self.verify_and_store {
+            validate_verify_and_store(self.deferred_pairing, self.batch_verify)?;
+        }
+        if self.on_verified_hook {
+            validate_on_verified_hook(
+                self.view,
+                self.hashed_instances,
+                self.deferred_pairing,
+                self.batch_verify,
+            )?;
+        }
+        if self.vk_codehash_check {
+            validate_vk_codehash_check(self.vk_address_source)?;
+        }
+        validate_pragma(&self.pragma)?;
+        validate_license(&self.license)?;
+        validate_contract_names(&self.contract_name, &self.vk_contract_name)?;
+        validate_fn_name(&self.fn_name)?;
+        validate_num_circuits(
+            self.num_circuits,
+            meta,
+            self.hashed_instances,
+            self.instance_bit_widths.is_some(),
+            self.acc_encoding.is_some(),
+        )
+    }
+}
+
+fn accumulator_offsets(
+    num_instances: &[usize],
+    indices: &[(usize, usize)],
+) -> Result<Vec<usize>, GeneratorError> {
+    if indices.is_empty() || indices.len() % 4 != 0 {
+        return Err(GeneratorError::InvalidOption {
+            option: "accumulator_indices",
+            reason: format!(
+                "expected a positive multiple of 4 limbs but got {}",
+                indices.len()
+            ),
+        });
+    }
+    indices
+        .iter()
+        .map(|&(column, row)| match num_instances.get(column) {
+            Some(num) if row < *num => Ok(num_instances[..column].iter().sum::<usize>() + row),
+            _ => Err(GeneratorError::InvalidOption {
+                option: "accumulator_indices",
+                reason: format!("({column}, {row}) is out of range of instances"),
+            }),
+        })
+        .collect()
+}
+
+fn snark_verifier_limb_bits(indices: &[(usize, usize)]) -> Result<usize, GeneratorError> {
+    match indices.len() {
+        12 => Ok(88),
+        16 => Ok(68),
+        len => Err(GeneratorError::InvalidOption {
+            option: "accumulator_indices",
+            reason: format!("expected 12 or 16 limbs as `snark_verifier` but got {len}"),
+        }),
+    }
+}
+
+fn validate_hashed_instances(
+    num_instances: &[usize],
+    acc_encoding: Option<AccumulatorEncoding>,
+) -> Result<(), GeneratorError> {
+    let num_instances = num_instances.iter().sum::<usize>();
+    if num_instances != 2 {
+        return Err(GeneratorError::InvalidOption {
+            option: "hashed_instances",
+            reason: format!(
+            "expected 2 instances for higher and lower 128 bits of hash but got {num_instances}"
+        ),
+        });
+    }
+    if acc_encoding.is_some() {
+        return Err(GeneratorError::InvalidOption {
+            option: "hashed_instances",
+            reason: "accumulator can't be read from hashed instances".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_packed_instances(
+    num_instances: &[usize],
+    bit_widths: &[usize],
+    hashed_instances: bool,
+    batch_verify: bool,
+) -> Result<(), GeneratorError> {
+    let num_instances = num_instances.iter().sum::<usize>();
+    if bit_widths.len() != num_instances {
+        return Err(GeneratorError::InvalidOption {
+            option: "packed_instances",
+            reason: format!(
+                "expected {num_instances} bit widths but got {}",
+                bit_widths.len()
+            ),
+        });
+    }
+    if let Some(bit_width) = bit_widths
+        .iter()
+        .find(|bit_width| !(1..=256).contains(*bit_width))
+    {
+        return Err(GeneratorError::InvalidOption {
+            option: "packed_instances",
+            reason: format!("bit width {bit_width} is out of range 1..=256"),
+        });
+    }
+    if hashed_instances {
+        return Err(GeneratorError::InvalidOption {
+            option: "packed_instances",
+            reason: "instances hash can't be packed".to_string(),
+        });
+    }
+    if batch_verify {
+        return Err(GeneratorError::InvalidOption {
+            option: "packed_instances",
+            reason: "packed instances can't be verified in batch".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_emit_event(view: bool) -> Result<(), GeneratorError> {
+    if view {
+        return Err(GeneratorError::InvalidOption {
+            option: "emit_event",
+            reason: "event can't be emitted by view function".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_deferred_pairing(
+    on_failure: OnFailure,
+    batch_verify: bool,
+    packed_instances: bool,
+    nested_instances: bool,
+    emit_event: bool,
+) -> Result<(), GeneratorError> {
+    let invalid = |details: &str| {
+        Err(GeneratorError::InvalidOption {
+            option: "deferred_pairing",
+            reason: details.to_string(),
+        })
+    };
+    if on_failure == OnFailure::ReturnFalse {
+        return invalid("zero return data of `OnFailure::ReturnFalse` would satisfy pairing");
+    }
+    if batch_verify {
+        return invalid("pairings are already combined by batch verify");
+    }
+    if packed_instances || nested_instances {
+        return invalid("packed or nested instances expect the verify function to return bool");
+    }
+    if emit_event {
+        return invalid("result to emit is unknown before pairing");
+    }
+    Ok(())
+}
+
+fn validate_verify_and_store(
+    deferred_pairing: bool,
+    batch_verify: bool,
+) -> Result<(), GeneratorError> {
+    if deferred_pairing || batch_verify {
+        return Err(GeneratorError::InvalidOption {
+            option: "verify_and_store",
+            reason: "verify function returns pairing instead of result".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_on_verified_hook(
+    view: bool,
+    hashed_instances: bool,
+    deferred_pairing: bool,
+    batch_verify: bool,
+) -> Result<(), GeneratorError> {
+    let invalid = |details: &str| {
+        Err(GeneratorError::InvalidOption {
+            option: "on_verified_hook",
+            reason: details.to_string(),
+        })
+    };
+    if view {
+        return invalid("hook can't modify state when called by view function");
+    }
+    if hashed_instances {
+        return invalid("instances are unknown to the verifier with hashed instances");
+    }
+    if deferred_pairing || batch_verify {
+        return invalid("verify function returns pairing instead of result");
+    }
+    Ok(())
+}
+
+fn validate_vk_codehash_check(vk_address_source: VkAddressSource) -> Result<(), GeneratorError> {
+    if vk_address_source == VkAddressSource::Ownable {
+        return Err(GeneratorError::InvalidOption {
+            option: "vk_codehash_check",
+            reason: "code hash can't be pinned when verifying key address can be updated"
+                .to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_nested_instances(
+    hashed_instances: bool,
+    batch_verify: bool,
+) -> Result<(), GeneratorError> {
+    if hashed_instances {
+        return Err(GeneratorError::InvalidOption {
+            option: "nested_instances",
+            reason: "instances hash can't be nested".to_string(),
+        });
+    }
+    if batch_verify {
+        return Err(GeneratorError::InvalidOption {
+            option: "nested_instances",
+            reason: "nested instances can't be verified in batch".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_num_proofs(
+    num_proofs: usize,
+    batch_verify: bool,
+    hashed_instances: bool,
+) -> Result<(), GeneratorError> {
+    if num_proofs == 0 {
+        return Err(GeneratorError::InvalidOption {
+            option: "num_proofs",
+            reason: "expected at least 1 proof".to_string(),
+        });
+    }
+    if !batch_verify {
+        return Err(GeneratorError::InvalidOption {
+            option: "num_proofs",
+            reason: "concatenated proofs are verified by `verifyProofs` of batch verify"
+                .to_string(),
+        });
+    }
+    if hashed_instances {
+        return Err(GeneratorError::InvalidOption {
+            option: "num_proofs",
+            reason: "instances hash can't be concatenated with proofs".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_num_circuits(
+    num_circuits: usize,
+    meta: &ConstraintSystemMeta,
+    hashed_instances: bool,
+    packed_instances: bool,
+    accumulator: bool,
+) -> Result<(), GeneratorError> {
+    let invalid = |details: &str| {
+        Err(GeneratorError::InvalidOption {
+            option: "num_circuits",
+            reason: details.to_string(),
+        })
+    };
+    if num_circuits == 0 {
+        return invalid("expected at least 1 circuit");
+    }
+    if num_circuits == 1 {
+        return Ok(());
+    }
+    if hashed_instances {
+        return invalid("instances hash can't cover instances of many circuits");
+    }
+    if packed_instances {
+        return invalid("instances of many circuits can't be packed");
     }
+    if accumulator {
+        return invalid("accumulator can't be read from instances of many circuits");
+    }
+    if !meta.rotated_instance_queries.is_empty() {
+        return invalid("instance queries with rotation can't be evaluated for many circuits");
+    }
+    Ok(())
 }
 
-impl<'a> SolidityGenerator<'a> {
-    /// Return a new `SolidityGenerator`.
-    pub fn new(
-        params: &'a ParamsKZG<bn256::Bn256>,
-        vk: &'a VerifyingKey<bn256::G1Affine>,
-        scheme: BatchOpenScheme,
-        num_instances: usize,
-    ) -> Self {
-        assert_ne!(vk.cs().num_advice_columns(), 0);
-        assert!(
-            vk.cs().num_instance_columns() <= 1,
-            "Multiple instance columns is not yet implemented"
-        );
-        assert!(
-            !vk.cs()
-                .instance_queries()
-                .iter()
-                .any(|(_, rotation)| *rotation != Rotation::cur()),
-            "Rotated query to instance column is not yet implemented"
+fn validate_instance_byte_order(
+    byte_order: InstanceByteOrder,
+    hashed_instances: bool,
+    packed_instances: bool,
+) -> Result<(), GeneratorError> {
+    if byte_order == InstanceByteOrder::BigEndian {
+        return Ok(());
+    }
+    if hashed_instances {
+        return Err(GeneratorError::InvalidOption {
+            option: "instance_encoder",
+            reason: "instances hash is always big-endian".to_string(),
+        });
+    }
+    if packed_instances {
+        return Err(GeneratorError::InvalidOption {
+            option: "instance_encoder",
+            reason: "packed instances are always big-endian".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn check_supported(cs: &ConstraintSystem<bn256::Fr>) -> Result<(), GeneratorError> {
+    let unsupported = |feature, details: String| Err(GeneratorError::Unsupported(feature, details));
+
+    if cs.num_advice_columns() == 0 {
+        return unsupported("circuit", "no advice column".to_string());
+    }
+    if cs.permutation().get_columns().is_empty() {
+        return unsupported("circuit", "no column with equality enabled".to_string());
+    }
+    // Instance query with positive rotation takes Lagrange bases of rows before the first one,
+    // which are only computed down to the last usable row.
+    let max_rotation = cs.blinding_factors() as i32 + 1;
+    if let Some((column, rotation)) = cs
+        .instance_queries()
+        .iter()
+        .find(|(_, rotation)| rotation.0 > max_rotation)
+    {
+        let details = format!(
+            "instance column #{} is queried with rotation {} greater than {max_rotation}",
+            column.index(),
+            rotation.0
         );
+        return unsupported("rotated instance query", details);
+    }
+    Ok(())
+}
 
-        Self {
-            params,
-            vk,
-            scheme,
-            num_instances,
-            acc_encoding: None,
-            meta: ConstraintSystemMeta::new(vk.cs()),
+const DEFAULT_PRAGMA: &str = "^0.8.0";
+const DEFAULT_LICENSE: &str = "MIT";
+const DEFAULT_CONTRACT_NAME: &str = "Halo2Verifier";
+const DEFAULT_VK_CONTRACT_NAME: &str = "Halo2VerifyingKey";
+const DEFAULT_FN_NAME: &str = "verifyProof";
+const DEFAULT_SOLC_VERSION: &str = "0.8.19";
+
+/// Parse words stored by rendered `Halo2VerifyingKey.sol` into map from name to word, checking
+/// they are stored contiguously from offset 0.
+fn parse_vk_words(vk_solidity: &str) -> Result<BTreeMap<String, U256>, InconsistencyError> {
+    let parse_hex = |hex: &str| {
+        hex.strip_prefix("0x")
+            .and_then(|hex| U256::from_str_radix(hex, 16).ok())
+    };
+    vk_solidity
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("mstore("))
+        .enumerate()
+        .map(|(idx, line)| {
+            let malformed = || InconsistencyError::Malformed(line.to_string());
+            let (args, name) = line["mstore(".len()..]
+                .split_once(") // ")
+                .ok_or_else(malformed)?;
+            let (offset, word) = args.split_once(", ").ok_or_else(malformed)?;
+            match (parse_hex(offset), parse_hex(word)) {
+                (Some(offset), Some(word)) if offset == U256::from(idx * 0x20) => {
+                    Ok((name.to_string(), word))
+                }
+                _ => Err(malformed()),
+            }
+        })
+        .collect()
+}
+
+/// Returns the first version in `pragma`, e.g. `0.8.0` of `^0.8.0`, or [`DEFAULT_SOLC_VERSION`]
+/// if there is none.
+fn solc_version_from_pragma(pragma: &str) -> String {
+    pragma
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find(|version| {
+            let parts = version.split('.').collect_vec();
+            parts.len() == 3 && parts.iter().all(|part| !part.is_empty())
+        })
+        .unwrap_or(DEFAULT_SOLC_VERSION)
+        .to_string()
+}
+
+/// Returns `value` quoted as JavaScript string literal.
+fn js_string(value: &str) -> String {
+    let escaped = value
+        .chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            '\n' => vec!['\\', 'n'],
+            '\r' => vec!['\\', 'r'],
+            _ => vec![c],
+        })
+        .collect::<String>();
+    format!("\"{escaped}\"")
+}
+
+fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn validate_fn_name(name: &str) -> Result<(), GeneratorError> {
+    if !is_valid_identifier(name) {
+        return Err(GeneratorError::InvalidOption {
+            option: "fn_name",
+            reason: format!("`{name}` is not a valid identifier"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_contract_names(name: &str, vk_name: &str) -> Result<(), GeneratorError> {
+    for (option, name) in [("contract_name", name), ("vk_contract_name", vk_name)] {
+        if !is_valid_identifier(name) {
+            return Err(GeneratorError::InvalidOption {
+                option,
+                reason: format!("`{name}` is not a valid identifier"),
+            });
         }
     }
+    if name == vk_name {
+        return Err(GeneratorError::InvalidOption {
+            option: "vk_contract_name",
+            reason: format!("`{name}` is also the name of verifier"),
+        });
+    }
+    Ok(())
+}
 
-    /// Set `AccumulatorEncoding`.
-    pub fn set_acc_encoding(mut self, acc_encoding: Option<AccumulatorEncoding>) -> Self {
-        self.acc_encoding = acc_encoding;
-        self
+// Accept version expressions like `0.8.20`, `^0.8.0`, `>=0.8.0 <0.9.0`, `0.8.0 - 0.8.20` or
+// `^0.7.0 || ^0.8.0`.
+fn validate_pragma(version: &str) -> Result<(), GeneratorError> {
+    let is_valid_version = |version: &str| {
+        let parts = version.split('.').collect_vec();
+        (1..=3).contains(&parts.len())
+            && parts.iter().all(|part| {
+                matches!(*part, "x" | "X" | "*")
+                    || (!part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+            })
+    };
+    let is_valid_token = |token: &str| {
+        matches!(token, "||" | "-")
+            || is_valid_version(
+                ["^", "~", ">=", "<=", ">", "<", "="]
+                    .into_iter()
+                    .find_map(|op| token.strip_prefix(op))
+                    .unwrap_or(token),
+            )
+    };
+    let tokens = version.split_whitespace().collect_vec();
+    let is_valid = !tokens.is_empty()
+        && tokens.iter().all(|token| is_valid_token(token))
+        && !matches!(tokens.first(), Some(&"||" | &"-"))
+        && !matches!(tokens.last(), Some(&"||" | &"-"));
+    if is_valid {
+        Ok(())
+    } else {
+        Err(GeneratorError::InvalidOption {
+            option: "pragma_version",
+            reason: format!("`{version}` is not a valid version expression"),
+        })
+    }
+}
+
+// Accept SPDX license expressions like `MIT`, `BUSL-1.1`, `LicenseRef-Proprietary` or
+// `(MIT OR Apache-2.0)`, which must fit in the single line comment header.
+fn validate_license(license: &str) -> Result<(), GeneratorError> {
+    let is_valid_token = |token: &str| {
+        let token = token.trim_start_matches('(').trim_end_matches(')');
+        !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+' | ':'))
+    };
+    let tokens = license.split(' ').collect_vec();
+    let is_valid = tokens.iter().all(|token| is_valid_token(token))
+        && license.matches('(').count() == license.matches(')').count();
+    if is_valid {
+        Ok(())
+    } else {
+        Err(GeneratorError::InvalidOption {
+            option: "license",
+            reason: format!("`{license}` is not a valid SPDX license expression"),
+        })
+    }
+}
+
+// Adapter to render into `io::Write`, which keeps the first I/O error since `fmt::Error` carries
+// no information.
+struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    fn into_error(self) -> io::Error {
+        self.error
+            .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, fmt::Error))
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error.get_or_insert(err);
+            fmt::Error
+        })
     }
 }
 
 impl<'a> SolidityGenerator<'a> {
     /// Render `Halo2Verifier.sol` with verifying key embedded into writer.
     pub fn render_into(&self, verifier_writer: &mut impl fmt::Write) -> Result<(), fmt::Error> {
-        self.generate_verifier(false).render(verifier_writer)
+        render(&self.generate_verifier(false), verifier_writer)
     }
 
     /// Render `Halo2Verifier.sol` with verifying key embedded and return it as `String`.
@@ -135,14 +2051,253 @@ impl<'a> SolidityGenerator<'a> {
         Ok(verifier_output)
     }
 
+    /// Render `Halo2Verifier.sol` with verifying key embedded same as [`Self::render`], and append
+    /// a block comment `/* SAMPLE CALLDATA: 0x... */` with the calldata of given `proof` and
+    /// `instances`, so the file shows what valid calldata looks like.
+    pub fn render_with_calldata_comment(
+        &self,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> Result<String, fmt::Error> {
+        let mut verifier_output = self.render()?;
+        let calldata = self.encode_calldata(None, proof, instances);
+        verifier_output.push_str(&format!(
+            "\n/* SAMPLE CALLDATA: 0x{} */\n",
+            hex::encode(calldata)
+        ));
+        Ok(verifier_output)
+    }
+
+    /// Render `Halo2Verifier` as a standalone Yul object with verifying key embedded and return
+    /// it as `String`.
+    ///
+    /// The Yul object shares the `assembly` block of [`Self::render`], and accepts the same
+    /// calldata of its verify function, but skips the function dispatching and ABI decoding of
+    /// Solidity. It's expected to be compiled by `solc --strict-assembly`.
+    ///
+    /// # Panics
+    /// Panics if any option is dispatched by Solidity, see [`Self::check_yul_support`].
+    pub fn render_yul(&self) -> Result<String, fmt::Error> {
+        if let Err(err) = self.check_yul_support() {
+            panic!("{err}");
+        }
+        let yul = Halo2VerifierYul {
+            verifier: self.generate_verifier(false),
+            fn_signature: self.fn_signature_with_name(&self.options.fn_name, false),
+            fn_sig: u32::from_be_bytes(self.fn_sig(false)),
+        };
+        let mut yul_output = String::new();
+        render(&yul, &mut yul_output)?;
+        Ok(yul.inline_constants(&yul_output))
+    }
+
+    /// Check if the verifier can be rendered as a Yul object by [`Self::render_yul`], which only
+    /// dispatches the verify function, so options adding functions or hooks dispatched by Solidity
+    /// are unsupported.
+    pub fn check_yul_support(&self) -> Result<(), GeneratorError> {
+        let options = [
+            ("batch_verify", self.options.batch_verify),
+            ("verify_and_store", self.options.verify_and_store),
+            ("on_verified_hook", self.options.on_verified_hook),
+            (
+                "packed_instances",
+                self.options.instance_bit_widths.is_some(),
+            ),
+            ("nested_instances", self.options.nested_instances),
+        ];
+        match options.into_iter().find(|(_, enabled)| *enabled) {
+            Some((option, _)) => Err(GeneratorError::Unsupported(
+                "Yul object",
+                format!("`{option}` is dispatched by Solidity"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Render `IHalo2Verifier.sol`, the interface of `Halo2Verifier.sol` rendered with verifying
+    /// key embedded, and return it as `String`.
+    ///
+    /// The interface is named after the verifier with prefix `I`, and has NatSpec describing the
+    /// calldata layout, so integrators can call the verifier without importing it.
+    pub fn render_interface(&self) -> Result<String, fmt::Error> {
+        let mut interface_output = String::new();
+        render(&self.generate_interface(false), &mut interface_output)?;
+        Ok(interface_output)
+    }
+
+    /// Render `IHalo2Verifier.sol`, the interface of `Halo2Verifier.sol` rendered by
+    /// `render_separately`, whose verify function takes verifying key address as the first
+    /// parameter, and return it as `String`.
+    pub fn render_separately_interface(&self) -> Result<String, fmt::Error> {
+        let mut interface_output = String::new();
+        render(&self.generate_interface(true), &mut interface_output)?;
+        Ok(interface_output)
+    }
+
+    /// Render `Halo2VerifierRegistry.sol` and return it as `String`, which dispatches proofs to
+    /// the verifier rendered by `render_separately` only with verifying keys registered by owner.
+    ///
+    /// The registry is named after the verifier with suffix `Registry`, and takes the verifier
+    /// address as constructor argument. Its verify function takes the same calldata as the
+    /// verifier, and verifying keys are registered with code hash returned by
+    /// [`Self::vk_codehash`]. With [`SolidityGeneratorBuilder::vk_address_source`] other than
+    /// `VkAddressSource::Calldata`, it checks the verifying key at `vkAddress()` of the verifier
+    /// instead, e.g. to reject proofs after an `Ownable` verifier is rotated to an unregistered
+    /// one. Calldata is forwarded by `call` unless the verifier is `view`, so the event and hook
+    /// of the verifier still work.
+    pub fn render_registry(&self) -> Result<String, fmt::Error> {
+        let registry = Halo2VerifierRegistry {
+            pragma: self.options.pragma.clone(),
+            license: self.options.license.clone(),
+            registry_name: format!("{}Registry", self.options.contract_name),
+            contract_name: self.options.contract_name.clone(),
+            fn_name: self.options.fn_name.clone(),
+            view: self.options.view,
+            vk_param: self.vk_param(true),
+            hashed_instances: self.options.hashed_instances,
+            deferred_pairing: self.options.deferred_pairing,
+        };
+        let mut registry_output = String::new();
+        render(&registry, &mut registry_output)?;
+        Ok(registry_output)
+    }
+
+    /// Render `Halo2PairingAggregator.sol` and return it as `String`, which checks pairings
+    /// returned by the verifier rendered with [`SolidityGeneratorBuilder::deferred_pairing`] all at
+    /// once.
+    ///
+    /// The aggregator is named after the verifier with suffix `PairingAggregator`, and embeds
+    /// `g2` and `s_g2` of `VerifierParams`, so it only checks pairings of verifiers with the same
+    /// params. Its `verifyPairings(uint256[4][] pairings)` combines pairings by random linear
+    /// combination with challenge derived from all of them, and performs a single `ecPairing`.
+    pub fn render_pairing_aggregator(&self) -> Result<String, fmt::Error> {
+        let g2 = g2_to_u256s(self.params.g2);
+        let neg_s_g2 = g2_to_u256s(-self.params.s_g2);
+        let aggregator = Halo2PairingAggregator {
+            pragma: self.options.pragma.clone(),
+            license: self.options.license.clone(),
+            aggregator_name: format!("{}PairingAggregator", self.options.contract_name),
+            contract_name: self.options.contract_name.clone(),
+            fn_name: self.options.fn_name.clone(),
+            g2_constants: [
+                ("g2_x_1", g2[0]),
+                ("g2_x_2", g2[1]),
+                ("g2_y_1", g2[2]),
+                ("g2_y_2", g2[3]),
+                ("neg_s_g2_x_1", neg_s_g2[0]),
+                ("neg_s_g2_x_2", neg_s_g2[1]),
+                ("neg_s_g2_y_1", neg_s_g2[2]),
+                ("neg_s_g2_y_2", neg_s_g2[3]),
+            ]
+            .map(|(name, chunk)| (name.to_string(), chunk))
+            .to_vec(),
+        };
+        let mut aggregator_output = String::new();
+        render(&aggregator, &mut aggregator_output)?;
+        Ok(aggregator_output)
+    }
+
+    /// Render `hardhat.config.js` deploying the contracts rendered by `render_separately` to
+    /// `network` at `rpc_url` with plugin `hardhat-deploy`, and return it as `String`.
+    ///
+    /// The contracts are expected at `contracts/` with [`Self::suggested_filename`] and
+    /// [`Self::suggested_vk_filename`], and the script rendered by
+    /// [`Self::render_hardhat_deploy_script`] at `deploy/`. The compiler version is the lowest
+    /// one allowed by pragma, and the deployer account is read from env `PRIVATE_KEY`.
+    pub fn render_hardhat_config(
+        &self,
+        network: &str,
+        rpc_url: &str,
+    ) -> Result<String, fmt::Error> {
+        let config = HardhatConfig {
+            verifier_filename: self.suggested_filename(),
+            vk_filename: self.suggested_vk_filename(),
+            solc_version: solc_version_from_pragma(&self.options.pragma),
+            network: js_string(network),
+            rpc_url: js_string(rpc_url),
+        };
+        let mut config_output = String::new();
+        render(&config, &mut config_output)?;
+        Ok(config_output)
+    }
+
+    /// Render deploy script of `hardhat-deploy` deploying the verifying key then the verifier
+    /// rendered by `render_separately`, passing the verifying key address to the constructor
+    /// unless it's read from calldata, and return it as `String`.
+    pub fn render_hardhat_deploy_script(&self) -> Result<String, fmt::Error> {
+        let script = HardhatDeployScript {
+            contract_name: self.options.contract_name.clone(),
+            vk_contract_name: self.options.vk_contract_name.clone(),
+            fn_name: self.options.fn_name.clone(),
+            vk_address_source: self.options.vk_address_source,
+        };
+        let mut script_output = String::new();
+        render(&script, &mut script_output)?;
+        Ok(script_output)
+    }
+
+    /// Render `VerifierTest.t.sol` of Foundry, which deploys the verifier rendered by
+    /// [`Self::render`] and calls it with calldata of given `proof` and `instances`, asserting it
+    /// verifies, and with the same calldata but lowest bit of the first proof word flipped,
+    /// asserting it reverts or returns `false`.
+    ///
+    /// The verifier is expected at `src/` with [`Self::suggested_filename`], and the test at
+    /// `test/` with `forge-std` installed, then `forge test` runs both cases. With
+    /// [`SolidityGeneratorBuilder::on_verified_hook`], the test deploys a subclass of the verifier
+    /// base with a no-op hook.
+    ///
+    /// # Panics
+    /// Panics with [`SolidityGeneratorBuilder::deferred_pairing`], whose verify function returns
+    /// pairing instead of result, or with a proof shorter than a word.
+    pub fn render_foundry_test(
+        &self,
+        proof: &[u8],
+        instances: &[impl AsRef<[bn256::Fr]>],
+    ) -> Result<String, fmt::Error> {
+        assert!(
+            !self.options.deferred_pairing,
+            "Verify function returns pairing instead of result"
+        );
+        assert!(
+            proof.len() >= 0x20,
+            "Expected proof of at least 32 bytes but got {}",
+            proof.len()
+        );
+        let mut tampered_proof = proof.to_vec();
+        tampered_proof[0x1f] ^= 1;
+        let test = FoundryTest {
+            pragma: self.options.pragma.clone(),
+            license: self.options.license.clone(),
+            test_name: format!("{}Test", self.options.contract_name),
+            contract_name: self.options.contract_name.clone(),
+            verifier_filename: self.suggested_filename(),
+            fn_name: self.options.fn_name.clone(),
+            on_verified_hook: self.options.on_verified_hook,
+            calldata: hex::encode(self.encode_calldata(None, proof, instances)),
+            tampered_calldata: hex::encode(self.encode_calldata(None, &tampered_proof, instances)),
+        };
+        let mut test_output = String::new();
+        render(&test, &mut test_output)?;
+        Ok(test_output)
+    }
+
+    /// Returns `keccak256` of the runtime code of verifying key contract rendered by
+    /// `render_separately`, which is `extcodehash` of the deployed verifying key.
+    ///
+    /// The runtime code is the verifying key itself returned by the constructor, so it's computed
+    /// without compiling, and it differs from `keccak256` of the creation bytecode.
+    pub fn vk_codehash(&self) -> [u8; 32] {
+        sha3::Keccak256::digest(self.generate_vk().to_bytes()).into()
+    }
+
     /// Render `Halo2Verifier.sol` and `Halo2VerifyingKey.sol` into writers.
     pub fn render_separately_into(
         &self,
         verifier_writer: &mut impl fmt::Write,
         vk_writer: &mut impl fmt::Write,
     ) -> Result<(), fmt::Error> {
-        self.generate_verifier(true).render(verifier_writer)?;
-        self.generate_vk().render(vk_writer)?;
+        render(&self.generate_verifier(true), verifier_writer)?;
+        render(&self.generate_vk(), vk_writer)?;
         Ok(())
     }
 
@@ -154,11 +2309,660 @@ impl<'a> SolidityGenerator<'a> {
         Ok((verifier_output, vk_output))
     }
 
+    /// Render `Halo2Verifier.sol` with verifying key embedded of both `a` and `b`, and return
+    /// the unified diff from `a` to `b`, which is empty if they are rendered the same, e.g. to
+    /// review what changes in verifier when the circuit is upgraded.
+    ///
+    /// # Panics
+    /// Panics if either fails to render.
+    pub fn diff(a: &SolidityGenerator, b: &SolidityGenerator) -> String {
+        let [(a_name, a_output), (b_name, b_output)] = [a, b].map(|generator| {
+            let output = generator
+                .render()
+                .unwrap_or_else(|err| panic!("Failed to render verifier: {err}"));
+            (generator.suggested_filename(), output)
+        });
+        similar::TextDiff::from_lines(&a_output, &b_output)
+            .unified_diff()
+            .header(&format!("a/{a_name}"), &format!("b/{b_name}"))
+            .to_string()
+    }
+
+    /// Render `Halo2Verifier.sol` with verifying key embedded and return it as `String` with
+    /// `VerifierMeta`.
+    pub fn render_with_meta(&self) -> Result<(String, VerifierMeta), fmt::Error> {
+        Ok((self.render()?, self.verifier_meta(false)))
+    }
+
+    /// Render `Halo2Verifier.sol` and `Halo2VerifyingKey.sol` and return them as `String` with
+    /// `VerifierMeta`.
+    pub fn render_separately_with_meta(
+        &self,
+    ) -> Result<(String, String, VerifierMeta), fmt::Error> {
+        let (verifier_output, vk_output) = self.render_separately()?;
+        Ok((verifier_output, vk_output, self.verifier_meta(true)))
+    }
+
+    fn verifier_meta(&self, separate: bool) -> VerifierMeta {
+        let proof_len = self.meta.proof_len(self.options.scheme);
+        let num_instances =
+            self.options.num_circuits * self.options.num_instances.iter().sum::<usize>();
+        let num_commitments = self.meta.num_advices().iter().sum::<usize>()
+            + self.meta.batch_open_proof_len(self.options.scheme) / 0x40;
+        // Selector, (vk address), offsets and lengths of proof and instances, then the contents,
+        // or with only offset and length of proof and the instances hash if instances are hashed
+        let vk_address_len = if self.vk_param(separate) { 0x20 } else { 0 };
+        let calldata_len = if self.options.hashed_instances {
+            4 + vk_address_len + 3 * 0x20 + proof_len
+        } else {
+            4 + vk_address_len + 4 * 0x20 + proof_len + num_instances * 0x20
+        };
+        VerifierMeta {
+            proof_len,
+            num_evals: self.meta.num_evals,
+            num_commitments,
+            calldata_len,
+            num_instances,
+        }
+    }
+
+    /// Render `Halo2Verifier.sol` with verifying key embedded into `io::Write` sink, e.g. `File`,
+    /// without holding the whole output in memory.
+    pub fn render_to_io(&self, verifier_writer: &mut impl io::Write) -> io::Result<()> {
+        let mut verifier_writer = IoWriter::new(verifier_writer);
+        match self.render_into(&mut verifier_writer) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(verifier_writer.into_error()),
+        }
+    }
+
+    /// Render `Halo2Verifier.sol` and `Halo2VerifyingKey.sol` into `io::Write` sinks, e.g. `File`,
+    /// without holding the whole outputs in memory.
+    pub fn render_separately_to_io(
+        &self,
+        verifier_writer: &mut impl io::Write,
+        vk_writer: &mut impl io::Write,
+    ) -> io::Result<()> {
+        let mut verifier_writer = IoWriter::new(verifier_writer);
+        let mut vk_writer = IoWriter::new(vk_writer);
+        match self.render_separately_into(&mut verifier_writer, &mut vk_writer) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(verifier_writer
+                .error
+                .or(vk_writer.error)
+                .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, fmt::Error))),
+        }
+    }
+
+    /// Render `Halo2VerifyingKey.sol` only and return it as `String`, to be used with the
+    /// verifier already deployed, which is rendered separately with `deployed_vk`.
+    ///
+    /// Returns [`IncompatibilityReason`] if the deployed verifier can't verify proofs of this
+    /// circuit, see [`Self::is_compatible_with`].
+    pub fn render_vk_only(
+        &self,
+        deployed_vk: &VerifyingKey<bn256::G1Affine>,
+    ) -> Result<String, IncompatibilityReason> {
+        self.is_compatible_with(deployed_vk)?;
+        let mut vk_output = String::new();
+        render(&self.generate_vk(), &mut vk_output).expect("Rendering into String never fails");
+        Ok(vk_output)
+    }
+
+    /// Render `Halo2VerifyingKey.sol`, deserialize the constants and commitments it stores back
+    /// into field elements and curve points, and check they match the `VerifyingKey` and
+    /// `VerifierParams` the generator is created with.
+    ///
+    /// It's an invariant that always holds, meant to be checked in tests to catch bugs of
+    /// rendering the verifying key.
+    pub fn verify_vk_consistency(&self) -> Result<(), InconsistencyError> {
+        let mut vk_output = String::new();
+        render(&self.generate_vk(), &mut vk_output).expect("Rendering into String never fails");
+        self.check_vk_consistency(&vk_output)
+    }
+
+    /// Check constants and commitments stored by given `Halo2VerifyingKey.sol` match the
+    /// `VerifyingKey` and `VerifierParams`.
+    pub(crate) fn check_vk_consistency(&self, vk_solidity: &str) -> Result<(), InconsistencyError> {
+        let words = parse_vk_words(vk_solidity)?;
+        let word = |name: &str| {
+            words
+                .get(name)
+                .copied()
+                .ok_or_else(|| InconsistencyError::Missing(name.to_string()))
+        };
+        let fq = |name: &str| {
+            Option::<bn256::Fq>::from(bn256::Fq::from_repr(word(name)?.to_le_bytes::<0x20>()))
+                .ok_or_else(|| InconsistencyError::InvalidValue(name.to_string()))
+        };
+        let fr = |name: &str| {
+            Option::<bn256::Fr>::from(bn256::Fr::from_repr(word(name)?.to_le_bytes::<0x20>()))
+                .ok_or_else(|| InconsistencyError::InvalidValue(name.to_string()))
+        };
+        let g1 = |name: &str, [x, y]: [String; 2]| {
+            Option::<bn256::G1Affine>::from(bn256::G1Affine::from_xy(fq(&x)?, fq(&y)?))
+                .ok_or_else(|| InconsistencyError::InvalidValue(name.to_string()))
+        };
+        let g2 = |name: &str| {
+            let [x_1, x_2, y_1, y_2] = ["x_1", "x_2", "y_1", "y_2"].map(|c| format!("{name}_{c}"));
+            let x = bn256::Fq2 {
+                c0: fq(&x_2)?,
+                c1: fq(&x_1)?,
+            };
+            let y = bn256::Fq2 {
+                c0: fq(&y_2)?,
+                c1: fq(&y_1)?,
+            };
+            Option::<bn256::G2Affine>::from(bn256::G2Affine::from_xy(x, y))
+                .ok_or_else(|| InconsistencyError::InvalidValue(name.to_string()))
+        };
+        let mismatch = |name: &str| Err(InconsistencyError::Mismatch(name.to_string()));
+
+        let domain = self.vk.get_domain();
+        let l = self.meta.rotation_last.unsigned_abs() as u64;
+        for (name, expected) in [
+            ("vk_digest", self.vk.transcript_repr()),
+            ("n_inv", bn256::Fr::from(1 << domain.k()).invert().unwrap()),
+            ("omega", domain.get_omega()),
+            ("omega_inv", domain.get_omega_inv()),
+            ("omega_inv_to_l", domain.get_omega_inv().pow_vartime([l])),
+        ] {
+            if fr(name)? != expected {
+                return mismatch(name);
+            }
+        }
+
+        let acc_encoding = self
+            .options
+            .acc_encoding
+            .unwrap_or(AccumulatorEncoding::new(0, 0, 0));
+        for (name, expected) in chain![
+            [
+                ("num_instances", self.options.num_instances.iter().sum()),
+                ("k", domain.k() as usize),
+                (
+                    "has_accumulator",
+                    self.options.acc_encoding.is_some() as usize
+                ),
+                ("acc_offset", acc_encoding.offset),
+                ("num_acc_limbs", acc_encoding.num_limbs),
+                ("num_acc_limb_bits", acc_encoding.num_limb_bits),
+            ]
+            .map(|(name, expected)| (name.to_string(), expected)),
+            self.options
+                .num_instances
+                .iter()
+                .enumerate()
+                .map(|(idx, num)| (format!("num_instances_{idx}"), *num)),
+        ] {
+            if word(&name)? != U256::from(expected) {
+                return mismatch(&name);
+            }
+        }
+
+        if g1("g1", ["g1_x".to_string(), "g1_y".to_string()])? != self.params.g1 {
+            return mismatch("g1");
+        }
+        if g2("g2")? != self.params.g2 {
+            return mismatch("g2");
+        }
+        if g2("neg_s_g2")? != -self.params.s_g2 {
+            return mismatch("neg_s_g2");
+        }
+
+        for (kind, comms) in [
+            ("fixed", self.vk.fixed_commitments()),
+            ("permutation", self.vk.permutation().commitments()),
+        ] {
+            let prefix = format!("{kind}_comms[");
+            let actual = words
+                .keys()
+                .filter(|name| name.starts_with(&prefix))
+                .count()
+                / 2;
+            if actual != comms.len() {
+                return Err(InconsistencyError::NumCommitments {
+                    kind,
+                    expected: comms.len(),
+                    actual,
+                });
+            }
+            for (idx, comm) in comms.iter().enumerate() {
+                let name = format!("{kind}_comms[{idx}]");
+                if g1(&name, [format!("{name}.x"), format!("{name}.y")])? != *comm {
+                    return mismatch(&name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Estimate gas cost of `verifyProof` with verifying key embedded, by deploying the rendered
+    /// `Halo2Verifier.sol` into an ephemeral [`Evm`](crate::Evm) and calling it with given `proof`
+    /// and `instances`.
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, compilation fails, or verification fails.
+    #[cfg(feature = "evm")]
+    pub fn estimate_gas(&self, proof: &[u8], instances: &[impl AsRef<[bn256::Fr]>]) -> u64 {
+        use crate::evm::test::{compile_solidity, Evm};
+
+        let verifier_creation_code = compile_solidity(self.render().unwrap());
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(verifier_creation_code);
+        let (gas_cost, output) = evm.call(
+            verifier_address,
+            self.encode_calldata(None, proof, instances),
+        );
+        assert_eq!(
+            output,
+            [vec![0; 31], vec![1]].concat(),
+            "Proof verification fails"
+        );
+        gas_cost
+    }
+
+    /// Compile the verifier rendered by `render` and return size of its runtime bytecode, or
+    /// [`BytecodeTooLargeError`] if it exceeds 24576 bytes and can't be deployed after EIP-170.
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, or compilation fails.
+    #[cfg(feature = "evm")]
+    pub fn check_bytecode_size(&self) -> Result<usize, BytecodeTooLargeError> {
+        use crate::evm::test::compile_solidity_runtime;
+
+        let size = compile_solidity_runtime(self.render().unwrap()).len();
+        if size > BytecodeTooLargeError::LIMIT {
+            return Err(BytecodeTooLargeError { size });
+        }
+        Ok(size)
+    }
+
+    /// Estimate gas cost of `verifyProof` with verifying key embedded from the shape of circuit,
+    /// without compiling or executing the verifier.
+    ///
+    /// Intrinsic cost, calldata, precompile calls and `keccak256` are priced by the gas schedule,
+    /// assuming proof and instances have no zero byte, and the rest is priced by rough costs
+    /// per item. So it's meant to catch gas regressions cheaply, and `estimate_gas` should be
+    /// used for the exact number.
+    pub fn estimate_gas_statically(&self) -> u64 {
+        const TX_BASE: usize = 21000;
+        const CALLDATA_ZERO_BYTE: usize = 4;
+        const CALLDATA_NONZERO_BYTE: usize = 16;
+        const WARM_STATICCALL: usize = 100;
+        const EC_ADD: usize = 150;
+        const EC_MUL: usize = 6000;
+        const EC_PAIRING_2: usize = 45000 + 2 * 34000;
+        // Modexp with 32 bytes base and modulus, and exponent `r - 2` of 254 bits (EIP-2565)
+        const MODEXP_INV: usize = 16 * 253 / 3;
+        const KECCAK256: usize = 30;
+        const KECCAK256_WORD: usize = 6;
+        const MEMORY_WORD: usize = 3;
+        // Rough costs of arithmetic and control flow
+        const DISPATCH: usize = 500;
+        const PER_EC_POINT: usize = 120;
+        const PER_EVAL: usize = 50;
+        const PER_INSTANCE: usize = 110;
+        const PER_LAGRANGE: usize = 150;
+        const PER_SQUARING: usize = 30;
+        const PER_VK_WORD: usize = 12;
+        const PER_EC_CALL: usize = 60;
+        const PER_LINE: usize = 30;
+
+        let verifier = self.generate_verifier(false);
+        let vk = verifier.embedded_vk.as_ref().unwrap();
+        let data = Data::new_per_circuit(&self.meta, vk, verifier.vk_mptr, verifier.proof_cptr);
+        let num_circuit_instances = self.options.num_instances.iter().sum::<usize>();
+        let num_instances = self.options.num_circuits * num_circuit_instances;
+        let VerifierMeta {
+            proof_len,
+            num_evals,
+            num_commitments,
+            ..
+        } = self.verifier_meta(false);
+
+        // Selector and 4 words of offsets and lengths, which have at most 2 nonzero bytes
+        let calldata = 4 * CALLDATA_NONZERO_BYTE
+            + 4 * (2 * CALLDATA_NONZERO_BYTE + 30 * CALLDATA_ZERO_BYTE)
+            + (proof_len + num_instances * 0x20) * CALLDATA_NONZERO_BYTE;
+
+        let precompiles = {
+            let num_ec_muls = self.options.scheme.num_ec_muls(&self.meta, &data)
+                + (self.meta.num_quotients - 1)
+                + if self.options.acc_encoding.is_some() {
+                    2
+                } else {
+                    0
+                };
+            let num_modexps = 1 + self.options.scheme.num_batch_inverts();
+            num_ec_muls * (EC_MUL + EC_ADD + 2 * (WARM_STATICCALL + PER_EC_CALL))
+                + num_modexps * (MODEXP_INV + WARM_STATICCALL)
+                + EC_PAIRING_2
+                + WARM_STATICCALL
+        };
+
+        let keccak256 = {
+            let transcript_words = izip!(0.., &verifier.num_advices, &verifier.num_challenges)
+                .flat_map(|(phase, num_advices, num_challenges)| {
+                    let num_instances = if phase == 0 { num_instances } else { 0 };
+                    chain![
+                        [1 + num_instances + 2 * num_advices],
+                        // Squeezing without new input hashes 0x21 bytes
+                        iter::repeat(2).take(num_challenges - 1)
+                    ]
+                });
+            let opening_words = match self.options.scheme {
+                BatchOpenScheme::Bdfg21 => vec![1 + num_evals, 2, 1 + 2],
+                BatchOpenScheme::Gwc19 => vec![1 + num_evals, 1 + 2 * self.meta.num_rotations],
+            };
+            let acc_words = self.options.acc_encoding.map(|_| 8);
+            chain![transcript_words, opening_words, acc_words]
+                .map(|num_words| KECCAK256 + num_words * KECCAK256_WORD)
+                .sum::<usize>()
+        };
+
+        let memory = {
+            let num_words = (verifier.vk_mptr.value().as_usize() + vk.len()) / 0x20;
+            num_words * MEMORY_WORD + num_words * num_words / 512
+        };
+
+        let execution = {
+            let num_lagranges = num_circuit_instances.max(1)
+                + verifier.num_neg_lagranges
+                + verifier.num_extra_lagranges
+                + 1;
+            let num_lines = chain![
+                &verifier.quotient_eval_numer_computations,
+                &verifier.pcs_computations
+            ]
+            .flatten()
+            .map(|line| line.trim())
+            .filter(|line| {
+                !(matches!(*line, "" | "for" | "{" | "}") || line.starts_with("success := ec_"))
+            })
+            .count();
+            DISPATCH
+                + num_commitments * PER_EC_POINT
+                + num_evals * PER_EVAL
+                + num_instances * PER_INSTANCE
+                + num_lagranges * PER_LAGRANGE
+                + self.vk.get_domain().k() as usize * PER_SQUARING
+                + vk.len() / 0x20 * PER_VK_WORD
+                + num_lines * PER_LINE
+        };
+
+        (TX_BASE + calldata + precompiles + keccak256 + memory + execution) as u64
+    }
+
+    fn generate_interface(&self, separate: bool) -> IHalo2Verifier {
+        let meta = self.verifier_meta(separate);
+        let vk_param = self.vk_param(separate);
+        let proof_cptr = if vk_param { 0x84 } else { 0x64 };
+        let instances_cptr = proof_cptr + meta.proof_len;
+        let calldata_layout = chain![
+            [(
+                0x00,
+                0x04,
+                format!("selector `0x{}`", hex::encode(self.fn_sig(vk_param)))
+            )],
+            vk_param.then(|| (0x04, 0x24, "address `vk`".to_string())),
+            if self.options.hashed_instances {
+                vec![
+                    (
+                        proof_cptr - 0x60,
+                        proof_cptr - 0x40,
+                        "offset of `proof`".to_string(),
+                    ),
+                    (
+                        proof_cptr - 0x40,
+                        proof_cptr - 0x20,
+                        "`instancesHash`".to_string(),
+                    ),
+                ]
+            } else {
+                vec![(
+                    proof_cptr - 0x60,
+                    proof_cptr - 0x20,
+                    "offsets of `proof` and `instances`".to_string(),
+                )]
+            },
+            [
+                (
+                    proof_cptr - 0x20,
+                    proof_cptr,
+                    format!("length of `proof`, which is {}", meta.proof_len),
+                ),
+                (proof_cptr, instances_cptr, "`proof`".to_string()),
+            ],
+            if self.options.hashed_instances {
+                Vec::new()
+            } else {
+                vec![
+                    (
+                        instances_cptr,
+                        instances_cptr + 0x20,
+                        format!("length of `instances`, which is {}", meta.num_instances),
+                    ),
+                    (
+                        instances_cptr + 0x20,
+                        meta.calldata_len,
+                        "`instances`, each in 32 bytes".to_string(),
+                    ),
+                ]
+            },
+        ]
+        .collect();
+
+        IHalo2Verifier {
+            pragma: self.options.pragma.clone(),
+            license: self.options.license.clone(),
+            interface_name: format!("I{}", self.options.contract_name),
+            contract_name: self.options.contract_name.clone(),
+            fn_name: self.options.fn_name.clone(),
+            on_failure: self.options.on_failure,
+            view: self.options.view,
+            emit_event: self.options.emit_event,
+            deferred_pairing: self.options.deferred_pairing,
+            verify_and_store: self.options.verify_and_store,
+            batch_verify: self.options.batch_verify,
+            hashed_instances: self.options.hashed_instances,
+            packed_instances: self.options.instance_bit_widths.is_some(),
+            nested_instances: self.options.nested_instances,
+            num_proofs: self.options.num_proofs,
+            separate: vk_param,
+            calldata_layout,
+        }
+    }
+
+    /// Whether the verify function takes verifying key address as the first parameter.
+    fn vk_param(&self, separate: bool) -> bool {
+        separate && self.options.vk_address_source == VkAddressSource::Calldata
+    }
+
+    /// Lines evaluating instances split from `instances_hash` by lagrange basis at `l_i_cptr`,
+    /// which are empty when instances are not hashed.
+    fn hashed_instance_computations(&self) -> Vec<String> {
+        if !self.options.hashed_instances {
+            return Vec::new();
+        }
+        let instances = [
+            "shr(128, instances_hash)",
+            "and(instances_hash, 0xffffffffffffffffffffffffffffffff)",
+        ];
+        let positions = self
+            .options
+            .num_instances
+            .iter()
+            .enumerate()
+            .flat_map(|(column, num)| (0..*num).map(move |row| (column, row)));
+        chain![
+            (0..self.options.num_instances.len())
+                .map(|column| format!("let instance_eval_{column} := 0")),
+            izip!(positions, instances).map(|((column, row), instance)| {
+                let eval = format!("instance_eval_{column}");
+                let l_i = format!("mload(add(l_i_cptr, {:#x}))", row * 0x20);
+                format!("{eval} := addmod({eval}, mulmod({l_i}, {instance}, r), r)")
+            }),
+        ]
+        .collect()
+    }
+
+    /// Lines evaluating instance column at rotated `x` for each rotated instance query, by the
+    /// Lagrange bases from `l_i_cptr` shifted by rotation, into variables returned by
+    /// [`Self::rotated_instance_evals`].
+    fn rotated_instance_computations(&self) -> Vec<String> {
+        let l_i_mptr = |offset: i64| match offset.cmp(&0) {
+            Ordering::Less => format!("sub(l_i_cptr, {:#x})", -offset * 0x20),
+            Ordering::Equal => "l_i_cptr".to_string(),
+            Ordering::Greater => format!("add(l_i_cptr, {:#x})", offset * 0x20),
+        };
+        izip!(
+            &self.meta.rotated_instance_queries,
+            self.rotated_instance_evals()
+        )
+        .flat_map(|(&(column, rotation), eval)| {
+            let computations = if self.options.hashed_instances {
+                let instances = [
+                    "shr(128, instances_hash)",
+                    "and(instances_hash, 0xffffffffffffffffffffffffffffffff)",
+                ];
+                let positions = self
+                    .options
+                    .num_instances
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(column, num)| (0..*num).map(move |row| (column, row)));
+                izip!(positions, instances)
+                    .filter(|((instance_column, _), _)| *instance_column == column)
+                    .map(|((_, row), instance)| {
+                        let l_i = format!("mload({})", l_i_mptr(row as i64 - rotation as i64));
+                        format!("{eval} := addmod({eval}, mulmod({l_i}, {instance}, r), r)")
+                    })
+                    .collect_vec()
+            } else {
+                // Instances are laid out column by column, so skip the ones of previous columns
+                let cptr = (0..column).fold("INSTANCE_CPTR".to_string(), |acc, idx| {
+                    format!("add({acc}, mul(0x20, mload(NUM_INSTANCES_{idx}_MPTR)))")
+                });
+                let len = format!("mul(0x20, mload(NUM_INSTANCES_{column}_MPTR))");
+                let instance = load_instance(self.options.instance_byte_order, "cptr");
+                let item = format!("mulmod(mload(l_i_mptr), {instance}, r)");
+                for_loop(
+                    [
+                        format!("let cptr := {cptr}"),
+                        format!("let cptr_end := add(cptr, {len})"),
+                        format!("let l_i_mptr := {}", l_i_mptr(-rotation as i64)),
+                    ],
+                    "lt(cptr, cptr_end)",
+                    ["cptr := add(cptr, 0x20)", "l_i_mptr := add(l_i_mptr, 0x20)"],
+                    [format!("{eval} := addmod({eval}, {item}, r)")],
+                )
+            };
+            chain![[format!("let {eval} := 0")], computations]
+        })
+        .collect()
+    }
+
+    /// Variables of instance evaluations of rotated instance queries, which are stored after the
+    /// ones of instance columns.
+    fn rotated_instance_evals(&self) -> Vec<String> {
+        self.meta
+            .rotated_instance_queries
+            .iter()
+            .map(|(column, rotation)| column_eval_var("instance_eval", *column, *rotation))
+            .collect()
+    }
+
+    /// Lines unpacking instances from `cptr` of `packedInstances` into `mptr` of `instances`, and
+    /// checking each is in range of its bit width, which are empty when instances are not packed.
+    ///
+    /// Consecutive instances with the same bit width are unpacked in a loop.
+    fn packed_instance_computations(&self) -> Vec<String> {
+        let bit_widths = match &self.options.instance_bit_widths {
+            Some(bit_widths) => bit_widths,
+            None => return Vec::new(),
+        };
+        bit_widths
+            .iter()
+            .dedup_with_count()
+            .flat_map(|(count, bit_width)| {
+                let num_bytes = (bit_width + 7) / 8;
+                let range_check = (*bit_width < 8 * num_bytes).then(|| {
+                    let bound = U256::from(1) << *bit_width;
+                    format!("success := and(success, lt(instance, 0x{bound:x}))")
+                });
+                for_loop(
+                    [format!(
+                        "let cptr_end := add(cptr, {:#x})",
+                        count * num_bytes
+                    )],
+                    "lt(cptr, cptr_end)",
+                    None::<String>,
+                    chain![
+                        [format!(
+                            "let instance := shr({:#x}, calldataload(cptr))",
+                            256 - 8 * num_bytes
+                        )],
+                        range_check,
+                        [
+                            "mstore(mptr, instance)".to_string(),
+                            format!("cptr := add(cptr, {num_bytes:#x})"),
+                            "mptr := add(mptr, 0x20)".to_string(),
+                        ],
+                    ],
+                )
+            })
+            .collect()
+    }
+
+    /// Lines reading accumulator from limbs at `acc_indices`, which are empty when accumulator is
+    /// encoded by `AccumulatorEncoding` only and read in a loop instead.
+    fn acc_limb_computations(&self) -> Vec<String> {
+        let (acc_encoding, indices) = match (self.options.acc_encoding, &self.options.acc_indices) {
+            (Some(acc_encoding), Some(indices)) => (acc_encoding, indices),
+            _ => return Vec::new(),
+        };
+        let offsets = accumulator_offsets(&self.options.num_instances, indices).unwrap();
+        let names = ["lhs_x", "lhs_y", "rhs_x", "rhs_y"];
+        izip!(names, offsets.chunks(acc_encoding.num_limbs))
+            .flat_map(|(name, offsets)| {
+                offsets.iter().enumerate().map(move |(idx, offset)| {
+                    let cptr = format!("add(INSTANCE_CPTR, {:#x})", offset * 0x20);
+                    let limb = load_instance(self.options.instance_byte_order, cptr);
+                    if idx == 0 {
+                        format!("let {name} := {limb}")
+                    } else {
+                        let shift = idx * acc_encoding.num_limb_bits;
+                        format!("{name} := add({name}, shl({shift}, {limb}))")
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn circuit_summary(&self) -> String {
+        let cs = self.vk.cs();
+        format!(
+            "k={}, num_instances={:?}, num_advice_columns={}, num_fixed_columns={}, \
+             num_lookups={}, num_shuffles={}, num_permutation_columns={}, degree={}, scheme={:?}",
+            self.vk.get_domain().k(),
+            self.options.num_instances,
+            cs.num_advice_columns(),
+            self.vk.fixed_commitments().len(),
+            cs.lookups().len(),
+            cs.shuffles().len(),
+            cs.permutation().get_columns().len(),
+            cs.degree(),
+            self.options.scheme,
+        )
+    }
+
     fn generate_vk(&self) -> Halo2VerifyingKey {
         let constants = {
             let domain = self.vk.get_domain();
             let vk_digest = fr_to_u256(self.vk.transcript_repr());
-            let num_instances = U256::from(self.num_instances);
+            let num_instances = U256::from(self.options.num_instances.iter().sum::<usize>());
             let k = U256::from(domain.k());
             let n_inv = fr_to_u256(bn256::Fr::from(1 << domain.k()).invert().unwrap());
             let omega = fr_to_u256(domain.get_omega());
@@ -167,46 +2971,57 @@ impl<'a> SolidityGenerator<'a> {
                 let l = self.meta.rotation_last.unsigned_abs() as u64;
                 fr_to_u256(domain.get_omega_inv().pow_vartime([l]))
             };
-            let has_accumulator = U256::from(self.acc_encoding.is_some() as usize);
+            let has_accumulator = U256::from(self.options.acc_encoding.is_some() as usize);
             let acc_offset = self
+                .options
                 .acc_encoding
                 .map(|acc_encoding| U256::from(acc_encoding.offset))
                 .unwrap_or_default();
             let num_acc_limbs = self
+                .options
                 .acc_encoding
                 .map(|acc_encoding| U256::from(acc_encoding.num_limbs))
                 .unwrap_or_default();
             let num_acc_limb_bits = self
+                .options
                 .acc_encoding
                 .map(|acc_encoding| U256::from(acc_encoding.num_limb_bits))
                 .unwrap_or_default();
-            let g1 = self.params.get_g()[0];
-            let g1 = g1_to_u256s(g1);
-            let g2 = g2_to_u256s(self.params.g2());
-            let neg_s_g2 = g2_to_u256s(-self.params.s_g2());
-            vec![
-                ("vk_digest", vk_digest),
-                ("num_instances", num_instances),
-                ("k", k),
-                ("n_inv", n_inv),
-                ("omega", omega),
-                ("omega_inv", omega_inv),
-                ("omega_inv_to_l", omega_inv_to_l),
-                ("has_accumulator", has_accumulator),
-                ("acc_offset", acc_offset),
-                ("num_acc_limbs", num_acc_limbs),
-                ("num_acc_limb_bits", num_acc_limb_bits),
-                ("g1_x", g1[0]),
-                ("g1_y", g1[1]),
-                ("g2_x_1", g2[0]),
-                ("g2_x_2", g2[1]),
-                ("g2_y_1", g2[2]),
-                ("g2_y_2", g2[3]),
-                ("neg_s_g2_x_1", neg_s_g2[0]),
-                ("neg_s_g2_x_2", neg_s_g2[1]),
-                ("neg_s_g2_y_1", neg_s_g2[2]),
-                ("neg_s_g2_y_2", neg_s_g2[3]),
+            let g1 = g1_to_u256s(self.params.g1);
+            let g2 = g2_to_u256s(self.params.g2);
+            let neg_s_g2 = g2_to_u256s(-self.params.s_g2);
+            chain![
+                [
+                    ("vk_digest", vk_digest),
+                    ("num_instances", num_instances),
+                    ("k", k),
+                    ("n_inv", n_inv),
+                    ("omega", omega),
+                    ("omega_inv", omega_inv),
+                    ("omega_inv_to_l", omega_inv_to_l),
+                    ("has_accumulator", has_accumulator),
+                    ("acc_offset", acc_offset),
+                    ("num_acc_limbs", num_acc_limbs),
+                    ("num_acc_limb_bits", num_acc_limb_bits),
+                    ("g1_x", g1[0]),
+                    ("g1_y", g1[1]),
+                    ("g2_x_1", g2[0]),
+                    ("g2_x_2", g2[1]),
+                    ("g2_y_1", g2[2]),
+                    ("g2_y_2", g2[3]),
+                    ("neg_s_g2_x_1", neg_s_g2[0]),
+                    ("neg_s_g2_x_2", neg_s_g2[1]),
+                    ("neg_s_g2_y_1", neg_s_g2[2]),
+                    ("neg_s_g2_y_2", neg_s_g2[3]),
+                ]
+                .map(|(name, chunk)| (name.to_string(), chunk)),
+                self.options
+                    .num_instances
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, num)| (format!("num_instances_{idx}"), U256::from(*num))),
             ]
+            .collect()
         };
         let fixed_comms = chain![self.vk.fixed_commitments()]
             .flat_map(g1_to_u256s)
@@ -217,6 +3032,10 @@ impl<'a> SolidityGenerator<'a> {
             .tuples()
             .collect();
         Halo2VerifyingKey {
+            pragma: self.options.pragma.clone(),
+            license: self.options.license.clone(),
+            contract_name: self.options.vk_contract_name.clone(),
+            natspec: self.options.natspec.then(|| self.circuit_summary()),
             constants,
             fixed_comms,
             permutation_comms,
@@ -224,62 +3043,130 @@ impl<'a> SolidityGenerator<'a> {
     }
 
     fn generate_verifier(&self, separate: bool) -> Halo2Verifier {
-        let proof_cptr = Ptr::calldata(if separate { 0x84 } else { 0x64 });
+        let proof_cptr = Ptr::calldata(if self.vk_param(separate) { 0x84 } else { 0x64 });
 
         let vk = self.generate_vk();
         let vk_len = vk.len();
+        let vk_codehash = (separate && self.options.vk_codehash_check)
+            .then(|| U256::from_be_bytes::<0x20>(sha3::Keccak256::digest(vk.to_bytes()).into()));
         let vk_mptr = Ptr::memory(self.static_working_memory_size(&vk, proof_cptr));
-        let data = Data::new(&self.meta, &vk, vk_mptr, proof_cptr);
+        let data = Data::new_per_circuit(&self.meta, &vk, vk_mptr, proof_cptr);
 
-        let evaluator = Evaluator::new(self.vk.cs(), &self.meta, &data);
-        let quotient_eval_numer_computations = chain![
-            evaluator.gate_computations(),
-            evaluator.permutation_computations(),
-            evaluator.lookup_computations()
-        ]
-        .enumerate()
-        .map(|(idx, (mut lines, var))| {
-            let line = if idx == 0 {
-                format!("quotient_eval_numer := {var}")
-            } else {
-                format!(
-                    "quotient_eval_numer := addmod(mulmod(quotient_eval_numer, y, r), {var}, r)"
-                )
-            };
-            lines.push(line);
-            lines
-        })
-        .collect();
+        // Expressions of all circuits are folded by `y` circuit by circuit
+        let quotient_eval_numer_computations = data
+            .iter()
+            .flat_map(|data| {
+                let evaluator = self.evaluator(data);
+                chain![
+                    evaluator.gate_computations(),
+                    evaluator.permutation_computations(),
+                    evaluator.lookup_computations(),
+                    evaluator.shuffle_computations()
+                ]
+                .collect_vec()
+            })
+            .enumerate()
+            .map(|(idx, (mut lines, var))| {
+                let line = if idx == 0 {
+                    format!("quotient_eval_numer := {var}")
+                } else {
+                    format!(
+                        "quotient_eval_numer := addmod(mulmod(quotient_eval_numer, y, r), {var}, r)"
+                    )
+                };
+                lines.push(line);
+                lines
+            })
+            .collect();
 
-        let pcs_computations = self.scheme.computations(&self.meta, &data);
+        let pcs_computations = self.options.scheme.computations(&self.meta, &data);
+        let acc_limb_computations = self.acc_limb_computations();
+        let hashed_instance_computations = self.hashed_instance_computations();
+        let rotated_instance_computations = self.rotated_instance_computations();
+        let rotated_instance_evals = self.rotated_instance_evals();
+        let packed_instances_len = self
+            .options
+            .instance_bit_widths
+            .as_ref()
+            .map(|bit_widths| bit_widths.iter().map(|bit_width| (bit_width + 7) / 8).sum());
+        let packed_instance_computations = self.packed_instance_computations();
+        let nested_instances_lens = self.options.nested_instances.then(|| {
+            iter::repeat(&self.options.num_instances)
+                .take(self.options.num_circuits)
+                .flatten()
+                .copied()
+                .collect()
+        });
 
         Halo2Verifier {
-            scheme: self.scheme,
+            pragma: self.options.pragma.clone(),
+            license: self.options.license.clone(),
+            contract_name: self.options.contract_name.clone(),
+            natspec: self.options.natspec.then(|| self.circuit_summary()),
+            fn_name: self.options.fn_name.clone(),
+            scheme: self.options.scheme,
+            on_failure: self.options.on_failure,
+            view: self.options.view,
+            emit_event: self.options.emit_event,
+            deferred_pairing: self.options.deferred_pairing,
+            verify_and_store: self.options.verify_and_store,
+            on_verified_hook: self.options.on_verified_hook,
+            batch_verify: self.options.batch_verify,
+            vk_address_source: self.options.vk_address_source,
             embedded_vk: (!separate).then_some(vk),
+            vk_digest: U256::from_be_bytes(self.vk_digest()),
+            vk_codehash,
             vk_len,
             vk_mptr,
             num_neg_lagranges: self.meta.rotation_last.unsigned_abs() as usize,
+            num_extra_lagranges: self.meta.num_extra_lagranges(),
+            num_instance_columns: self.meta.num_instance_columns,
             num_advices: self.meta.num_advices(),
             num_challenges: self.meta.num_challenges(),
             num_rotations: self.meta.num_rotations,
             num_evals: self.meta.num_evals,
             num_quotients: self.meta.num_quotients,
             proof_cptr,
-            quotient_comm_cptr: data.quotient_comm_cptr,
-            proof_len: self.meta.proof_len(self.scheme),
-            challenge_mptr: data.challenge_mptr,
-            theta_mptr: data.theta_mptr,
+            quotient_comm_cptr: data[0].quotient_comm_cptr,
+            proof_len: self.meta.proof_len(self.options.scheme),
+            challenge_mptr: data[0].challenge_mptr,
+            theta_mptr: data[0].theta_mptr,
+            theta_used: self.meta.num_lookup_zs + self.meta.num_shuffle_zs != 0,
             quotient_eval_numer_computations,
             pcs_computations,
+            acc_limb_computations,
+            hashed_instances: self.options.hashed_instances,
+            hashed_instance_computations,
+            instance_byte_order: self.options.instance_byte_order,
+            rotated_instance_computations,
+            rotated_instance_evals,
+            num_instances: self.options.num_circuits
+                * self.options.num_instances.iter().sum::<usize>(),
+            num_circuits: self.options.num_circuits,
+            packed_instances_len,
+            packed_instance_computations,
+            nested_instances_lens,
+            num_proofs: self.options.num_proofs,
+        }
+    }
+
+    fn evaluator<'b>(&'b self, data: &'b Data) -> Evaluator<'b, bn256::Fr> {
+        let evaluator = Evaluator::new(self.vk.cs(), &self.meta, data);
+        if self.cse {
+            evaluator.with_cse(self.options.scratch_reuse)
+        } else {
+            evaluator
         }
     }
 
     fn static_working_memory_size(&self, vk: &Halo2VerifyingKey, proof_cptr: Ptr) -> usize {
-        let pcs_computation = {
-            let mock_vk_mptr = Ptr::memory(0x100000);
-            let mock = Data::new(&self.meta, vk, mock_vk_mptr, proof_cptr);
-            self.scheme.static_working_memory_size(&self.meta, &mock)
-        };
+        let mock_vk_mptr = Ptr::memory(0x100000);
+        let mock = Data::new_per_circuit(&self.meta, vk, mock_vk_mptr, proof_cptr);
+        let pcs_computation = self
+            .options
+            .scheme
+            .static_working_memory_size(&self.meta, &mock);
+        let shared_subexpressions = 1 + self.evaluator(&mock[0]).num_slots();
 
         itertools::max([
             // Keccak256 input (can overwrite vk)
@@ -291,6 +3178,8 @@ impl<'a> SolidityGenerator<'a> {
             .saturating_sub(vk.len() / 0x20),
             // PCS computation
             pcs_computation,
+            // Shared subexpressions of gates after the word used by permutation
+            shared_subexpressions,
             // Pairing
             12,
         ])
@@ -1,16 +1,21 @@
 #![allow(clippy::useless_format)]
 
-use crate::codegen::util::{code_block, fe_to_u256, ConstraintSystemMeta, Data};
+use crate::codegen::util::{code_block, fe_to_u256, ConstraintSystemMeta, Data, Ptr};
 use halo2_proofs::{
     halo2curves::ff::PrimeField,
     plonk::{
-        Advice, AdviceQuery, Any, Challenge, ConstraintSystem, Expression, Fixed, FixedQuery, Gate,
+        Advice, AdviceQuery, Any, ConstraintSystem, Expression, Fixed, FixedQuery, Gate,
         InstanceQuery,
     },
 };
 use itertools::{chain, izip, Itertools};
 use ruint::aliases::U256;
-use std::{cell::RefCell, cmp::Ordering, collections::HashMap, iter};
+use std::{
+    cell::RefCell,
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    iter,
+};
 
 #[derive(Debug)]
 pub(crate) struct Evaluator<'a, F: PrimeField> {
@@ -19,6 +24,10 @@ pub(crate) struct Evaluator<'a, F: PrimeField> {
     data: &'a Data,
     var_counter: RefCell<usize>,
     var_cache: RefCell<HashMap<String, String>>,
+    shared: BTreeMap<String, Ptr>,
+    shared_expressions: Vec<(Ptr, Expression<F>)>,
+    definitions: Vec<Vec<usize>>,
+    num_slots: usize,
 }
 
 impl<'a, F> Evaluator<'a, F>
@@ -36,7 +45,144 @@ where
             data,
             var_counter: Default::default(),
             var_cache: Default::default(),
+            shared: Default::default(),
+            shared_expressions: Default::default(),
+            definitions: Default::default(),
+            num_slots: Default::default(),
+        }
+    }
+
+    /// Eliminate common subexpressions of gates, which are structurally identical subexpressions
+    /// evaluated by more than one polynomial (or by another shared subexpression). Each of them
+    /// is computed once into a memory slot starting from `0x20` right before the first polynomial
+    /// using it, then loaded wherever it appears.
+    ///
+    /// If `reuse_slots`, a slot is reused by later shared subexpressions once its last use has
+    /// been evaluated, otherwise each shared subexpression has its own slot.
+    pub(crate) fn with_cse(mut self, reuse_slots: bool) -> Self {
+        // Distinct compound subexpressions with their referrers
+        let polynomials = self
+            .cs
+            .gates()
+            .iter()
+            .flat_map(Gate::polynomials)
+            .map(fold_constants)
+            .collect_vec();
+        let mut refs = BTreeMap::new();
+        let mut keys = HashMap::new();
+        for (idx, polynomial) in polynomials.iter().enumerate() {
+            self.collect_refs(polynomial, Scope::Polynomial(idx), &mut keys, &mut refs);
         }
+
+        // Referrers always have longer keys than their operands, so they are visited first, and a
+        // subexpression is shared when it's evaluated from more than one scope
+        let mut scopes = BTreeMap::<&String, BTreeSet<Scope>>::new();
+        let mut shared = Vec::new();
+        let mut shared_keys = BTreeSet::new();
+        let refs = refs.iter().sorted_by_key(|(key, _)| Reverse(key.len()));
+        for (key, (expression, referrers)) in refs {
+            let scope = referrers
+                .iter()
+                .flat_map(|referrer| match referrer {
+                    Scope::Subexpression(parent) if !shared_keys.contains(parent) => {
+                        scopes[parent].clone()
+                    }
+                    referrer => BTreeSet::from([referrer.clone()]),
+                })
+                .collect::<BTreeSet<_>>();
+            if scope.len() > 1 {
+                shared.push((key.clone(), (*expression).clone()));
+                shared_keys.insert(key);
+            }
+            scopes.insert(key, scope);
+        }
+
+        // Operands are loaded by their referrers, so they are sorted before them
+        shared.sort_by_key(|(key, _)| key.len());
+        let indices = izip!(0.., &shared)
+            .map(|(idx, (key, _))| (key.clone(), idx))
+            .collect::<BTreeMap<_, _>>();
+        let mut loads_of = |expression: &Expression<F>, load_root: bool| {
+            let mut loads = BTreeSet::new();
+            self.key(expression, &mut keys);
+            self.collect_loads(expression, &keys, &indices, load_root, &mut loads);
+            loads
+        };
+        let definition_loads = shared
+            .iter()
+            .map(|(_, expression)| loads_of(expression, false))
+            .collect_vec();
+        let polynomial_loads = polynomials
+            .iter()
+            .map(|polynomial| loads_of(polynomial, true))
+            .collect_vec();
+
+        // Shared subexpressions are defined right before the first polynomial loading them
+        let mut defined = BTreeSet::new();
+        self.definitions = polynomial_loads
+            .iter()
+            .map(|loads| {
+                let mut needed = BTreeSet::new();
+                let mut queue = loads.iter().copied().collect_vec();
+                while let Some(idx) = queue.pop() {
+                    if !defined.contains(&idx) && needed.insert(idx) {
+                        queue.extend(&definition_loads[idx]);
+                    }
+                }
+                defined.extend(&needed);
+                needed.into_iter().collect_vec()
+            })
+            .collect();
+
+        // Allocate slots in the order of evaluation, and free them after their last use
+        let steps = izip!(&self.definitions, &polynomial_loads)
+            .flat_map(|(definitions, loads)| {
+                chain![
+                    definitions
+                        .iter()
+                        .map(|idx| (Some(*idx), &definition_loads[*idx])),
+                    [(None, loads)],
+                ]
+            })
+            .collect_vec();
+        let mut last_use = vec![0; shared.len()];
+        for (step, (_, loads)) in steps.iter().enumerate() {
+            for idx in loads.iter() {
+                last_use[*idx] = step;
+            }
+        }
+        let mut slots = vec![0; shared.len()];
+        let mut live = Vec::new();
+        let mut free = BTreeSet::new();
+        for (step, (definition, _)) in steps.iter().enumerate() {
+            if let Some(idx) = definition {
+                if reuse_slots {
+                    let (dead, alive): (Vec<_>, Vec<_>) =
+                        live.into_iter().partition(|(end, _)| *end < step);
+                    free.extend(dead.into_iter().map(|(_, slot)| slot));
+                    live = alive;
+                }
+                slots[*idx] = free.pop_first().unwrap_or_else(|| {
+                    self.num_slots += 1;
+                    self.num_slots - 1
+                });
+                live.push((last_use[*idx], slots[*idx]));
+            }
+        }
+
+        let mptr = Ptr::memory(0x20);
+        self.shared = izip!(&shared, &slots)
+            .map(|((key, _), slot)| (key.clone(), mptr + *slot))
+            .collect();
+        self.shared_expressions = izip!(shared, slots)
+            .map(|((_, expression), slot)| (mptr + slot, expression))
+            .collect();
+        self
+    }
+
+    /// Returns the number of memory slots used by shared subexpressions.
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
     }
 
     pub fn gate_computations(&self) -> Vec<(Vec<String>, String)> {
@@ -44,7 +190,19 @@ where
             .gates()
             .iter()
             .flat_map(Gate::polynomials)
-            .map(|expression| self.evaluate_and_reset(expression))
+            .enumerate()
+            .map(|(idx, expression)| {
+                let definitions = self
+                    .definitions
+                    .get(idx)
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|idx| code_block::<1, false>(self.shared_computation(*idx)))
+                    .collect_vec();
+                let (lines, var) = self.evaluate_shared(expression, true);
+                self.reset();
+                (chain![definitions, lines].collect(), var)
+            })
             .collect()
     }
 
@@ -219,11 +377,90 @@ where
             .collect_vec()
     }
 
+    pub fn shuffle_computations(&self) -> Vec<(Vec<String>, String)> {
+        let input_shuffles = self
+            .cs
+            .shuffles()
+            .iter()
+            .map(|shuffle| {
+                let [(input_lines, inputs), (shuffle_lines, shuffles)] =
+                    [shuffle.input_expressions(), shuffle.shuffle_expressions()].map(
+                        |expressions| {
+                            let (lines, inputs) = expressions
+                                .iter()
+                                .map(|expression| self.evaluate(expression))
+                                .fold((Vec::new(), Vec::new()), |mut acc, result| {
+                                    acc.0.extend(result.0);
+                                    acc.1.push(result.1);
+                                    acc
+                                });
+                            self.reset();
+                            (lines, inputs)
+                        },
+                    );
+                (input_lines, inputs, shuffle_lines, shuffles)
+            })
+            .collect_vec();
+        izip!(input_shuffles, &self.data.shuffle_evals)
+            .flat_map(|(input_shuffle, evals)| {
+                let (input_lines, inputs, shuffle_lines, shuffles) = input_shuffle;
+                let (input_0, rest_inputs) = inputs.split_first().unwrap();
+                let (shuffle_0, rest_shuffles) = shuffles.split_first().unwrap();
+                let (z, z_next) = evals;
+                [
+                    vec![
+                        format!("let l_0 := mload(L_0_MPTR)"),
+                        format!("let eval := addmod(l_0, mulmod(l_0, sub(r, {z}), r), r)"),
+                    ],
+                    {
+                        let item = format!("addmod(mulmod({z}, {z}, r), sub(r, {z}), r)");
+                        vec![
+                            format!("let l_last := mload(L_LAST_MPTR)"),
+                            format!("let eval := mulmod(l_last, {item}, r)"),
+                        ]
+                    },
+                    chain![
+                        ["let theta := mload(THETA_MPTR)", "let input"].map(str::to_string),
+                        code_block::<1, false>(chain![
+                            input_lines,
+                            [format!("input := {input_0}")],
+                            rest_inputs.iter().map(|input| format!(
+                                "input := addmod(mulmod(input, theta, r), {input}, r)"
+                            ))
+                        ]),
+                        ["let shuffle"].map(str::to_string),
+                        code_block::<1, false>(chain![
+                            shuffle_lines,
+                            [format!("shuffle := {shuffle_0}")],
+                            rest_shuffles.iter().map(|shuffle| format!(
+                                "shuffle := addmod(mulmod(shuffle, theta, r), {shuffle}, r)"
+                            ))
+                        ]),
+                        [
+                            format!("let gamma := mload(GAMMA_MPTR)"),
+                            format!("let lhs := mulmod({z_next}, addmod(shuffle, gamma, r), r)"),
+                            format!("let rhs := mulmod({z}, addmod(input, gamma, r), r)"),
+                        ],
+                        {
+                            let l_inactive = "addmod(mload(L_BLIND_MPTR), mload(L_LAST_MPTR), r)";
+                            let l_active = format!("addmod(1, sub(r, {l_inactive}), r)");
+                            [format!(
+                                "let eval := mulmod({l_active}, addmod(lhs, sub(r, rhs), r), r)"
+                            )]
+                        },
+                    ]
+                    .collect_vec(),
+                ]
+            })
+            .zip(iter::repeat("eval".to_string()))
+            .collect_vec()
+    }
+
     fn eval(&self, column_type: impl Into<Any>, column_index: usize, rotation: i32) -> String {
         match column_type.into() {
             Any::Advice(_) => self.data.advice_evals[&(column_index, rotation)].to_string(),
             Any::Fixed => self.data.fixed_evals[&(column_index, rotation)].to_string(),
-            Any::Instance => self.data.instance_eval.to_string(),
+            Any::Instance => self.data.instance_evals[&(column_index, rotation)].to_string(),
         }
     }
 
@@ -232,62 +469,182 @@ where
         *self.var_cache.borrow_mut() = Default::default();
     }
 
-    fn evaluate_and_reset(&self, expression: &Expression<F>) -> (Vec<String>, String) {
-        let result = self.evaluate(expression);
+    fn evaluate(&self, expression: &Expression<F>) -> (Vec<String>, String) {
+        self.evaluate_expression(&fold_constants(expression), &HashMap::new(), false)
+    }
+
+    /// Evaluate `expression` with shared subexpressions loaded from memory, including itself if
+    /// `load_root`.
+    fn evaluate_shared(
+        &self,
+        expression: &Expression<F>,
+        load_root: bool,
+    ) -> (Vec<String>, String) {
+        let expression = fold_constants(expression);
+        let mut keys = HashMap::new();
+        if !self.shared.is_empty() {
+            self.key(&expression, &mut keys);
+        }
+        self.evaluate_expression(&expression, &keys, load_root)
+    }
+
+    fn shared_computation(&self, idx: usize) -> Vec<String> {
+        let (mptr, expression) = &self.shared_expressions[idx];
+        let (mut lines, var) = self.evaluate_shared(expression, false);
         self.reset();
-        result
+        lines.push(format!("mstore({mptr}, {var})"));
+        lines
     }
 
-    fn evaluate(&self, expression: &Expression<F>) -> (Vec<String>, String) {
-        evaluate(
-            expression,
-            &|constant| {
-                let constant = u256_string(constant);
+    fn evaluate_expression(
+        &self,
+        expression: &Expression<F>,
+        keys: &HashMap<*const Expression<F>, String>,
+        load_root: bool,
+    ) -> (Vec<String>, String) {
+        let shared = keys
+            .get(&(expression as *const _))
+            .and_then(|key| self.shared.get(key));
+        if let (true, Some(mptr)) = (load_root, shared) {
+            return self.init_var(format!("mload({mptr})"), None);
+        }
+
+        let evaluate = |expression| self.evaluate_expression(expression, keys, true);
+        match expression {
+            Expression::Constant(scalar) => {
+                let constant = u256_string(fe_to_u256(*scalar));
                 self.init_var(constant, None)
-            },
-            &|query| {
-                self.init_var(
-                    self.eval(Fixed, query.column_index(), query.rotation().0),
-                    Some(fixed_eval_var(query)),
-                )
-            },
-            &|query| {
-                self.init_var(
-                    self.eval(Advice::default(), query.column_index(), query.rotation().0),
-                    Some(advice_eval_var(query)),
-                )
-            },
-            &|_| self.init_var(self.data.instance_eval, Some("i_eval".to_string())),
-            &|challenge| {
-                self.init_var(
-                    self.data.challenges[challenge.index()],
-                    Some(format!("c_{}", challenge.index())),
-                )
-            },
-            &|(mut acc, var)| {
+            }
+            Expression::Selector(_) => unreachable!(),
+            Expression::Fixed(query) => self.init_var(
+                self.eval(Fixed, query.column_index(), query.rotation().0),
+                Some(fixed_eval_var(*query)),
+            ),
+            Expression::Advice(query) => self.init_var(
+                self.eval(Advice::default(), query.column_index(), query.rotation().0),
+                Some(advice_eval_var(*query)),
+            ),
+            Expression::Instance(query) => self.init_var(
+                self.eval(Any::Instance, query.column_index(), query.rotation().0),
+                Some(instance_eval_var(*query)),
+            ),
+            Expression::Challenge(challenge) => self.init_var(
+                self.data.challenges[challenge.index()],
+                Some(format!("c_{}", challenge.index())),
+            ),
+            Expression::Negated(value) => {
+                let (mut acc, var) = evaluate(value);
                 let (lines, var) = self.init_var(format!("sub(r, {var})"), None);
                 acc.extend(lines);
                 (acc, var)
-            },
-            &|(mut lhs_acc, lhs_var), (rhs_acc, rhs_var)| {
+            }
+            Expression::Sum(lhs, rhs) => {
+                let (mut lhs_acc, lhs_var) = evaluate(lhs);
+                let (rhs_acc, rhs_var) = evaluate(rhs);
                 let (lines, var) = self.init_var(format!("addmod({lhs_var}, {rhs_var}, r)"), None);
                 lhs_acc.extend(rhs_acc);
                 lhs_acc.extend(lines);
                 (lhs_acc, var)
-            },
-            &|(mut lhs_acc, lhs_var), (rhs_acc, rhs_var)| {
+            }
+            Expression::Product(lhs, rhs) => {
+                let (mut lhs_acc, lhs_var) = evaluate(lhs);
+                let (rhs_acc, rhs_var) = evaluate(rhs);
                 let (lines, var) = self.init_var(format!("mulmod({lhs_var}, {rhs_var}, r)"), None);
                 lhs_acc.extend(rhs_acc);
                 lhs_acc.extend(lines);
                 (lhs_acc, var)
-            },
-            &|(mut acc, var), scalar| {
-                let scalar = u256_string(scalar);
+            }
+            Expression::Scaled(value, scalar) => {
+                let (mut acc, var) = evaluate(value);
+                let scalar = u256_string(fe_to_u256(*scalar));
                 let (lines, var) = self.init_var(format!("mulmod({var}, {scalar}, r)"), None);
                 acc.extend(lines);
                 (acc, var)
-            },
-        )
+            }
+        }
+    }
+
+    /// Returns the fully inlined Yul of `expression` as its structural key, and memoizes keys of
+    /// all its subexpressions by address.
+    fn key(
+        &self,
+        expression: &Expression<F>,
+        keys: &mut HashMap<*const Expression<F>, String>,
+    ) -> String {
+        let key = match expression {
+            Expression::Constant(scalar) => u256_string(fe_to_u256(*scalar)),
+            Expression::Selector(_) => unreachable!(),
+            Expression::Fixed(query) => self.eval(Fixed, query.column_index(), query.rotation().0),
+            Expression::Advice(query) => {
+                self.eval(Advice::default(), query.column_index(), query.rotation().0)
+            }
+            Expression::Instance(query) => {
+                self.eval(Any::Instance, query.column_index(), query.rotation().0)
+            }
+            Expression::Challenge(challenge) => self.data.challenges[challenge.index()].to_string(),
+            Expression::Negated(value) => format!("sub(r, {})", self.key(value, keys)),
+            Expression::Sum(lhs, rhs) => {
+                format!("addmod({}, {}, r)", self.key(lhs, keys), self.key(rhs, keys))
+            }
+            Expression::Product(lhs, rhs) => {
+                format!("mulmod({}, {}, r)", self.key(lhs, keys), self.key(rhs, keys))
+            }
+            Expression::Scaled(value, scalar) => {
+                let scalar = u256_string(fe_to_u256(*scalar));
+                format!("mulmod({}, {scalar}, r)", self.key(value, keys))
+            }
+        };
+        keys.insert(expression as *const _, key.clone());
+        key
+    }
+
+    fn collect_refs<'e>(
+        &self,
+        expression: &'e Expression<F>,
+        referrer: Scope,
+        keys: &mut HashMap<*const Expression<F>, String>,
+        refs: &mut BTreeMap<String, (&'e Expression<F>, BTreeSet<Scope>)>,
+    ) {
+        let operands = operands(expression);
+        if operands.is_empty() {
+            return;
+        }
+        let key = match keys.get(&(expression as *const _)) {
+            Some(key) => key.clone(),
+            None => self.key(expression, keys),
+        };
+        let visited = refs.contains_key(&key);
+        refs.entry(key.clone())
+            .or_insert_with(|| (expression, BTreeSet::new()))
+            .1
+            .insert(referrer);
+        if !visited {
+            for operand in operands {
+                self.collect_refs(operand, Scope::Subexpression(key.clone()), keys, refs);
+            }
+        }
+    }
+
+    /// Collect indices of shared subexpressions loaded when evaluating `expression`, which stops
+    /// descending at them.
+    fn collect_loads(
+        &self,
+        expression: &Expression<F>,
+        keys: &HashMap<*const Expression<F>, String>,
+        indices: &BTreeMap<String, usize>,
+        load_root: bool,
+        loads: &mut BTreeSet<usize>,
+    ) {
+        let idx = keys
+            .get(&(expression as *const _))
+            .and_then(|key| indices.get(key));
+        if let (true, Some(idx)) = (load_root, idx) {
+            loads.insert(*idx);
+            return;
+        }
+        for operand in operands(expression) {
+            self.collect_loads(operand, keys, indices, true, loads);
+        }
     }
 
     fn init_var(&self, value: impl ToString, var: Option<String>) -> (Vec<String>, String) {
@@ -326,45 +683,83 @@ fn advice_eval_var(advice_query: AdviceQuery) -> String {
     column_eval_var("a", advice_query.column_index(), advice_query.rotation().0)
 }
 
-fn column_eval_var(prefix: &'static str, column_index: usize, rotation: i32) -> String {
-    match rotation.cmp(&0) {
-        Ordering::Less => format!("{prefix}_{column_index}_prev_{}", rotation.abs()),
-        Ordering::Equal => format!("{prefix}_{column_index}"),
-        Ordering::Greater => format!("{prefix}_{column_index}_next_{rotation}"),
+fn instance_eval_var(instance_query: InstanceQuery) -> String {
+    column_eval_var("i_eval", instance_query.column_index(), instance_query.rotation().0)
+}
+
+fn operands<F>(expression: &Expression<F>) -> Vec<&Expression<F>> {
+    match expression {
+        Expression::Negated(value) | Expression::Scaled(value, _) => vec![value],
+        Expression::Sum(lhs, rhs) | Expression::Product(lhs, rhs) => vec![lhs, rhs],
+        _ => Vec::new(),
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn evaluate<F, T>(
-    expression: &Expression<F>,
-    constant: &impl Fn(U256) -> T,
-    fixed: &impl Fn(FixedQuery) -> T,
-    advice: &impl Fn(AdviceQuery) -> T,
-    instance: &impl Fn(InstanceQuery) -> T,
-    challenge: &impl Fn(Challenge) -> T,
-    negated: &impl Fn(T) -> T,
-    sum: &impl Fn(T, T) -> T,
-    product: &impl Fn(T, T) -> T,
-    scaled: &impl Fn(T, U256) -> T,
-) -> T
-where
-    F: PrimeField<Repr = [u8; 0x20]>,
-{
-    let evaluate = |expr| {
-        evaluate(
-            expr, constant, fixed, advice, instance, challenge, negated, sum, product, scaled,
-        )
-    };
+/// Fold constant subexpressions of `expression`, so they are emitted as pre-reduced literals
+/// instead of being computed at runtime. Besides evaluating operations on constants, it drops
+/// zero terms, one factors and double negations, replaces products by zero with zero, and turns
+/// products with a constant into scaling.
+pub(crate) fn fold_constants<F: PrimeField>(expression: &Expression<F>) -> Expression<F> {
     match expression {
-        Expression::Constant(scalar) => constant(fe_to_u256(*scalar)),
-        Expression::Selector(_) => unreachable!(),
-        Expression::Fixed(query) => fixed(*query),
-        Expression::Advice(query) => advice(*query),
-        Expression::Instance(query) => instance(*query),
-        Expression::Challenge(value) => challenge(*value),
-        Expression::Negated(value) => negated(evaluate(value)),
-        Expression::Sum(lhs, rhs) => sum(evaluate(lhs), evaluate(rhs)),
-        Expression::Product(lhs, rhs) => product(evaluate(lhs), evaluate(rhs)),
-        Expression::Scaled(value, scalar) => scaled(evaluate(value), fe_to_u256(*scalar)),
+        Expression::Negated(value) => negated(fold_constants(value)),
+        Expression::Sum(lhs, rhs) => match (fold_constants(lhs), fold_constants(rhs)) {
+            (Expression::Constant(lhs), Expression::Constant(rhs)) => {
+                Expression::Constant(lhs + rhs)
+            }
+            (Expression::Constant(zero), value) | (value, Expression::Constant(zero))
+                if bool::from(zero.is_zero()) =>
+            {
+                value
+            }
+            (lhs, rhs) => Expression::Sum(Box::new(lhs), Box::new(rhs)),
+        },
+        Expression::Product(lhs, rhs) => match (fold_constants(lhs), fold_constants(rhs)) {
+            (Expression::Constant(constant), value) | (value, Expression::Constant(constant)) => {
+                scaled(value, constant)
+            }
+            (lhs, rhs) => Expression::Product(Box::new(lhs), Box::new(rhs)),
+        },
+        Expression::Scaled(value, scalar) => scaled(fold_constants(value), *scalar),
+        _ => expression.clone(),
+    }
+}
+
+fn negated<F: PrimeField>(value: Expression<F>) -> Expression<F> {
+    match value {
+        Expression::Constant(constant) => Expression::Constant(-constant),
+        Expression::Negated(value) => *value,
+        Expression::Scaled(value, scalar) => scaled(*value, -scalar),
+        value => Expression::Negated(Box::new(value)),
+    }
+}
+
+fn scaled<F: PrimeField>(value: Expression<F>, scalar: F) -> Expression<F> {
+    match value {
+        _ if bool::from(scalar.is_zero()) => Expression::Constant(F::ZERO),
+        value if scalar == F::ONE => value,
+        value if scalar == -F::ONE => negated(value),
+        Expression::Constant(constant) => Expression::Constant(constant * scalar),
+        Expression::Negated(value) => scaled(*value, -scalar),
+        Expression::Scaled(value, inner) => scaled(*value, inner * scalar),
+        value => Expression::Scaled(Box::new(value), scalar),
+    }
+}
+
+/// Where a subexpression is evaluated from, either a gate polynomial or a shared subexpression.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Scope {
+    Polynomial(usize),
+    Subexpression(String),
+}
+
+pub(crate) fn column_eval_var(
+    prefix: &'static str,
+    column_index: usize,
+    rotation: i32,
+) -> String {
+    match rotation.cmp(&0) {
+        Ordering::Less => format!("{prefix}_{column_index}_prev_{}", rotation.abs()),
+        Ordering::Equal => format!("{prefix}_{column_index}"),
+        Ordering::Greater => format!("{prefix}_{column_index}_next_{rotation}"),
     }
 }
@@ -0,0 +1,62 @@
+use crate::codegen::{GeneratorError, Options, SolidityGenerator, VerifierParams};
+use halo2_proofs::{
+    halo2curves::bn256,
+    plonk::{Circuit, VerifyingKey},
+    SerdeFormat,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Cow;
+
+/// Everything of `SolidityGenerator` except `VerifierParams`, with verifying key in hex of
+/// `SerdeFormat::RawBytes`.
+#[derive(Serialize, Deserialize)]
+struct SerializedGenerator {
+    vk: String,
+    #[serde(flatten)]
+    options: Options,
+}
+
+impl Serialize for SolidityGenerator<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedGenerator {
+            vk: hex::encode(self.vk.to_bytes(SerdeFormat::RawBytes)),
+            options: self.options.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl SolidityGenerator<'_> {
+    /// Serialize into JSON, which contains verifying key and all options but `VerifierParams`,
+    /// so generated artifacts can be cached and the generator can be restored by
+    /// [`SolidityGenerator::from_json`] without the circuit or the full SRS.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Deserialize JSON serialized by [`SolidityGenerator::to_json`], with `params` given again
+    /// since they are not serialized.
+    ///
+    /// Only the shape of `ConcreteCircuit` is used to recover the `ConstraintSystem`, same as
+    /// [`SolidityGenerator::from_vk_bytes`].
+    pub fn from_json<ConcreteCircuit: Circuit<bn256::Fr>>(
+        params: impl Into<VerifierParams>,
+        json: &str,
+        #[cfg(feature = "circuit-params")] circuit_params: ConcreteCircuit::Params,
+    ) -> Result<SolidityGenerator<'static>, GeneratorError> {
+        let serialized: SerializedGenerator = serde_json::from_str(json)
+            .map_err(|err| GeneratorError::InvalidJson(err.to_string()))?;
+        let vk_bytes = hex::decode(&serialized.vk)
+            .map_err(|err| GeneratorError::InvalidJson(err.to_string()))?;
+        let vk = VerifyingKey::from_bytes::<ConcreteCircuit>(
+            &vk_bytes,
+            SerdeFormat::RawBytes,
+            #[cfg(feature = "circuit-params")]
+            circuit_params,
+        )
+        .map_err(|err| GeneratorError::InvalidVerifyingKey(err.to_string()))?;
+        let mut builder = SolidityGenerator::builder_with_vk(params.into(), Cow::Owned(vk));
+        builder.options = serialized.options;
+        builder.build()
+    }
+}
@@ -6,15 +6,22 @@ mod gwc19;
 
 /// KZG batch open schemes in `halo2`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum BatchOpenScheme {
     /// Batch open scheme in [Plonk] paper.
     /// Corresponding to `halo2_proofs::poly::kzg::multiopen::ProverGWC`
     ///
+    /// The batch opening proof contains one EC point per distinct rotation queried in the circuit,
+    /// so the proof is `(num_rotations - 2) * 64` bytes longer than the one of [`Self::Bdfg21`].
+    ///
     /// [Plonk]: https://eprint.iacr.org/2019/953.pdf
     Gwc19,
     /// Batch open scheme in [BDFG21] paper.
     /// Corresponding to `halo2_proofs::poly::kzg::multiopen::ProverSHPLONK`
     ///
+    /// The batch opening proof always contains 2 EC points.
+    ///
     /// [BDFG21]: https://eprint.iacr.org/2020/081.pdf
     Bdfg21,
 }
@@ -23,7 +30,7 @@ impl BatchOpenScheme {
     pub(crate) fn static_working_memory_size(
         &self,
         meta: &ConstraintSystemMeta,
-        data: &Data,
+        data: &[Data],
     ) -> usize {
         match self {
             Self::Bdfg21 => bdfg21::static_working_memory_size(meta, data),
@@ -34,13 +41,42 @@ impl BatchOpenScheme {
     pub(crate) fn computations(
         &self,
         meta: &ConstraintSystemMeta,
-        data: &Data,
+        data: &[Data],
     ) -> Vec<Vec<String>> {
         match self {
             Self::Bdfg21 => bdfg21::computations(meta, data),
             Self::Gwc19 => gwc19::computations(meta, data),
         }
     }
+
+    /// Return number of `ecMul` calls in computations, where each is followed by an `ecAdd`.
+    pub(crate) fn num_ec_muls(&self, meta: &ConstraintSystemMeta, data: &[Data]) -> usize {
+        let queries = queries(meta, data);
+        match self {
+            // Each distinct commitment and each rotation set costs one except the first ones,
+            // then `G1`, `W` and `W'` cost one respectively.
+            Self::Bdfg21 => {
+                let comms = queries.iter().fold(Vec::new(), |mut comms, query| {
+                    if !comms.contains(&query.comm) {
+                        comms.push(query.comm);
+                    }
+                    comms
+                });
+                comms.len() + 2
+            }
+            // Each query costs one except the first one, then `G1` costs one, and each `W` costs
+            // one to be scaled by its point and one to be combined except the first one.
+            Self::Gwc19 => queries.len() + 2 * meta.num_rotations - 1,
+        }
+    }
+
+    /// Return number of `batch_invert` calls in computations.
+    pub(crate) fn num_batch_inverts(&self) -> usize {
+        match self {
+            Self::Bdfg21 => 2,
+            Self::Gwc19 => 0,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -56,7 +92,17 @@ impl Query {
     }
 }
 
-pub(crate) fn queries(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Query> {
+/// Return queries of each circuit in `data`, followed by the common ones of fixed columns,
+/// permutation columns and vanishing argument, in the same order as `halo2` verifier.
+pub(crate) fn queries(meta: &ConstraintSystemMeta, data: &[Data]) -> Vec<Query> {
+    chain![
+        data.iter().flat_map(|data| circuit_queries(meta, data)),
+        common_queries(meta, &data[0]),
+    ]
+    .collect()
+}
+
+fn circuit_queries(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Query> {
     chain![
         meta.advice_queries.iter().map(|query| {
             let comm = data.advice_comms[query.0];
@@ -84,6 +130,15 @@ pub(crate) fn queries(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Query> {
                 Query::new(z_comm, 1, evals.1),
             ]
         }),
+        izip!(&data.shuffle_z_comms, &data.shuffle_evals).flat_map(|(&comm, evals)| {
+            [Query::new(comm, 0, evals.0), Query::new(comm, 1, evals.1)]
+        }),
+    ]
+    .collect()
+}
+
+fn common_queries(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Query> {
+    chain![
         meta.fixed_queries.iter().map(|query| {
             let comm = data.fixed_comms[query.0];
             let eval = data.fixed_evals[query];
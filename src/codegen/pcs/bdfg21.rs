@@ -10,20 +10,20 @@ use crate::codegen::{
 use itertools::{chain, izip, Itertools};
 use std::collections::{BTreeMap, BTreeSet};
 
-pub(super) fn static_working_memory_size(meta: &ConstraintSystemMeta, data: &Data) -> usize {
+pub(super) fn static_working_memory_size(meta: &ConstraintSystemMeta, data: &[Data]) -> usize {
     let (superset, sets) = rotation_sets(&queries(meta, data));
     let num_coeffs = sets.iter().map(|set| set.rots().len()).sum::<usize>();
     2 * (1 + num_coeffs) + 6 + 2 * superset.len() + 1 + 3 * sets.len()
 }
 
-pub(super) fn computations(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Vec<String>> {
+pub(super) fn computations(meta: &ConstraintSystemMeta, data: &[Data]) -> Vec<Vec<String>> {
     let (superset, sets) = rotation_sets(&queries(meta, data));
     let min_rot = *superset.first().unwrap();
     let max_rot = *superset.last().unwrap();
     let num_coeffs = sets.iter().map(|set| set.rots().len()).sum::<usize>();
 
-    let w = EcPoint::from(data.w_cptr);
-    let w_prime = EcPoint::from(data.w_cptr + 2);
+    let w = EcPoint::from(data[0].w_cptr);
+    let w_prime = EcPoint::from(data[0].w_cptr + 2);
 
     let diff_0 = Word::from(Ptr::memory(0x00));
     let coeffs = sets
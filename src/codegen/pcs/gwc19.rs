@@ -10,11 +10,11 @@ use crate::codegen::{
 use itertools::{chain, izip, Itertools};
 use std::collections::BTreeMap;
 
-pub(super) fn static_working_memory_size(meta: &ConstraintSystemMeta, _: &Data) -> usize {
+pub(super) fn static_working_memory_size(meta: &ConstraintSystemMeta, _: &[Data]) -> usize {
     0x100 + meta.num_rotations * 0x40
 }
 
-pub(super) fn computations(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Vec<String>> {
+pub(super) fn computations(meta: &ConstraintSystemMeta, data: &[Data]) -> Vec<Vec<String>> {
     let sets = rotation_sets(&queries(meta, data));
     let rots = sets.iter().map(|set| set.rot).collect_vec();
     let (min_rot, max_rot) = rots
@@ -24,7 +24,7 @@ pub(super) fn computations(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Vec<
         .into_option()
         .unwrap_or_default();
 
-    let ws = EcPoint::range(data.w_cptr).take(sets.len()).collect_vec();
+    let ws = EcPoint::range(data[0].w_cptr).take(sets.len()).collect_vec();
 
     let point_w_mptr = Ptr::memory(0x100);
     let point_ws = izip!(rots, EcPoint::range(point_w_mptr)).collect::<BTreeMap<_, _>>();
@@ -125,7 +125,7 @@ pub(super) fn computations(meta: &ConstraintSystemMeta, data: &Data) -> Vec<Vec<
 
     let point_w_computations = for_loop(
         [
-            format!("let cptr := {}", data.w_cptr),
+            format!("let cptr := {}", data[0].w_cptr),
             format!("let mptr := {point_w_mptr}"),
             format!("let mptr_end := {}", point_w_mptr + 2 * sets.len()),
         ],
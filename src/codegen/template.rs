@@ -1,15 +1,25 @@
-use crate::codegen::{
-    pcs::BatchOpenScheme::{self, Bdfg21, Gwc19},
-    util::Ptr,
+use crate::{
+    codegen::{
+        pcs::BatchOpenScheme::{self, Bdfg21, Gwc19},
+        util::{load_instance, Ptr},
+        OnFailure::{self, CustomError, ReturnFalse, Revert},
+        VkAddressSource::{self, Calldata, Immutable, Ownable},
+    },
+    evm::InstanceByteOrder,
 };
 use askama::{Error, Template};
+use itertools::chain;
 use ruint::aliases::U256;
-use std::fmt;
+use std::{collections::BTreeMap, fmt, ops::Deref};
 
 #[derive(Template)]
 #[template(path = "Halo2VerifyingKey.sol")]
 pub(crate) struct Halo2VerifyingKey {
-    pub(crate) constants: Vec<(&'static str, U256)>,
+    pub(crate) pragma: String,
+    pub(crate) license: String,
+    pub(crate) contract_name: String,
+    pub(crate) natspec: Option<String>,
+    pub(crate) constants: Vec<(String, U256)>,
     pub(crate) fixed_comms: Vec<(U256, U256)>,
     pub(crate) permutation_comms: Vec<(U256, U256)>,
 }
@@ -19,21 +29,50 @@ impl Halo2VerifyingKey {
         (self.constants.len() * 0x20)
             + (self.fixed_comms.len() + self.permutation_comms.len()) * 0x40
     }
+
+    /// Returns runtime code of the deployed verifying key contract, which is the verifying key
+    /// itself.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        chain![
+            self.constants.iter().map(|(_, chunk)| *chunk),
+            chain![&self.fixed_comms, &self.permutation_comms].flat_map(|(x, y)| [*x, *y]),
+        ]
+        .flat_map(|word| word.to_be_bytes::<0x20>())
+        .collect()
+    }
 }
 
 #[derive(Template)]
 #[template(path = "Halo2Verifier.sol")]
 pub(crate) struct Halo2Verifier {
+    pub(crate) pragma: String,
+    pub(crate) license: String,
+    pub(crate) contract_name: String,
+    pub(crate) natspec: Option<String>,
+    pub(crate) fn_name: String,
     pub(crate) scheme: BatchOpenScheme,
+    pub(crate) on_failure: OnFailure,
+    pub(crate) view: bool,
+    pub(crate) emit_event: bool,
+    pub(crate) deferred_pairing: bool,
+    pub(crate) verify_and_store: bool,
+    pub(crate) on_verified_hook: bool,
+    pub(crate) batch_verify: bool,
+    pub(crate) vk_address_source: VkAddressSource,
     pub(crate) embedded_vk: Option<Halo2VerifyingKey>,
+    pub(crate) vk_digest: U256,
+    pub(crate) vk_codehash: Option<U256>,
     pub(crate) vk_len: usize,
     pub(crate) proof_len: usize,
     pub(crate) vk_mptr: Ptr,
     pub(crate) challenge_mptr: Ptr,
     pub(crate) theta_mptr: Ptr,
+    pub(crate) theta_used: bool,
     pub(crate) proof_cptr: Ptr,
     pub(crate) quotient_comm_cptr: Ptr,
     pub(crate) num_neg_lagranges: usize,
+    pub(crate) num_extra_lagranges: usize,
+    pub(crate) num_instance_columns: usize,
     pub(crate) num_advices: Vec<usize>,
     pub(crate) num_challenges: Vec<usize>,
     pub(crate) num_rotations: usize,
@@ -41,24 +80,307 @@ pub(crate) struct Halo2Verifier {
     pub(crate) num_quotients: usize,
     pub(crate) quotient_eval_numer_computations: Vec<Vec<String>>,
     pub(crate) pcs_computations: Vec<Vec<String>>,
+    pub(crate) acc_limb_computations: Vec<String>,
+    pub(crate) hashed_instances: bool,
+    pub(crate) hashed_instance_computations: Vec<String>,
+    pub(crate) instance_byte_order: InstanceByteOrder,
+    pub(crate) rotated_instance_computations: Vec<String>,
+    pub(crate) rotated_instance_evals: Vec<String>,
+    pub(crate) num_instances: usize,
+    pub(crate) num_circuits: usize,
+    pub(crate) packed_instances_len: Option<usize>,
+    pub(crate) packed_instance_computations: Vec<String>,
+    pub(crate) nested_instances_lens: Option<Vec<usize>>,
+    pub(crate) num_proofs: Option<usize>,
 }
 
-impl Halo2VerifyingKey {
-    pub(crate) fn render(&self, writer: &mut impl fmt::Write) -> Result<(), fmt::Error> {
-        self.render_into(writer).map_err(|err| match err {
-            Error::Fmt(err) => err,
-            _ => unreachable!(),
-        })
+#[derive(Template)]
+#[template(path = "IHalo2Verifier.sol")]
+pub(crate) struct IHalo2Verifier {
+    pub(crate) pragma: String,
+    pub(crate) license: String,
+    pub(crate) interface_name: String,
+    pub(crate) contract_name: String,
+    pub(crate) fn_name: String,
+    pub(crate) on_failure: OnFailure,
+    pub(crate) view: bool,
+    pub(crate) emit_event: bool,
+    pub(crate) deferred_pairing: bool,
+    pub(crate) verify_and_store: bool,
+    pub(crate) batch_verify: bool,
+    pub(crate) hashed_instances: bool,
+    pub(crate) packed_instances: bool,
+    pub(crate) nested_instances: bool,
+    pub(crate) num_proofs: Option<usize>,
+    pub(crate) separate: bool,
+    pub(crate) calldata_layout: Vec<(usize, usize, String)>,
+}
+
+#[derive(Template)]
+#[template(path = "Halo2VerifierRegistry.sol")]
+pub(crate) struct Halo2VerifierRegistry {
+    pub(crate) pragma: String,
+    pub(crate) license: String,
+    pub(crate) registry_name: String,
+    pub(crate) contract_name: String,
+    pub(crate) fn_name: String,
+    pub(crate) view: bool,
+    pub(crate) vk_param: bool,
+    pub(crate) hashed_instances: bool,
+    pub(crate) deferred_pairing: bool,
+}
+
+#[derive(Template)]
+#[template(path = "Halo2PairingAggregator.sol")]
+pub(crate) struct Halo2PairingAggregator {
+    pub(crate) pragma: String,
+    pub(crate) license: String,
+    pub(crate) aggregator_name: String,
+    pub(crate) contract_name: String,
+    pub(crate) fn_name: String,
+    pub(crate) g2_constants: Vec<(String, U256)>,
+}
+
+#[derive(Template)]
+#[template(path = "hardhat.config.js")]
+pub(crate) struct HardhatConfig {
+    pub(crate) verifier_filename: String,
+    pub(crate) vk_filename: String,
+    pub(crate) solc_version: String,
+    pub(crate) network: String,
+    pub(crate) rpc_url: String,
+}
+
+#[derive(Template)]
+#[template(path = "deploy.js")]
+pub(crate) struct HardhatDeployScript {
+    pub(crate) contract_name: String,
+    pub(crate) vk_contract_name: String,
+    pub(crate) fn_name: String,
+    pub(crate) vk_address_source: VkAddressSource,
+}
+
+#[derive(Template)]
+#[template(path = "VerifierTest.t.sol")]
+pub(crate) struct FoundryTest {
+    pub(crate) pragma: String,
+    pub(crate) license: String,
+    pub(crate) test_name: String,
+    pub(crate) contract_name: String,
+    pub(crate) verifier_filename: String,
+    pub(crate) fn_name: String,
+    pub(crate) on_verified_hook: bool,
+    pub(crate) calldata: String,
+    pub(crate) tampered_calldata: String,
+}
+
+#[derive(Template)]
+#[template(path = "Halo2Verifier.yul")]
+pub(crate) struct Halo2VerifierYul {
+    pub(crate) verifier: Halo2Verifier,
+    pub(crate) fn_signature: String,
+    pub(crate) fn_sig: u32,
+}
+
+impl Deref for Halo2VerifierYul {
+    type Target = Halo2Verifier;
+
+    fn deref(&self) -> &Self::Target {
+        &self.verifier
+    }
+}
+
+impl Halo2VerifierYul {
+    /// Replace constants in rendered `code` by their values, since Yul doesn't support constants.
+    pub(crate) fn inline_constants(&self, code: &str) -> String {
+        let constants = self
+            .constants()
+            .into_iter()
+            .flat_map(|(_, constants)| constants)
+            .map(|(name, value)| (name, value.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        let mut inlined = String::new();
+        let mut ident = String::new();
+        for c in code.chars() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                ident.push(c);
+            } else {
+                inlined.push_str(constants.get(&ident).unwrap_or(&ident));
+                inlined.push(c);
+                ident.clear();
+            }
+        }
+        inlined.push_str(constants.get(&ident).unwrap_or(&ident));
+        inlined
     }
 }
 
 impl Halo2Verifier {
-    pub(crate) fn render(&self, writer: &mut impl fmt::Write) -> Result<(), fmt::Error> {
-        self.render_into(writer).map_err(|err| match err {
-            Error::Fmt(err) => err,
-            _ => unreachable!(),
-        })
+    /// Calldata and memory pointers in groups with description, which are declared as constants
+    /// by Solidity and inlined by Yul.
+    fn constants(&self) -> Vec<(&'static str, Vec<(String, Ptr)>)> {
+        let named = |names: &[&str], ptr: Ptr| {
+            names
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| (name.to_string(), ptr + idx))
+                .collect::<Vec<_>>()
+        };
+        let instance_cptrs = if self.hashed_instances {
+            named(&["INSTANCES_HASH_CPTR"], self.proof_cptr - 2)
+        } else {
+            let num_instance_cptr = self.proof_cptr + self.proof_len / 0x20;
+            named(&["NUM_INSTANCE_CPTR", "INSTANCE_CPTR"], num_instance_cptr)
+        };
+        let num_instances_mptrs = (0..self.num_instance_columns).map(|column| {
+            (
+                format!("NUM_INSTANCES_{column}_MPTR"),
+                self.vk_mptr + (21 + column),
+            )
+        });
+        let theta_mptrs = chain![
+            self.theta_used
+                .then(|| ("THETA_MPTR".to_string(), self.theta_mptr)),
+            named(
+                &["BETA_MPTR", "GAMMA_MPTR", "Y_MPTR", "X_MPTR"],
+                self.theta_mptr + 1
+            ),
+            match self.scheme {
+                Bdfg21 => named(&["ZETA_MPTR", "NU_MPTR", "MU_MPTR"], self.theta_mptr + 5),
+                Gwc19 => named(&["NU_MPTR", "MU_MPTR"], self.theta_mptr + 5),
+            },
+        ];
+        vec![
+            (
+                "Calldata pointers of proof and instances.",
+                chain![
+                    named(&["PROOF_LEN_CPTR", "PROOF_CPTR"], self.proof_cptr - 1),
+                    instance_cptrs
+                ]
+                .collect(),
+            ),
+            (
+                "Calldata pointers of the first and the last quotient commitments.",
+                vec![
+                    ("FIRST_QUOTIENT_X_CPTR".to_string(), self.quotient_comm_cptr),
+                    (
+                        "LAST_QUOTIENT_X_CPTR".to_string(),
+                        self.quotient_comm_cptr + 2 * (self.num_quotients - 1),
+                    ),
+                ],
+            ),
+            (
+                "Memory pointers of verifying key, which is embedded or copied from `vk`.",
+                chain![
+                    [("VK_MPTR".to_string(), self.vk_mptr)],
+                    named(
+                        &[
+                            "VK_DIGEST_MPTR",
+                            "NUM_INSTANCES_MPTR",
+                            "K_MPTR",
+                            "N_INV_MPTR",
+                            "OMEGA_MPTR",
+                            "OMEGA_INV_MPTR",
+                            "OMEGA_INV_TO_L_MPTR",
+                            "HAS_ACCUMULATOR_MPTR",
+                            "ACC_OFFSET_MPTR",
+                            "NUM_ACC_LIMBS_MPTR",
+                            "NUM_ACC_LIMB_BITS_MPTR",
+                            "G1_X_MPTR",
+                            "G1_Y_MPTR",
+                            "G2_X_1_MPTR",
+                            "G2_X_2_MPTR",
+                            "G2_Y_1_MPTR",
+                            "G2_Y_2_MPTR",
+                            "NEG_S_G2_X_1_MPTR",
+                            "NEG_S_G2_X_2_MPTR",
+                            "NEG_S_G2_Y_1_MPTR",
+                            "NEG_S_G2_Y_2_MPTR",
+                        ],
+                        self.vk_mptr,
+                    ),
+                    num_instances_mptrs,
+                ]
+                .collect(),
+            ),
+            (
+                "Memory pointer of challenges squeezed after each phase.",
+                vec![("CHALLENGE_MPTR".to_string(), self.challenge_mptr)],
+            ),
+            (
+                "Memory pointers of challenges squeezed for permutation, lookup and batch opening.",
+                theta_mptrs.collect(),
+            ),
+            (
+                "Memory pointers of accumulator and intermediate values of verification.",
+                named(
+                    &[
+                        "ACC_LHS_X_MPTR",
+                        "ACC_LHS_Y_MPTR",
+                        "ACC_RHS_X_MPTR",
+                        "ACC_RHS_Y_MPTR",
+                        "X_N_MPTR",
+                        "X_N_MINUS_1_INV_MPTR",
+                        "L_LAST_MPTR",
+                        "L_BLIND_MPTR",
+                        "L_0_MPTR",
+                        "QUOTIENT_EVAL_MPTR",
+                        "QUOTIENT_X_MPTR",
+                        "QUOTIENT_Y_MPTR",
+                        "G1_SCALAR_MPTR",
+                        "PAIRING_LHS_X_MPTR",
+                        "PAIRING_LHS_Y_MPTR",
+                        "PAIRING_RHS_X_MPTR",
+                        "PAIRING_RHS_Y_MPTR",
+                        "INSTANCE_EVAL_MPTR",
+                    ],
+                    self.theta_mptr + 8,
+                ),
+            ),
+        ]
+    }
+
+    /// Pad `name` at start to align with the longest name of its group `constants`.
+    fn pad_constant_name(&self, name: &str, constants: &[(String, Ptr)]) -> String {
+        let width = constants
+            .iter()
+            .map(|(other, _)| other.len())
+            .max()
+            .unwrap_or(0);
+        format!("{name:>width$}")
+    }
+
+    /// Whether verifying key address is passed as the first parameter of verify functions.
+    fn vk_param(&self) -> bool {
+        self.embedded_vk.is_none() && self.vk_address_source == Calldata
     }
+
+    /// Whether verifying key address is read from state set by constructor.
+    fn vk_state(&self) -> bool {
+        self.embedded_vk.is_none() && self.vk_address_source != Calldata
+    }
+
+    /// Whether instances are encoded in little-endian and need `reverse_bytes` to be loaded.
+    fn little_endian_instances(&self) -> bool {
+        self.instance_byte_order == InstanceByteOrder::LittleEndian
+    }
+
+    /// Load instance at `cptr` of calldata in the byte order of instances.
+    fn load_instance(&self, cptr: &str) -> String {
+        load_instance(self.instance_byte_order, cptr)
+    }
+}
+
+/// Render `template` into `writer`, which only fails when `writer` does, since the templates
+/// don't call any fallible filter.
+pub(crate) fn render<T: Template>(
+    template: &T,
+    writer: &mut impl fmt::Write,
+) -> Result<(), fmt::Error> {
+    template.render_into(writer).map_err(|err| match err {
+        Error::Fmt(err) => err,
+        _ => unreachable!(),
+    })
 }
 
 mod filters {
@@ -1,6 +1,9 @@
-use crate::codegen::{
-    template::Halo2VerifyingKey,
-    BatchOpenScheme::{self, Bdfg21, Gwc19},
+use crate::{
+    codegen::{
+        template::Halo2VerifyingKey,
+        BatchOpenScheme::{self, Bdfg21, Gwc19},
+    },
+    evm::InstanceByteOrder,
 };
 use halo2_proofs::{
     halo2curves::{bn256, ff::PrimeField, CurveAffine},
@@ -10,7 +13,7 @@ use itertools::{chain, izip, Itertools};
 use ruint::{aliases::U256, UintTryFrom};
 use std::{
     borrow::Borrow,
-    collections::HashMap,
+    collections::BTreeMap,
     fmt::{self, Display, Formatter},
     ops::{Add, Sub},
 };
@@ -18,11 +21,14 @@ use std::{
 #[derive(Debug)]
 pub(crate) struct ConstraintSystemMeta {
     pub(crate) num_fixeds: usize,
+    pub(crate) num_instance_columns: usize,
+    pub(crate) rotated_instance_queries: Vec<(usize, i32)>,
     pub(crate) permutation_columns: Vec<Column<Any>>,
     pub(crate) permutation_chunk_len: usize,
     pub(crate) num_lookup_permuteds: usize,
     pub(crate) num_permutation_zs: usize,
     pub(crate) num_lookup_zs: usize,
+    pub(crate) num_shuffle_zs: usize,
     pub(crate) num_quotients: usize,
     pub(crate) advice_queries: Vec<(usize, i32)>,
     pub(crate) fixed_queries: Vec<(usize, i32)>,
@@ -33,11 +39,19 @@ pub(crate) struct ConstraintSystemMeta {
     pub(crate) advice_indices: Vec<usize>,
     pub(crate) challenge_indices: Vec<usize>,
     pub(crate) rotation_last: i32,
+    pub(crate) num_circuits: usize,
 }
 
 impl ConstraintSystemMeta {
     pub(crate) fn new(cs: &ConstraintSystem<impl PrimeField>) -> Self {
         let num_fixeds = cs.num_fixed_columns();
+        let num_instance_columns = cs.num_instance_columns();
+        let rotated_instance_queries = cs
+            .instance_queries()
+            .iter()
+            .map(|(column, rotation)| (column.index(), rotation.0))
+            .filter(|(_, rotation)| *rotation != 0)
+            .collect_vec();
         let permutation_columns = cs.permutation().get_columns();
         let permutation_chunk_len = cs.degree() - 2;
         let num_lookup_permuteds = 2 * cs.lookups().len();
@@ -47,6 +61,7 @@ impl ConstraintSystemMeta {
             .chunks(cs.degree() - 2)
             .count();
         let num_lookup_zs = cs.lookups().len();
+        let num_shuffle_zs = cs.shuffles().len();
         let num_quotients = cs.degree() - 1;
         let advice_queries = cs
             .advice_queries()
@@ -59,11 +74,12 @@ impl ConstraintSystemMeta {
             .map(|(column, rotation)| (column.index(), rotation.0))
             .collect_vec();
         let num_evals = advice_queries.len()
+            + (3 * num_permutation_zs - 1)
+            + 5 * cs.lookups().len()
+            + 2 * cs.shuffles().len()
             + fixed_queries.len()
             + 1
-            + cs.permutation().get_columns().len()
-            + (3 * num_permutation_zs - 1)
-            + 5 * cs.lookups().len();
+            + cs.permutation().get_columns().len();
         let num_phase = *cs.advice_column_phase().iter().max().unwrap_or(&0) as usize + 1;
         // Indices of advice and challenge are not same as their position in calldata/memory,
         // because we support multiple phases, we need to remap them and find their actual indices.
@@ -104,16 +120,23 @@ impl ConstraintSystemMeta {
                 .then_some([-1, 0, 1])
                 .into_iter()
                 .flatten(),
+            (num_shuffle_zs > 0)
+                .then_some([0, 1])
+                .into_iter()
+                .flatten(),
         ]
         .unique()
         .count();
         Self {
             num_fixeds,
+            num_instance_columns,
+            rotated_instance_queries,
             permutation_columns,
             permutation_chunk_len,
             num_lookup_permuteds,
             num_permutation_zs,
             num_lookup_zs,
+            num_shuffle_zs,
             num_quotients,
             advice_queries,
             fixed_queries,
@@ -124,16 +147,39 @@ impl ConstraintSystemMeta {
             advice_indices,
             challenge_indices,
             rotation_last,
+            num_circuits: 1,
         }
     }
 
+    /// Replicate advices, grand products and their evaluations for `num_circuits` circuits
+    /// proven in one transcript, which share fixed columns, permutation commitments and quotient.
+    pub(crate) fn with_num_circuits(mut self, num_circuits: usize) -> Self {
+        let num_circuit_evals = self.num_circuit_evals();
+        self.num_evals = self.num_evals - self.num_circuits * num_circuit_evals
+            + num_circuits * num_circuit_evals;
+        self.num_circuits = num_circuits;
+        self
+    }
+
+    /// Number of evaluations of each circuit, which are the ones of advices, permutation, lookup
+    /// and shuffle grand products.
+    pub(crate) fn num_circuit_evals(&self) -> usize {
+        self.advice_queries.len()
+            + (3 * self.num_permutation_zs - 1)
+            + self.num_lookup_evals()
+            + 2 * self.num_shuffles()
+    }
+
     pub(crate) fn num_advices(&self) -> Vec<usize> {
+        let n = self.num_circuits;
         chain![
-            self.num_user_advices.iter().cloned(),
-            (self.num_lookup_permuteds != 0).then_some(self.num_lookup_permuteds), // lookup permuted
+            self.num_user_advices.iter().map(|num| n * num),
+            // lookup permuted
+            (self.num_lookup_permuteds != 0).then_some(n * self.num_lookup_permuteds),
             [
-                self.num_permutation_zs + self.num_lookup_zs + 1, // permutation and lookup grand products, random
-                self.num_quotients,                               // quotients
+                // permutation, lookup and shuffle grand products, random
+                n * (self.num_permutation_zs + self.num_lookup_zs + self.num_shuffle_zs) + 1,
+                self.num_quotients, // quotients
             ],
         ]
         .collect()
@@ -161,6 +207,16 @@ impl ConstraintSystemMeta {
         num_challenges
     }
 
+    /// Number of Lagrange bases needed after the ones of instances, for instance queries with
+    /// negative rotation.
+    pub(crate) fn num_extra_lagranges(&self) -> usize {
+        self.rotated_instance_queries
+            .iter()
+            .map(|(_, rotation)| (-rotation).max(0) as usize)
+            .max()
+            .unwrap_or_default()
+    }
+
     pub(crate) fn num_permutations(&self) -> usize {
         self.permutation_columns.len()
     }
@@ -169,6 +225,16 @@ impl ConstraintSystemMeta {
         self.num_lookup_zs
     }
 
+    /// Number of evaluations of lookup arguments, which are `z`, `z_next`, `permuted_input`,
+    /// `permuted_input_prev` and `permuted_table` of each.
+    pub(crate) fn num_lookup_evals(&self) -> usize {
+        5 * self.num_lookups()
+    }
+
+    pub(crate) fn num_shuffles(&self) -> usize {
+        self.num_shuffle_zs
+    }
+
     pub(crate) fn proof_len(&self, scheme: BatchOpenScheme) -> usize {
         self.num_advices().iter().sum::<usize>() * 0x40
             + self.num_evals * 0x20
@@ -192,22 +258,24 @@ pub(crate) struct Data {
     pub(crate) w_cptr: Ptr,
 
     pub(crate) fixed_comms: Vec<EcPoint>,
-    pub(crate) permutation_comms: HashMap<Column<Any>, EcPoint>,
+    pub(crate) permutation_comms: BTreeMap<Column<Any>, EcPoint>,
     pub(crate) advice_comms: Vec<EcPoint>,
     pub(crate) lookup_permuted_comms: Vec<(EcPoint, EcPoint)>,
     pub(crate) permutation_z_comms: Vec<EcPoint>,
     pub(crate) lookup_z_comms: Vec<EcPoint>,
+    pub(crate) shuffle_z_comms: Vec<EcPoint>,
     pub(crate) random_comm: EcPoint,
 
     pub(crate) challenges: Vec<Word>,
 
-    pub(crate) instance_eval: Word,
-    pub(crate) advice_evals: HashMap<(usize, i32), Word>,
-    pub(crate) fixed_evals: HashMap<(usize, i32), Word>,
+    pub(crate) instance_evals: BTreeMap<(usize, i32), Word>,
+    pub(crate) advice_evals: BTreeMap<(usize, i32), Word>,
+    pub(crate) fixed_evals: BTreeMap<(usize, i32), Word>,
     pub(crate) random_eval: Word,
-    pub(crate) permutation_evals: HashMap<Column<Any>, Word>,
+    pub(crate) permutation_evals: BTreeMap<Column<Any>, Word>,
     pub(crate) permutation_z_evals: Vec<(Word, Word, Word)>,
     pub(crate) lookup_evals: Vec<(Word, Word, Word, Word, Word)>,
+    pub(crate) shuffle_evals: Vec<(Word, Word)>,
 
     pub(crate) computed_quotient_comm: EcPoint,
     pub(crate) computed_quotient_eval: Word,
@@ -220,26 +288,69 @@ impl Data {
         vk_mptr: Ptr,
         proof_cptr: Ptr,
     ) -> Self {
+        Self::new_for_circuit(meta, vk, vk_mptr, proof_cptr, 0)
+    }
+
+    /// Return `Data` of each circuit proven in one transcript, which differ only in advices,
+    /// instance evaluations and grand products.
+    pub(crate) fn new_per_circuit(
+        meta: &ConstraintSystemMeta,
+        vk: &Halo2VerifyingKey,
+        vk_mptr: Ptr,
+        proof_cptr: Ptr,
+    ) -> Vec<Self> {
+        (0..meta.num_circuits)
+            .map(|circuit| Self::new_for_circuit(meta, vk, vk_mptr, proof_cptr, circuit))
+            .collect()
+    }
+
+    fn new_for_circuit(
+        meta: &ConstraintSystemMeta,
+        vk: &Halo2VerifyingKey,
+        vk_mptr: Ptr,
+        proof_cptr: Ptr,
+        circuit: usize,
+    ) -> Self {
+        let n = meta.num_circuits;
         let fixed_comm_mptr = vk_mptr + vk.constants.len();
         let permutation_comm_mptr = fixed_comm_mptr + 2 * vk.fixed_comms.len();
         let challenge_mptr = permutation_comm_mptr + 2 * vk.permutation_comms.len();
         let theta_mptr = challenge_mptr + meta.challenge_indices.len();
 
+        // Commitments of each phase are laid out circuit by circuit
         let advice_comm_start = proof_cptr;
-        let lookup_permuted_comm_start = advice_comm_start + 2 * meta.advice_indices.len();
-        let permutation_z_comm_start = lookup_permuted_comm_start + 2 * meta.num_lookup_permuteds;
-        let lookup_z_comm_start = permutation_z_comm_start + 2 * meta.num_permutation_zs;
-        let random_comm_start = lookup_z_comm_start + 2 * meta.num_lookup_zs;
+        let lookup_permuted_comm_start = advice_comm_start + 2 * n * meta.advice_indices.len();
+        let permutation_z_comm_start =
+            lookup_permuted_comm_start + 2 * n * meta.num_lookup_permuteds;
+        let lookup_z_comm_start = permutation_z_comm_start + 2 * n * meta.num_permutation_zs;
+        let shuffle_z_comm_start = lookup_z_comm_start + 2 * n * meta.num_lookup_zs;
+        let random_comm_start = shuffle_z_comm_start + 2 * n * meta.num_shuffle_zs;
         let quotient_comm_start = random_comm_start + 2;
 
+        // Evaluations of advices are followed by the ones of fixed columns, random and
+        // permutation columns, then the ones of grand products are laid out circuit by circuit
+        let num_permutation_z_evals = 3 * meta.num_permutation_zs - 1;
         let eval_cptr = quotient_comm_start + 2 * meta.num_quotients;
         let advice_eval_cptr = eval_cptr;
-        let fixed_eval_cptr = advice_eval_cptr + meta.advice_queries.len();
+        let fixed_eval_cptr = advice_eval_cptr + n * meta.advice_queries.len();
         let random_eval_cptr = fixed_eval_cptr + meta.fixed_queries.len();
         let permutation_eval_cptr = random_eval_cptr + 1;
         let permutation_z_eval_cptr = permutation_eval_cptr + meta.num_permutations();
-        let lookup_eval_cptr = permutation_z_eval_cptr + 3 * meta.num_permutation_zs - 1;
-        let w_cptr = lookup_eval_cptr + 5 * meta.num_lookups();
+        let lookup_eval_cptr = permutation_z_eval_cptr + n * num_permutation_z_evals;
+        let shuffle_eval_cptr = lookup_eval_cptr + n * meta.num_lookup_evals();
+        let w_cptr = shuffle_eval_cptr + 2 * n * meta.num_shuffles();
+
+        let lookup_permuted_comm_start =
+            lookup_permuted_comm_start + 2 * circuit * meta.num_lookup_permuteds;
+        let permutation_z_comm_start =
+            permutation_z_comm_start + 2 * circuit * meta.num_permutation_zs;
+        let lookup_z_comm_start = lookup_z_comm_start + 2 * circuit * meta.num_lookup_zs;
+        let shuffle_z_comm_start = shuffle_z_comm_start + 2 * circuit * meta.num_shuffle_zs;
+        let advice_eval_cptr = advice_eval_cptr + circuit * meta.advice_queries.len();
+        let permutation_z_eval_cptr =
+            permutation_z_eval_cptr + circuit * num_permutation_z_evals;
+        let lookup_eval_cptr = lookup_eval_cptr + circuit * meta.num_lookup_evals();
+        let shuffle_eval_cptr = shuffle_eval_cptr + 2 * circuit * meta.num_shuffles();
 
         let fixed_comms = EcPoint::range(fixed_comm_mptr)
             .take(meta.num_fixeds)
@@ -249,10 +360,26 @@ impl Data {
             EcPoint::range(permutation_comm_mptr)
         )
         .collect();
+        // Advices of each phase are laid out circuit by circuit, so find the phase of each
+        // advice by its index in a single circuit, then skip the ones of previous circuits
+        let phase_ranges = meta
+            .num_user_advices
+            .iter()
+            .scan(0, |end, num| {
+                *end += num;
+                Some((*end - num, *num))
+            })
+            .collect_vec();
         let advice_comms = meta
             .advice_indices
             .iter()
-            .map(|idx| advice_comm_start + 2 * idx)
+            .map(|idx| {
+                let (start, num) = phase_ranges
+                    .iter()
+                    .find(|(start, num)| *idx < start + num)
+                    .unwrap();
+                advice_comm_start + 2 * (n * start + circuit * num + idx - start)
+            })
             .map_into()
             .collect();
         let lookup_permuted_comms = EcPoint::range(lookup_permuted_comm_start)
@@ -265,6 +392,9 @@ impl Data {
         let lookup_z_comms = EcPoint::range(lookup_z_comm_start)
             .take(meta.num_lookup_zs)
             .collect();
+        let shuffle_z_comms = EcPoint::range(shuffle_z_comm_start)
+            .take(meta.num_shuffle_zs)
+            .collect();
         let random_comm = random_comm_start.into();
         let computed_quotient_comm = EcPoint::new(
             Ptr::memory("QUOTIENT_X_MPTR"),
@@ -277,7 +407,20 @@ impl Data {
             .map(|idx| challenge_mptr + *idx)
             .map_into()
             .collect_vec();
-        let instance_eval = Ptr::memory("INSTANCE_EVAL_MPTR").into();
+        // Evaluations of instance columns at `x` circuit by circuit, followed by the ones of
+        // rotated queries
+        let instance_eval_mptr = theta_mptr + 25;
+        let instance_evals = chain![
+            izip!(
+                (0..meta.num_instance_columns).map(|column| (column, 0)),
+                Word::range(instance_eval_mptr + circuit * meta.num_instance_columns),
+            ),
+            izip!(
+                meta.rotated_instance_queries.iter().cloned(),
+                Word::range(instance_eval_mptr + n * meta.num_instance_columns),
+            ),
+        ]
+        .collect();
         let advice_evals = izip!(
             meta.advice_queries.iter().cloned(),
             Word::range(advice_eval_cptr)
@@ -299,7 +442,11 @@ impl Data {
             .tuples()
             .collect_vec();
         let lookup_evals = Word::range(lookup_eval_cptr)
-            .take(5 * meta.num_lookup_zs)
+            .take(meta.num_lookup_evals())
+            .tuples()
+            .collect_vec();
+        let shuffle_evals = Word::range(shuffle_eval_cptr)
+            .take(2 * meta.num_shuffle_zs)
             .tuples()
             .collect_vec();
         let computed_quotient_eval = Ptr::memory("QUOTIENT_EVAL_MPTR").into();
@@ -316,17 +463,19 @@ impl Data {
             lookup_permuted_comms,
             permutation_z_comms,
             lookup_z_comms,
+            shuffle_z_comms,
             random_comm,
             computed_quotient_comm,
 
             challenges,
 
-            instance_eval,
+            instance_evals,
             advice_evals,
             fixed_evals,
             permutation_evals,
             permutation_z_evals,
             lookup_evals,
+            shuffle_evals,
             random_eval,
             computed_quotient_eval,
         }
@@ -578,6 +727,15 @@ pub(crate) fn code_block<const N: usize, const PACKED: bool>(
     }
 }
 
+/// Load instance at `cptr` of calldata, with bytes reversed by `reverse_bytes` if it's encoded in
+/// little-endian.
+pub(crate) fn load_instance(byte_order: InstanceByteOrder, cptr: impl Display) -> String {
+    match byte_order {
+        InstanceByteOrder::BigEndian => format!("calldataload({cptr})"),
+        InstanceByteOrder::LittleEndian => format!("reverse_bytes(calldataload({cptr}))"),
+    }
+}
+
 /// Create a for loop with proper indention.
 pub(crate) fn for_loop(
     initialization: impl IntoIterator<Item = impl Into<String>>,
@@ -1,61 +1,918 @@
-use crate::codegen::util::{fr_to_u256, to_u256_be_bytes};
-use halo2_proofs::halo2curves::bn256;
-use itertools::chain;
+use crate::{
+    codegen::util::{fr_to_u256, to_u256_be_bytes, ConstraintSystemMeta},
+    BatchOpenScheme, Keccak256Transcript,
+};
+use halo2_proofs::{
+    halo2curves::{bn256, ff::PrimeField},
+    plonk::{verify_proof, VerifyingKey},
+    poly::kzg::{
+        commitment::ParamsKZG,
+        multiopen::{VerifierGWC, VerifierSHPLONK},
+        strategy::SingleStrategy,
+    },
+};
+use halo2_solidity_verifier_calldata::{
+    encode_calldata_hashed_words_with_fn_sig, encode_calldata_words_with_fn_sig,
+    FN_SIG_VERIFY_PROOF, FN_SIG_VERIFY_PROOF_HASHED, FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS,
+};
+use itertools::{chain, Itertools};
 use ruint::aliases::U256;
+use sha3::{Digest, Keccak256};
+use std::{fmt, iter, slice};
 
-/// Function signature of `verifyProof(bytes,uint256[])`.
-pub const FN_SIG_VERIFY_PROOF: [u8; 4] = [0x1e, 0x8e, 0x1e, 0x13];
+/// Function signature of `verifyProofPacked(bytes,bytes)`, which is rendered with
+/// `SolidityGeneratorBuilder::packed_instances`.
+pub const FN_SIG_VERIFY_PROOF_PACKED: [u8; 4] = [0x5e, 0xd2, 0x0e, 0x2e];
 
-/// Function signature of `verifyProof(address,bytes,uint256[])`.
-pub const FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS: [u8; 4] = [0xaf, 0x83, 0xa1, 0x8d];
+/// Function signature of `verifyProofPacked(address,bytes,bytes)`, which is rendered with
+/// `SolidityGeneratorBuilder::packed_instances`.
+pub const FN_SIG_VERIFY_PROOF_PACKED_WITH_VK_ADDRESS: [u8; 4] = [0x4d, 0x17, 0xc4, 0x0d];
+
+/// Function signature of `verifyProofNested(bytes,uint256[][])`, which is rendered with
+/// `SolidityGeneratorBuilder::nested_instances`.
+pub const FN_SIG_VERIFY_PROOF_NESTED: [u8; 4] = [0x1e, 0x8d, 0xf0, 0xfe];
+
+/// Function signature of `verifyProofNested(address,bytes,uint256[][])`, which is rendered with
+/// `SolidityGeneratorBuilder::nested_instances`.
+pub const FN_SIG_VERIFY_PROOF_NESTED_WITH_VK_ADDRESS: [u8; 4] = [0x6b, 0x08, 0xb7, 0x05];
+
+/// Function signature of `verifyProofs(bytes[],uint256[][])`.
+pub const FN_SIG_VERIFY_PROOFS: [u8; 4] = [0xad, 0x85, 0x6b, 0xf5];
+
+/// Function signature of `verifyProofs(address,bytes[],uint256[][])`.
+pub const FN_SIG_VERIFY_PROOFS_WITH_VK_ADDRESS: [u8; 4] = [0x16, 0x35, 0xf0, 0x2c];
+
+/// Function signature of `verifyProofsV2(bytes)`, which is rendered with
+/// `SolidityGeneratorBuilder::num_proofs`.
+pub const FN_SIG_VERIFY_PROOFS_V2: [u8; 4] = [0x67, 0xf1, 0xa4, 0x2d];
+
+/// Function signature of `verifyProofsV2(address,bytes)`, which is rendered with
+/// `SolidityGeneratorBuilder::num_proofs`.
+pub const FN_SIG_VERIFY_PROOFS_V2_WITH_VK_ADDRESS: [u8; 4] = [0xb1, 0xf1, 0x55, 0xa1];
+
+/// Function signature of `verifyAndStore(bytes,uint256[])`, which is rendered with
+/// `SolidityGeneratorBuilder::verify_and_store`.
+pub const FN_SIG_VERIFY_AND_STORE: [u8; 4] = [0x55, 0x70, 0xdc, 0x11];
+
+/// Function signature of `verifyAndStore(address,bytes,uint256[])`, which is rendered with
+/// `SolidityGeneratorBuilder::verify_and_store`.
+pub const FN_SIG_VERIFY_AND_STORE_WITH_VK_ADDRESS: [u8; 4] = [0x84, 0xc7, 0xd4, 0x79];
+
+/// Function signature of `isVerified(bytes32)`, which is rendered with
+/// `SolidityGeneratorBuilder::verify_and_store`.
+pub const FN_SIG_IS_VERIFIED: [u8; 4] = [0xc1, 0x81, 0xb2, 0x73];
+
+/// Error signature of `ProofInvalid(uint256)`, which `verifyProof` reverts with when rendered
+/// with `OnFailure::CustomError`.
+pub const ERROR_SIG_PROOF_INVALID: [u8; 4] = [0x43, 0x52, 0x21, 0xf7];
+
+/// Error signature of `VkCodehashMismatch()`, which `verifyProof` reverts with when rendered
+/// with `vk_codehash_check(true)` and given verifying key has unexpected code hash.
+pub const ERROR_SIG_VK_CODEHASH_MISMATCH: [u8; 4] = [0x1c, 0xdc, 0xb7, 0x0b];
+
+/// Event signature of `Verified(bool)`, which is the first topic of the event emitted by the
+/// verify function when rendered with `emit_event(true)`.
+pub const EVENT_SIG_VERIFIED: [u8; 32] = [
+    0x31, 0xf0, 0xcd, 0x20, 0x56, 0xcb, 0x14, 0x96, 0x18, 0x26, 0x08, 0x78, 0x72, 0xd6, 0x4b, 0x91,
+    0x3f, 0xa6, 0x11, 0x81, 0x27, 0xd4, 0xfc, 0xea, 0xde, 0x8a, 0x9c, 0xfe, 0x80, 0xcc, 0xe5, 0xf5,
+];
 
 /// Encode proof into calldata to invoke `Halo2Verifier.verifyProof`.
 ///
 /// For `vk_address`:
 /// - Pass `None` if verifying key is embedded in `Halo2Verifier`
 /// - Pass `Some(vk_address)` if verifying key is separated and deployed at `vk_address`
+///
+/// For `instances`, pass instances of each instance column in the same order as the instance
+/// columns are created in the circuit, they will be flattened column by column. Pass
+/// `&[] as &[Vec<bn256::Fr>]` if the circuit has no instance column.
+///
+/// The calldata is the standard ABI encoding of `verifyProof(bytes,uint256[])` (or
+/// `verifyProof(address,bytes,uint256[])`), so other contracts can call the verifier by interface
+/// with the same flattened instances.
+///
+/// If the verify function is renamed by `SolidityGeneratorBuilder::fn_name`, use
+/// `SolidityGenerator::encode_calldata` instead.
 pub fn encode_calldata(
     vk_address: Option<[u8; 20]>,
     proof: &[u8],
-    instances: &[bn256::Fr],
+    instances: &[impl AsRef<[bn256::Fr]>],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF
+    };
+    let instances = instances
+        .iter()
+        .flat_map(|instances| instances.as_ref().iter())
+        .flat_map(|instance| fr_to_u256(instance).to_be_bytes::<0x20>())
+        .collect_vec();
+    encode_calldata_with_fn_sig(fn_sig, vk_address, proof, &instances)
+}
+
+/// Encode proof into calldata same as [`encode_calldata`] but with given function signature and
+/// instances already encoded by an [`InstanceEncoder`].
+pub(crate) fn encode_calldata_with_fn_sig(
+    fn_sig: [u8; 4],
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    encoded_instances: &[u8],
+) -> Vec<u8> {
+    CalldataPayload {
+        vk_address,
+        proof_bytes: proof.to_vec(),
+        instance_scalars: encoded_instances
+            .chunks(0x20)
+            .map(|word| word.try_into().unwrap())
+            .collect(),
+    }
+    .to_bytes_with_fn_sig(fn_sig)
+}
+
+/// Encode proof into calldata to invoke `Halo2Verifier.verifyProof` rendered with
+/// `SolidityGeneratorBuilder::hashed_instances`, which takes `instances_hash` instead of instances.
+///
+/// For `vk_address`, pass the same as [`encode_calldata`]. For `instances_hash`, pass the hash of
+/// public inputs constrained by the circuit, e.g. computed by [`hash_instances`].
+///
+/// The calldata is the standard ABI encoding of `verifyProof(bytes,bytes32)` (or
+/// `verifyProof(address,bytes,bytes32)`).
+pub fn encode_calldata_hashed(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances_hash: [u8; 32],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF_HASHED
+    };
+    encode_calldata_hashed_with_fn_sig(fn_sig, vk_address, proof, instances_hash)
+}
+
+/// Encode proof into calldata same as [`encode_calldata_hashed`] but with given function
+/// signature.
+pub(crate) fn encode_calldata_hashed_with_fn_sig(
+    fn_sig: [u8; 4],
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances_hash: [u8; 32],
+) -> Vec<u8> {
+    encode_calldata_hashed_words_with_fn_sig(fn_sig, vk_address, proof, instances_hash)
+}
+
+/// Hash instances flattened column by column into `keccak256(abi.encodePacked(instances))`,
+/// which is what a circuit for `SolidityGeneratorBuilder::hashed_instances` is expected to
+/// constrain and expose by [`instances_from_hash`].
+pub fn hash_instances(instances: &[impl AsRef<[bn256::Fr]>]) -> [u8; 32] {
+    let words = instances
+        .iter()
+        .flat_map(|instances| instances.as_ref().iter())
+        .flat_map(|instance| fr_to_u256(instance).to_be_bytes::<0x20>())
+        .collect::<Vec<_>>();
+    Keccak256::digest(words).into()
+}
+
+/// Split `instances_hash` into the higher and lower 128 bits, which are the 2 instances of a
+/// circuit for `SolidityGeneratorBuilder::hashed_instances` in order.
+pub fn instances_from_hash(instances_hash: [u8; 32]) -> [bn256::Fr; 2] {
+    let [hi, lo] = [&instances_hash[..16], &instances_hash[16..]]
+        .map(|half| u128::from_be_bytes(half.try_into().unwrap()));
+    [hi, lo].map(bn256::Fr::from_u128)
+}
+
+/// Recompose instances hash from the 2 instances split by [`instances_from_hash`].
+///
+/// # Panics
+/// Panics if there are not exactly 2 instances, or any of them has more than 128 bits.
+pub(crate) fn hash_from_instances(instances: &[impl AsRef<[bn256::Fr]>]) -> [u8; 32] {
+    let halves = instances
+        .iter()
+        .flat_map(|instances| instances.as_ref().iter())
+        .map(|instance| {
+            let bytes = fr_to_u256(instance).to_be_bytes::<0x20>();
+            assert_eq!(bytes[..16], [0; 16], "Instance has more than 128 bits");
+            bytes[16..].to_vec()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(halves.len(), 2, "Hashed instances are not exactly 2");
+    halves.concat().try_into().unwrap()
+}
+
+/// Encode proof into calldata to invoke `Halo2Verifier.verifyProofPacked`, which is only rendered
+/// with `SolidityGeneratorBuilder::packed_instances`.
+///
+/// For `vk_address` and `instances`, pass the same as [`encode_calldata`]. Each instance is
+/// packed big-endian into the least bytes holding its width in `bit_widths`, which are
+/// concatenated as `packedInstances`, see [`pack_instances`].
+///
+/// The calldata is the standard ABI encoding of `verifyProofPacked(bytes,bytes)` (or
+/// `verifyProofPacked(address,bytes,bytes)`).
+///
+/// # Panics
+/// Panics if number of instances mismatches `bit_widths`, or any instance doesn't fit in the
+/// bytes of its width.
+pub fn encode_calldata_packed(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances: &[impl AsRef<[bn256::Fr]>],
+    bit_widths: &[usize],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_PACKED_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF_PACKED
+    };
+    let packed_instances = pack_instances(instances, bit_widths);
+    encode_calldata_packed_with_fn_sig(fn_sig, vk_address, proof, &packed_instances)
+}
+
+/// Encode proof into calldata same as [`encode_calldata_packed`] but with given function
+/// signature and instances already packed.
+pub(crate) fn encode_calldata_packed_with_fn_sig(
+    fn_sig: [u8; 4],
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    packed_instances: &[u8],
+) -> Vec<u8> {
+    let (vk_address, proof_offset) = if let Some(vk_address) = vk_address {
+        let vk_address = U256::try_from_be_slice(&vk_address)
+            .unwrap()
+            .to_be_bytes::<0x20>();
+        (Some(vk_address), 0x60)
+    } else {
+        (None, 0x40)
+    };
+    let padded_len = |len: usize| (len + 0x1f) / 0x20 * 0x20;
+    let packed_instances_offset = proof_offset + 0x20 + padded_len(proof.len());
+    chain![
+        fn_sig,                                    // function signature
+        vk_address.into_iter().flatten(),          // verifying key address
+        to_u256_be_bytes(proof_offset),            // offset of proof
+        to_u256_be_bytes(packed_instances_offset), // offset of packed instances
+        to_u256_be_bytes(proof.len()),             // length of proof
+        proof.iter().cloned(),                     // proof
+        iter::repeat(0).take(padded_len(proof.len()) - proof.len()),
+        to_u256_be_bytes(packed_instances.len()), // length of packed instances
+        packed_instances.iter().cloned(),         // packed instances
+        iter::repeat(0).take(padded_len(packed_instances.len()) - packed_instances.len()),
+    ]
+    .collect()
+}
+
+/// Pack instances flattened column by column, each into `(bit_width + 7) / 8` bytes big-endian,
+/// which is `packedInstances` taken by `Halo2Verifier.verifyProofPacked`.
+///
+/// # Panics
+/// Panics if number of instances mismatches `bit_widths`, or any instance doesn't fit in the
+/// bytes of its width.
+pub fn pack_instances(instances: &[impl AsRef<[bn256::Fr]>], bit_widths: &[usize]) -> Vec<u8> {
+    let instances = instances
+        .iter()
+        .flat_map(|instances| instances.as_ref().iter())
+        .collect_vec();
+    assert_eq!(
+        instances.len(),
+        bit_widths.len(),
+        "Number of instances mismatches number of bit widths"
+    );
+    instances
+        .into_iter()
+        .zip(bit_widths)
+        .flat_map(|(instance, bit_width)| {
+            let bytes = fr_to_u256(instance).to_be_bytes::<0x20>();
+            let (high, low) = bytes.split_at(0x20 - (bit_width + 7) / 8);
+            assert!(
+                high.iter().all(|byte| *byte == 0),
+                "Instance doesn't fit in {bit_width} bits"
+            );
+            low.to_vec()
+        })
+        .collect()
+}
+
+/// Encode proof into calldata to invoke `Halo2Verifier.verifyProofNested`, which is only rendered
+/// with `SolidityGeneratorBuilder::nested_instances`.
+///
+/// For `vk_address`, pass the same as [`encode_calldata`]. For `instances`, pass instances of
+/// each instance column in the same order as the instance columns are created in the circuit,
+/// each of them is encoded as a separate array, so columns can have different lengths.
+///
+/// The calldata is the standard ABI encoding of `verifyProofNested(bytes,uint256[][])` (or
+/// `verifyProofNested(address,bytes,uint256[][])`), and the verifier flattens the arrays column
+/// by column into the same instances taken by [`encode_calldata`].
+pub fn encode_calldata_nested(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances: &[impl AsRef<[bn256::Fr]>],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_NESTED_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF_NESTED
+    };
+    let columns = instances
+        .iter()
+        .map(|column| BigEndianEncoder::encode(slice::from_ref(column)))
+        .collect_vec();
+    encode_calldata_nested_with_fn_sig(fn_sig, vk_address, proof, &columns)
+}
+
+/// Encode proof into calldata same as [`encode_calldata_nested`] but with given function
+/// signature and instances of each column already encoded by an [`InstanceEncoder`].
+pub(crate) fn encode_calldata_nested_with_fn_sig(
+    fn_sig: [u8; 4],
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    encoded_columns: &[Vec<u8>],
+) -> Vec<u8> {
+    let (vk_address, proof_offset) = if let Some(vk_address) = vk_address {
+        let vk_address = U256::try_from_be_slice(&vk_address)
+            .unwrap()
+            .to_be_bytes::<0x20>();
+        (Some(vk_address), 0x60)
+    } else {
+        (None, 0x40)
+    };
+    let padded_len = |len: usize| (len + 0x1f) / 0x20 * 0x20;
+    let instances_offset = proof_offset + 0x20 + padded_len(proof.len());
+    let encoded_instances = encode_dynamic_array(encoded_columns.iter().map(|column| {
+        chain![
+            to_u256_be_bytes(column.len() / 0x20),
+            column.iter().cloned()
+        ]
+        .collect::<Vec<_>>()
+    }));
+    chain![
+        fn_sig,                             // function signature
+        vk_address.into_iter().flatten(),   // verifying key address
+        to_u256_be_bytes(proof_offset),     // offset of proof
+        to_u256_be_bytes(instances_offset), // offset of instances
+        to_u256_be_bytes(proof.len()),      // length of proof
+        proof.iter().cloned(),              // proof
+        iter::repeat(0).take(padded_len(proof.len()) - proof.len()),
+        encoded_instances, // instances of each column
+    ]
+    .collect()
+}
+
+/// Encode proofs into calldata to invoke `Halo2Verifier.verifyProofs`, which is only rendered
+/// with `SolidityGeneratorBuilder::batch_verify`.
+///
+/// For `vk_address`, pass the same as [`encode_calldata`]. Each item of `proofs` is a proof with
+/// its instances, which are flattened column by column same as [`encode_calldata`].
+///
+/// The calldata is the standard ABI encoding of `verifyProofs(bytes[],uint256[][])` (or
+/// `verifyProofs(address,bytes[],uint256[][])`).
+pub fn encode_calldata_batch(
+    vk_address: Option<[u8; 20]>,
+    proofs: &[(&[u8], &[impl AsRef<[bn256::Fr]>])],
 ) -> Vec<u8> {
-    let (fn_sig, offset) = if vk_address.is_some() {
-        (FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS, 0x60)
+    let (fn_sig, vk_address, offset) = if let Some(vk_address) = vk_address {
+        let vk_address = U256::try_from_be_slice(&vk_address)
+            .unwrap()
+            .to_be_bytes::<0x20>();
+        (FN_SIG_VERIFY_PROOFS_WITH_VK_ADDRESS, Some(vk_address), 0x60)
     } else {
-        (FN_SIG_VERIFY_PROOF, 0x40)
+        (FN_SIG_VERIFY_PROOFS, None, 0x40)
     };
-    let vk_address = if let Some(vk_address) = vk_address {
-        U256::try_from_be_slice(&vk_address)
+    let encoded_proofs = encode_dynamic_array(proofs.iter().map(|(proof, _)| {
+        chain![to_u256_be_bytes(proof.len()), proof.iter().cloned()].collect::<Vec<_>>()
+    }));
+    let encoded_instances = encode_dynamic_array(proofs.iter().map(|(_, instances)| {
+        let instances = instances
+            .iter()
+            .flat_map(|instances| instances.as_ref().iter())
+            .collect::<Vec<_>>();
+        chain![
+            to_u256_be_bytes(instances.len()),
+            instances
+                .into_iter()
+                .flat_map(|instance| fr_to_u256(instance).to_be_bytes::<0x20>()),
+        ]
+        .collect::<Vec<_>>()
+    }));
+    chain![
+        fn_sig,                                          // function signature
+        vk_address.into_iter().flatten(),                // verifying key address
+        to_u256_be_bytes(offset),                        // offset of proofs
+        to_u256_be_bytes(offset + encoded_proofs.len()), // offset of instances
+        encoded_proofs,                                  // proofs
+        encoded_instances,                               // instances
+    ]
+    .collect()
+}
+
+/// Encode proofs into calldata to invoke `Halo2Verifier.verifyProofsV2`, which is only rendered
+/// with `SolidityGeneratorBuilder::num_proofs`.
+///
+/// For `vk_address`, pass the same as [`encode_calldata`]. Each item of `proofs` is a proof with
+/// its instances, which are flattened column by column same as [`encode_calldata`].
+///
+/// Proofs are concatenated into one `bytes`, each as length of proof, proof, number of instances
+/// and instances, with lengths and instances in 32 bytes big-endian words. The calldata is the
+/// standard ABI encoding of `verifyProofsV2(bytes)` (or `verifyProofsV2(address,bytes)`).
+pub fn encode_calldata_v2(
+    vk_address: Option<[u8; 20]>,
+    proofs: &[(&[u8], &[impl AsRef<[bn256::Fr]>])],
+) -> Vec<u8> {
+    encode_calldata_v2_with_byte_order(vk_address, proofs, InstanceByteOrder::BigEndian)
+}
+
+/// Encode proofs into calldata same as [`encode_calldata_v2`] but with given byte order of
+/// instances.
+pub(crate) fn encode_calldata_v2_with_byte_order(
+    vk_address: Option<[u8; 20]>,
+    proofs: &[(&[u8], &[impl AsRef<[bn256::Fr]>])],
+    byte_order: InstanceByteOrder,
+) -> Vec<u8> {
+    let (fn_sig, vk_address, offset) = if let Some(vk_address) = vk_address {
+        let vk_address = U256::try_from_be_slice(&vk_address)
             .unwrap()
-            .to_be_bytes::<0x20>()
-            .to_vec()
+            .to_be_bytes::<0x20>();
+        (
+            FN_SIG_VERIFY_PROOFS_V2_WITH_VK_ADDRESS,
+            Some(vk_address),
+            0x40,
+        )
     } else {
-        Vec::new()
+        (FN_SIG_VERIFY_PROOFS_V2, None, 0x20)
     };
-    let num_instances = instances.len();
+    let concatenated = proofs
+        .iter()
+        .flat_map(|(proof, instances)| {
+            let instances = byte_order.encode(instances);
+            chain![
+                to_u256_be_bytes(proof.len()),
+                proof.iter().cloned(),
+                to_u256_be_bytes(instances.len() / 0x20),
+                instances,
+            ]
+        })
+        .collect::<Vec<_>>();
+    let padding = (0x20 - concatenated.len() % 0x20) % 0x20;
     chain![
-        fn_sig,                                                      // function signature
-        vk_address,                                                  // verifying key address
-        to_u256_be_bytes(offset),                                    // offset of proof
-        to_u256_be_bytes(offset + 0x20 + proof.len()),               // offset of instances
-        to_u256_be_bytes(proof.len()),                               // length of proof
-        proof.iter().cloned(),                                       // proof
-        to_u256_be_bytes(num_instances),                             // length of instances
-        instances.iter().map(fr_to_u256).flat_map(to_u256_be_bytes), // instances
+        fn_sig,                               // function signature
+        vk_address.into_iter().flatten(),     // verifying key address
+        to_u256_be_bytes(offset),             // offset of proofs
+        to_u256_be_bytes(concatenated.len()), // length of proofs
+        concatenated,                         // proofs
+        iter::repeat(0).take(padding),        // padding
     ]
     .collect()
 }
 
+/// Encode dynamic array of already encoded dynamic items, by prepending length and offsets.
+fn encode_dynamic_array(items: impl IntoIterator<Item = Vec<u8>>) -> Vec<u8> {
+    let items = items.into_iter().collect::<Vec<_>>();
+    let offsets = items
+        .iter()
+        .scan(0x20 * items.len(), |offset, item| {
+            let item_offset = *offset;
+            *offset += item.len();
+            Some(item_offset)
+        })
+        .collect::<Vec<_>>();
+    chain![
+        to_u256_be_bytes(items.len()),
+        offsets.into_iter().flat_map(to_u256_be_bytes),
+        items.into_iter().flatten(),
+    ]
+    .collect()
+}
+
+/// Encode proof into [`CalldataPayload`], which keeps the parts of calldata returned by
+/// [`encode_calldata`] separately, so they can be inspected or mutated before encoding.
+pub fn encode_calldata_typed(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances: &[impl AsRef<[bn256::Fr]>],
+) -> CalldataPayload {
+    CalldataPayload {
+        vk_address,
+        proof_bytes: proof.to_vec(),
+        instance_scalars: instances
+            .iter()
+            .flat_map(|instances| instances.as_ref().iter())
+            .map(|instance| fr_to_u256(instance).to_be_bytes::<0x20>())
+            .collect(),
+    }
+}
+
+/// Encode proof into calldata same as [`encode_calldata`], but with instances serialized by
+/// `E`, e.g. [`LittleEndianEncoder`] for a verifier rendered with
+/// `SolidityGeneratorBuilder::instance_encoder::<LittleEndianEncoder>`.
+pub fn encode_calldata_with_encoder<E: InstanceEncoder>(
+    vk_address: Option<[u8; 20]>,
+    proof: &[u8],
+    instances: &[impl AsRef<[bn256::Fr]>],
+) -> Vec<u8> {
+    let fn_sig = if vk_address.is_some() {
+        FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS
+    } else {
+        FN_SIG_VERIFY_PROOF
+    };
+    encode_calldata_with_fn_sig(fn_sig, vk_address, proof, &E::encode(instances))
+}
+
+/// Byte order of each instance word in calldata, which decides how the verifier loads instances.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstanceByteOrder {
+    /// Each instance is a big-endian word, same as ABI encoding of `uint256`.
+    #[default]
+    BigEndian,
+    /// Each instance is a little-endian word, same as `PrimeField::to_repr` of `bn256::Fr`, which
+    /// the verifier reverses before use.
+    LittleEndian,
+}
+
+impl InstanceByteOrder {
+    pub(crate) fn encode(self, instances: &[impl AsRef<[bn256::Fr]>]) -> Vec<u8> {
+        match self {
+            Self::BigEndian => BigEndianEncoder::encode(instances),
+            Self::LittleEndian => LittleEndianEncoder::encode(instances),
+        }
+    }
+}
+
+/// Strategy to serialize instances flattened column by column into 32 bytes words in calldata.
+///
+/// The verifier decodes words by [`InstanceEncoder::BYTE_ORDER`] when rendered with
+/// `SolidityGeneratorBuilder::instance_encoder`.
+pub trait InstanceEncoder {
+    /// Byte order of each encoded word.
+    const BYTE_ORDER: InstanceByteOrder;
+
+    /// Serialize instances into `0x20` bytes per instance.
+    fn encode(instances: &[impl AsRef<[bn256::Fr]>]) -> Vec<u8>;
+}
+
+/// [`InstanceEncoder`] encoding each instance in big-endian, which is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BigEndianEncoder;
+
+impl InstanceEncoder for BigEndianEncoder {
+    const BYTE_ORDER: InstanceByteOrder = InstanceByteOrder::BigEndian;
+
+    fn encode(instances: &[impl AsRef<[bn256::Fr]>]) -> Vec<u8> {
+        instances
+            .iter()
+            .flat_map(|instances| instances.as_ref().iter())
+            .flat_map(|instance| fr_to_u256(instance).to_be_bytes::<0x20>())
+            .collect()
+    }
+}
+
+/// [`InstanceEncoder`] encoding each instance in little-endian, which saves reversing bytes for
+/// provers or contracts that already hold instances in the native representation of `bn256::Fr`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LittleEndianEncoder;
+
+impl InstanceEncoder for LittleEndianEncoder {
+    const BYTE_ORDER: InstanceByteOrder = InstanceByteOrder::LittleEndian;
+
+    fn encode(instances: &[impl AsRef<[bn256::Fr]>]) -> Vec<u8> {
+        instances
+            .iter()
+            .flat_map(|instances| instances.as_ref().iter())
+            .flat_map(|instance| instance.to_repr())
+            .collect()
+    }
+}
+
+/// Parts of calldata to invoke `Halo2Verifier.verifyProof`, returned by
+/// [`encode_calldata_typed`].
+///
+/// Fields are not validated, e.g. an instance scalar could be out of scalar field, which is
+/// useful to construct negative test cases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalldataPayload {
+    /// Address of verifying key, or `None` if verifying key is embedded in `Halo2Verifier`.
+    pub vk_address: Option<[u8; 20]>,
+    /// Proof in bytes.
+    pub proof_bytes: Vec<u8>,
+    /// Instances flattened column by column, each in big-endian unless encoded by
+    /// [`encode_calldata_with_encoder`].
+    pub instance_scalars: Vec<[u8; 32]>,
+}
+
+impl CalldataPayload {
+    /// Encode into calldata, which is same as the output of [`encode_calldata`] if fields are not
+    /// mutated.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let fn_sig = if self.vk_address.is_some() {
+            FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS
+        } else {
+            FN_SIG_VERIFY_PROOF
+        };
+        self.to_bytes_with_fn_sig(fn_sig)
+    }
+
+    fn to_bytes_with_fn_sig(&self, fn_sig: [u8; 4]) -> Vec<u8> {
+        encode_calldata_words_with_fn_sig(
+            fn_sig,
+            self.vk_address,
+            &self.proof_bytes,
+            &self.instance_scalars,
+        )
+    }
+}
+
+/// Decode calldata encoded by [`encode_calldata`] into [`CalldataPayload`].
+///
+/// Whether verifying key address is present is detected by the offset of proof, so calldata of
+/// renamed verify function is also accepted.
+pub fn decode_calldata(calldata: &[u8]) -> Result<CalldataPayload, CalldataError> {
+    let reader = CalldataReader(calldata);
+    let (vk_address, offsets_cptr) = reader.vk_address_and_offsets_cptr()?;
+    let proof_cptr = offsets_cptr + 0x40;
+    let proof_len = reader.usize(proof_cptr)?;
+    if proof_len % 0x20 != 0 || proof_len > calldata.len() {
+        return Err(malformed(format!("unexpected length of proof {proof_len}")));
+    }
+    let instances_cptr = checked_add(proof_cptr + 0x20, proof_len)?;
+    if checked_add(reader.usize(offsets_cptr + 0x20)?, 0x04)? != instances_cptr {
+        return Err(malformed("unexpected offset of instances".to_string()));
+    }
+    let num_instances = reader.usize(instances_cptr)?;
+    if num_instances > calldata.len() {
+        return Err(malformed(format!(
+            "unexpected number of instances {num_instances}"
+        )));
+    }
+    let calldata_len = checked_add(checked_add(instances_cptr, 0x20)?, num_instances * 0x20)?;
+    if calldata.len() != calldata_len {
+        return Err(malformed(format!(
+            "expected {calldata_len} bytes but got {}",
+            calldata.len()
+        )));
+    }
+
+    Ok(CalldataPayload {
+        vk_address,
+        proof_bytes: calldata[proof_cptr + 0x20..instances_cptr].to_vec(),
+        instance_scalars: calldata[instances_cptr + 0x20..]
+            .chunks(0x20)
+            .map(|chunk| <[u8; 0x20]>::try_from(chunk).unwrap())
+            .collect(),
+    })
+}
+
+/// Decode calldata encoded by [`encode_calldata_nested`] into [`NestedCalldataPayload`].
+///
+/// Same as [`decode_calldata`], calldata of renamed verify function is also accepted, but the
+/// ABI encoding is expected to be the canonical one, e.g. without gaps between arrays.
+pub fn decode_calldata_nested(calldata: &[u8]) -> Result<NestedCalldataPayload, CalldataError> {
+    let reader = CalldataReader(calldata);
+    let (vk_address, offsets_cptr) = reader.vk_address_and_offsets_cptr()?;
+
+    // Lengths and offsets beyond calldata are rejected early to bound allocation
+    let read_bounded = |offset: usize| {
+        let value = reader.usize(offset)?;
+        if value > calldata.len() {
+            return Err(malformed(format!("word at {offset:#x} is out of calldata")));
+        }
+        Ok(value)
+    };
+    let read_bytes = |cptr: usize, len: usize| {
+        calldata
+            .get(cptr..)
+            .and_then(|bytes| bytes.get(..len))
+            .ok_or_else(|| malformed(format!("calldata ends before {len} bytes at {cptr:#x}")))
+    };
+    let proof_cptr = offsets_cptr + 0x40;
+    let proof_len = read_bounded(proof_cptr)?;
+    let proof_bytes = read_bytes(proof_cptr + 0x20, proof_len)?.to_vec();
+    let instances_cptr = checked_add(0x04, read_bounded(offsets_cptr + 0x20)?)?;
+    let num_columns = read_bounded(instances_cptr)?;
+    let instance_columns = (0..num_columns)
+        .map(|idx| {
+            let offset = read_bounded(checked_add(instances_cptr, 0x20 * (idx + 1))?)?;
+            let column_cptr = checked_add(checked_add(instances_cptr, 0x20)?, offset)?;
+            let len = read_bounded(column_cptr)?;
+            let words = read_bytes(checked_add(column_cptr, 0x20)?, len * 0x20)?;
+            Ok(words
+                .chunks(0x20)
+                .map(|chunk| <[u8; 0x20]>::try_from(chunk).unwrap())
+                .collect())
+        })
+        .collect::<Result<Vec<Vec<_>>, CalldataError>>()?;
+
+    // Encode again to reject any non-canonical encoding, e.g. with extra bytes
+    let payload = NestedCalldataPayload {
+        vk_address,
+        proof_bytes,
+        instance_columns,
+    };
+    let fn_sig = calldata[..4].try_into().unwrap();
+    if payload.to_bytes_with_fn_sig(fn_sig) != calldata {
+        return Err(malformed("non-canonical encoding".to_string()));
+    }
+    Ok(payload)
+}
+
+/// Reader of words in calldata of the verify function, shared by decoders.
+struct CalldataReader<'a>(&'a [u8]);
+
+impl CalldataReader<'_> {
+    fn word(&self, offset: usize) -> Result<U256, CalldataError> {
+        self.0
+            .get(offset..)
+            .and_then(|bytes| bytes.get(..0x20))
+            .map(U256::from_be_slice)
+            .ok_or_else(|| malformed(format!("calldata ends before word at {offset:#x}")))
+    }
+
+    fn usize(&self, offset: usize) -> Result<usize, CalldataError> {
+        let word = self.word(offset)?;
+        usize::try_from(word).map_err(|_| malformed(format!("word at {offset:#x} is too large")))
+    }
+
+    /// Return verifying key address if present, and pointer of offsets of proof and instances,
+    /// which are relative to the end of function signature.
+    ///
+    /// Verifying key address is detected first, by offset of proof `0x60` following it, since an
+    /// address could be `0x…40`, the offset of proof without it. The only calldata without
+    /// verifying key address detected as with it is the one with empty proof, which never
+    /// verifies.
+    fn vk_address_and_offsets_cptr(&self) -> Result<(Option<[u8; 20]>, usize), CalldataError> {
+        if self.word(0x24)? == U256::from(0x60) && self.0[0x04..0x10] == [0; 12] {
+            Ok((
+                Some(<[u8; 20]>::try_from(&self.0[0x10..0x24]).unwrap()),
+                0x24,
+            ))
+        } else if self.word(0x04)? == U256::from(0x40) {
+            Ok((None, 0x04))
+        } else {
+            Err(malformed("unexpected offset of proof".to_string()))
+        }
+    }
+}
+
+fn malformed(details: String) -> CalldataError {
+    CalldataError::Malformed(details)
+}
+
+/// Add offsets or lengths read from calldata, which is untrusted, so overflow is rejected as
+/// malformed instead of panicking.
+fn checked_add(lhs: usize, rhs: usize) -> Result<usize, CalldataError> {
+    lhs.checked_add(rhs)
+        .ok_or_else(|| malformed(format!("{lhs:#x} + {rhs:#x} overflows")))
+}
+
+/// Parts of calldata to invoke `Halo2Verifier.verifyProofNested`, returned by
+/// [`decode_calldata_nested`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NestedCalldataPayload {
+    /// Address of verifying key, or `None` if verifying key is embedded in `Halo2Verifier`.
+    pub vk_address: Option<[u8; 20]>,
+    /// Proof in bytes.
+    pub proof_bytes: Vec<u8>,
+    /// Instances of each instance column.
+    pub instance_columns: Vec<Vec<[u8; 32]>>,
+}
+
+impl NestedCalldataPayload {
+    /// Flatten instances column by column into [`CalldataPayload`], which is the canonical
+    /// layout of instances taken by `Halo2Verifier.verifyProof`.
+    pub fn flatten(&self) -> CalldataPayload {
+        CalldataPayload {
+            vk_address: self.vk_address,
+            proof_bytes: self.proof_bytes.clone(),
+            instance_scalars: self.instance_columns.concat(),
+        }
+    }
+
+    fn to_bytes_with_fn_sig(&self, fn_sig: [u8; 4]) -> Vec<u8> {
+        let columns = self
+            .instance_columns
+            .iter()
+            .map(|column| column.concat())
+            .collect_vec();
+        encode_calldata_nested_with_fn_sig(fn_sig, self.vk_address, &self.proof_bytes, &columns)
+    }
+}
+
+/// Decode calldata encoded by [`encode_calldata`] and verify the proof natively, so calldata can
+/// be checked before submitting on-chain without running EVM.
+///
+/// Same as the rendered verifier, proof is expected to be of exact length, and each instance is
+/// expected to be in scalar field. Instances are split into columns by `num_instances`, which is
+/// the same as the one given to `SolidityGenerator`.
+pub fn verify_calldata(
+    calldata: &[u8],
+    vk: &VerifyingKey<bn256::G1Affine>,
+    params: &ParamsKZG<bn256::Bn256>,
+    scheme: BatchOpenScheme,
+    num_instances: &[usize],
+) -> Result<(), CalldataError> {
+    let payload = decode_calldata(calldata)?;
+
+    let proof_len = ConstraintSystemMeta::new(vk.cs()).proof_len(scheme);
+    if payload.proof_bytes.len() != proof_len {
+        return Err(CalldataError::Malformed(format!(
+            "expected proof of {proof_len} bytes but got {}",
+            payload.proof_bytes.len()
+        )));
+    }
+    let expected = num_instances.iter().sum();
+    if payload.instance_scalars.len() != expected {
+        return Err(CalldataError::InstancesMismatch {
+            expected,
+            actual: payload.instance_scalars.len(),
+        });
+    }
+
+    let mut instances = payload
+        .instance_scalars
+        .iter()
+        .map(|scalar| {
+            let mut repr = *scalar;
+            repr.reverse();
+            Option::from(bn256::Fr::from_repr(repr)).ok_or_else(|| {
+                let scalar = hex::encode(scalar);
+                CalldataError::Malformed(format!("instance 0x{scalar} is out of scalar field"))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+    let instances = num_instances
+        .iter()
+        .map(|num_instances| instances.by_ref().take(*num_instances).collect_vec())
+        .collect_vec();
+    let instance_slices = instances.iter().map(Vec::as_slice).collect_vec();
+
+    let mut transcript = Keccak256Transcript::from_proof(&payload.proof_bytes);
+    let strategy = SingleStrategy::new(params);
+    let result = match scheme {
+        BatchOpenScheme::Bdfg21 => verify_proof::<_, VerifierSHPLONK<_>, _, _, SingleStrategy<_>>(
+            params,
+            vk,
+            strategy,
+            &[instance_slices.as_slice()],
+            &mut transcript,
+        ),
+        BatchOpenScheme::Gwc19 => verify_proof::<_, VerifierGWC<_>, _, _, SingleStrategy<_>>(
+            params,
+            vk,
+            strategy,
+            &[instance_slices.as_slice()],
+            &mut transcript,
+        ),
+    };
+    result.map_err(|err| CalldataError::VerificationFailed(format!("{err:?}")))
+}
+
+/// Error returned by [`decode_calldata`] and [`verify_calldata`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CalldataError {
+    /// Calldata is not the ABI encoding of verify function, e.g. of unexpected length, with
+    /// details about what's wrong.
+    Malformed(String),
+    /// Number of instances in calldata mismatches the expected one.
+    InstancesMismatch {
+        /// Total number of instances expected.
+        expected: usize,
+        /// Number of instances in calldata.
+        actual: usize,
+    },
+    /// Proof fails native verification, with the error returned by `halo2_proofs`.
+    VerificationFailed(String),
+}
+
+impl fmt::Display for CalldataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(details) => write!(f, "Malformed calldata: {details}"),
+            Self::InstancesMismatch { expected, actual } => write!(
+                f,
+                "Number of instances mismatches, expected {expected} but got {actual}"
+            ),
+            Self::VerificationFailed(err) => write!(f, "Proof verification failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CalldataError {}
+
 #[cfg(any(test, feature = "evm"))]
 pub(crate) mod test {
     pub use revm;
     use revm::{
-        primitives::{Address, CreateScheme, ExecutionResult, Output, TransactTo, TxEnv},
-        InMemoryDB, EVM,
+        interpreter::{opcode, InstructionResult, Interpreter},
+        primitives::{
+            Address, CreateScheme, ExecutionResult, Output, SpecId, TransactTo, TxEnv, U256,
+        },
+        Database, EVMData, InMemoryDB, Inspector, EVM,
     };
+    use sha3::Digest;
     use std::{
         fmt::{self, Debug, Formatter},
         io::{self, Write},
+        iter,
         process::{Command, Stdio},
         str,
     };
@@ -65,12 +922,101 @@ pub(crate) mod test {
     /// # Panics
     /// Panics if executable `solc` can not be found, or compilation fails.
     pub fn compile_solidity(solidity: impl AsRef<[u8]>) -> Vec<u8> {
+        compile_solidity_with_args(solidity, &["--optimize"])
+    }
+
+    /// Compile solidity with optimizer enabled like [`compile_solidity`], then return runtime
+    /// bytecode, whose size is limited to 24576 bytes by EIP-170.
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, or compilation fails.
+    pub fn compile_solidity_runtime(solidity: impl AsRef<[u8]>) -> Vec<u8> {
+        let (stdout, stderr) = solc(solidity, &["--optimize", "--bin-runtime"]);
+        match find_runtime_binary(&stdout) {
+            Some(binary) => hex::decode(binary).unwrap(),
+            None => panic!("Compilation fails:\n{stderr}"),
+        }
+    }
+
+    /// Compile solidity with optimizer enabled and `--optimize-runs` set to `runs`, then return
+    /// creation bytecode.
+    ///
+    /// The `runs` is the number of times each opcode is expected to be executed over the lifetime
+    /// of the contract. Lower `runs` (e.g. `1`) produces smaller bytecode which is cheaper to
+    /// deploy, while higher `runs` produces larger bytecode which is cheaper to call. The default
+    /// of `solc` and Hardhat is `200`.
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, or compilation fails.
+    pub fn compile_solidity_with_optimizer(solidity: impl AsRef<[u8]>, runs: u32) -> Vec<u8> {
+        compile_solidity_with_args(
+            solidity,
+            &["--optimize", "--optimize-runs", &runs.to_string()],
+        )
+    }
+
+    /// Compile solidity with optimizer enabled like [`compile_solidity`], then return creation
+    /// bytecode and JSON ABI, e.g. to generate `ethers-rs` bindings of the verifier.
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, or compilation fails.
+    pub fn compile_solidity_to_abi(solidity: impl AsRef<[u8]>) -> (Vec<u8>, String) {
+        let (stdout, stderr) = solc(solidity, &["--optimize", "--abi"]);
+        match find_binary_and_abi(&stdout) {
+            Some((binary, abi)) => (hex::decode(binary).unwrap(), abi.to_string()),
+            None => panic!("Compilation fails:\n{stderr}"),
+        }
+    }
+
+    /// Compile solidity with optimizer enabled, then return creation bytecode of contract
+    /// `contract_name` in hex, with placeholders of libraries unresolved.
+    ///
+    /// The returned bytecode is expected to be deployed by [`Evm::deploy_and_link`].
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, compilation fails, or contract
+    /// `contract_name` is not found.
+    pub fn compile_solidity_unlinked(solidity: impl AsRef<[u8]>, contract_name: &str) -> Vec<u8> {
+        let (stdout, stderr) = solc(solidity, &["--optimize"]);
+        match find_unlinked_binary(&stdout, contract_name) {
+            Some(binary) => binary.as_bytes().to_vec(),
+            None => panic!("Compilation fails or contract '{contract_name}' not found:\n{stderr}"),
+        }
+    }
+
+    /// Compile Yul object with optimizer enabled by `solc --strict-assembly`, then return creation
+    /// bytecode.
+    ///
+    /// # Panics
+    /// Panics if executable `solc` can not be found, or compilation fails.
+    pub fn compile_yul(source: impl AsRef<[u8]>) -> Vec<u8> {
+        let (stdout, stderr) = solc(source, &["--strict-assembly", "--optimize"]);
+        match stdout
+            .lines()
+            .skip_while(|line| *line != "Binary representation:")
+            .nth(1)
+        {
+            Some(binary) => hex::decode(binary.trim()).unwrap(),
+            None => panic!("Compilation fails:\n{stderr}"),
+        }
+    }
+
+    fn compile_solidity_with_args(solidity: impl AsRef<[u8]>, args: &[&str]) -> Vec<u8> {
+        let (stdout, stderr) = solc(solidity, args);
+        if let Some(binary) = find_binary(&stdout) {
+            binary
+        } else {
+            panic!("Compilation fails:\n{stderr}")
+        }
+    }
+
+    fn solc(solidity: impl AsRef<[u8]>, args: &[&str]) -> (String, String) {
         let mut process = match Command::new("solc")
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .arg("--bin")
-            .arg("--optimize")
+            .args(args)
             .arg("-")
             .spawn()
         {
@@ -89,16 +1035,10 @@ pub(crate) mod test {
             .write_all(solidity.as_ref())
             .unwrap();
         let output = process.wait_with_output().unwrap();
-        // println!("{:?}", output);
-        let stdout = str::from_utf8(&output.stdout).unwrap();
-        if let Some(binary) = find_binary(stdout) {
-            binary
-        } else {
-            panic!(
-                "Compilation fails:\n{}",
-                str::from_utf8(&output.stderr).unwrap()
-            )
-        }
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
     }
 
     fn find_binary(stdout: &str) -> Option<Vec<u8>> {
@@ -106,9 +1046,195 @@ pub(crate) mod test {
         Some(hex::decode(&stdout[start..stdout.len() - 1]).unwrap())
     }
 
-    /// Evm runner.
+    fn find_binary_and_abi(stdout: &str) -> Option<(&str, &str)> {
+        let mut lines = stdout.lines().skip_while(|line| *line != "Binary:").skip(1);
+        let binary = lines.next()?;
+        let abi = lines
+            .skip_while(|line| *line != "Contract JSON ABI")
+            .nth(1)?;
+        Some((binary.trim(), abi.trim()))
+    }
+
+    /// Find the first non-empty runtime binary, which skips interfaces.
+    fn find_runtime_binary(stdout: &str) -> Option<&str> {
+        let mut lines = stdout.lines();
+        iter::from_fn(|| {
+            lines.find(|line| *line == "Binary of the runtime part:")?;
+            lines.next()
+        })
+        .map(str::trim)
+        .find(|binary| !binary.is_empty())
+    }
+
+    fn find_unlinked_binary<'a>(stdout: &'a str, contract_name: &str) -> Option<&'a str> {
+        let header = format!(":{contract_name} =======");
+        let start = stdout.find(&header)? + header.len();
+        stdout[start..]
+            .lines()
+            .skip_while(|line| *line != "Binary:")
+            .nth(1)
+    }
+
+    /// Decode reason of revert data encoded as `Error(string)`.
+    fn decode_revert_reason(output: &[u8]) -> Option<String> {
+        const ERROR_SIG: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+        if output.len() < 0x44 || output[..4] != ERROR_SIG {
+            return None;
+        }
+        let len = U256::try_from_be_slice(&output[0x24..0x44])?.saturating_to::<usize>();
+        let reason = output.get(0x44..0x44usize.checked_add(len)?)?;
+        String::from_utf8(reason.to_vec()).ok()
+    }
+
+    /// Replace placeholders of libraries in hex `bytecode` with their addresses, then return
+    /// linked bytecode.
+    ///
+    /// Each placeholder is `__$` followed by the first 34 hex characters of keccak256 of the fully
+    /// qualified library name and `$__`, where the fully qualified name is like
+    /// `<stdin>:Pairing` when compiled by [`compile_solidity_unlinked`].
+    ///
+    /// # Panics
+    /// Panics if any placeholder is not resolved, or `bytecode` is not valid hex.
+    pub fn link_libraries(bytecode: &[u8], libs: &[(&str, Address)]) -> Vec<u8> {
+        let mut bytecode = str::from_utf8(bytecode).unwrap().trim().to_string();
+        for (name, address) in libs {
+            let hash = hex::encode(sha3::Keccak256::digest(name));
+            let placeholder = format!("__${}$__", &hash[..34]);
+            bytecode = bytecode.replace(&placeholder, &hex::encode(<[u8; 20]>::from(*address)));
+        }
+        assert!(
+            !bytecode.contains("__$"),
+            "Unresolved library placeholder in bytecode"
+        );
+        hex::decode(bytecode).unwrap()
+    }
+
+    /// Gas price and ether price used to estimate cost of transaction in USD.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct GweiSchedule {
+        /// Gas price in gwei.
+        pub gwei_per_gas: u64,
+        /// Price of 1 ether in USD.
+        pub eth_usd: f64,
+    }
+
+    impl GweiSchedule {
+        /// Return a new `GweiSchedule`.
+        pub fn new(gwei_per_gas: u64, eth_usd: f64) -> Self {
+            Self {
+                gwei_per_gas,
+                eth_usd,
+            }
+        }
+
+        /// Return estimated cost in USD of given `gas_used`.
+        pub fn usd_cost(&self, gas_used: u64) -> f64 {
+            (gas_used as f64) * (self.gwei_per_gas as f64) * 1e-9 * self.eth_usd
+        }
+    }
+
+    /// Gas used by a call estimated for each category of operations, returned by
+    /// [`Evm::call_with_breakdown`].
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct GasBreakdown {
+        /// Gas used by `KECCAK256` opcodes, which are mostly for squeezing challenges.
+        pub transcript_ops: u64,
+        /// Gas used by `STATICCALL`s to `ecAdd` precompile, including the call itself.
+        pub ec_add_ops: u64,
+        /// Gas used by `STATICCALL`s to `ecMul` precompile, including the call itself.
+        pub ec_mul_ops: u64,
+        /// Gas used by `STATICCALL`s to `ecPairing` precompile, including the call itself.
+        pub pairing_ops: u64,
+        /// Rest of `gas_used`, including intrinsic gas of transaction.
+        pub other: u64,
+    }
+
+    /// Category of operations tracked by `GasInspector`.
+    #[derive(Clone, Copy, Debug)]
+    enum GasCategory {
+        Transcript,
+        EcAdd,
+        EcMul,
+        Pairing,
+    }
+
+    /// Inspector attributing gas used by each opcode to its `GasCategory`.
+    #[derive(Debug, Default)]
+    struct GasInspector {
+        breakdown: GasBreakdown,
+        // Category of opcode being executed and remaining gas before it, for each call depth.
+        steps: Vec<(Option<GasCategory>, u64)>,
+    }
+
+    impl<DB: Database> Inspector<DB> for GasInspector {
+        fn step(&mut self, interp: &mut Interpreter, _: &mut EVMData<'_, DB>) -> InstructionResult {
+            let category = match interp.current_opcode() {
+                opcode::KECCAK256 => Some(GasCategory::Transcript),
+                opcode::STATICCALL => match interp.stack.peek(1) {
+                    Ok(address) if address == U256::from(0x06) => Some(GasCategory::EcAdd),
+                    Ok(address) if address == U256::from(0x07) => Some(GasCategory::EcMul),
+                    Ok(address) if address == U256::from(0x08) => Some(GasCategory::Pairing),
+                    _ => None,
+                },
+                _ => None,
+            };
+            self.steps.push((category, interp.gas.remaining()));
+            InstructionResult::Continue
+        }
+
+        fn step_end(
+            &mut self,
+            interp: &mut Interpreter,
+            _: &mut EVMData<'_, DB>,
+            _: InstructionResult,
+        ) -> InstructionResult {
+            if let Some((Some(category), remaining)) = self.steps.pop() {
+                let gas = remaining.saturating_sub(interp.gas.remaining());
+                match category {
+                    GasCategory::Transcript => self.breakdown.transcript_ops += gas,
+                    GasCategory::EcAdd => self.breakdown.ec_add_ops += gas,
+                    GasCategory::EcMul => self.breakdown.ec_mul_ops += gas,
+                    GasCategory::Pairing => self.breakdown.pairing_ops += gas,
+                }
+            }
+            InstructionResult::Continue
+        }
+    }
+
+    /// Event emitted by a contract, returned by [`Evm::logs`].
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Log {
+        /// Address of the contract emitting the event.
+        pub address: Address,
+        /// Topics of the event, where the first one is the event signature unless anonymous.
+        pub topics: Vec<[u8; 32]>,
+        /// Non-indexed parameters of the event in ABI encoding.
+        pub data: Vec<u8>,
+    }
+
+    impl From<revm::primitives::Log> for Log {
+        fn from(log: revm::primitives::Log) -> Self {
+            Self {
+                address: log.address,
+                topics: log.topics.into_iter().map(|topic| topic.0).collect(),
+                data: log.data.to_vec(),
+            }
+        }
+    }
+
+    /// Identifier of state snapshot taken by [`Evm::snapshot`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SnapshotId(usize);
+
+    /// Evm runner built on `revm`, whose gas accounting follows the hardfork set by
+    /// [`Evm::set_spec_id`], which defaults to the latest one.
     pub struct Evm {
         evm: EVM<InMemoryDB>,
+        gwei_schedule: Option<GweiSchedule>,
+        snapshots: Vec<(SnapshotId, InMemoryDB)>,
+        next_snapshot_id: usize,
+        logs: Vec<Log>,
+        caller: Address,
     }
 
     impl Debug for Evm {
@@ -117,6 +1243,10 @@ pub(crate) mod test {
             debug_struct
                 .field("env", &self.evm.env)
                 .field("db", &self.evm.db.as_ref().unwrap())
+                .field("gwei_schedule", &self.gwei_schedule)
+                .field("num_snapshots", &self.snapshots.len())
+                .field("num_logs", &self.logs.len())
+                .field("caller", &self.caller)
                 .finish()
         }
     }
@@ -128,11 +1258,87 @@ pub(crate) mod test {
                     env: Default::default(),
                     db: Some(Default::default()),
                 },
+                gwei_schedule: None,
+                snapshots: Vec::new(),
+                next_snapshot_id: 0,
+                logs: Vec::new(),
+                caller: Address::default(),
             }
         }
     }
 
     impl Evm {
+        /// Set `GweiSchedule` used by [`Evm::call_with_cost`].
+        pub fn set_gwei_schedule(&mut self, gwei_schedule: Option<GweiSchedule>) {
+            self.gwei_schedule = gwei_schedule;
+        }
+
+        /// Set block gas limit, which is also the gas limit of each transaction. It's unlimited by
+        /// default, so verifiers exceeding mainnet limit can still be tested.
+        pub fn set_block_gas_limit(&mut self, limit: u64) -> &mut Self {
+            self.evm.env.block.gas_limit = U256::from(limit);
+            self
+        }
+
+        /// Return block gas limit.
+        pub fn block_gas_limit(&self) -> u64 {
+            self.evm.env.block.gas_limit.saturating_to()
+        }
+
+        /// Set hardfork whose rules are used to execute transactions, e.g. `SpecId::SHANGHAI`, to
+        /// measure gas cost on chains not upgraded to the latest one yet.
+        pub fn set_spec_id(&mut self, spec_id: SpecId) -> &mut Self {
+            self.evm.env.cfg.spec_id = spec_id;
+            self
+        }
+
+        /// Return hardfork whose rules are used to execute transactions.
+        pub fn spec_id(&self) -> SpecId {
+            self.evm.env.cfg.spec_id
+        }
+
+        /// Set sender of following transactions, i.e. `msg.sender` of the contract called, which
+        /// defaults to address zero, so access control like `onlyOwner` can be tested.
+        pub fn set_caller(&mut self, caller: Address) -> &mut Self {
+            self.caller = caller;
+            self
+        }
+
+        /// Return sender of following transactions.
+        pub fn caller(&self) -> Address {
+            self.caller
+        }
+
+        /// Take a snapshot of current state, which can be restored by [`Evm::revert_to_snapshot`],
+        /// like `evm_snapshot` of Hardhat.
+        pub fn snapshot(&mut self) -> SnapshotId {
+            let id = SnapshotId(self.next_snapshot_id);
+            self.next_snapshot_id += 1;
+            self.snapshots.push((id, self.evm.db.clone().unwrap()));
+            id
+        }
+
+        /// Restore state to the snapshot `id`, like `evm_revert` of Hardhat, which discards the
+        /// snapshot along with the ones taken after it, so take another snapshot to revert again.
+        ///
+        /// # Panics
+        /// Panics if the snapshot `id` is already discarded.
+        pub fn revert_to_snapshot(&mut self, id: SnapshotId) {
+            let idx = self
+                .snapshots
+                .iter()
+                .position(|(snapshot_id, _)| *snapshot_id == id)
+                .unwrap_or_else(|| panic!("Snapshot {} is already discarded", id.0));
+            let (_, db) = self.snapshots.drain(idx..).next().unwrap();
+            self.evm.db = Some(db);
+        }
+
+        /// Return events emitted by all successful transactions applied so far, in order, which
+        /// is useful to assert a verifier emits expected events.
+        pub fn logs(&self) -> Vec<Log> {
+            self.logs.clone()
+        }
+
         /// Return code_size of given address.
         ///
         /// # Panics
@@ -146,6 +1352,32 @@ pub(crate) mod test {
                 .len()
         }
 
+        /// Return `keccak256` of runtime code of given `address`, like `extcodehash`, which is zero
+        /// if the account doesn't exist.
+        pub fn code_hash(&self, address: Address) -> [u8; 32] {
+            self.evm
+                .db
+                .as_ref()
+                .unwrap()
+                .accounts
+                .get(&address)
+                .map(|account| account.info.code_hash.0)
+                .unwrap_or_default()
+        }
+
+        /// Return value of storage `slot` of given `address`, like `eth_getStorageAt`, which is
+        /// zero if the slot is never written or the account doesn't exist.
+        pub fn get_storage_at(&self, address: Address, slot: U256) -> U256 {
+            self.evm
+                .db
+                .as_ref()
+                .unwrap()
+                .accounts
+                .get(&address)
+                .and_then(|account| account.storage.get(&slot).copied())
+                .unwrap_or_default()
+        }
+
         /// Apply create transaction with given `bytecode` as creation bytecode.
         /// Return created `address`.
         ///
@@ -153,7 +1385,8 @@ pub(crate) mod test {
         /// Panics if execution reverts or halts unexpectedly.
         pub fn create(&mut self, bytecode: Vec<u8>) -> Address {
             let (_, output) = self.transact_success_or_panic(TxEnv {
-                gas_limit: u64::MAX,
+                caller: self.caller,
+                gas_limit: self.block_gas_limit(),
                 transact_to: TransactTo::Create(CreateScheme::Create),
                 data: bytecode.into(),
                 ..Default::default()
@@ -164,14 +1397,24 @@ pub(crate) mod test {
             }
         }
 
+        /// Link libraries into hex `bytecode` by [`link_libraries`], then apply create transaction
+        /// with it as creation bytecode. Return created `address`.
+        ///
+        /// # Panics
+        /// Panics if any placeholder is not resolved, or execution reverts or halts unexpectedly.
+        pub fn deploy_and_link(&mut self, bytecode: Vec<u8>, libs: &[(&str, Address)]) -> Address {
+            self.create(link_libraries(&bytecode, libs))
+        }
+
         /// Apply call transaction to given `address` with `calldata`.
         /// Returns `gas_used` and `return_data`.
         ///
         /// # Panics
-        /// Panics if execution reverts or halts unexpectedly.
+        /// Panics if execution reverts or halts unexpectedly, e.g. runs out of block gas limit.
         pub fn call(&mut self, address: Address, calldata: Vec<u8>) -> (u64, Vec<u8>) {
             let (gas_used, output) = self.transact_success_or_panic(TxEnv {
-                gas_limit: u64::MAX,
+                caller: self.caller,
+                gas_limit: self.block_gas_limit(),
                 transact_to: TransactTo::Call(address),
                 data: calldata.into(),
                 ..Default::default()
@@ -182,36 +1425,157 @@ pub(crate) mod test {
             }
         }
 
-        fn transact_success_or_panic(&mut self, tx: TxEnv) -> (u64, Output) {
-            self.evm.env.tx = tx;
-            let result = self.evm.transact_commit().unwrap();
+        /// Apply call transaction to given `address` with `calldata` while tracing opcodes.
+        /// Returns `gas_used`, `return_data` and `GasBreakdown`, whose fields sum to `gas_used`.
+        ///
+        /// It's an estimation, e.g. `KECCAK256` for hashing instances is counted as
+        /// transcript, and memory expansion is counted into the opcode triggering it.
+        ///
+        /// # Panics
+        /// Panics if execution reverts or halts unexpectedly.
+        pub fn call_with_breakdown(
+            &mut self,
+            address: Address,
+            calldata: Vec<u8>,
+        ) -> (u64, Vec<u8>, GasBreakdown) {
+            let mut inspector = GasInspector::default();
+            self.evm.env.tx = TxEnv {
+                caller: self.caller,
+                gas_limit: self.block_gas_limit(),
+                transact_to: TransactTo::Call(address),
+                data: calldata.into(),
+                ..Default::default()
+            };
+            let result = self.evm.inspect_commit(&mut inspector).unwrap();
             self.evm.env.tx = Default::default();
+            self.record_logs(&result);
+            let (gas_used, output) = success_or_panic(result);
+            let mut breakdown = inspector.breakdown;
+            breakdown.other = gas_used.saturating_sub(
+                breakdown.transcript_ops
+                    + breakdown.ec_add_ops
+                    + breakdown.ec_mul_ops
+                    + breakdown.pairing_ops,
+            );
+            match output {
+                Output::Call(output) => (gas_used, output.into(), breakdown),
+                _ => unreachable!(),
+            }
+        }
+
+        /// Apply call transaction to given `address` with `calldata`.
+        /// Returns `gas_used`, `return_data` and estimated cost in USD.
+        ///
+        /// # Panics
+        /// Panics if `GweiSchedule` is not set, or execution reverts or halts unexpectedly.
+        pub fn call_with_cost(
+            &mut self,
+            address: Address,
+            calldata: Vec<u8>,
+        ) -> (u64, Vec<u8>, f64) {
+            let gwei_schedule = self.gwei_schedule.expect("GweiSchedule is not set");
+            let (gas_used, output) = self.call(address, calldata);
+            (gas_used, output, gwei_schedule.usd_cost(gas_used))
+        }
+
+        /// Apply call transaction to given `address` with `calldata`.
+        /// Returns `gas_used` and `return_data`, or `ExecutionResult` if execution reverts or
+        /// halts.
+        pub fn try_call(
+            &mut self,
+            address: Address,
+            calldata: Vec<u8>,
+        ) -> Result<(u64, Vec<u8>), ExecutionResult> {
+            let result = self.transact(TxEnv {
+                caller: self.caller,
+                gas_limit: self.block_gas_limit(),
+                transact_to: TransactTo::Call(address),
+                data: calldata.into(),
+                ..Default::default()
+            });
             match result {
                 ExecutionResult::Success {
                     gas_used,
-                    output,
-                    logs,
+                    output: Output::Call(output),
                     ..
-                } => {
-                    if !logs.is_empty() {
-                        println!("--- logs from {} ---", logs[0].address);
-                        for (log_idx, log) in logs.iter().enumerate() {
-                            println!("log#{log_idx}");
-                            for (topic_idx, topic) in log.topics.iter().enumerate() {
-                                println!("  topic{topic_idx}: {topic:?}");
-                            }
+                } => Ok((gas_used, output.into())),
+                _ => Err(result),
+            }
+        }
+
+        /// Apply call transaction to given `address` with `calldata`, and assert it reverts.
+        /// If `expected_reason` is given, also assert the revert data is `Error(string)` with it
+        /// as reason, which is what `revert("...")` and `require(..., "...")` return.
+        ///
+        /// # Panics
+        /// Panics if execution succeeds or halts, or reverts with unexpected reason.
+        pub fn assert_revert(
+            &mut self,
+            address: Address,
+            calldata: Vec<u8>,
+            expected_reason: Option<&str>,
+        ) {
+            let output = match self.try_call(address, calldata) {
+                Err(ExecutionResult::Revert { output, .. }) => output,
+                Ok((gas_used, output)) => panic!(
+                    "Transaction succeeds with gas_used {gas_used} and output 0x{}",
+                    hex::encode(output)
+                ),
+                Err(result) => panic!("Transaction doesn't revert but {result:?}"),
+            };
+            if let Some(expected_reason) = expected_reason {
+                match decode_revert_reason(&output) {
+                    Some(reason) => assert_eq!(reason, expected_reason),
+                    None => panic!("Transaction reverts without reason but output {output:#x}"),
+                }
+            }
+        }
+
+        fn transact(&mut self, tx: TxEnv) -> ExecutionResult {
+            self.evm.env.tx = tx;
+            let result = self.evm.transact_commit().unwrap();
+            self.evm.env.tx = Default::default();
+            self.record_logs(&result);
+            result
+        }
+
+        fn record_logs(&mut self, result: &ExecutionResult) {
+            if let ExecutionResult::Success { logs, .. } = result {
+                self.logs.extend(logs.iter().cloned().map(Log::from));
+            }
+        }
+
+        fn transact_success_or_panic(&mut self, tx: TxEnv) -> (u64, Output) {
+            success_or_panic(self.transact(tx))
+        }
+    }
+
+    fn success_or_panic(result: ExecutionResult) -> (u64, Output) {
+        match result {
+            ExecutionResult::Success {
+                gas_used,
+                output,
+                logs,
+                ..
+            } => {
+                if !logs.is_empty() {
+                    println!("--- logs from {} ---", logs[0].address);
+                    for (log_idx, log) in logs.iter().enumerate() {
+                        println!("log#{log_idx}");
+                        for (topic_idx, topic) in log.topics.iter().enumerate() {
+                            println!("  topic{topic_idx}: {topic:?}");
                         }
-                        println!("--- end ---");
                     }
-                    (gas_used, output)
+                    println!("--- end ---");
                 }
-                ExecutionResult::Revert { gas_used, output } => {
-                    panic!("Transaction reverts with gas_used {gas_used} and output {output:#x}")
-                }
-                ExecutionResult::Halt { reason, gas_used } => panic!(
-                    "Transaction halts unexpectedly with gas_used {gas_used} and reason {reason:?}"
-                ),
+                (gas_used, output)
+            }
+            ExecutionResult::Revert { gas_used, output } => {
+                panic!("Transaction reverts with gas_used {gas_used} and output {output:#x}")
             }
+            ExecutionResult::Halt { reason, gas_used } => panic!(
+                "Transaction halts unexpectedly with gas_used {gas_used} and reason {reason:?}"
+            ),
         }
     }
 }
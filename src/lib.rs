@@ -6,16 +6,47 @@
 #![deny(missing_debug_implementations)]
 #![deny(rustdoc::broken_intra_doc_links)]
 
+#[cfg(feature = "cli")]
+pub mod cli;
 mod codegen;
 mod evm;
+mod proof;
 mod transcript;
 
 #[cfg(test)]
 mod test;
 
-pub use codegen::{AccumulatorEncoding, BatchOpenScheme, SolidityGenerator};
-pub use evm::{encode_calldata, FN_SIG_VERIFY_PROOF, FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS};
+pub use codegen::{
+    AccumulatorEncoding, BatchOpenScheme, CircuitMetrics, FailureStage, GeneratorError,
+    IncompatibilityReason, InconsistencyError, OnFailure, SolidityGenerator,
+    SolidityGeneratorBuilder, VerifierMeta, VerifierParams, VkAddressSource,
+};
+pub use evm::{
+    decode_calldata, decode_calldata_nested, encode_calldata, encode_calldata_batch,
+    encode_calldata_hashed, encode_calldata_nested, encode_calldata_packed, encode_calldata_typed,
+    encode_calldata_v2, encode_calldata_with_encoder, hash_instances, instances_from_hash,
+    pack_instances, verify_calldata, BigEndianEncoder, CalldataError, CalldataPayload,
+    InstanceByteOrder, InstanceEncoder, LittleEndianEncoder, NestedCalldataPayload,
+    ERROR_SIG_PROOF_INVALID, ERROR_SIG_VK_CODEHASH_MISMATCH, EVENT_SIG_VERIFIED,
+    FN_SIG_IS_VERIFIED, FN_SIG_VERIFY_AND_STORE, FN_SIG_VERIFY_AND_STORE_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOFS, FN_SIG_VERIFY_PROOFS_V2, FN_SIG_VERIFY_PROOFS_V2_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOFS_WITH_VK_ADDRESS, FN_SIG_VERIFY_PROOF_NESTED,
+    FN_SIG_VERIFY_PROOF_NESTED_WITH_VK_ADDRESS, FN_SIG_VERIFY_PROOF_PACKED,
+    FN_SIG_VERIFY_PROOF_PACKED_WITH_VK_ADDRESS,
+};
+pub use halo2_solidity_verifier_calldata::{
+    encode_calldata_hashed_words, encode_calldata_words, fr_repr_to_word, FN_SIG_VERIFY_PROOF,
+    FN_SIG_VERIFY_PROOF_HASHED, FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS,
+};
+pub use proof::{decode_proof, encode_proof, OpeningProof, PlonkProof, ProofError};
 pub use transcript::Keccak256Transcript;
 
 #[cfg(feature = "evm")]
-pub use evm::test::{compile_solidity, revm, Evm};
+pub use codegen::BytecodeTooLargeError;
+#[cfg(feature = "evm")]
+pub use evm::test::{
+    compile_solidity, compile_solidity_runtime, compile_solidity_to_abi, compile_solidity_unlinked,
+    compile_solidity_with_optimizer, compile_yul, link_libraries, revm, Evm, GasBreakdown,
+    GweiSchedule, Log, SnapshotId,
+};
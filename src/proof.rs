@@ -0,0 +1,183 @@
+use crate::codegen::util::ConstraintSystemMeta;
+use halo2_proofs::{
+    halo2curves::{
+        bn256::{Fq, Fr, G1Affine},
+        ff::PrimeField,
+        CurveAffine,
+    },
+    plonk::VerifyingKey,
+};
+use itertools::{chain, Itertools};
+use std::fmt;
+
+/// Proof created with `Keccak256Transcript`, split into commitments, evaluations and batch
+/// opening proof in the order they are read by verifier, returned by [`decode_proof`].
+///
+/// Instances are absorbed into transcript directly instead of being committed with KZG, so there
+/// is no instance commitment in proof.
+///
+/// Fields are not validated against the circuit, so any of them can be replaced to construct
+/// negative test cases, then encoded back by [`encode_proof`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlonkProof {
+    /// Commitments of advice columns, phase by phase.
+    pub advice_commitments: Vec<G1Affine>,
+    /// Commitments of permuted input and permuted table of each lookup.
+    pub lookup_permuted_commitments: Vec<(G1Affine, G1Affine)>,
+    /// Commitments of permutation grand products, one for each chunk of permutation columns.
+    pub permutation_z_commitments: Vec<G1Affine>,
+    /// Commitments of lookup grand products, one for each lookup.
+    pub lookup_z_commitments: Vec<G1Affine>,
+    /// Commitments of shuffle grand products, one for each shuffle.
+    pub shuffle_z_commitments: Vec<G1Affine>,
+    /// Commitment of random polynomial of vanishing argument.
+    pub random_commitment: G1Affine,
+    /// Commitments of quotient chunks.
+    pub quotient_commitments: Vec<G1Affine>,
+    /// Evaluations of advice, fixed, random, permutation, lookup and shuffle polynomials.
+    pub evaluations: Vec<Fr>,
+    /// Batch opening proof.
+    pub opening_proof: OpeningProof,
+}
+
+/// Batch opening proof, which is `W` and `W'` for `BatchOpenScheme::Bdfg21`, or `W` of each
+/// rotation for `BatchOpenScheme::Gwc19`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningProof {
+    /// Commitments of opening quotients.
+    pub commitments: Vec<G1Affine>,
+}
+
+/// Decode proof created with `Keccak256Transcript` for the circuit of `vk` into [`PlonkProof`].
+///
+/// The batch opening proof is what remains after evaluations, so `BatchOpenScheme` is not needed.
+pub fn decode_proof(proof: &[u8], vk: &VerifyingKey<G1Affine>) -> Result<PlonkProof, ProofError> {
+    let meta = ConstraintSystemMeta::new(vk.cs());
+    let num_commitments = meta.num_advices().iter().sum::<usize>();
+    let evals_start = num_commitments * 0x40;
+    let opening_start = evals_start + meta.num_evals * 0x20;
+    if proof.len() <= opening_start || (proof.len() - opening_start) % 0x40 != 0 {
+        return Err(ProofError::Malformed(format!(
+            "expected {opening_start} bytes followed by opening proof but got {} bytes",
+            proof.len()
+        )));
+    }
+
+    let mut commitments = proof[..evals_start]
+        .chunks(0x40)
+        .enumerate()
+        .map(|(idx, bytes)| read_point(bytes, idx * 0x40))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+    let mut take = |n: usize| commitments.by_ref().take(n).collect_vec();
+    let advice_commitments = take(meta.advice_indices.len());
+    let lookup_permuted_commitments = take(meta.num_lookup_permuteds)
+        .into_iter()
+        .tuples()
+        .collect();
+    let permutation_z_commitments = take(meta.num_permutation_zs);
+    let lookup_z_commitments = take(meta.num_lookup_zs);
+    let shuffle_z_commitments = take(meta.num_shuffle_zs);
+    let random_commitment = take(1)[0];
+    let quotient_commitments = take(meta.num_quotients);
+
+    let evaluations = proof[evals_start..opening_start]
+        .chunks(0x20)
+        .enumerate()
+        .map(|(idx, bytes)| read_scalar(bytes, evals_start + idx * 0x20))
+        .collect::<Result<_, _>>()?;
+    let opening_proof = OpeningProof {
+        commitments: proof[opening_start..]
+            .chunks(0x40)
+            .enumerate()
+            .map(|(idx, bytes)| read_point(bytes, opening_start + idx * 0x40))
+            .collect::<Result<_, _>>()?,
+    };
+
+    Ok(PlonkProof {
+        advice_commitments,
+        lookup_permuted_commitments,
+        permutation_z_commitments,
+        lookup_z_commitments,
+        shuffle_z_commitments,
+        random_commitment,
+        quotient_commitments,
+        evaluations,
+        opening_proof,
+    })
+}
+
+/// Encode [`PlonkProof`] into bytes as written by `Keccak256Transcript`, which is the inverse of
+/// [`decode_proof`].
+pub fn encode_proof(proof: &PlonkProof) -> Vec<u8> {
+    let commitments = chain![
+        &proof.advice_commitments,
+        proof
+            .lookup_permuted_commitments
+            .iter()
+            .flat_map(|(input, table)| [input, table]),
+        &proof.permutation_z_commitments,
+        &proof.lookup_z_commitments,
+        &proof.shuffle_z_commitments,
+        [&proof.random_commitment],
+        &proof.quotient_commitments,
+    ];
+    chain![
+        commitments.flat_map(point_to_bytes),
+        proof.evaluations.iter().flat_map(scalar_to_bytes),
+        proof.opening_proof.commitments.iter().flat_map(point_to_bytes),
+    ]
+    .collect()
+}
+
+/// Error returned by [`decode_proof`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// Proof is of unexpected length for the circuit, with details about what's wrong.
+    Malformed(String),
+    /// Commitment at the byte offset is not a valid point on BN254.
+    InvalidPoint(usize),
+    /// Evaluation at the byte offset is out of scalar field.
+    InvalidScalar(usize),
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(details) => write!(f, "Malformed proof: {details}"),
+            Self::InvalidPoint(offset) => write!(f, "Invalid point at {offset:#x}"),
+            Self::InvalidScalar(offset) => write!(f, "Invalid scalar at {offset:#x}"),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+fn read_point(bytes: &[u8], offset: usize) -> Result<G1Affine, ProofError> {
+    let [x, y] = [&bytes[..0x20], &bytes[0x20..]].map(|bytes| {
+        let mut repr = <[u8; 0x20]>::try_from(bytes).unwrap();
+        repr.reverse();
+        Option::<Fq>::from(Fq::from_repr(repr))
+    });
+    x.zip(y)
+        .and_then(|(x, y)| Option::from(G1Affine::from_xy(x, y)))
+        .ok_or(ProofError::InvalidPoint(offset))
+}
+
+fn read_scalar(bytes: &[u8], offset: usize) -> Result<Fr, ProofError> {
+    let mut repr = <[u8; 0x20]>::try_from(bytes).unwrap();
+    repr.reverse();
+    Option::from(Fr::from_repr(repr)).ok_or(ProofError::InvalidScalar(offset))
+}
+
+fn point_to_bytes(point: &G1Affine) -> Vec<u8> {
+    let coords = point.coordinates().unwrap();
+    [*coords.x(), *coords.y()]
+        .iter()
+        .flat_map(|coord| coord.to_repr().into_iter().rev())
+        .collect()
+}
+
+fn scalar_to_bytes(scalar: &Fr) -> impl Iterator<Item = u8> {
+    scalar.to_repr().into_iter().rev()
+}
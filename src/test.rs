@@ -1,14 +1,35 @@
 use crate::{
     codegen::{
+        util::{fr_to_u256, to_u256_be_bytes},
         AccumulatorEncoding,
         BatchOpenScheme::{self, Bdfg21, Gwc19},
-        SolidityGenerator,
+        CircuitMetrics, FailureStage, GeneratorError, IncompatibilityReason, InconsistencyError,
+        OnFailure, SolidityGenerator, VerifierParams, VkAddressSource,
     },
-    encode_calldata,
-    evm::test::{compile_solidity, Evm},
-    FN_SIG_VERIFY_PROOF, FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS,
+    decode_calldata, decode_calldata_nested, decode_proof, encode_calldata, encode_calldata_batch,
+    encode_calldata_hashed, encode_calldata_hashed_words, encode_calldata_nested,
+    encode_calldata_packed, encode_calldata_typed, encode_calldata_v2,
+    encode_calldata_with_encoder, encode_calldata_words, encode_proof,
+    evm::test::{
+        compile_solidity, compile_solidity_to_abi, compile_solidity_unlinked,
+        compile_solidity_with_optimizer, compile_yul, Evm, GweiSchedule,
+    },
+    fr_repr_to_word, hash_instances, instances_from_hash, verify_calldata, BigEndianEncoder,
+    CalldataError, InstanceEncoder, LittleEndianEncoder, PlonkProof, ProofError,
+    ERROR_SIG_PROOF_INVALID, ERROR_SIG_VK_CODEHASH_MISMATCH, EVENT_SIG_VERIFIED,
+    FN_SIG_IS_VERIFIED, FN_SIG_VERIFY_AND_STORE, FN_SIG_VERIFY_AND_STORE_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF, FN_SIG_VERIFY_PROOFS, FN_SIG_VERIFY_PROOFS_V2,
+    FN_SIG_VERIFY_PROOFS_V2_WITH_VK_ADDRESS, FN_SIG_VERIFY_PROOFS_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF_HASHED, FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF_NESTED, FN_SIG_VERIFY_PROOF_NESTED_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF_PACKED, FN_SIG_VERIFY_PROOF_PACKED_WITH_VK_ADDRESS,
+    FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS,
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::VerifyingKey,
+    SerdeFormat,
 };
-use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
 use rand::{rngs::StdRng, RngCore, SeedableRng};
 use sha3::Digest;
 use std::{fs::File, io::Write};
@@ -21,236 +42,4175 @@ fn function_signature() {
             "verifyProof(address,bytes,uint256[])",
             FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS,
         ),
+        ("verifyProofs(bytes[],uint256[][])", FN_SIG_VERIFY_PROOFS),
+        (
+            "verifyProofs(address,bytes[],uint256[][])",
+            FN_SIG_VERIFY_PROOFS_WITH_VK_ADDRESS,
+        ),
+        ("verifyProofsV2(bytes)", FN_SIG_VERIFY_PROOFS_V2),
+        (
+            "verifyProofsV2(address,bytes)",
+            FN_SIG_VERIFY_PROOFS_V2_WITH_VK_ADDRESS,
+        ),
+        ("verifyProof(bytes,bytes32)", FN_SIG_VERIFY_PROOF_HASHED),
+        (
+            "verifyProof(address,bytes,bytes32)",
+            FN_SIG_VERIFY_PROOF_HASHED_WITH_VK_ADDRESS,
+        ),
+        ("verifyProofPacked(bytes,bytes)", FN_SIG_VERIFY_PROOF_PACKED),
+        (
+            "verifyProofPacked(address,bytes,bytes)",
+            FN_SIG_VERIFY_PROOF_PACKED_WITH_VK_ADDRESS,
+        ),
+        (
+            "verifyProofNested(bytes,uint256[][])",
+            FN_SIG_VERIFY_PROOF_NESTED,
+        ),
+        (
+            "verifyProofNested(address,bytes,uint256[][])",
+            FN_SIG_VERIFY_PROOF_NESTED_WITH_VK_ADDRESS,
+        ),
+        ("ProofInvalid(uint256)", ERROR_SIG_PROOF_INVALID),
+        ("verifyAndStore(bytes,uint256[])", FN_SIG_VERIFY_AND_STORE),
+        (
+            "verifyAndStore(address,bytes,uint256[])",
+            FN_SIG_VERIFY_AND_STORE_WITH_VK_ADDRESS,
+        ),
+        ("isVerified(bytes32)", FN_SIG_IS_VERIFIED),
+        ("VkCodehashMismatch()", ERROR_SIG_VK_CODEHASH_MISMATCH),
     ] {
         assert_eq!(
             <[u8; 32]>::from(sha3::Keccak256::digest(fn_name))[..4],
             fn_sig,
         );
     }
+    assert_eq!(
+        <[u8; 32]>::from(sha3::Keccak256::digest("Verified(bool)")),
+        EVENT_SIG_VERIFIED,
+    );
 }
 
 #[test]
-fn render_bdfg21_huge() {
-    run_render::<halo2::huge::HugeCircuit<Bn256>>(Bdfg21)
+fn gwei_schedule_usd_cost() {
+    // 1M gas at 10 gwei is 0.01 ether
+    let gwei_schedule = GweiSchedule::new(10, 2000.0);
+    assert!((gwei_schedule.usd_cost(1_000_000) - 20.0).abs() < 1e-9);
 }
 
 #[test]
-fn render_bdfg21_maingate() {
-    run_render::<halo2::maingate::MainGateWithRange<Bn256>>(Bdfg21)
+#[should_panic(expected = "Transaction halts")]
+fn call_exceeding_block_gas_limit() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+
+    let mut evm = Evm::default();
+    assert_eq!(evm.block_gas_limit(), u64::MAX);
+    let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+
+    // Verification costs more than 200k gas
+    assert_eq!(evm.set_block_gas_limit(100_000).block_gas_limit(), 100_000);
+    evm.call(verifier_address, encode_calldata(None, &proof, &instances));
 }
 
 #[test]
-fn render_gwc19_huge() {
-    run_render::<halo2::huge::HugeCircuit<Bn256>>(Gwc19)
+fn assert_revert() {
+    let solidity = r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Reverter {
+    fallback() external {
+        require(msg.data.length == 0, "Unexpected calldata");
+        revert();
+    }
 }
+"#;
+    let mut evm = Evm::default();
+    let reverter_address = evm.create(compile_solidity(solidity));
+    evm.assert_revert(reverter_address, Vec::new(), None);
+    evm.assert_revert(reverter_address, vec![1], None);
+    evm.assert_revert(reverter_address, vec![1], Some("Unexpected calldata"));
 
-#[test]
-fn render_gwc19_maingate() {
-    run_render::<halo2::maingate::MainGateWithRange<Bn256>>(Gwc19)
+    // Tampered proof makes verifier revert instead of returning `false`
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+    let mut tampered_proof = proof;
+    tampered_proof[0x20..0x40].fill(0xff);
+    evm.assert_revert(
+        verifier_address,
+        encode_calldata(None, &tampered_proof, &instances),
+        None,
+    );
 }
 
 #[test]
-fn render_separately_bdfg21_huge() {
-    run_render_separately::<halo2::huge::HugeCircuit<Bn256>>(Bdfg21)
+#[should_panic(expected = "Transaction reverts without reason")]
+fn assert_revert_without_reason() {
+    let solidity = r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Reverter {
+    fallback() external {
+        revert();
+    }
+}
+"#;
+    let mut evm = Evm::default();
+    let reverter_address = evm.create(compile_solidity(solidity));
+    evm.assert_revert(reverter_address, Vec::new(), Some("Unexpected calldata"));
 }
 
 #[test]
-fn render_separately_bdfg21_maingate() {
-    run_render_separately::<halo2::maingate::MainGateWithRange<Bn256>>(Bdfg21)
+fn builder_validation() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+
+    assert_eq!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&[3])
+            .build()
+            .unwrap_err(),
+        GeneratorError::InstanceColumnsMismatch {
+            expected: 2,
+            actual: 1
+        }
+    );
+    assert_eq!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .acc_encoding(AccumulatorEncoding::new(0, 4, 68).into())
+            .build()
+            .unwrap_err(),
+        GeneratorError::AccumulatorOutOfRange {
+            offset: 0,
+            num_limbs: 16,
+            num_instances: 8
+        }
+    );
+    assert!(SolidityGenerator::builder(&params, &vk)
+        .scheme(Gwc19)
+        .num_instances(&num_instances(&instances))
+        .build()
+        .is_ok());
 }
 
 #[test]
-fn render_separately_gwc19_huge() {
-    run_render_separately::<halo2::huge::HugeCircuit<Bn256>>(Gwc19)
+fn builder_setters() {
+    type C1 = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    type C2 = halo2::fixed_table::FixedTable<Fr>;
+    let k = <C1 as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C1>(k, Bdfg21, None, std_rng());
+    let k = <C2 as halo2::TestCircuit<Fr>>::min_k();
+    let (other_params, other_vk, other_instances, _) =
+        halo2::create_testdata::<C2>(k, Bdfg21, None, std_rng());
+
+    let mut builder = SolidityGenerator::builder(&params, &vk);
+    builder
+        .num_instances(&num_instances(&instances))
+        .pragma_version("^0.8.20");
+    let generator = builder.build().unwrap();
+
+    // Builder is kept by `build`, and setters replace params and verifying key given to it
+    builder
+        .params(&other_params)
+        .vk(&other_vk)
+        .num_instances(&num_instances(&other_instances));
+    let other_generator = builder.build().unwrap();
+    let expected = SolidityGenerator::builder(&other_params, &other_vk)
+        .num_instances(&num_instances(&other_instances))
+        .pragma_version("^0.8.20")
+        .build()
+        .unwrap();
+    assert_eq!(
+        other_generator.render().unwrap(),
+        expected.render().unwrap()
+    );
+    assert_ne!(
+        generator.render().unwrap(),
+        other_generator.render().unwrap()
+    );
 }
 
 #[test]
-fn render_separately_gwc19_maingate() {
-    run_render_separately::<halo2::maingate::MainGateWithRange<Bn256>>(Gwc19)
-}
+fn option_validation() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let bit_widths = vec![64; instances.concat().len()];
 
-fn run_render<C: halo2::TestCircuit<Fr>>(scheme: BatchOpenScheme) {
-    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
-    let (params, vk, instances, proof) =
-        halo2::create_testdata::<C>(C::min_k(), scheme, acc_encoding, std_rng());
+    // Batch verify returns pairing, which other options can't take as result
+    let results = [
+        builder().packed_instances(&bit_widths),
+        builder().nested_instances(true),
+        builder().deferred_pairing(true),
+        builder().verify_and_store(true),
+        builder().view(false).on_verified_hook(true),
+    ]
+    .map(|builder| builder.batch_verify(true).build().unwrap_err());
+    let options = results.map(|err| match err {
+        GeneratorError::InvalidOption { option, .. } => option,
+        err => panic!("Unexpected error {err}"),
+    });
+    assert_eq!(
+        options,
+        [
+            "packed_instances",
+            "nested_instances",
+            "deferred_pairing",
+            "verify_and_store",
+            "on_verified_hook"
+        ]
+    );
+    assert!(builder().batch_verify(true).build().is_ok());
 
-    let generator = SolidityGenerator::new(&params, &vk, scheme, instances.len())
-        .set_acc_encoding(acc_encoding);
-    let verifier_solidity = generator.render().unwrap();
-    let verifier_creation_code = compile_solidity(verifier_solidity);
-    let verifier_creation_code_size = verifier_creation_code.len();
+    // Deferred pairing can't return false, and view function can't modify state
+    assert!(matches!(
+        builder()
+            .deferred_pairing(true)
+            .on_failure(OnFailure::ReturnFalse)
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "deferred_pairing",
+            ..
+        })
+    ));
+    assert!(matches!(
+        builder().emit_event(true).build(),
+        Err(GeneratorError::InvalidOption {
+            option: "emit_event",
+            ..
+        })
+    ));
+    assert!(matches!(
+        builder().on_verified_hook(true).build(),
+        Err(GeneratorError::InvalidOption {
+            option: "on_verified_hook",
+            ..
+        })
+    ));
 
-    let mut evm = Evm::default();
-    let verifier_address = evm.create(verifier_creation_code);
-    let verifier_runtime_code_size = evm.code_size(verifier_address);
+    // Code hash of verifying key can't be pinned when owner can update its address
+    assert!(matches!(
+        builder()
+            .vk_address_source(VkAddressSource::Ownable)
+            .vk_codehash_check(true)
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "vk_codehash_check",
+            ..
+        })
+    ));
+    assert!(builder()
+        .vk_address_source(VkAddressSource::Immutable)
+        .vk_codehash_check(true)
+        .build()
+        .is_ok());
+}
 
-    println!("Verifier creation code size: {verifier_creation_code_size}");
-    println!("Verifier runtime code size: {verifier_runtime_code_size}");
+#[test]
+fn new_auto() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
 
-    let (gas_cost, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
-    assert_eq!(output, [vec![0; 31], vec![1]].concat());
-    println!("Gas cost: {gas_cost}");
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let generator_auto = SolidityGenerator::new_auto(&params, &vk, Bdfg21, &instances).unwrap();
+    assert_eq!(generator.render(), generator_auto.render());
+
+    assert_eq!(
+        SolidityGenerator::new_auto(&params, &vk, Bdfg21, &instances[..1]).unwrap_err(),
+        GeneratorError::InstanceColumnsMismatch {
+            expected: 2,
+            actual: 1
+        }
+    );
 }
 
-fn run_render_separately<C: halo2::TestCircuit<Fr>>(scheme: BatchOpenScheme) {
-    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
-    let (params, vk, instances, _) =
-        halo2::create_testdata::<C>(C::min_k(), scheme, acc_encoding, std_rng());
+#[test]
+fn unsupported_features() {
+    use halo2::unsupported::{NoEquality, RotatedInstanceQuery};
+    use halo2_proofs::{plonk::keygen_vk, poly::kzg::commitment::ParamsKZG};
 
-    let generator = SolidityGenerator::new(&params, &vk, scheme, instances.len())
-        .set_acc_encoding(acc_encoding);
-    let (verifier_solidity, _vk_solidity) = generator.render_separately().unwrap();
-    let verifier_creation_code = compile_solidity(&verifier_solidity);
-    let verifier_creation_code_size = verifier_creation_code.len();
+    let params = ParamsKZG::<Bn256>::setup(4, std_rng());
 
-    let mut evm = Evm::default();
-    let verifier_address = evm.create(verifier_creation_code);
-    let verifier_runtime_code_size = evm.code_size(verifier_address);
+    let vk = keygen_vk(&params, &NoEquality::<Fr>::default()).unwrap();
+    let err = SolidityGenerator::builder(&params, &vk)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unsupported circuit: no column with equality enabled"
+    );
 
-    println!("Verifier creation code size: {verifier_creation_code_size}");
-    println!("Verifier runtime code size: {verifier_runtime_code_size}");
+    let vk = keygen_vk(&params, &RotatedInstanceQuery::<Fr>::default()).unwrap();
+    let err = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&[1])
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unsupported rotated instance query: instance column #0 is queried with rotation 7 greater than 6"
+    );
+}
 
-    let deployed_verifier_solidity = verifier_solidity;
+#[test]
+fn render_with_verifier_params() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let num_instances = num_instances(&instances);
 
-    for k in C::min_k()..C::min_k() + 4 {
-        let (params, vk, instances, proof) =
-            halo2::create_testdata::<C>(k, scheme, acc_encoding, std_rng());
-        let generator = SolidityGenerator::new(&params, &vk, scheme, instances.len())
-            .set_acc_encoding(acc_encoding);
+    let mut verifier_params_bytes = Vec::new();
+    VerifierParams::from(&params)
+        .write(&mut verifier_params_bytes)
+        .unwrap();
+    let verifier_params = VerifierParams::read(&mut verifier_params_bytes.as_slice()).unwrap();
+    assert_eq!(verifier_params, VerifierParams::from(&params));
+
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances);
+    let generator_without_srs =
+        SolidityGenerator::new(verifier_params, &vk, Bdfg21, &num_instances);
+    assert_eq!(generator.render(), generator_without_srs.render());
+    assert_eq!(
+        generator.render_separately(),
+        generator_without_srs.render_separately()
+    );
+}
+
+#[test]
+fn render_with_pragma() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+
+    for pragma in ["", "^", "0.8.", "^0.8.0 ||", "0.8.0;", "latest"] {
+        assert_eq!(
+            builder().pragma_version(pragma).build().unwrap_err(),
+            GeneratorError::InvalidOption {
+                option: "pragma_version",
+                reason: format!("`{pragma}` is not a valid version expression"),
+            }
+        );
+    }
 
+    for pragma in [
+        "0.8.20",
+        "^0.8.20",
+        ">=0.8.0 <0.9.0",
+        "0.8.0 - 0.8.20",
+        "^0.7.0 || ^0.8.0",
+    ] {
+        let generator = builder().pragma_version(pragma).build().unwrap();
         let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
-        assert_eq!(deployed_verifier_solidity, verifier_solidity);
+        assert!(verifier_solidity.contains(&format!("pragma solidity {pragma};")));
+        assert!(vk_solidity.contains(&format!("pragma solidity {pragma};")));
+    }
+}
 
-        let vk_creation_code = compile_solidity(&vk_solidity);
-        let vk_address = evm.create(vk_creation_code);
+#[test]
+fn render_with_fn_name() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
 
-        let (gas_cost, output) = evm.call(
-            verifier_address,
-            encode_calldata(Some(vk_address.into()), &proof, &instances),
+    let generator = builder().build().unwrap();
+    assert_eq!(generator.fn_sig(false), FN_SIG_VERIFY_PROOF);
+    assert_eq!(generator.fn_sig(true), FN_SIG_VERIFY_PROOF_WITH_VK_ADDRESS);
+    for name in ["", "1verify", "verify proof", "verify()"] {
+        assert_eq!(
+            builder().fn_name(name).build().unwrap_err(),
+            GeneratorError::InvalidOption {
+                option: "fn_name",
+                reason: format!("`{name}` is not a valid identifier"),
+            }
         );
-        assert_eq!(output, [vec![0; 31], vec![1]].concat());
-        println!("Gas cost: {gas_cost}");
     }
+
+    let generator = builder().fn_name("verify").build().unwrap();
+    assert_eq!(
+        generator.fn_sig(false),
+        <[u8; 32]>::from(sha3::Keccak256::digest("verify(bytes,uint256[])"))[..4]
+    );
+
+    let mut evm = Evm::default();
+
+    let verifier_solidity = generator.render().unwrap();
+    assert!(verifier_solidity.contains("function verify("));
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let calldata = generator.encode_calldata(None, &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    // Default selector is not found
+    evm.assert_revert(
+        verifier_address,
+        encode_calldata(None, &proof, &instances),
+        None,
+    );
+
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let vk_address = evm.create(compile_solidity(&vk_solidity));
+    let calldata = generator.encode_calldata(Some(vk_address.into()), &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
 }
 
-fn std_rng() -> impl RngCore + Clone {
-    StdRng::seed_from_u64(0)
+#[test]
+fn render_diff() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let generator = || SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+
+    assert_eq!(SolidityGenerator::diff(&generator(), &generator()), "");
+
+    let other = builder().fn_name("verify").build().unwrap();
+    let diff = SolidityGenerator::diff(&generator(), &other);
+    assert!(diff.starts_with("--- a/Halo2Verifier.sol\n+++ b/Halo2Verifier.sol\n@@ "));
+    assert!(diff.contains("\n-    function verifyProof(\n"));
+    assert!(diff.contains("\n+    function verify(\n"));
+    // Only the function name changes, besides the header
+    let num_changes = diff
+        .lines()
+        .filter(|line| line.starts_with(['-', '+']))
+        .count();
+    assert_eq!(num_changes, 2 + 2);
 }
 
-#[allow(dead_code)]
-fn save_generated(verifier: &str, vk: Option<&str>) {
-    const DIR_GENERATED: &str = "./target/generated";
+#[test]
+fn render_with_calldata_comment() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let verifier_solidity = generator
+        .render_with_calldata_comment(&proof, &instances)
+        .unwrap();
+    assert!(verifier_solidity.starts_with(&generator.render().unwrap()));
 
-    std::fs::create_dir_all(DIR_GENERATED).unwrap();
-    File::create(format!("{DIR_GENERATED}/Halo2Verifier.sol"))
-        .unwrap()
-        .write_all(verifier.as_bytes())
+    let sample_calldata = verifier_solidity
+        .split("/* SAMPLE CALLDATA: 0x")
+        .nth(1)
+        .and_then(|comment| comment.split(" */").next())
+        .map(|calldata| hex::decode(calldata).unwrap())
         .unwrap();
-    if let Some(vk) = vk {
-        File::create(format!("{DIR_GENERATED}/Halo2VerifyingKey.sol"))
-            .unwrap()
-            .write_all(vk.as_bytes())
-            .unwrap();
-    }
+    assert_eq!(sample_calldata, encode_calldata(None, &proof, &instances));
+
+    // Comment doesn't affect compilation, and the sample calldata is valid
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let (_, output) = evm.call(verifier_address, sample_calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
 }
 
-mod halo2 {
-    use crate::{
-        codegen::AccumulatorEncoding,
-        transcript::Keccak256Transcript,
-        BatchOpenScheme::{self, Bdfg21, Gwc19},
-    };
-    use halo2_proofs::{
-        arithmetic::CurveAffine,
-        halo2curves::{
-            bn256,
-            ff::{Field, PrimeField},
-            group::{prime::PrimeCurveAffine, Curve, Group},
-            pairing::{MillerLoopResult, MultiMillerLoop},
-        },
-        plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, VerifyingKey},
-        poly::kzg::{
-            commitment::ParamsKZG,
-            multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
-            strategy::SingleStrategy,
-        },
-        transcript::TranscriptWriterBuffer,
+#[test]
+fn render_with_natspec() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
     };
-    use itertools::Itertools;
-    use rand::RngCore;
-    use ruint::aliases::U256;
-    use std::borrow::Borrow;
-
-    pub trait TestCircuit<F: Field>: Circuit<F> {
-        fn min_k() -> u32;
+    let generator = || SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
 
-        fn new(acc_encoding: Option<AccumulatorEncoding>, rng: impl RngCore) -> Self;
+    let (verifier_solidity, vk_solidity) = generator().render_separately().unwrap();
+    assert!(!verifier_solidity.contains("/// @"));
+    assert!(!vk_solidity.contains("/// @"));
 
-        fn instances(&self) -> Vec<F>;
+    let generator = builder().natspec(true).build().unwrap();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let summary = format!("k={k}, num_instances={:?}, ", num_instances(&instances));
+    for (solidity, notice) in [
+        (&verifier_solidity, "/// @notice Verifier of halo2 proof"),
+        (&vk_solidity, "/// @notice Verifying key of halo2 circuit"),
+    ] {
+        let line = solidity
+            .lines()
+            .find(|line| line.starts_with(notice))
+            .unwrap();
+        assert!(line.contains(&summary));
+        assert!(line.ends_with("scheme=Bdfg21."));
     }
+    assert!(verifier_solidity.contains("/// @param vk "));
 
-    #[allow(clippy::type_complexity)]
-    pub fn create_testdata<C: TestCircuit<bn256::Fr>>(
-        k: u32,
-        scheme: BatchOpenScheme,
-        acc_encoding: Option<AccumulatorEncoding>,
-        mut rng: impl RngCore + Clone,
-    ) -> (
-        ParamsKZG<bn256::Bn256>,
-        VerifyingKey<bn256::G1Affine>,
-        Vec<bn256::Fr>,
-        Vec<u8>,
-    ) {
-        match scheme {
-            Bdfg21 => {
-                create_testdata_inner!(ProverSHPLONK<_>, VerifierSHPLONK<_>, k, acc_encoding, rng)
+    // NatSpec is only comments, so verification behaves the same
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let vk_address = evm.create(compile_solidity(&vk_solidity));
+    let calldata = encode_calldata(Some(vk_address.into()), &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+}
+
+#[test]
+fn render_with_license() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+
+    for license in ["", "MIT\ncontract", "MIT  OR Apache-2.0", "(MIT", "MIT;"] {
+        assert_eq!(
+            builder().license(license).build().unwrap_err(),
+            GeneratorError::InvalidOption {
+                option: "license",
+                reason: format!("`{license}` is not a valid SPDX license expression"),
             }
-            Gwc19 => create_testdata_inner!(ProverGWC<_>, VerifierGWC<_>, k, acc_encoding, rng),
+        );
+    }
+
+    for license in [
+        "MIT",
+        "BUSL-1.1",
+        "LicenseRef-Proprietary",
+        "(MIT OR Apache-2.0)",
+    ] {
+        let generator = builder()
+            .pragma_version("0.8.21")
+            .license(license)
+            .build()
+            .unwrap();
+        let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+        for solidity in [&verifier_solidity, &vk_solidity] {
+            let header = format!("// SPDX-License-Identifier: {license}\n");
+            assert!(solidity.starts_with(&header));
+            assert!(solidity.contains("pragma solidity 0.8.21;"));
         }
     }
+}
 
-    macro_rules! create_testdata_inner {
-        ($p:ty, $v:ty, $k:ident, $acc_encoding:ident, $rng:ident) => {{
-            let circuit = C::new($acc_encoding, $rng.clone());
-            let instances = circuit.instances();
+#[test]
+fn render_to_file() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
 
-            let params = ParamsKZG::<bn256::Bn256>::setup($k, &mut $rng);
-            let vk = keygen_vk(&params, &circuit).unwrap();
-            let pk = keygen_pk(&params, vk.clone(), &circuit).unwrap();
+    let dir = std::env::temp_dir().join(format!("halo2_verifier_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let verifier_path = dir.join("Halo2Verifier.sol");
+    let vk_path = dir.join("Halo2VerifyingKey.sol");
 
-            let proof = {
-                let mut transcript = Keccak256Transcript::new(Vec::new());
-                create_proof::<_, $p, _, _, _, _>(
-                    &params,
-                    &pk,
-                    &[circuit],
-                    &[&[&instances]],
-                    &mut $rng,
-                    &mut transcript,
-                )
-                .unwrap();
-                transcript.finalize()
-            };
+    generator
+        .render_to_io(&mut File::create(&verifier_path).unwrap())
+        .unwrap();
+    let verifier_solidity = std::fs::read_to_string(&verifier_path).unwrap();
+    assert_eq!(verifier_solidity, generator.render().unwrap());
 
-            let result = {
-                let mut transcript = Keccak256Transcript::new(proof.as_slice());
-                verify_proof::<_, $v, _, _, SingleStrategy<_>>(
-                    &params,
-                    pk.get_vk(),
-                    SingleStrategy::new(&params),
-                    &[&[&instances]],
-                    &mut transcript,
-                )
-            };
-            assert!(result.is_ok());
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let calldata = encode_calldata(None, &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
 
-            (params, vk, instances, proof)
-        }};
+    generator
+        .render_separately_to_io(
+            &mut File::create(&verifier_path).unwrap(),
+            &mut File::create(&vk_path).unwrap(),
+        )
+        .unwrap();
+    let verifier_solidity = std::fs::read_to_string(&verifier_path).unwrap();
+    let vk_solidity = std::fs::read_to_string(&vk_path).unwrap();
+    assert_eq!(
+        (verifier_solidity.clone(), vk_solidity.clone()),
+        generator.render_separately().unwrap()
+    );
+
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let vk_address = evm.create(compile_solidity(&vk_solidity));
+    let calldata = encode_calldata(Some(vk_address.into()), &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compile_with_optimizer_runs() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let verifier_solidity = generator.render().unwrap();
+
+    let mut evm = Evm::default();
+    for runs in [1, 200] {
+        let verifier_creation_code = compile_solidity_with_optimizer(&verifier_solidity, runs);
+        let verifier_creation_code_size = verifier_creation_code.len();
+        let verifier_address = evm.create(verifier_creation_code);
+
+        let (gas_cost, output) =
+            evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+        println!("Verifier creation code size with {runs} runs: {verifier_creation_code_size}");
+        println!("Gas cost with {runs} runs: {gas_cost}");
     }
+}
 
-    use create_testdata_inner;
+#[test]
+fn compile_solidity_with_abi() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let verifier_solidity = generator.render().unwrap();
 
-    fn random_accumulator_limbs<M>(
+    let (verifier_creation_code, abi) = compile_solidity_to_abi(&verifier_solidity);
+    assert_eq!(verifier_creation_code, compile_solidity(&verifier_solidity));
+    assert!(abi.starts_with('[') && abi.ends_with(']'));
+    assert!(abi.contains(r#""name":"verifyProof""#));
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(verifier_creation_code);
+    let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+}
+
+#[test]
+fn deploy_and_link() {
+    let solidity = r#"
+pragma solidity ^0.8.0;
+
+library Double {
+    function double(uint256 x) external pure returns (uint256) {
+        return 2 * x;
+    }
+}
+
+contract Caller {
+    fallback(bytes calldata input) external returns (bytes memory) {
+        return abi.encode(Double.double(abi.decode(input, (uint256))));
+    }
+}
+"#;
+
+    let mut evm = Evm::default();
+    let lib_address = evm.deploy_and_link(compile_solidity_unlinked(solidity, "Double"), &[]);
+    let caller_address = evm.deploy_and_link(
+        compile_solidity_unlinked(solidity, "Caller"),
+        &[("<stdin>:Double", lib_address)],
+    );
+
+    let (_, output) = evm.call(caller_address, [vec![0; 31], vec![7]].concat());
+    assert_eq!(output, [vec![0; 31], vec![14]].concat());
+}
+
+#[test]
+fn render_with_contract_names() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let generator = || SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+
+    for name in ["", "1Verifier", "My-Verifier"] {
+        assert_eq!(
+            builder().contract_name(name).build().unwrap_err(),
+            GeneratorError::InvalidOption {
+                option: "contract_name",
+                reason: format!("`{name}` is not a valid identifier"),
+            }
+        );
+    }
+    assert_eq!(
+        builder()
+            .contract_name("Halo2VerifyingKey")
+            .build()
+            .unwrap_err(),
+        GeneratorError::InvalidOption {
+            option: "vk_contract_name",
+            reason: "`Halo2VerifyingKey` is also the name of verifier".to_string(),
+        }
+    );
+
+    let generator_with_names = builder()
+        .contract_name("MyVerifier")
+        .vk_contract_name("MyVerifyingKey")
+        .build()
+        .unwrap();
+    assert_eq!(generator().suggested_filename(), "Halo2Verifier.sol");
+    assert_eq!(generator_with_names.suggested_filename(), "MyVerifier.sol");
+    assert_eq!(
+        generator_with_names.suggested_vk_filename(),
+        "MyVerifyingKey.sol"
+    );
+
+    let [verifier_a, verifier_b] = ["VerifierA", "VerifierB"].map(|name| {
+        builder()
+            .contract_name(name)
+            .build()
+            .unwrap()
+            .render()
+            .unwrap()
+    });
+    // Only one SPDX license identifier is allowed in a source file
+    let solidity = verifier_a + &verifier_b.replacen("// SPDX-License-Identifier: MIT\n", "", 1);
+
+    let mut evm = Evm::default();
+    for name in ["VerifierA", "VerifierB"] {
+        let verifier_address = evm.deploy_and_link(compile_solidity_unlinked(&solidity, name), &[]);
+        let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    }
+}
+
+#[test]
+fn verifier_compatibility() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let min_k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(min_k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let (verifier_solidity, _) = generator.render_separately().unwrap();
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+
+    // Domain size is stored in verifying key, so the verifier can be shared
+    for k in [min_k, min_k + 1] {
+        let (params, vk, instances, proof) =
+            halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+        assert_eq!(generator.is_compatible_with(&vk), Ok(()));
+
+        let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+        let (_, vk_solidity) = generator.render_separately().unwrap();
+        let vk_address = evm.create(compile_solidity(&vk_solidity));
+        let calldata = encode_calldata(Some(vk_address.into()), &proof, &instances);
+        let (_, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    }
+
+    type D = halo2::no_instance::NoInstance<Fr>;
+    let (_, vk, _, _) = halo2::create_testdata::<D>(
+        <D as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    assert_eq!(
+        generator.is_compatible_with(&vk),
+        Err(IncompatibilityReason::NumColumns {
+            kind: "instance",
+            expected: 2,
+            actual: 0
+        })
+    );
+}
+
+#[test]
+fn verify_proof_by_staticcall() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let generator = || SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+
+    let non_view_solidity = builder().view(false).build().unwrap().render().unwrap();
+    assert!(non_view_solidity.contains(") public returns (bool) {"));
+
+    let verifier_solidity = generator().render().unwrap();
+    assert!(verifier_solidity.contains(") public view returns (bool) {"));
+
+    // Wrapper only compiles if `verifyProof` is `view`, and calls it by `staticcall`
+    let wrapper_solidity = format!(
+        "{verifier_solidity}
+contract Wrapper {{
+    function verifyProof(
+        address verifier,
+        bytes calldata proof,
+        uint256[] calldata instances
+    ) external view returns (bool) {{
+        return Halo2Verifier(verifier).verifyProof(proof, instances);
+    }}
+}}
+"
+    );
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let wrapper_address =
+        evm.deploy_and_link(compile_solidity_unlinked(&wrapper_solidity, "Wrapper"), &[]);
+
+    // Same layout as `verifyProof(address,bytes,uint256[])` with verifier as the address
+    let calldata = encode_calldata(Some(verifier_address.into()), &proof, &instances);
+    let (_, output) = evm.call(wrapper_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+}
+
+#[test]
+fn verify_proof_by_registry() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let (other_params, other_vk, other_instances, other_proof) =
+        halo2::create_testdata::<C>(k, Bdfg21, None, StdRng::seed_from_u64(1));
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let other_generator =
+        SolidityGenerator::new(&other_params, &other_vk, Bdfg21, &num_instances(&instances));
+
+    let mut evm = Evm::default();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let vk_address: [u8; 20] = evm.create(compile_solidity(vk_solidity)).into();
+    let other_vk_address: [u8; 20] = evm
+        .create(compile_solidity(
+            other_generator.render_separately().unwrap().1,
+        ))
+        .into();
+    let registry_creation_code = [
+        compile_solidity(generator.render_registry().unwrap()),
+        vec![0; 12],
+        <[u8; 20]>::from(verifier_address).to_vec(),
+    ]
+    .concat();
+    let registry_address = evm.create(registry_creation_code);
+
+    // Verifier alone accepts proof with verifying key of any circuit in the same shape
+    let other_calldata = encode_calldata(Some(other_vk_address), &other_proof, &other_instances);
+    let (_, output) = evm.call(verifier_address, other_calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Register verifying key with code hash computed off-chain
+    let register_calldata = |vk_address: [u8; 20], codehash: [u8; 32]| {
+        let fn_sig = sha3::Keccak256::digest("register(address,bytes32)");
+        [&fn_sig[..4], &[0; 12][..], &vk_address[..], &codehash[..]].concat()
+    };
+    evm.assert_revert(
+        registry_address,
+        register_calldata(vk_address, other_generator.vk_codehash()),
+        Some("codehash mismatch"),
+    );
+    evm.call(
+        registry_address,
+        register_calldata(vk_address, generator.vk_codehash()),
+    );
+
+    // Registry accepts proof with registered verifying key only
+    let calldata = encode_calldata(Some(vk_address), &proof, &instances);
+    let (_, output) = evm.call(registry_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    evm.assert_revert(registry_address, other_calldata, Some("vk not registered"));
+}
+
+#[test]
+fn verify_proof_by_registry_with_event() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder
+            .num_instances(&num_instances(&instances))
+            .view(false)
+            .emit_event(true);
+        builder
+    };
+    let creation_code = |solidity: String, address: [u8; 20]| {
+        [compile_solidity(solidity), vec![0; 12], address.to_vec()].concat()
+    };
+    let register_calldata = |vk_address: [u8; 20], codehash: [u8; 32]| {
+        let fn_sig = sha3::Keccak256::digest("register(address,bytes32)");
+        [&fn_sig[..4], &[0; 12][..], &vk_address[..], &codehash[..]].concat()
+    };
+
+    // Verifier emitting event is called by `call`, with verifying key address passed in calldata
+    // or read from the verifier
+    for vk_address_source in [VkAddressSource::Calldata, VkAddressSource::Immutable] {
+        let generator = builder()
+            .vk_address_source(vk_address_source)
+            .build()
+            .unwrap();
+        let mut evm = Evm::default();
+        let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+        let vk_address: [u8; 20] = evm.create(compile_solidity(vk_solidity)).into();
+        let (verifier_address, calldata) = match vk_address_source {
+            VkAddressSource::Calldata => (
+                evm.create(compile_solidity(verifier_solidity)),
+                encode_calldata(Some(vk_address), &proof, &instances),
+            ),
+            _ => (
+                evm.create(creation_code(verifier_solidity, vk_address)),
+                encode_calldata(None, &proof, &instances),
+            ),
+        };
+        let registry_solidity = generator.render_registry().unwrap();
+        assert!(registry_solidity.contains("verifier.call(msg.data)"));
+        let registry_address =
+            evm.create(creation_code(registry_solidity, verifier_address.into()));
+
+        evm.assert_revert(
+            registry_address,
+            calldata.clone(),
+            Some("vk not registered"),
+        );
+        evm.call(
+            registry_address,
+            register_calldata(vk_address, generator.vk_codehash()),
+        );
+        let num_logs = evm.logs().len();
+        let (_, output) = evm.call(registry_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+        let logs = evm.logs();
+        assert_eq!(logs.len(), num_logs + 1);
+        assert_eq!(logs[num_logs].address, verifier_address);
+        assert_eq!(logs[num_logs].topics, [EVENT_SIG_VERIFIED]);
+        assert_eq!(logs[num_logs].data, [vec![0; 31], vec![1]].concat());
+    }
+}
+
+#[test]
+fn verify_proof_with_vk_codehash_check() {
+    use revm::primitives::ExecutionResult;
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let (other_params, other_vk, other_instances, other_proof) =
+        halo2::create_testdata::<C>(k, Bdfg21, None, StdRng::seed_from_u64(1));
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .vk_codehash_check(true)
+        .build()
+        .unwrap();
+    let other_generator =
+        SolidityGenerator::new(&other_params, &other_vk, Bdfg21, &num_instances(&instances));
+    assert!(generator.is_compatible_with(&other_vk).is_ok());
+
+    let mut evm = Evm::default();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    assert!(verifier_solidity.contains(&hex::encode(generator.vk_codehash())));
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let vk_creation_code = compile_solidity(vk_solidity);
+    let vk_address = evm.create(vk_creation_code.clone());
+    let other_vk_address = evm.create(compile_solidity(
+        other_generator.render_separately().unwrap().1,
+    ));
+    assert_eq!(evm.code_hash(vk_address), generator.vk_codehash());
+    assert_eq!(
+        evm.code_hash(other_vk_address),
+        other_generator.vk_codehash()
+    );
+    let vk_creation_code_hash: [u8; 32] = sha3::Keccak256::digest(vk_creation_code).into();
+    assert_ne!(generator.vk_codehash(), vk_creation_code_hash);
+
+    let calldata = encode_calldata(Some(vk_address.into()), &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Look-alike verifying key of circuit in the same shape is rejected before being copied
+    let other_calldata = encode_calldata(
+        Some(other_vk_address.into()),
+        &other_proof,
+        &other_instances,
+    );
+    let output = match evm.try_call(verifier_address, other_calldata) {
+        Err(ExecutionResult::Revert { output, .. }) => output,
+        result => panic!("Expected revert, got {result:?}"),
+    };
+    assert_eq!(output[..], ERROR_SIG_VK_CODEHASH_MISMATCH);
+}
+
+#[test]
+fn render_hardhat_config() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder
+            .num_instances(&num_instances(&instances))
+            .pragma_version(">=0.8.19 <0.9.0")
+            .contract_name("MyVerifier")
+            .vk_contract_name("MyVerifyingKey");
+        builder
+    };
+    let generator = builder().build().unwrap();
+
+    let config = generator
+        .render_hardhat_config("sepolia", "https://rpc.sepolia.org/\"key\"")
+        .unwrap();
+    assert!(config.contains("require(\"hardhat-deploy\");"));
+    assert!(config.contains("version: \"0.8.19\","));
+    assert!(config.contains("\"sepolia\": {"));
+    assert!(config.contains("url: \"https://rpc.sepolia.org/\\\"key\\\"\","));
+    assert!(config.contains("contracts/MyVerifier.sol"));
+    assert!(config.contains("contracts/MyVerifyingKey.sol"));
+
+    // Verifying key address is passed to constructor only when it's not read from calldata
+    let script = generator.render_hardhat_deploy_script().unwrap();
+    assert!(script.contains("deploy(\"MyVerifyingKey\""));
+    assert!(script.contains("deploy(\"MyVerifier\""));
+    assert!(!script.contains("args: [vk.address],"));
+    let script = builder()
+        .vk_address_source(VkAddressSource::Immutable)
+        .build()
+        .unwrap()
+        .render_hardhat_deploy_script()
+        .unwrap();
+    assert!(script.contains("args: [vk.address],"));
+}
+
+#[test]
+fn render_foundry_test() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .contract_name("MyVerifier")
+        .build()
+        .unwrap();
+
+    let foundry_test = generator.render_foundry_test(&proof, &instances).unwrap();
+    assert!(foundry_test.contains("import {Test} from \"forge-std/Test.sol\";"));
+    assert!(foundry_test.contains("import \"../src/MyVerifier.sol\";"));
+    assert!(foundry_test.contains("contract MyVerifierTest is Test {"));
+    assert!(foundry_test.contains("verifier = address(new MyVerifier());"));
+    assert!(foundry_test.contains("assertTrue(success);"));
+    assert!(foundry_test.contains("assertFalse(success);"));
+
+    // Embedded calldata verifies, and the tampered one differs by a single bit and fails
+    let [calldata, tampered_calldata] = ["CALLDATA", "TAMPERED_CALLDATA"].map(|name| {
+        foundry_test
+            .lines()
+            .find_map(|line| line.split(&format!(" {name} = hex\"")).nth(1))
+            .map(|hex| hex::decode(hex.trim_end_matches("\";")).unwrap())
+            .unwrap()
+    });
+    assert_eq!(calldata, encode_calldata(None, &proof, &instances));
+    assert_eq!(
+        calldata
+            .iter()
+            .zip(&tampered_calldata)
+            .map(|(lhs, rhs)| (lhs ^ rhs).count_ones())
+            .sum::<u32>(),
+        1
+    );
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    assert!(evm.try_call(verifier_address, tampered_calldata).is_err());
+
+    // Abstract verifier base with hook is deployed by a subclass with no-op hook
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .contract_name("MyVerifier")
+        .view(false)
+        .on_verified_hook(true)
+        .build()
+        .unwrap();
+    let foundry_test = generator.render_foundry_test(&proof, &instances).unwrap();
+    assert!(foundry_test.contains("import \"../src/MyVerifierBase.sol\";"));
+    assert!(foundry_test.contains("verifier = address(new MyVerifierHarness());"));
+    let harness = foundry_test
+        .split("\ncontract MyVerifierHarness")
+        .nth(1)
+        .and_then(|rest| rest.split("\n}\n").next())
+        .unwrap();
+    let verifier_solidity = generator.render().unwrap();
+    let solidity = format!("{verifier_solidity}\ncontract MyVerifierHarness{harness}\n}}\n");
+    let harness_address = evm.deploy_and_link(
+        compile_solidity_unlinked(solidity, "MyVerifierHarness"),
+        &[],
+    );
+    let (_, output) = evm.call(harness_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+}
+
+#[test]
+#[should_panic(expected = "Verify function returns pairing instead of result")]
+fn render_foundry_test_with_deferred_pairing() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .deferred_pairing(true)
+        .build()
+        .unwrap();
+    let _ = generator.render_foundry_test(&proof, &instances);
+}
+
+#[test]
+#[should_panic(expected = "Expected proof of at least 32 bytes but got 31")]
+fn render_foundry_test_with_short_proof() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let _ = generator.render_foundry_test(&proof[..0x1f], &instances);
+}
+
+#[test]
+fn verify_proof_with_yul() {
+    use revm::primitives::ExecutionResult;
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+
+    // Yul object shares the assembly block of the Solidity verifier with constants inlined
+    let generator = builder()
+        .fn_name("verify")
+        .on_failure(OnFailure::CustomError)
+        .build()
+        .unwrap();
+    let verifier_yul = generator.render_yul().unwrap();
+    assert!(verifier_yul.contains("object \"Halo2Verifier\" {"));
+    assert!(verifier_yul.contains("`verify(bytes,uint256[])`"));
+    assert!(!verifier_yul.contains("_MPTR") && !verifier_yul.contains("_CPTR"));
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_yul(&verifier_yul));
+    let calldata = generator.encode_calldata(None, &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    let mut tampered = calldata;
+    tampered[0x64 + 0x1f] ^= 1;
+    let output = match evm.try_call(verifier_address, tampered) {
+        Err(ExecutionResult::Revert { output, .. }) => output,
+        result => panic!("Expected revert, got {result:?}"),
+    };
+    assert_eq!(output[..4], ERROR_SIG_PROOF_INVALID);
+
+    // Functions and hooks dispatched by Solidity can't be rendered as Yul
+    for (option, generator) in [
+        ("batch_verify", builder().batch_verify(true).build()),
+        ("verify_and_store", builder().verify_and_store(true).build()),
+        (
+            "on_verified_hook",
+            builder().view(false).on_verified_hook(true).build(),
+        ),
+    ] {
+        match generator.unwrap().check_yul_support() {
+            Err(GeneratorError::Unsupported("Yul object", details)) => {
+                assert_eq!(details, format!("`{option}` is dispatched by Solidity"))
+            }
+            result => panic!("Expected unsupported option `{option}`, got {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn verify_proof_with_vk_address_source() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let (other_params, other_vk, other_instances, other_proof) =
+        halo2::create_testdata::<C>(k, Bdfg21, None, StdRng::seed_from_u64(1));
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let other_generator =
+        SolidityGenerator::new(&other_params, &other_vk, Bdfg21, &num_instances(&instances));
+    let creation_code = |verifier_solidity: String, vk_address: [u8; 20]| {
+        [
+            compile_solidity(verifier_solidity),
+            vec![0; 12],
+            vk_address.to_vec(),
+        ]
+        .concat()
+    };
+
+    // Verifying key address baked in by constructor
+    let mut evm = Evm::default();
+    let generator = builder()
+        .vk_address_source(VkAddressSource::Immutable)
+        .build()
+        .unwrap();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    assert!(verifier_solidity.contains("address public immutable vkAddress;"));
+    assert!(!verifier_solidity.contains("address vk,"));
+    let vk_address: [u8; 20] = evm.create(compile_solidity(&vk_solidity)).into();
+    let verifier_address = evm.create(creation_code(verifier_solidity, vk_address));
+    let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Verifying key address rotated by owner
+    let generator = builder()
+        .vk_address_source(VkAddressSource::Ownable)
+        .build()
+        .unwrap();
+    let verifier_solidity = generator.render_separately().unwrap().0;
+    let other_vk_address: [u8; 20] = evm
+        .create(compile_solidity(
+            other_generator.render_separately().unwrap().1,
+        ))
+        .into();
+    let verifier_address = evm.create(creation_code(verifier_solidity, vk_address));
+    let calldata = encode_calldata(None, &proof, &instances);
+    let other_calldata = encode_calldata(None, &other_proof, &other_instances);
+    let (_, output) = evm.call(verifier_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    evm.assert_revert(verifier_address, other_calldata.clone(), None);
+
+    let fn_sig = sha3::Keccak256::digest("setVkAddress(address)");
+    let set_vk_address_calldata = [&fn_sig[..4], &[0; 12][..], &other_vk_address[..]].concat();
+    // Only owner, which is the deployer at address zero, can rotate verifying key
+    evm.set_caller([0xab; 20].into());
+    evm.assert_revert(
+        verifier_address,
+        set_vk_address_calldata.clone(),
+        Some("not owner"),
+    );
+    evm.set_caller(Default::default());
+    evm.call(verifier_address, set_vk_address_calldata);
+    let (_, output) = evm.call(verifier_address, other_calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    evm.assert_revert(verifier_address, calldata, None);
+}
+
+#[test]
+fn verify_proof_with_hashed_instances() {
+    type C = halo2::hashed_instances::HashedInstances<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let circuit = <C as halo2::TestCircuit<Fr>>::new(None, std_rng());
+    let instances_hash = hash_instances(&[circuit.public_inputs().to_vec()]);
+    assert_eq!(instances, [instances_from_hash(instances_hash).to_vec()]);
+
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .hashed_instances(true)
+        .build()
+        .unwrap();
+    let calldata = encode_calldata_hashed(None, &proof, instances_hash);
+    assert_eq!(
+        generator.encode_calldata(None, &proof, &instances),
+        calldata
+    );
+
+    let mut evm = Evm::default();
+    let (verifier_solidity, meta) = generator.render_with_meta().unwrap();
+    assert_eq!(calldata.len(), meta.calldata_len);
+    assert!(verifier_solidity.contains("bytes32 instancesHash"));
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let (gas_cost, output) = evm.call(verifier_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!("Gas cost with hashed instances: {gas_cost}");
+
+    // Hash of other public inputs
+    let mut tampered = calldata;
+    tampered[0x24] ^= 1;
+    evm.assert_revert(verifier_address, tampered, None);
+
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let vk_address = evm.create(compile_solidity(vk_solidity)).into();
+    let calldata = encode_calldata_hashed(Some(vk_address), &proof, instances_hash);
+    assert_eq!(
+        generator.encode_calldata(Some(vk_address), &proof, &instances),
+        calldata
+    );
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Circuit with more than 2 instances can't expose hash only
+    type D = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<D>(
+        <D as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    assert!(matches!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .hashed_instances(true)
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "hashed_instances",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn verify_proof_with_packed_instances() {
+    use halo2::small_instances::BIT_WIDTHS;
+    type C = halo2::small_instances::SmallInstances<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    // The instance of 60 bits is at its max value
+    assert_eq!(instances[0][1], Fr::from((1 << 60) - 1));
+
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .packed_instances(&BIT_WIDTHS)
+        .build()
+        .unwrap();
+    let calldata = encode_calldata_packed(None, &proof, &instances, &BIT_WIDTHS);
+    assert_eq!(
+        generator.encode_calldata_packed(None, &proof, &instances),
+        calldata
+    );
+    assert!(calldata.len() < encode_calldata(None, &proof, &instances).len());
+
+    let mut evm = Evm::default();
+    let verifier_solidity = generator.render().unwrap();
+    assert!(verifier_solidity.contains("bytes calldata packedInstances"));
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let (gas_cost, output) = evm.call(verifier_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!("Gas cost with packed instances: {gas_cost}");
+    let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Packed instances start after selector, 2 offsets, length of proof, proof and its length
+    let packed_instances_cptr = 4 + 0x80 + proof.len();
+    // The instance of 60 bits packed in 8 bytes overflows to 2^60
+    let mut out_of_range = calldata.clone();
+    out_of_range[packed_instances_cptr + 8..packed_instances_cptr + 16]
+        .copy_from_slice(&(1u64 << 60).to_be_bytes());
+    evm.assert_revert(verifier_address, out_of_range, None);
+    // Truncated packed instances
+    let mut truncated = calldata;
+    truncated[packed_instances_cptr - 1] -= 1;
+    evm.assert_revert(verifier_address, truncated, None);
+
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let vk_address = evm.create(compile_solidity(vk_solidity)).into();
+    let calldata = encode_calldata_packed(Some(vk_address), &proof, &instances, &BIT_WIDTHS);
+    assert_eq!(
+        generator.encode_calldata_packed(Some(vk_address), &proof, &instances),
+        calldata
+    );
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    for (bit_widths, batch_verify) in [
+        (&BIT_WIDTHS[1..], false),
+        (&[0; 8][..], false),
+        (&BIT_WIDTHS[..], true),
+    ] {
+        assert!(matches!(
+            SolidityGenerator::builder(&params, &vk)
+                .num_instances(&num_instances(&instances))
+                .batch_verify(batch_verify)
+                .packed_instances(bit_widths)
+                .build(),
+            Err(GeneratorError::InvalidOption {
+                option: "packed_instances",
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn verify_proof_with_nested_instances() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    // Instance columns are of different lengths
+    assert_eq!(num_instances(&instances), [3, 5]);
+
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .nested_instances(true)
+        .build()
+        .unwrap();
+    let calldata = encode_calldata_nested(None, &proof, &instances);
+    assert_eq!(
+        generator.encode_calldata_nested(None, &proof, &instances),
+        calldata
+    );
+
+    // Decoded columns flatten into the same payload of `encode_calldata`
+    let payload = decode_calldata_nested(&calldata).unwrap();
+    let lens = payload
+        .instance_columns
+        .iter()
+        .map(Vec::len)
+        .collect::<Vec<_>>();
+    assert_eq!(lens, [3, 5]);
+    assert_eq!(
+        payload.flatten(),
+        encode_calldata_typed(None, &proof, &instances)
+    );
+    let mut extra = calldata.clone();
+    extra.extend([0; 0x20]);
+    assert!(matches!(
+        decode_calldata_nested(&extra),
+        Err(CalldataError::Malformed(_))
+    ));
+
+    let mut evm = Evm::default();
+    let verifier_solidity = generator.render().unwrap();
+    assert!(verifier_solidity.contains("uint256[][] calldata nestedInstances"));
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let (gas_cost, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!("Gas cost with nested instances: {gas_cost}");
+    let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Columns swapped, merged or with an instance moved to the other column
+    let moved = vec![
+        instances[0][..2].to_vec(),
+        [&instances[0][2..], &instances[1]].concat(),
+    ];
+    for instances in [
+        vec![instances[1].clone(), instances[0].clone()],
+        vec![instances.concat()],
+        moved,
+    ] {
+        let calldata = encode_calldata_nested(None, &proof, &instances);
+        evm.assert_revert(verifier_address, calldata, None);
+    }
+
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let vk_address = evm.create(compile_solidity(vk_solidity)).into();
+    let calldata = encode_calldata_nested(Some(vk_address), &proof, &instances);
+    assert_eq!(
+        generator.encode_calldata_nested(Some(vk_address), &proof, &instances),
+        calldata
+    );
+    let payload = decode_calldata_nested(&calldata).unwrap();
+    assert_eq!(payload.vk_address, Some(vk_address));
+    let mut address_0x40 = [0; 20];
+    address_0x40[19] = 0x40;
+    let calldata_0x40 = encode_calldata_nested(Some(address_0x40), &proof, &instances);
+    let payload = decode_calldata_nested(&calldata_0x40).unwrap();
+    assert_eq!(payload.vk_address, Some(address_0x40));
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    assert!(matches!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .batch_verify(true)
+            .nested_instances(true)
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "nested_instances",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn verify_proof_with_little_endian_instances() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let payload = encode_calldata_typed(None, &proof, &instances);
+    assert_eq!(
+        BigEndianEncoder::encode(&instances),
+        payload.instance_scalars.concat()
+    );
+
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .instance_encoder::<LittleEndianEncoder>()
+        .build()
+        .unwrap();
+    let calldata = encode_calldata_with_encoder::<LittleEndianEncoder>(None, &proof, &instances);
+    assert_eq!(
+        generator.encode_calldata(None, &proof, &instances),
+        calldata
+    );
+    assert_ne!(calldata, encode_calldata(None, &proof, &instances));
+
+    let mut evm = Evm::default();
+    let verifier_solidity = generator.render().unwrap();
+    assert!(verifier_solidity.contains("function reverse_bytes(v)"));
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let (gas_cost, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!("Gas cost with little-endian instances: {gas_cost}");
+    // Big-endian instances are out of order for the verifier
+    evm.assert_revert(
+        verifier_address,
+        encode_calldata(None, &proof, &instances),
+        None,
+    );
+
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let vk_address = evm.create(compile_solidity(vk_solidity)).into();
+    let calldata = generator.encode_calldata(Some(vk_address), &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Instances hash and packed instances are always big-endian
+    assert!(matches!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .packed_instances(&vec![64; instances.concat().len()])
+            .instance_encoder::<LittleEndianEncoder>()
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "instance_encoder",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn evm_get_storage_at() {
+    use revm::primitives::U256;
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .vk_address_source(VkAddressSource::Ownable)
+        .build()
+        .unwrap();
+
+    let mut evm = Evm::default();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let vk_address: [u8; 20] = evm.create(compile_solidity(&vk_solidity)).into();
+    let other_vk_address: [u8; 20] = evm.create(compile_solidity(&vk_solidity)).into();
+    let verifier_address = evm.create(
+        [
+            compile_solidity(verifier_solidity),
+            vec![0; 12],
+            vk_address.to_vec(),
+        ]
+        .concat(),
+    );
+
+    // `vkAddress` and `owner` are stored in slot 0 and 1, and owner is the default caller
+    let slot = |idx: u64| U256::from(idx);
+    let word = |address: [u8; 20]| U256::from_be_slice(&address);
+    assert_eq!(
+        evm.get_storage_at(verifier_address, slot(0)),
+        word(vk_address)
+    );
+    assert_eq!(evm.get_storage_at(verifier_address, slot(1)), U256::ZERO);
+    assert_eq!(evm.get_storage_at(verifier_address, slot(2)), U256::ZERO);
+    assert_eq!(evm.get_storage_at(vk_address.into(), slot(0)), U256::ZERO);
+
+    let fn_sig = sha3::Keccak256::digest("setVkAddress(address)");
+    evm.call(
+        verifier_address,
+        [&fn_sig[..4], &[0; 12][..], &other_vk_address[..]].concat(),
+    );
+    assert_eq!(
+        evm.get_storage_at(verifier_address, slot(0)),
+        word(other_vk_address)
+    );
+}
+
+#[test]
+fn evm_snapshot() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let (other_params, other_vk, other_instances, other_proof) =
+        halo2::create_testdata::<C>(k, Bdfg21, None, StdRng::seed_from_u64(1));
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .vk_address_source(VkAddressSource::Ownable)
+        .build()
+        .unwrap();
+    let other_generator =
+        SolidityGenerator::new(&other_params, &other_vk, Bdfg21, &num_instances(&instances));
+
+    let mut evm = Evm::default();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let vk_address: [u8; 20] = evm.create(compile_solidity(vk_solidity)).into();
+    let other_vk_address: [u8; 20] = evm
+        .create(compile_solidity(
+            other_generator.render_separately().unwrap().1,
+        ))
+        .into();
+    let verifier_address = evm.create(
+        [
+            compile_solidity(verifier_solidity),
+            vec![0; 12],
+            vk_address.to_vec(),
+        ]
+        .concat(),
+    );
+    let calldata = encode_calldata(None, &proof, &instances);
+    let other_calldata = encode_calldata(None, &other_proof, &other_instances);
+
+    let snapshot = evm.snapshot();
+    let fn_sig = sha3::Keccak256::digest("setVkAddress(address)");
+    evm.call(
+        verifier_address,
+        [&fn_sig[..4], &[0; 12][..], &other_vk_address[..]].concat(),
+    );
+    let (_, output) = evm.call(verifier_address, other_calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    evm.assert_revert(verifier_address, calldata.clone(), None);
+
+    // Verifying key address is restored without re-deploying
+    evm.revert_to_snapshot(snapshot);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    evm.assert_revert(verifier_address, other_calldata, None);
+}
+
+#[test]
+fn evm_call_with_breakdown() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+    let calldata = encode_calldata(None, &proof, &instances);
+    let (gas_used, output) = evm.call(verifier_address, calldata.clone());
+    let (gas_used_traced, output_traced, breakdown) =
+        evm.call_with_breakdown(verifier_address, calldata);
+    assert_eq!((gas_used_traced, output_traced), (gas_used, output));
+    assert_eq!(
+        breakdown.transcript_ops
+            + breakdown.ec_add_ops
+            + breakdown.ec_mul_ops
+            + breakdown.pairing_ops
+            + breakdown.other,
+        gas_used
+    );
+    assert!(breakdown.transcript_ops > 0);
+    assert!(breakdown.ec_add_ops > 0);
+    // Each `ecMul` costs 6000 but `ecAdd` only 150, and pairing of 2 pairs costs 113000
+    assert!(breakdown.ec_mul_ops > breakdown.ec_add_ops);
+    assert!(breakdown.pairing_ops >= 113000);
+}
+
+#[test]
+fn evm_spec_id() {
+    use crate::evm::test::revm::primitives::SpecId;
+
+    // Runtime code `staticcall(gas(), 0x08, 0, 0, 0, 0)` calling `ecPairing` with no pair
+    let runtime_code = hex::decode("600060006000600060085afa00").unwrap();
+    let creation_code = [
+        hex::decode("600d600c600039600d6000f3").unwrap(),
+        runtime_code,
+    ]
+    .concat();
+
+    let mut evm = Evm::default();
+    assert_eq!(evm.spec_id(), SpecId::LATEST);
+    let address = evm.create(creation_code);
+    let (gas_used, _) = evm.call(address, Vec::new());
+    let (byzantium_gas_used, _) = evm.set_spec_id(SpecId::BYZANTIUM).call(address, Vec::new());
+    // Base cost of `ecPairing` is 100000 before EIP-1108 and 45000 after, and `staticcall` to
+    // precompile costs 700 before EIP-2929 and 100 after
+    assert_eq!(byzantium_gas_used - gas_used, 55000 + 600);
+}
+
+#[test]
+#[should_panic(expected = "Snapshot 0 is already discarded")]
+fn evm_revert_to_discarded_snapshot() {
+    let mut evm = Evm::default();
+    let snapshot = evm.snapshot();
+    evm.snapshot();
+    evm.revert_to_snapshot(snapshot);
+    evm.revert_to_snapshot(snapshot);
+}
+
+#[test]
+fn verify_proof_through_interface() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+
+    for separate in [false, true] {
+        let mut evm = Evm::default();
+        let (interface_solidity, verifier_address, vk_address) = if separate {
+            let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+            (
+                generator.render_separately_interface().unwrap(),
+                evm.create(compile_solidity(&verifier_solidity)),
+                Some(evm.create(compile_solidity(&vk_solidity)).into()),
+            )
+        } else {
+            (
+                generator.render_interface().unwrap(),
+                evm.create(compile_solidity(generator.render().unwrap())),
+                None,
+            )
+        };
+        assert!(interface_solidity.contains("interface IHalo2Verifier {"));
+
+        // Consumer only knows the interface, and forwards calldata to verifier as is
+        let (vk_param, vk_arg) = if separate {
+            ("address vk,", "vk, ")
+        } else {
+            ("", "")
+        };
+        let consumer_solidity = format!(
+            "{interface_solidity}
+contract Consumer {{
+    IHalo2Verifier internal immutable verifier;
+
+    constructor(IHalo2Verifier _verifier) {{
+        verifier = _verifier;
+    }}
+
+    function verifyProof(
+        {vk_param}
+        bytes calldata proof,
+        uint256[] calldata instances
+    ) external view returns (bool) {{
+        return verifier.verifyProof({vk_arg}proof, instances);
+    }}
+}}
+"
+        );
+        let verifier_address: [u8; 20] = verifier_address.into();
+        let consumer_creation_code = [
+            compile_solidity_unlinked(&consumer_solidity, "Consumer"),
+            vec![0; 12],
+            verifier_address.to_vec(),
+        ]
+        .concat();
+        let consumer_address = evm.deploy_and_link(consumer_creation_code, &[]);
+
+        let calldata = encode_calldata(vk_address, &proof, &instances);
+        let (_, output) = evm.call(consumer_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    }
+}
+
+#[test]
+fn circuit_metrics() {
+    type C1 = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (_, vk, _, proof) = halo2::create_testdata::<C1>(
+        <C1 as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let metrics = CircuitMetrics::from_vk(&vk);
+    assert_eq!(metrics.num_advice_columns, 2);
+    assert_eq!(metrics.num_instance_columns, 2);
+    assert_eq!(metrics.num_lookups, 0);
+    assert_eq!(metrics.num_permutation_chunks, 4);
+    assert_eq!(metrics.degree, 3);
+    assert_eq!(metrics.proof_size_bytes, proof.len());
+
+    type C2 = halo2::fixed_table::FixedTable<Fr>;
+    let (_, vk, _, proof) = halo2::create_testdata::<C2>(
+        <C2 as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let metrics = CircuitMetrics::from_vk(&vk);
+    assert_eq!(metrics.num_lookups, 1);
+    assert_eq!(metrics.proof_size_bytes, proof.len());
+}
+
+#[test]
+fn verify_proof_without_lookup() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    for scheme in [Bdfg21, Gwc19] {
+        let (params, vk, instances, proof) =
+            halo2::create_testdata::<C>(k, scheme, None, std_rng());
+        assert!(vk.cs().lookups().is_empty());
+
+        // Theta is still squeezed for transcript compatibility but never loaded
+        let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances));
+        let verifier_solidity = generator.render().unwrap();
+        assert!(!verifier_solidity.contains("THETA_MPTR"));
+
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(verifier_solidity));
+        let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    }
+
+    type D = halo2::fixed_table::FixedTable<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<D>(
+        <D as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    assert!(generator.render().unwrap().contains("THETA_MPTR"));
+}
+
+#[test]
+fn verify_calldata_natively() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let num_instances = num_instances(&instances);
+    let verify = |calldata: &[u8]| verify_calldata(calldata, &vk, &params, Bdfg21, &num_instances);
+
+    // Verifying key address ending with `0x40`, same as offset of proof without it
+    let mut address_0x40 = [0; 20];
+    address_0x40[19] = 0x40;
+    for vk_address in [None, Some([0xff; 20]), Some(address_0x40)] {
+        let calldata = encode_calldata(vk_address, &proof, &instances);
+        assert_eq!(
+            decode_calldata(&calldata),
+            Ok(encode_calldata_typed(vk_address, &proof, &instances))
+        );
+        assert_eq!(verify(&calldata), Ok(()));
+
+        // Truncated calldata
+        assert!(matches!(
+            verify(&calldata[..calldata.len() - 1]),
+            Err(CalldataError::Malformed(_))
+        ));
+
+        // Missing the last instance
+        let mut payload = encode_calldata_typed(vk_address, &proof, &instances);
+        payload.instance_scalars.pop();
+        assert_eq!(
+            verify(&payload.to_bytes()),
+            Err(CalldataError::InstancesMismatch {
+                expected: 8,
+                actual: 7
+            })
+        );
+
+        // Instance out of scalar field
+        let mut payload = encode_calldata_typed(vk_address, &proof, &instances);
+        payload.instance_scalars[0] = [0xff; 32];
+        assert!(matches!(
+            verify(&payload.to_bytes()),
+            Err(CalldataError::Malformed(_))
+        ));
+
+        // Wrong instance
+        let mut payload = encode_calldata_typed(vk_address, &proof, &instances);
+        payload.instance_scalars[0][31] ^= 1;
+        assert!(matches!(
+            verify(&payload.to_bytes()),
+            Err(CalldataError::VerificationFailed(_))
+        ));
+    }
+}
+
+#[test]
+fn decode_calldata_with_overflowing_offset() {
+    let proof = [0xab; 0x40];
+    let instances = [vec![Fr::from(1), Fr::from(2)]];
+    for vk_address in [None, Some([0xcd; 20])] {
+        let offsets_cptr = if vk_address.is_some() { 0x24 } else { 0x04 };
+        let overflowing = to_u256_be_bytes(usize::MAX - 1);
+
+        // Offset of instances overflows when added to the offset of function signature
+        let mut calldata = encode_calldata(vk_address, &proof, &instances);
+        calldata[offsets_cptr + 0x20..offsets_cptr + 0x40].copy_from_slice(&overflowing);
+        assert!(matches!(
+            decode_calldata(&calldata),
+            Err(CalldataError::Malformed(_))
+        ));
+        let mut calldata = encode_calldata_nested(vk_address, &proof, &instances);
+        calldata[offsets_cptr + 0x20..offsets_cptr + 0x40].copy_from_slice(&overflowing);
+        assert!(matches!(
+            decode_calldata_nested(&calldata),
+            Err(CalldataError::Malformed(_))
+        ));
+
+        // Offset of instance column overflows when added to the pointer of instances
+        let mut calldata = encode_calldata_nested(vk_address, &proof, &instances);
+        let instances_cptr = offsets_cptr + 0x60 + proof.len();
+        calldata[instances_cptr + 0x20..instances_cptr + 0x40].copy_from_slice(&overflowing);
+        assert!(matches!(
+            decode_calldata_nested(&calldata),
+            Err(CalldataError::Malformed(_))
+        ));
+    }
+}
+
+#[test]
+fn verify_proofs_in_batch() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, testdata) =
+        halo2::create_testdata_batch::<C>(<C as halo2::TestCircuit<Fr>>::min_k(), 8, std_rng());
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&testdata[0].0))
+        .batch_verify(true)
+        .build()
+        .unwrap();
+
+    for separate in [false, true] {
+        let mut evm = Evm::default();
+        let (verifier_address, vk_address) = if separate {
+            let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+            (
+                evm.create(compile_solidity(verifier_solidity)),
+                Some(evm.create(compile_solidity(vk_solidity)).into()),
+            )
+        } else {
+            (
+                evm.create(compile_solidity(generator.render().unwrap())),
+                None,
+            )
+        };
+
+        let individual_gas_cost = testdata
+            .iter()
+            .map(|(instances, proof)| {
+                let calldata = encode_calldata(vk_address, proof, instances);
+                let (gas_cost, output) = evm.call(verifier_address, calldata);
+                assert_eq!(output, [vec![0; 31], vec![1]].concat());
+                gas_cost
+            })
+            .sum::<u64>();
+
+        let proofs = testdata
+            .iter()
+            .map(|(instances, proof)| (proof.as_slice(), instances.as_slice()))
+            .collect::<Vec<_>>();
+        let calldata = encode_calldata_batch(vk_address, &proofs);
+        let (batch_gas_cost, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+        let num_proofs = testdata.len() as u64;
+        println!(
+            "Gas cost per proof (separate: {separate}): {} individually, {} in batch",
+            individual_gas_cost / num_proofs,
+            batch_gas_cost / num_proofs,
+        );
+        assert!(batch_gas_cost < individual_gas_cost);
+
+        // Any invalid proof fails the combined pairing
+        let mut tampered_instances = testdata[3].0.clone();
+        tampered_instances[0][0] += Fr::from(1);
+        let mut tampered_proofs = proofs.clone();
+        tampered_proofs[3].1 = tampered_instances.as_slice();
+        let calldata = encode_calldata_batch(vk_address, &tampered_proofs);
+        evm.assert_revert(verifier_address, calldata, None);
+
+        // Empty proofs fails
+        let no_proofs: &[(&[u8], &[Vec<Fr>])] = &[];
+        evm.assert_revert(
+            verifier_address,
+            encode_calldata_batch(vk_address, no_proofs),
+            None,
+        );
+    }
+}
+
+#[test]
+fn verify_proofs_with_deferred_pairing() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, testdata) =
+        halo2::create_testdata_batch::<C>(<C as halo2::TestCircuit<Fr>>::min_k(), 3, std_rng());
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&testdata[0].0))
+        .deferred_pairing(true)
+        .build()
+        .unwrap();
+    let verifier_solidity = generator.render().unwrap();
+    assert!(verifier_solidity.contains("returns (uint256[4] memory)"));
+    let aggregator_solidity = generator.render_pairing_aggregator().unwrap();
+    assert!(aggregator_solidity.contains("contract Halo2VerifierPairingAggregator"));
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let aggregator_address = evm.create(compile_solidity(aggregator_solidity));
+
+    // Each verification returns pairing lhs and rhs instead of performing pairing
+    let pairings = testdata
+        .iter()
+        .map(|(instances, proof)| {
+            let calldata = encode_calldata(None, proof, instances);
+            let (_, output) = evm.call(verifier_address, calldata);
+            assert_eq!(output.len(), 0x80);
+            output
+        })
+        .collect::<Vec<_>>();
+    let mut tampered_proof = testdata[0].1.clone();
+    tampered_proof[..0x20].fill(0xff);
+    let calldata = encode_calldata(None, &tampered_proof, &testdata[0].0);
+    evm.assert_revert(verifier_address, calldata, None);
+
+    // Fold all pairings and finalize with one pairing
+    let verify_pairings = |pairings: &[Vec<u8>]| {
+        let fn_sig = sha3::Keccak256::digest("verifyPairings(uint256[4][])");
+        [
+            fn_sig[..4].to_vec(),
+            to_u256_be_bytes(0x20).to_vec(),
+            to_u256_be_bytes(pairings.len()).to_vec(),
+            pairings.concat(),
+        ]
+        .concat()
+    };
+    let (gas_cost, output) = evm.call(aggregator_address, verify_pairings(&pairings));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!(
+        "Gas cost of aggregating {} pairings: {gas_cost}",
+        pairings.len()
+    );
+
+    // Any pairing with lhs and rhs swapped fails the folded pairing, and so do no pairings
+    let mut swapped = pairings.clone();
+    swapped[1] = [&pairings[1][0x40..], &pairings[1][..0x40]].concat();
+    let (_, output) = evm.call(aggregator_address, verify_pairings(&swapped));
+    assert_eq!(output, vec![0; 32]);
+    let (_, output) = evm.call(aggregator_address, verify_pairings(&[]));
+    assert_eq!(output, vec![0; 32]);
+
+    for on_failure in [OnFailure::Revert, OnFailure::ReturnFalse] {
+        let result = SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&testdata[0].0))
+            .on_failure(on_failure)
+            .batch_verify(on_failure == OnFailure::Revert)
+            .deferred_pairing(true)
+            .build();
+        assert!(matches!(
+            result,
+            Err(GeneratorError::InvalidOption {
+                option: "deferred_pairing",
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn verify_proofs_concatenated() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, testdata) =
+        halo2::create_testdata_batch::<C>(<C as halo2::TestCircuit<Fr>>::min_k(), 4, std_rng());
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&testdata[0].0))
+        .batch_verify(true)
+        .num_proofs(Some(testdata.len()))
+        .build()
+        .unwrap();
+    let proofs = testdata
+        .iter()
+        .map(|(instances, proof)| (proof.as_slice(), instances.as_slice()))
+        .collect::<Vec<_>>();
+
+    for separate in [false, true] {
+        let mut evm = Evm::default();
+        let (verifier_address, vk_address) = if separate {
+            let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+            (
+                evm.create(compile_solidity(verifier_solidity)),
+                Some(evm.create(compile_solidity(vk_solidity)).into()),
+            )
+        } else {
+            (
+                evm.create(compile_solidity(generator.render().unwrap())),
+                None,
+            )
+        };
+
+        let calldata = encode_calldata_v2(vk_address, &proofs);
+        assert_eq!(generator.encode_calldata_v2(vk_address, &proofs), calldata);
+        let (gas_cost, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+        println!("Gas cost of concatenated proofs (separate: {separate}): {gas_cost}");
+
+        // Any invalid proof fails the combined pairing
+        let mut tampered_instances = testdata[2].0.clone();
+        tampered_instances[0][0] += Fr::from(1);
+        let mut tampered_proofs = proofs.clone();
+        tampered_proofs[2].1 = tampered_instances.as_slice();
+        let calldata = encode_calldata_v2(vk_address, &tampered_proofs);
+        evm.assert_revert(verifier_address, calldata, None);
+
+        // Number of proofs is fixed
+        let calldata = encode_calldata_v2(vk_address, &proofs[1..]);
+        evm.assert_revert(verifier_address, calldata, None);
+    }
+
+    for (num_proofs, batch_verify) in [(0, true), (4, false)] {
+        assert!(matches!(
+            SolidityGenerator::builder(&params, &vk)
+                .num_instances(&num_instances(&testdata[0].0))
+                .batch_verify(batch_verify)
+                .num_proofs(Some(num_proofs))
+                .build(),
+            Err(GeneratorError::InvalidOption {
+                option: "num_proofs",
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn verify_proof_on_failure() {
+    use revm::primitives::ExecutionResult;
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let meta = generator.render_with_meta().unwrap().1;
+
+    // Tamper an evaluation to fail the pairing check, and the first EC point to fail earlier
+    let mut tampered_eval = proof.clone();
+    tampered_eval[meta.proof_len - meta.num_evals * 0x20 - 0x80 + 0x1f] ^= 1;
+    let mut tampered_point = proof.clone();
+    tampered_point[..0x20].fill(0xff);
+
+    for on_failure in [
+        OnFailure::Revert,
+        OnFailure::ReturnFalse,
+        OnFailure::CustomError,
+    ] {
+        let verifier_solidity = SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .on_failure(on_failure)
+            .build()
+            .unwrap()
+            .render()
+            .unwrap();
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+
+        let calldata = |proof: &[u8]| encode_calldata(None, proof, &instances);
+        let (_, output) = evm.call(verifier_address, calldata(&proof));
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+        for tampered_proof in [&tampered_eval, &tampered_point] {
+            let result = evm.try_call(verifier_address, calldata(tampered_proof));
+            match on_failure {
+                OnFailure::Revert => assert!(result.is_err()),
+                OnFailure::ReturnFalse => assert_eq!(result.unwrap().1, vec![0; 32]),
+                OnFailure::CustomError => match result {
+                    Err(ExecutionResult::Revert { output, .. }) => {
+                        assert_eq!(output[..4], ERROR_SIG_PROOF_INVALID)
+                    }
+                    result => panic!("Expected revert, got {result:?}"),
+                },
+            }
+        }
+    }
+}
+
+#[test]
+fn verify_proof_with_emit_event() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let mut tampered_proof = proof.clone();
+    tampered_proof[..0x20].fill(0xff);
+
+    for on_failure in [OnFailure::Revert, OnFailure::ReturnFalse] {
+        let verifier_solidity = SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .on_failure(on_failure)
+            .view(false)
+            .emit_event(true)
+            .build()
+            .unwrap()
+            .render()
+            .unwrap();
+        assert!(verifier_solidity.contains("event Verified(bool success);"));
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+        assert!(evm.logs().is_empty());
+
+        let calldata = |proof: &[u8]| encode_calldata(None, proof, &instances);
+        evm.call(verifier_address, calldata(&proof));
+        let logs = evm.logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].address, verifier_address);
+        assert_eq!(logs[0].topics, [EVENT_SIG_VERIFIED]);
+        assert_eq!(logs[0].data, [vec![0; 31], vec![1]].concat());
+
+        // Nothing is emitted when reverting, otherwise `Verified(false)` is emitted
+        let result = evm.try_call(verifier_address, calldata(&tampered_proof));
+        let logs = evm.logs();
+        match on_failure {
+            OnFailure::Revert => {
+                assert!(result.is_err());
+                assert_eq!(logs.len(), 1);
+            }
+            OnFailure::ReturnFalse => {
+                assert_eq!(logs.len(), 2);
+                assert_eq!(logs[1].topics, [EVENT_SIG_VERIFIED]);
+                assert_eq!(logs[1].data, vec![0; 32]);
+            }
+            OnFailure::CustomError => unreachable!(),
+        }
+    }
+
+    assert!(matches!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .emit_event(true)
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "emit_event",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn verify_proof_with_verify_and_store() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .verify_and_store(true)
+        .build()
+        .unwrap();
+    let verifier_solidity = generator.render().unwrap();
+    // The verify function is untouched and stays `view`
+    assert!(verifier_solidity.contains(") public view returns (bool) {\n        assembly {"));
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let is_verified = |evm: &mut Evm, digest: [u8; 32]| {
+        let calldata = [FN_SIG_IS_VERIFIED.to_vec(), digest.to_vec()].concat();
+        let (gas_cost, output) = evm.call(verifier_address, calldata);
+        (gas_cost, output == [vec![0; 31], vec![1]].concat())
+    };
+    let digest = generator.proof_digest(None, &proof, &instances);
+    assert!(!is_verified(&mut evm, digest).1);
+
+    // The first call verifies the proof and records its digest, then re-check is cheap
+    let calldata = generator.encode_calldata_verify_and_store(None, &proof, &instances);
+    assert_eq!(calldata[..4], FN_SIG_VERIFY_AND_STORE);
+    let (verify_gas_cost, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    let (is_verified_gas_cost, verified) = is_verified(&mut evm, digest);
+    assert!(verified);
+    assert!(is_verified_gas_cost * 10 < verify_gas_cost);
+
+    // Different instances don't collide, and can't be recorded without valid proof
+    let mut other_instances = instances.clone();
+    other_instances[0][0] += Fr::from(1);
+    let other_digest = generator.proof_digest(None, &proof, &other_instances);
+    assert_ne!(digest, other_digest);
+    let calldata = generator.encode_calldata_verify_and_store(None, &proof, &other_instances);
+    assert!(evm.try_call(verifier_address, calldata).is_err());
+    assert!(!is_verified(&mut evm, other_digest).1);
+
+    // Verifying key address passed in is part of the digest
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+    let vk_address = evm.create(compile_solidity(&vk_solidity));
+    let calldata =
+        generator.encode_calldata_verify_and_store(Some(vk_address.into()), &proof, &instances);
+    assert_eq!(calldata[..4], FN_SIG_VERIFY_AND_STORE_WITH_VK_ADDRESS);
+    evm.call(verifier_address, calldata);
+    for (vk_address, expected) in [(Some(vk_address.into()), true), (None, false)] {
+        let digest = generator.proof_digest(vk_address, &proof, &instances);
+        let calldata = [FN_SIG_IS_VERIFIED.to_vec(), digest.to_vec()].concat();
+        let (_, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![expected as u8]].concat());
+    }
+
+    assert!(matches!(
+        SolidityGenerator::builder(&params, &vk)
+            .num_instances(&num_instances(&instances))
+            .deferred_pairing(true)
+            .verify_and_store(true)
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "verify_and_store",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn verify_proof_with_on_verified_hook() {
+    use revm::primitives::U256;
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    assert!(builder()
+        .build()
+        .unwrap()
+        .render()
+        .unwrap()
+        .contains("\ncontract Halo2Verifier {"));
+    assert!(matches!(
+        builder().on_verified_hook(true).build(),
+        Err(GeneratorError::InvalidOption {
+            option: "on_verified_hook",
+            ..
+        })
+    ));
+
+    let generator = builder()
+        .view(false)
+        .on_verified_hook(true)
+        .build()
+        .unwrap();
+    assert_eq!(generator.suggested_filename(), "Halo2VerifierBase.sol");
+    let base_solidity = generator.render().unwrap();
+    assert!(base_solidity.contains("\nabstract contract Halo2VerifierBase {"));
+
+    // Child contract records instances in storage once the proof is verified
+    let recorder_solidity = format!(
+        "{base_solidity}
+contract Recorder is Halo2VerifierBase {{
+    uint256[] public recorded;
+
+    function _onVerified(uint256[] memory instances) internal override {{
+        recorded = instances;
+    }}
+}}
+"
+    );
+    let mut evm = Evm::default();
+    let recorder_address = evm.deploy_and_link(
+        compile_solidity_unlinked(&recorder_solidity, "Recorder"),
+        &[],
+    );
+    let recorded = |evm: &mut Evm, idx: usize| {
+        let fn_sig = sha3::Keccak256::digest("recorded(uint256)");
+        let calldata = [&fn_sig[..4], &U256::from(idx).to_be_bytes::<0x20>()[..]].concat();
+        evm.try_call(recorder_address, calldata).ok()
+    };
+    assert!(recorded(&mut evm, 0).is_none());
+
+    // Invalid proof reverts without calling the hook
+    let mut other_instances = instances.clone();
+    other_instances[0][0] += Fr::from(1);
+    let calldata = encode_calldata(None, &proof, &other_instances);
+    assert!(evm.try_call(recorder_address, calldata).is_err());
+    assert!(recorded(&mut evm, 0).is_none());
+
+    let calldata = encode_calldata(None, &proof, &instances);
+    let (_, output) = evm.call(recorder_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    let num_instances = instances.iter().map(Vec::len).sum::<usize>();
+    let encoded_instances = &calldata[calldata.len() - num_instances * 0x20..];
+    for (idx, instance) in encoded_instances.chunks(0x20).enumerate() {
+        assert_eq!(recorded(&mut evm, idx).unwrap().1, instance);
+    }
+    assert!(recorded(&mut evm, num_instances).is_none());
+}
+
+#[test]
+fn verify_proof_with_custom_error() {
+    use revm::primitives::ExecutionResult;
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .on_failure(OnFailure::CustomError)
+        .build()
+        .unwrap();
+    let (verifier_solidity, meta) = generator.render_with_meta().unwrap();
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+
+    let calldata = encode_calldata(None, &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Last instance not in scalar field
+    let mut invalid_instance = calldata.clone();
+    let len = invalid_instance.len();
+    invalid_instance[len - 0x20..].fill(0xff);
+    // First EC point not on affine plane
+    let mut invalid_point = calldata.clone();
+    invalid_point[0x64..0x84].fill(0xff);
+    // First evaluation tampered, so only the pairing check fails
+    let mut tampered_eval = calldata;
+    tampered_eval[0x64 + meta.proof_len - meta.num_evals * 0x20 - 0x80 + 0x1f] ^= 1;
+
+    for (calldata, stage) in [
+        (invalid_instance, FailureStage::Instances),
+        (invalid_point, FailureStage::Transcript),
+        (tampered_eval, FailureStage::Pairing),
+    ] {
+        let output = match evm.try_call(verifier_address, calldata) {
+            Err(ExecutionResult::Revert { output, .. }) => output,
+            result => panic!("Expected revert, got {result:?}"),
+        };
+        assert_eq!(output[..4], ERROR_SIG_PROOF_INVALID);
+        let stage_word = [vec![0; 31], vec![stage as u8]].concat();
+        assert_eq!(output[4..], stage_word);
+        assert_eq!(FailureStage::from_stage(stage as u64), Some(stage));
+    }
+}
+
+#[test]
+fn squeeze_n_challenges() {
+    use crate::transcript::{ChallengeEvm, Keccak256Transcript};
+    use halo2_proofs::{
+        halo2curves::ff::{Field, PrimeField},
+        transcript::{EncodedChallenge, Transcript},
+    };
+
+    // Same squeezing as the verifier, which absorbs calldata then squeezes `n` challenges
+    let solidity = r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Squeezer {
+    fallback() external {
+        assembly {
+            let r := 21888242871839275222246405745257275088548364400416034343698204186575808495617
+            let n := calldataload(0)
+            let len := sub(calldatasize(), 0x20)
+            let challenge_mptr := add(0x40, len)
+            calldatacopy(0x00, 0x20, len)
+            let hash := keccak256(0x00, len)
+            mstore(challenge_mptr, mod(hash, r))
+            mstore(0x00, hash)
+            for { let idx := 1 } lt(idx, n) { idx := add(idx, 1) } {
+                mstore8(0x20, 0x01)
+                hash := keccak256(0x00, 0x21)
+                mstore(add(challenge_mptr, mul(idx, 0x20)), mod(hash, r))
+                mstore(0x00, hash)
+            }
+            return(challenge_mptr, mul(n, 0x20))
+        }
+    }
+}
+"#;
+    let mut evm = Evm::default();
+    let squeezer_address = evm.create(compile_solidity(solidity));
+
+    let mut rng = std_rng();
+    // Absorb more than a single word, which would be hashed with an extra 0x01 like a previous hash
+    for (num_scalars, n) in [(2, 1), (3, 3), (5, 8)] {
+        let scalars = (0..num_scalars)
+            .map(|_| Fr::random(&mut rng))
+            .collect::<Vec<_>>();
+        let mut transcript = Keccak256Transcript::<G1Affine, _>::new(Vec::new());
+        let mut expected = Keccak256Transcript::<G1Affine, _>::new(Vec::new());
+        for scalar in scalars.iter() {
+            Transcript::<_, ChallengeEvm<_>>::common_scalar(&mut transcript, *scalar).unwrap();
+            Transcript::<_, ChallengeEvm<_>>::common_scalar(&mut expected, *scalar).unwrap();
+        }
+        let challenges = transcript.squeeze_n_challenges(n);
+        let expected_challenges = (0..n)
+            .map(|_| {
+                Transcript::<_, ChallengeEvm<_>>::squeeze_challenge(&mut expected).get_scalar()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(challenges, expected_challenges);
+
+        // Following squeeze continues from the last hash in both
+        assert_eq!(
+            Transcript::<_, ChallengeEvm<_>>::squeeze_challenge(&mut transcript).get_scalar(),
+            Transcript::<_, ChallengeEvm<_>>::squeeze_challenge(&mut expected).get_scalar(),
+        );
+
+        let calldata = [
+            to_u256_be_bytes(n).to_vec(),
+            scalars
+                .iter()
+                .flat_map(|scalar| fr_to_u256(scalar).to_be_bytes::<0x20>())
+                .collect(),
+        ]
+        .concat();
+        let (_, output) = evm.call(squeezer_address, calldata);
+        let challenges_solidity = output
+            .chunks(0x20)
+            .map(|word| {
+                let mut repr = <Fr as PrimeField>::Repr::default();
+                repr.copy_from_slice(word);
+                repr.reverse();
+                Fr::from_repr(repr).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(challenges, challenges_solidity);
+    }
+    assert!(Keccak256Transcript::<G1Affine, _>::new(Vec::<u8>::new())
+        .squeeze_n_challenges(0)
+        .is_empty());
+}
+
+#[test]
+fn inversions_are_batched() {
+    // Lagrange denominators `x - omega^i` of every instance row, and SHPLONK denominators, are
+    // inverted by `batch_invert` with a single modexp each, so gas doesn't grow by a modexp per
+    // instance.
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    for scheme in [Bdfg21, Gwc19] {
+        let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+            <C as halo2::TestCircuit<Fr>>::min_k(),
+            scheme,
+            None,
+            std_rng(),
+        );
+        let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances));
+        let verifier_solidity = generator.render().unwrap();
+        assert_eq!(
+            verifier_solidity.matches("staticcall(gas(), 0x05").count(),
+            1
+        );
+        assert_eq!(
+            verifier_solidity.matches(":= batch_invert(").count(),
+            1 + scheme.num_batch_inverts()
+        );
+
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+        let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+        assert_eq!(output, [[0; 31], [1]].concat());
+    }
+}
+
+#[test]
+fn render_vk_only() {
+    type C = halo2::fixed_table::FixedTable<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let (verifier_solidity, _) = generator.render_separately().unwrap();
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(&verifier_solidity));
+
+    // Table is changed, so only verifying key needs to be deployed again
+    let (params, updated_vk, instances, proof) =
+        halo2::create_testdata::<C>(k, Bdfg21, None, StdRng::seed_from_u64(1));
+    assert_ne!(vk.fixed_commitments(), updated_vk.fixed_commitments());
+    let generator =
+        SolidityGenerator::new(&params, &updated_vk, Bdfg21, &num_instances(&instances));
+    let vk_solidity = generator.render_vk_only(&vk).unwrap();
+    assert_eq!(vk_solidity, generator.render_separately().unwrap().1);
+
+    let vk_address = evm.create(compile_solidity(&vk_solidity));
+    let calldata = encode_calldata(Some(vk_address.into()), &proof, &instances);
+    let (_, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    type D = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (_, other_vk, _, _) = halo2::create_testdata::<D>(
+        <D as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    assert_eq!(
+        generator.render_vk_only(&other_vk),
+        Err(IncompatibilityReason::NumColumns {
+            kind: "instance",
+            expected: 1,
+            actual: 2
+        })
+    );
+}
+
+#[test]
+fn vk_digest() {
+    let fn_sig = sha3::Keccak256::digest("VERIFYING_KEY_DIGEST()")[..4].to_vec();
+    let mut evm = Evm::default();
+    let mut on_chain_vk_digest = |generator: &SolidityGenerator| {
+        let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+        let (_, output) = evm.call(verifier_address, fn_sig.clone());
+        output
+    };
+
+    type C1 = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C1 as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C1>(k, Bdfg21, None, std_rng());
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let generator = builder().build().unwrap();
+    let mut vk_digest = vk.transcript_repr().to_bytes();
+    vk_digest.reverse();
+    assert_eq!(generator.vk_digest(), vk_digest);
+    assert_eq!(on_chain_vk_digest(&generator), generator.vk_digest());
+
+    type C2 = halo2::fixed_table::FixedTable<Fr>;
+    let k = <C2 as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C2>(k, Bdfg21, None, std_rng());
+    let other_generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    assert_eq!(
+        on_chain_vk_digest(&other_generator),
+        other_generator.vk_digest()
+    );
+    assert_ne!(generator.vk_digest(), other_generator.vk_digest());
+
+    // Verifier with verifying key address in state reads the digest from its runtime code
+    let generator = builder()
+        .vk_address_source(VkAddressSource::Immutable)
+        .build()
+        .unwrap();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let vk_address: [u8; 20] = evm.create(compile_solidity(&vk_solidity)).into();
+    let creation_code = [
+        compile_solidity(verifier_solidity),
+        vec![0; 12],
+        vk_address.to_vec(),
+    ]
+    .concat();
+    let verifier_address = evm.create(creation_code);
+    let (_, output) = evm.call(verifier_address, fn_sig);
+    assert_eq!(output, generator.vk_digest());
+
+    // Verifier with verifying key address in calldata reads the digest from runtime code of the
+    // given one
+    let fn_sig = sha3::Keccak256::digest("VERIFYING_KEY_DIGEST(address)")[..4].to_vec();
+    let generator = builder().build().unwrap();
+    let (verifier_solidity, _) = generator.render_separately().unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let other_vk_address: [u8; 20] = evm
+        .create(compile_solidity(
+            other_generator.render_separately().unwrap().1,
+        ))
+        .into();
+    for (vk_address, vk_digest) in [
+        (vk_address, generator.vk_digest()),
+        (other_vk_address, other_generator.vk_digest()),
+    ] {
+        let calldata = [&fn_sig[..], &[0; 12][..], &vk_address[..]].concat();
+        let (_, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, vk_digest);
+    }
+}
+
+#[test]
+fn verify_vk_consistency() {
+    type C = halo2::fixed_table::FixedTable<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    assert_eq!(generator.verify_vk_consistency(), Ok(()));
+
+    // Verifying key rendered for another table mismatches in fixed commitments
+    let (params, updated_vk, instances, _) =
+        halo2::create_testdata::<C>(k, Bdfg21, None, StdRng::seed_from_u64(1));
+    let updated_generator =
+        SolidityGenerator::new(&params, &updated_vk, Bdfg21, &num_instances(&instances));
+    let (_, updated_vk_solidity) = updated_generator.render_separately().unwrap();
+    assert!(matches!(
+        generator.check_vk_consistency(&updated_vk_solidity),
+        Err(InconsistencyError::Mismatch(_))
+    ));
+
+    let (_, vk_solidity) = generator.render_separately().unwrap();
+    let num_permutation_comms = vk.permutation().commitments().len();
+    let last_permutation_comm = format!("// permutation_comms[{}]", num_permutation_comms - 1);
+    let truncated = vk_solidity
+        .lines()
+        .filter(|line| !line.contains(&last_permutation_comm))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert_eq!(
+        generator.check_vk_consistency(&truncated),
+        Err(InconsistencyError::NumCommitments {
+            kind: "permutation",
+            expected: num_permutation_comms,
+            actual: num_permutation_comms - 1,
+        })
+    );
+
+    let misplaced = vk_solidity.replacen("mstore(0x0000,", "mstore(0x0020,", 1);
+    assert!(matches!(
+        generator.check_vk_consistency(&misplaced),
+        Err(InconsistencyError::Malformed(_))
+    ));
+}
+
+#[test]
+fn verify_from_contract() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let verifier_creation_code = compile_solidity(generator.render().unwrap());
+
+    // Forward to the verifier, which receives calldata encoded by Solidity ABI encoder
+    let caller_solidity = r#"
+pragma solidity ^0.8.0;
+
+interface IVerifier {
+    function verifyProof(bytes calldata proof, uint256[] calldata instances) external returns (bool);
+}
+
+contract Caller {
+    function verifyProof(
+        address verifier,
+        bytes calldata proof,
+        uint256[] calldata instances
+    ) external returns (bool) {
+        return IVerifier(verifier).verifyProof(proof, instances);
+    }
+}
+"#;
+    let caller_creation_code = compile_solidity(caller_solidity);
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(verifier_creation_code);
+    let caller_address = evm.create(caller_creation_code);
+
+    // Calldata of `Caller.verifyProof` is same as the one with verifying key address
+    let calldata = encode_calldata(Some(verifier_address.into()), &proof, &instances);
+    let (_, output) = evm.call(caller_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+}
+
+#[test]
+fn render_deterministically() {
+    type C = halo2::maingate::MainGateWithRange<Bn256>;
+    for scheme in [Bdfg21, Gwc19] {
+        let render = || {
+            let (params, vk, instances, _) = halo2::create_testdata::<C>(
+                <C as halo2::TestCircuit<Fr>>::min_k(),
+                scheme,
+                None,
+                std_rng(),
+            );
+            let generator =
+                SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances));
+            let outputs = [
+                generator.render().unwrap(),
+                generator.render_separately().unwrap().0,
+                generator.render_separately().unwrap().1,
+            ];
+            // Rendering again with the same generator
+            assert_eq!(generator.render().unwrap(), outputs[0]);
+            outputs
+        };
+        // Rendering with verifying key generated again
+        assert_eq!(render(), render());
+    }
+}
+
+#[test]
+fn render_bdfg21_huge() {
+    run_render::<halo2::huge::HugeCircuit<Bn256>>(Bdfg21)
+}
+
+#[test]
+fn render_bdfg21_maingate() {
+    run_render::<halo2::maingate::MainGateWithRange<Bn256>>(Bdfg21)
+}
+
+#[test]
+fn render_gwc19_huge() {
+    run_render::<halo2::huge::HugeCircuit<Bn256>>(Gwc19)
+}
+
+#[test]
+fn render_gwc19_maingate() {
+    run_render::<halo2::maingate::MainGateWithRange<Bn256>>(Gwc19)
+}
+
+#[test]
+fn render_separately_bdfg21_huge() {
+    run_render_separately::<halo2::huge::HugeCircuit<Bn256>>(Bdfg21)
+}
+
+#[test]
+fn render_separately_bdfg21_maingate() {
+    run_render_separately::<halo2::maingate::MainGateWithRange<Bn256>>(Bdfg21)
+}
+
+#[test]
+fn render_separately_gwc19_huge() {
+    run_render_separately::<halo2::huge::HugeCircuit<Bn256>>(Gwc19)
+}
+
+#[test]
+fn render_separately_gwc19_maingate() {
+    run_render_separately::<halo2::maingate::MainGateWithRange<Bn256>>(Gwc19)
+}
+
+#[test]
+fn render_bdfg21_two_instance_columns() {
+    run_render_with::<halo2::two_instance_columns::TwoInstanceColumns<Fr>>(Bdfg21, None)
+}
+
+#[test]
+fn render_gwc19_two_instance_columns() {
+    run_render_with::<halo2::two_instance_columns::TwoInstanceColumns<Fr>>(Gwc19, None)
+}
+
+#[test]
+fn verify_proof_with_two_phase_rlc() {
+    // Challenge squeezed after first phase changes with instances, so does the accumulation
+    run_verify::<halo2::two_phase_rlc::TwoPhaseRlc<Fr>>(|vk, _, instances| {
+        assert_eq!(vk.cs().advice_column_phase(), [0, 1]);
+        assert_eq!(vk.cs().challenge_phase(), [0]);
+        instances[0].swap(0, 1);
+    })
+}
+
+#[test]
+fn verify_proof_with_shuffle() {
+    // Shuffle grand product is opened at both `x` and `omega * x`
+    run_verify::<halo2::shuffle::Shuffle<Fr>>(|vk, proof, _| {
+        assert_eq!(vk.cs().shuffles().len(), 1);
+        assert_eq!(CircuitMetrics::from_vk(vk).num_shuffles, 1);
+        assert_eq!(proof.shuffle_z_commitments.len(), 1);
+        proof.shuffle_z_commitments[0] = proof.permutation_z_commitments[0];
+    })
+}
+
+#[test]
+fn verify_proof_with_dynamic_table() {
+    // Lookup grand product is opened at both `x` and `omega * x`
+    run_verify::<halo2::dynamic_table::DynamicTable<Fr>>(|vk, proof, _| {
+        assert_eq!(vk.cs().lookups().len(), 1);
+        proof.lookup_z_commitments[0] = proof.permutation_z_commitments[0];
+    })
+}
+
+#[test]
+fn verify_proof_with_high_degree_gate() {
+    // Each quotient chunk is scaled by a different power of `x^n` in reconstruction
+    run_verify::<halo2::high_degree::HighDegree<Fr>>(|vk, proof, _| {
+        assert_eq!(vk.cs().degree(), 9);
+        assert_eq!(proof.quotient_commitments.len(), 8);
+        proof.quotient_commitments.swap(3, 4);
+    })
+}
+
+#[test]
+fn verify_proof_with_shared_subexpressions() {
+    type C = halo2::shared_subexpressions::SharedSubexpressions<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    for scheme in [Bdfg21, Gwc19] {
+        let (params, vk, instances, proof) =
+            halo2::create_testdata::<C>(k, scheme, None, std_rng());
+        let calldata = encode_calldata(None, &proof, &instances);
+
+        // Shared subexpressions are computed once instead of by each polynomial
+        let mut evm = Evm::default();
+        let [(solidity, gas_cost), (solidity_without_cse, gas_cost_without_cse)] = [true, false]
+            .map(|cse| {
+                let generator =
+                    SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances))
+                        .with_cse(cse);
+                let solidity = generator.render().unwrap();
+                let verifier_address = evm.create(compile_solidity(&solidity));
+                let (gas_cost, output) = evm.call(verifier_address, calldata.clone());
+                assert_eq!(output, [vec![0; 31], vec![1]].concat());
+                (solidity, gas_cost)
+            });
+        assert!(solidity.len() < solidity_without_cse.len());
+        assert!(gas_cost < gas_cost_without_cse);
+    }
+}
+
+#[test]
+fn verify_proof_with_scratch_reuse() {
+    type C = halo2::many_gates::ManyGates<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    assert_eq!(vk.cs().gates().len(), halo2::many_gates::NUM_GATES as usize);
+    let calldata = encode_calldata(None, &proof, &instances);
+
+    // Each gate's shared subexpression is dead after the gate, so its slot is reused by the next
+    // one, and verifying key is placed right after the smaller scratch memory
+    let mut evm = Evm::default();
+    let [(vk_mptr, gas_cost), (vk_mptr_without_reuse, gas_cost_without_reuse)] =
+        [true, false].map(|scratch_reuse| {
+            let generator = SolidityGenerator::builder(&params, &vk)
+                .num_instances(&num_instances(&instances))
+                .scratch_reuse(scratch_reuse)
+                .build()
+                .unwrap();
+            let solidity = generator.render().unwrap();
+            let vk_mptr = solidity
+                .lines()
+                .find_map(|line| line.split(" VK_MPTR = 0x").nth(1))
+                .map(|hex| usize::from_str_radix(hex.trim_end_matches(';'), 16).unwrap())
+                .unwrap();
+            let verifier_address = evm.create(compile_solidity(&solidity));
+            let (gas_cost, output) = evm.call(verifier_address, calldata.clone());
+            assert_eq!(output, [vec![0; 31], vec![1]].concat());
+            (vk_mptr, gas_cost)
+        });
+    assert!(vk_mptr_without_reuse >= (1 + halo2::many_gates::NUM_GATES as usize) * 0x20);
+    assert!(vk_mptr < vk_mptr_without_reuse);
+    assert!(gas_cost < gas_cost_without_reuse);
+}
+
+#[test]
+fn fold_constants_of_random_expressions() {
+    use crate::codegen::evaluator::fold_constants;
+    use halo2_proofs::{
+        halo2curves::ff::Field,
+        plonk::{Advice, Column, ConstraintSystem, Expression, VirtualCells},
+        poly::Rotation,
+    };
+
+    fn random_expression(
+        meta: &mut VirtualCells<Fr>,
+        advices: &[Column<Advice>; 3],
+        depth: usize,
+        rng: &mut impl RngCore,
+    ) -> Expression<Fr> {
+        let constants = [
+            Fr::ZERO,
+            Fr::ONE,
+            -Fr::ONE,
+            Fr::from(2),
+            Fr::random(&mut *rng),
+        ];
+        let constant = constants[rng.next_u32() as usize % constants.len()];
+        if depth == 0 || rng.next_u32() % 4 == 0 {
+            return if rng.next_u32() % 2 == 0 {
+                Expression::Constant(constant)
+            } else {
+                meta.query_advice(advices[rng.next_u32() as usize % 3], Rotation::cur())
+            };
+        }
+        match rng.next_u32() % 4 {
+            0 => -random_expression(meta, advices, depth - 1, rng),
+            1 => {
+                random_expression(meta, advices, depth - 1, rng)
+                    + random_expression(meta, advices, depth - 1, rng)
+            }
+            2 => {
+                random_expression(meta, advices, depth - 1, rng)
+                    * random_expression(meta, advices, depth - 1, rng)
+            }
+            _ => random_expression(meta, advices, depth - 1, rng) * constant,
+        }
+    }
+
+    let mut rng = std_rng();
+    let mut cs = ConstraintSystem::<Fr>::default();
+    let advices = [(); 3].map(|_| cs.advice_column());
+    cs.create_gate("random", |meta| {
+        let [a, ..] = advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+        let constant = |value: u64| Expression::Constant(Fr::from(value));
+        [
+            constant(2) * constant(3) * a.clone(),
+            (a.clone() * constant(1) + constant(0)) * constant(1),
+            constant(0) * a.clone(),
+            -(-a),
+        ]
+        .into_iter()
+        .chain((0..64).map(|_| random_expression(meta, &advices, 6, &mut rng)))
+        .collect::<Vec<_>>()
+    });
+    let polynomials = cs.gates()[0].polynomials();
+
+    // Constants are pre-reduced and identities are dropped
+    let folded = polynomials.iter().map(fold_constants).collect::<Vec<_>>();
+    assert!(matches!(
+        &folded[0],
+        Expression::Scaled(value, scalar)
+            if matches!(**value, Expression::Advice(_)) && *scalar == Fr::from(6)
+    ));
+    assert!(matches!(folded[1], Expression::Advice(_)));
+    assert!(matches!(folded[2], Expression::Constant(zero) if zero == Fr::ZERO));
+    assert!(matches!(folded[3], Expression::Advice(_)));
+
+    // Folded expressions evaluate to same values as unfolded ones for random assignments
+    for _ in 0..4 {
+        let values = [(); 3].map(|_| Fr::random(&mut rng));
+        let evaluate = |expression: &Expression<Fr>| {
+            expression.evaluate(
+                &|constant| constant,
+                &|_| unreachable!(),
+                &|_| unreachable!(),
+                &|query| values[query.column_index()],
+                &|_| unreachable!(),
+                &|_| unreachable!(),
+                &|value| -value,
+                &|lhs, rhs| lhs + rhs,
+                &|lhs, rhs| lhs * rhs,
+                &|value, scalar| value * scalar,
+            )
+        };
+        for (polynomial, folded) in polynomials.iter().zip(&folded) {
+            assert_eq!(evaluate(folded), evaluate(polynomial));
+        }
+    }
+}
+
+#[test]
+fn verify_proof_with_unblinded_advice() {
+    // Commitment of unblinded column is still bound by transcript and openings
+    run_verify::<halo2::unblinded_advice::UnblindedAdvice<Fr>>(|_, proof, _| {
+        proof.advice_commitments.swap(0, 1);
+    })
+}
+
+#[test]
+fn verify_proof_with_long_rotations() {
+    run_verify::<halo2::long_rotations::LongRotations<Fr>>(|vk, _, instances| {
+        let rotations = vk
+            .cs()
+            .advice_queries()
+            .iter()
+            .map(|(_, rotation)| rotation.0)
+            .collect::<Vec<_>>();
+        assert!(rotations.contains(&-5) && rotations.contains(&-3) && rotations.contains(&7));
+        instances[0].swap(1, 2);
+    })
+}
+
+#[test]
+fn verify_proof_with_many_permutation_chunks() {
+    // Swapping adjacent chunks breaks the consistency between last and first rows of them
+    run_verify::<halo2::many_equalities::ManyEqualities<Fr>>(|vk, proof, _| {
+        assert_eq!(vk.cs().degree(), 4);
+        assert_eq!(CircuitMetrics::from_vk(vk).num_permutation_chunks, 8);
+        assert_eq!(proof.permutation_z_commitments.len(), 8);
+        proof.permutation_z_commitments.swap(3, 4);
+    })
+}
+
+#[test]
+fn verify_proof_with_rotated_instance() {
+    // Only the first instance is copy constrained, the others are checked by the gate
+    run_verify::<halo2::rotated_instance::RotatedInstance<Fr>>(|_, _, instances| {
+        instances[0].swap(3, 4);
+    })
+}
+
+#[test]
+fn verify_proof_with_uncompressed_selectors() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    for scheme in [Bdfg21, Gwc19] {
+        let mut num_fixed_columns = Vec::new();
+        for compress_selectors in [true, false] {
+            let (params, vk, instances, proof) = halo2::create_testdata_with_compress_selectors::<C>(
+                k,
+                scheme,
+                None,
+                compress_selectors,
+                std_rng(),
+            );
+            let generator =
+                SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances));
+            num_fixed_columns.push(generator.num_fixed_columns());
+
+            let mut evm = Evm::default();
+            let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+            let calldata = encode_calldata(None, &proof, &instances);
+            let (_, output) = evm.call(verifier_address, calldata);
+            assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+            let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+            let verifier_address = evm.create(compile_solidity(verifier_solidity));
+            let vk_address = evm.create(compile_solidity(vk_solidity));
+            let calldata = encode_calldata(Some(vk_address.into()), &proof, &instances);
+            let (_, output) = evm.call(verifier_address, calldata);
+            assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+            // Selector layout is recovered from serialized verifying key
+            let restored = SolidityGenerator::from_vk_bytes::<C>(
+                &params,
+                &vk.to_bytes(SerdeFormat::RawBytes),
+                SerdeFormat::RawBytes,
+                #[cfg(feature = "circuit-params")]
+                (),
+                scheme,
+                &num_instances(&instances),
+            )
+            .unwrap();
+            assert_eq!(restored.render().unwrap(), generator.render().unwrap());
+        }
+        // 10 fixed columns and 20 selectors each converted into a fixed column
+        assert_eq!(num_fixed_columns[1], 30);
+        assert!(num_fixed_columns[0] < num_fixed_columns[1]);
+    }
+}
+
+#[test]
+fn verify_proof_of_many_circuits() {
+    fn run<C: halo2::TestCircuit<Fr>>(scheme: BatchOpenScheme) {
+        let k = C::min_k();
+        let (params, vk, instances, proof) =
+            halo2::create_testdata_multi::<C>(k, scheme, 2, std_rng());
+        let generator = SolidityGenerator::builder(&params, &vk)
+            .scheme(scheme)
+            .num_instances(&num_instances(&instances[0]))
+            .num_circuits(2)
+            .build()
+            .unwrap();
+        let instances = instances.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+        let calldata = generator.encode_calldata_multi(None, &proof, &instances);
+        assert_eq!(calldata, encode_calldata(None, &proof, &instances.concat()));
+        let (_, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+        let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+        let verifier_address = evm.create(compile_solidity(verifier_solidity));
+        let vk_address = Some(evm.create(compile_solidity(vk_solidity)).into());
+        let calldata = generator.encode_calldata_multi(vk_address, &proof, &instances);
+        let (_, output) = evm.call(verifier_address, calldata);
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+        // Instances are bound to their own circuit
+        let swapped_instances = [instances[1], instances[0]];
+        let calldata = generator.encode_calldata_multi(vk_address, &proof, &swapped_instances);
+        evm.assert_revert(verifier_address, calldata, None);
+
+        // Instances of only the first circuit mismatch the length
+        let calldata = encode_calldata(vk_address, &proof, instances[0]);
+        evm.assert_revert(verifier_address, calldata, None);
+    }
+
+    for scheme in [Bdfg21, Gwc19] {
+        run::<halo2::huge::HugeCircuit<Bn256>>(scheme);
+        run::<halo2::two_phase_rlc::TwoPhaseRlc<Fr>>(scheme);
+    }
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    assert!(matches!(
+        builder().num_circuits(0).build(),
+        Err(GeneratorError::InvalidOption {
+            option: "num_circuits",
+            ..
+        })
+    ));
+    assert!(matches!(
+        builder()
+            .num_circuits(2)
+            .packed_instances(&vec![64; instances.concat().len()])
+            .build(),
+        Err(GeneratorError::InvalidOption {
+            option: "num_circuits",
+            ..
+        })
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Transaction reverts")]
+fn render_bdfg21_huge_with_mismatched_acc_encoding() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        acc_encoding,
+        std_rng(),
+    );
+
+    // Limbs are recomposed with wrong number of bits, so the accumulator is not on curve
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .acc_encoding(AccumulatorEncoding::new(0, 4, 64).into())
+        .build()
+        .unwrap();
+    let verifier_creation_code = compile_solidity(generator.render().unwrap());
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(verifier_creation_code);
+    evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+}
+
+#[test]
+fn render_bdfg21_huge_with_accumulator_indices() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        acc_encoding,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+    let indices = (0..16).map(|row| (0, row)).collect::<Vec<_>>();
+
+    let mut evm = Evm::default();
+    let calldata = encode_calldata(None, &proof, &instances);
+    let verifier_solidity = builder()
+        .accumulator_indices(&indices, 68)
+        .build()
+        .unwrap()
+        .render()
+        .unwrap();
+    assert!(verifier_solidity.contains("let lhs_x := calldataload(add(INSTANCE_CPTR, 0x0))"));
+    assert!(verifier_solidity
+        .contains("rhs_y := add(rhs_y, shl(204, calldataload(add(INSTANCE_CPTR, 0x1e0))))"));
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let (_, output) = evm.call(verifier_address, calldata.clone());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Limbs of x and y of lhs are swapped, so the accumulator is not on curve
+    let swapped = [&indices[4..8], &indices[..4], &indices[8..]].concat();
+    let verifier_solidity = builder()
+        .accumulator_indices(&swapped, 68)
+        .build()
+        .unwrap()
+        .render()
+        .unwrap();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    evm.assert_revert(verifier_address, calldata, None);
+}
+
+#[test]
+fn render_bdfg21_huge_aggregating_two_proofs() {
+    use crate::test::halo2::TestCircuit;
+    use halo2_proofs::{
+        halo2curves::ff::Field,
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    };
+
+    type Inner = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    type Outer = halo2::huge::HugeCircuit<Bn256>;
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68);
+    let mut rng = std_rng();
+
+    // Inner and outer proofs share the same setup, so the accumulator of inner proofs can be
+    // checked by the pairing of outer proof
+    let params = ParamsKZG::<Bn256>::setup(Outer::min_k(), &mut rng);
+    let mut inner_params = params.clone();
+    inner_params.downsize(Inner::min_k());
+    let inner_circuits = (0..2).map(|_| Inner::new(None, &mut rng)).collect();
+    let (inner_vk, inner_testdata) =
+        halo2::create_testdata_with_params(&inner_params, inner_circuits, &mut rng);
+
+    // Inner verifier returns pairing lhs and rhs of each proof instead of performing pairing
+    let mut evm = Evm::default();
+    let inner_num_instances = num_instances(&inner_testdata[0].0);
+    let inner_generator = SolidityGenerator::builder(&inner_params, &inner_vk)
+        .num_instances(&inner_num_instances)
+        .deferred_pairing(true)
+        .build()
+        .unwrap();
+    let inner_verifier_address = evm.create(compile_solidity(inner_generator.render().unwrap()));
+    let pairings = inner_testdata
+        .iter()
+        .map(|(instances, proof)| {
+            let calldata = encode_calldata(None, proof, instances);
+            evm.call(inner_verifier_address, calldata).1
+        })
+        .collect::<Vec<_>>();
+
+    // Aggregate both pairings into the accumulator exposed by outer circuit, folded by a
+    // challenge which an aggregation circuit would squeeze from its transcript
+    let challenge = Fr::random(&mut rng);
+    let mut aggregate = |pairings: &[Vec<u8>]| {
+        let limbs = halo2::fold_pairings_into_limbs(pairings, challenge, acc_encoding);
+        let outer_circuit = Outer::from_instances(limbs);
+        let (vk, mut testdata) =
+            halo2::create_testdata_with_params(&params, vec![outer_circuit], &mut rng);
+        let (instances, proof) = testdata.pop().unwrap();
+        (vk, instances, proof)
+    };
+    let (vk, instances, proof) = aggregate(&pairings);
+
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .acc_encoding(acc_encoding.into())
+        .build()
+        .unwrap();
+    let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+    let (gas_cost, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!(
+        "Gas cost of verifying outer proof aggregating {} proofs: {gas_cost}",
+        pairings.len()
+    );
+
+    // Outer proof carrying accumulator of an inner pairing with lhs and rhs swapped is rejected
+    let mut swapped = pairings.clone();
+    swapped[1] = [&pairings[1][0x40..], &pairings[1][..0x40]].concat();
+    let (_, instances, proof) = aggregate(&swapped);
+    evm.assert_revert(
+        verifier_address,
+        encode_calldata(None, &proof, &instances),
+        None,
+    );
+}
+
+#[test]
+fn render_for_aggregation() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        acc_encoding,
+        std_rng(),
+    );
+    let num_instances = num_instances(&instances);
+    let indices = (0..16).map(|row| (0, row)).collect::<Vec<_>>();
+
+    // Limbs of 68 bits are chosen for 4 limbs like `snark_verifier`
+    let generator =
+        SolidityGenerator::for_aggregation(&params, &vk, Bdfg21, &num_instances, &indices);
+    let verifier_solidity = generator.render().unwrap();
+    let expected = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances)
+        .accumulator_indices(&indices, 68)
+        .build()
+        .unwrap();
+    assert_eq!(verifier_solidity, expected.render().unwrap());
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(verifier_solidity));
+    let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+}
+
+#[test]
+fn invalid_accumulator_indices() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        acc_encoding,
+        std_rng(),
+    );
+    let builder = || {
+        let mut builder = SolidityGenerator::builder(&params, &vk);
+        builder.num_instances(&num_instances(&instances));
+        builder
+    };
+
+    for indices in [
+        vec![],
+        vec![(0, 0); 15],
+        vec![(0, 16); 16],
+        vec![(1, 0); 16],
+    ] {
+        assert!(matches!(
+            builder().accumulator_indices(&indices, 68).build(),
+            Err(GeneratorError::InvalidOption {
+                option: "accumulator_indices",
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Transaction reverts")]
+fn render_bdfg21_huge_with_gwc19_proof() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Gwc19,
+        acc_encoding,
+        std_rng(),
+    );
+
+    // Proof created by `ProverGWC` has different length than the one expected by BDFG21 verifier
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .num_instances(&num_instances(&instances))
+        .acc_encoding(acc_encoding)
+        .build()
+        .unwrap();
+    let verifier_creation_code = compile_solidity(generator.render().unwrap());
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(verifier_creation_code);
+    evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+}
+
+#[test]
+fn num_columns() {
+    type C = halo2::no_instance::NoInstance<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    assert_eq!(generator.num_advice_columns(), 3);
+    // The only selector is compressed into a fixed column
+    assert_eq!(generator.num_fixed_columns(), 1);
+}
+
+#[test]
+fn render_bdfg21_no_instance() {
+    run_render_with::<halo2::no_instance::NoInstance<Fr>>(Bdfg21, None)
+}
+
+#[test]
+fn render_gwc19_no_instance() {
+    run_render_with::<halo2::no_instance::NoInstance<Fr>>(Gwc19, None)
+}
+
+#[test]
+fn encode_calldata_without_instances() {
+    let proof = [0xab; 0x40];
+    let calldata = encode_calldata(None, &proof, &[] as &[Vec<Fr>]);
+    // function signature, offset of proof, offset of instances, proof and empty instances
+    assert_eq!(calldata.len(), 4 + 0x20 * 2 + (0x20 + 0x40) + 0x20);
+    assert_eq!(calldata[calldata.len() - 0x20..], [0; 0x20]);
+}
+
+#[test]
+fn encode_calldata_column_by_column() {
+    let proof = [0xab; 0x40];
+    let instances = vec![vec![Fr::from(1), Fr::from(2)], vec![Fr::from(3)]];
+    let calldata = encode_calldata(None, &proof, &instances);
+    // instances are appended after proof and their length, flattened column by column
+    let instances_start = 4 + 0x20 * 2 + (0x20 + 0x40);
+    assert_eq!(calldata[instances_start + 0x1f], 3);
+    for (idx, value) in [1, 2, 3].into_iter().enumerate() {
+        let start = instances_start + 0x20 * (idx + 1);
+        assert_eq!(calldata[start..start + 0x1f], [0; 0x1f]);
+        assert_eq!(calldata[start + 0x1f], value);
+    }
+    assert_eq!(calldata.len(), instances_start + 0x20 * 4);
+}
+
+#[test]
+fn encode_calldata_from_words() {
+    use halo2_proofs::halo2curves::ff::PrimeField;
+
+    let proof = [0xab; 0x40];
+    let instances = vec![vec![Fr::from(1), -Fr::from(2)], vec![Fr::from(3)]];
+    let words = instances
+        .iter()
+        .flatten()
+        .map(|instance| fr_repr_to_word(instance.to_repr()))
+        .collect::<Vec<_>>();
+    for vk_address in [None, Some([0xcd; 20])] {
+        assert_eq!(
+            encode_calldata_words(vk_address, &proof, &words),
+            encode_calldata(vk_address, &proof, &instances)
+        );
+        let instances_hash = hash_instances(&instances);
+        assert_eq!(
+            encode_calldata_hashed_words(vk_address, &proof, instances_hash),
+            encode_calldata_hashed(vk_address, &proof, instances_hash)
+        );
+    }
+}
+
+#[test]
+fn encode_calldata_typed_payload() {
+    let proof = [0xab; 0x40];
+    let instances = vec![vec![Fr::from(1), Fr::from(2)], vec![Fr::from(3)]];
+    for vk_address in [None, Some([0xcd; 20])] {
+        let payload = encode_calldata_typed(vk_address, &proof, &instances);
+        assert_eq!(payload.vk_address, vk_address);
+        assert_eq!(payload.proof_bytes, proof);
+        assert_eq!(payload.instance_scalars.len(), 3);
+        assert_eq!(payload.instance_scalars[2][..0x1f], [0; 0x1f]);
+        assert_eq!(payload.instance_scalars[2][0x1f], 3);
+        assert_eq!(
+            payload.to_bytes(),
+            encode_calldata(vk_address, &proof, &instances)
+        );
+    }
+}
+
+#[test]
+fn decode_and_encode_proof() {
+    type C = halo2::huge::HugeCircuit<Bn256>;
+    let k = <C as halo2::TestCircuit<Fr>>::min_k();
+    for scheme in [Bdfg21, Gwc19] {
+        let (params, vk, instances, proof) =
+            halo2::create_testdata::<C>(k, scheme, None, std_rng());
+        let cs = vk.cs();
+        let decoded = decode_proof(&proof, &vk).unwrap();
+        assert_eq!(decoded.advice_commitments.len(), cs.num_advice_columns());
+        assert_eq!(
+            decoded.lookup_permuted_commitments.len(),
+            cs.lookups().len()
+        );
+        assert_eq!(decoded.lookup_z_commitments.len(), cs.lookups().len());
+        assert_eq!(decoded.shuffle_z_commitments.len(), cs.shuffles().len());
+        assert_eq!(decoded.quotient_commitments.len(), cs.degree() - 1);
+        if scheme == Bdfg21 {
+            assert_eq!(decoded.opening_proof.commitments.len(), 2);
+        }
+        assert_eq!(encode_proof(&decoded), proof);
+
+        // Replace a commitment by another valid point
+        let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances));
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+        let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+        let mut mutated = decoded.clone();
+        mutated.advice_commitments[1] = decoded.random_commitment;
+        let calldata = encode_calldata(None, &encode_proof(&mutated), &instances);
+        evm.assert_revert(verifier_address, calldata, None);
+    }
+
+    let (_, vk, _, proof) = halo2::create_testdata::<C>(k, Bdfg21, None, std_rng());
+    assert!(matches!(
+        decode_proof(&proof[..proof.len() - 0x20], &vk),
+        Err(ProofError::Malformed(_))
+    ));
+    let mut invalid = proof.clone();
+    invalid[0x40..0x60].fill(0xff);
+    assert_eq!(
+        decode_proof(&invalid, &vk),
+        Err(ProofError::InvalidPoint(0x40))
+    );
+}
+
+#[test]
+fn verify_proof_with_mutated_payload() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, proof) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Bdfg21,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Bdfg21, &num_instances(&instances));
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+
+    let payload = encode_calldata_typed(None, &proof, &instances);
+    let (_, output) = evm.call(verifier_address, payload.to_bytes());
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+    // Flip a bit of an instance
+    let mut mutated = payload.clone();
+    mutated.instance_scalars[0][0x1f] ^= 1;
+    evm.assert_revert(verifier_address, mutated.to_bytes(), None);
+
+    // Drop an instance
+    let mut mutated = payload.clone();
+    mutated.instance_scalars.pop();
+    evm.assert_revert(verifier_address, mutated.to_bytes(), None);
+
+    // Flip a bit of proof
+    let mut mutated = payload;
+    mutated.proof_bytes[0x1f] ^= 1;
+    evm.assert_revert(verifier_address, mutated.to_bytes(), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn generator_json_round_trip() {
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Gwc19,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::builder(&params, &vk)
+        .scheme(Gwc19)
+        .num_instances(&num_instances(&instances))
+        .on_failure(OnFailure::CustomError)
+        .natspec(true)
+        .batch_verify(true)
+        .vk_codehash_check(true)
+        .contract_name("Verifier")
+        .build()
+        .unwrap();
+
+    let json = generator.to_json();
+    let restored = SolidityGenerator::from_json::<C>(
+        &params,
+        &json,
+        #[cfg(feature = "circuit-params")]
+        (),
+    )
+    .unwrap();
+    assert_eq!(restored.to_json(), json);
+    assert_eq!(restored.render().unwrap(), generator.render().unwrap());
+    assert_eq!(
+        restored.render_separately().unwrap(),
+        generator.render_separately().unwrap()
+    );
+
+    assert!(matches!(
+        SolidityGenerator::from_json::<C>(
+            &params,
+            "{}",
+            #[cfg(feature = "circuit-params")]
+            (),
+        ),
+        Err(GeneratorError::InvalidJson(_))
+    ));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_json_output() {
+    use crate::cli::{run, Args};
+
+    type C = halo2::two_instance_columns::TwoInstanceColumns<Fr>;
+    let (params, vk, instances, _) = halo2::create_testdata::<C>(
+        <C as halo2::TestCircuit<Fr>>::min_k(),
+        Gwc19,
+        None,
+        std_rng(),
+    );
+    let generator = SolidityGenerator::new(&params, &vk, Gwc19, &num_instances(&instances));
+
+    let dir = std::env::temp_dir().join(format!("halo2_cli_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let params_path = dir.join("params.bin");
+    let vk_path = dir.join("vk.bin");
+    VerifierParams::from(&params)
+        .write(&mut File::create(&params_path).unwrap())
+        .unwrap();
+    vk.write(&mut File::create(&vk_path).unwrap(), SerdeFormat::RawBytes)
+        .unwrap();
+
+    let mut args = Args {
+        params: params_path,
+        vk: vk_path,
+        scheme: Gwc19,
+        num_instances: num_instances(&instances),
+        json: false,
+    };
+    let output = |args: &Args| {
+        run::<C>(
+            args,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+        .unwrap()
+    };
+    assert_eq!(output(&args), generator.render().unwrap());
+
+    args.json = true;
+    let output: serde_json::Value = serde_json::from_str(&output(&args)).unwrap();
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    assert_eq!(output["verifier"], verifier_solidity);
+    assert_eq!(output["vk"], vk_solidity);
+    assert_eq!(output["gas_estimate"], generator.estimate_gas_statically());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn run_render<C: halo2::TestCircuit<Fr>>(scheme: BatchOpenScheme) {
+    run_render_with::<C>(scheme, AccumulatorEncoding::new(0, 4, 68).into())
+}
+
+fn run_render_with<C: halo2::TestCircuit<Fr>>(
+    scheme: BatchOpenScheme,
+    acc_encoding: Option<AccumulatorEncoding>,
+) {
+    let (params, vk, instances, proof) =
+        halo2::create_testdata::<C>(C::min_k(), scheme, acc_encoding, std_rng());
+
+    let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances))
+        .set_acc_encoding(acc_encoding);
+    let verifier_solidity = generator.render().unwrap();
+    let verifier_creation_code = compile_solidity(verifier_solidity);
+    let verifier_creation_code_size = verifier_creation_code.len();
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(verifier_creation_code);
+    let verifier_runtime_code_size = evm.code_size(verifier_address);
+
+    println!("Verifier creation code size: {verifier_creation_code_size}");
+    println!("Verifier runtime code size: {verifier_runtime_code_size}");
+
+    let (gas_cost, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    println!("Gas cost: {gas_cost}");
+}
+
+/// Verify proof of `C` by verifier rendered with each batch open scheme, then assert it rejects
+/// the decoded proof and instances after `tamper`, which also checks the shape of the circuit by
+/// its verifying key.
+fn run_verify<C: halo2::TestCircuit<Fr>>(
+    tamper: impl Fn(&VerifyingKey<G1Affine>, &mut PlonkProof, &mut [Vec<Fr>]),
+) {
+    for scheme in [Bdfg21, Gwc19] {
+        let (params, vk, instances, proof) =
+            halo2::create_testdata::<C>(C::min_k(), scheme, None, std_rng());
+
+        let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances));
+        let mut evm = Evm::default();
+        let verifier_address = evm.create(compile_solidity(generator.render().unwrap()));
+        let (_, output) = evm.call(verifier_address, encode_calldata(None, &proof, &instances));
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+
+        let mut tampered_proof = decode_proof(&proof, &vk).unwrap();
+        let mut tampered_instances = instances;
+        tamper(&vk, &mut tampered_proof, &mut tampered_instances);
+        let calldata = encode_calldata(None, &encode_proof(&tampered_proof), &tampered_instances);
+        evm.assert_revert(verifier_address, calldata, None);
+    }
+}
+
+fn run_render_separately<C: halo2::TestCircuit<Fr>>(scheme: BatchOpenScheme) {
+    let acc_encoding = AccumulatorEncoding::new(0, 4, 68).into();
+    let (params, vk, instances, _) =
+        halo2::create_testdata::<C>(C::min_k(), scheme, acc_encoding, std_rng());
+
+    let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances))
+        .set_acc_encoding(acc_encoding);
+    let (verifier_solidity, _vk_solidity) = generator.render_separately().unwrap();
+    let verifier_creation_code = compile_solidity(&verifier_solidity);
+    let verifier_creation_code_size = verifier_creation_code.len();
+
+    let mut evm = Evm::default();
+    let verifier_address = evm.create(verifier_creation_code);
+    let verifier_runtime_code_size = evm.code_size(verifier_address);
+
+    println!("Verifier creation code size: {verifier_creation_code_size}");
+    println!("Verifier runtime code size: {verifier_runtime_code_size}");
+
+    let deployed_verifier_solidity = verifier_solidity;
+
+    for k in C::min_k()..C::min_k() + 4 {
+        let (params, vk, instances, proof) =
+            halo2::create_testdata::<C>(k, scheme, acc_encoding, std_rng());
+        let generator = SolidityGenerator::new(&params, &vk, scheme, &num_instances(&instances))
+            .set_acc_encoding(acc_encoding);
+
+        let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+        assert_eq!(deployed_verifier_solidity, verifier_solidity);
+
+        let vk_creation_code = compile_solidity(&vk_solidity);
+        let vk_address = evm.create(vk_creation_code);
+
+        let (gas_cost, output) = evm.call(
+            verifier_address,
+            encode_calldata(Some(vk_address.into()), &proof, &instances),
+        );
+        assert_eq!(output, [vec![0; 31], vec![1]].concat());
+        println!("Gas cost: {gas_cost}");
+    }
+}
+
+fn num_instances(instances: &[Vec<Fr>]) -> Vec<usize> {
+    instances.iter().map(Vec::len).collect()
+}
+
+fn std_rng() -> impl RngCore + Clone {
+    StdRng::seed_from_u64(0)
+}
+
+#[allow(dead_code)]
+fn save_generated(verifier: &str, vk: Option<&str>) {
+    const DIR_GENERATED: &str = "./target/generated";
+
+    std::fs::create_dir_all(DIR_GENERATED).unwrap();
+    File::create(format!("{DIR_GENERATED}/Halo2Verifier.sol"))
+        .unwrap()
+        .write_all(verifier.as_bytes())
+        .unwrap();
+    if let Some(vk) = vk {
+        File::create(format!("{DIR_GENERATED}/Halo2VerifyingKey.sol"))
+            .unwrap()
+            .write_all(vk.as_bytes())
+            .unwrap();
+    }
+}
+
+mod halo2 {
+    use crate::{
+        codegen::AccumulatorEncoding,
+        transcript::Keccak256Transcript,
+        BatchOpenScheme::{self, Bdfg21, Gwc19},
+    };
+    use halo2_proofs::{
+        arithmetic::CurveAffine,
+        halo2curves::{
+            bn256,
+            ff::{Field, PrimeField},
+            group::{prime::PrimeCurveAffine, Curve, Group},
+            pairing::{MillerLoopResult, MultiMillerLoop},
+        },
+        plonk::{
+            create_proof, keygen_pk, keygen_vk, keygen_vk_custom, verify_proof, Circuit,
+            VerifyingKey,
+        },
+        poly::kzg::{
+            commitment::ParamsKZG,
+            multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+        transcript::TranscriptWriterBuffer,
+    };
+    use itertools::Itertools;
+    use rand::RngCore;
+    use ruint::aliases::U256;
+    use std::{borrow::Borrow, iter};
+
+    pub trait TestCircuit<F: Field>: Circuit<F> {
+        fn min_k() -> u32;
+
+        fn new(acc_encoding: Option<AccumulatorEncoding>, rng: impl RngCore) -> Self;
+
+        fn instances(&self) -> Vec<Vec<F>>;
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn create_testdata<C: TestCircuit<bn256::Fr>>(
+        k: u32,
+        scheme: BatchOpenScheme,
+        acc_encoding: Option<AccumulatorEncoding>,
+        mut rng: impl RngCore + Clone,
+    ) -> (
+        ParamsKZG<bn256::Bn256>,
+        VerifyingKey<bn256::G1Affine>,
+        Vec<Vec<bn256::Fr>>,
+        Vec<u8>,
+    ) {
+        create_testdata_with_compress_selectors::<C>(k, scheme, acc_encoding, true, rng)
+    }
+
+    /// Create proof same as [`create_testdata`], but with selectors converted into fixed columns
+    /// one by one instead of compressed when `compress_selectors` is `false`.
+    #[allow(clippy::type_complexity)]
+    pub fn create_testdata_with_compress_selectors<C: TestCircuit<bn256::Fr>>(
+        k: u32,
+        scheme: BatchOpenScheme,
+        acc_encoding: Option<AccumulatorEncoding>,
+        compress_selectors: bool,
+        mut rng: impl RngCore + Clone,
+    ) -> (
+        ParamsKZG<bn256::Bn256>,
+        VerifyingKey<bn256::G1Affine>,
+        Vec<Vec<bn256::Fr>>,
+        Vec<u8>,
+    ) {
+        let compress = compress_selectors;
+        match scheme {
+            Bdfg21 => create_testdata_inner!(
+                ProverSHPLONK<_>,
+                VerifierSHPLONK<_>,
+                k,
+                acc_encoding,
+                compress,
+                rng
+            ),
+            Gwc19 => {
+                create_testdata_inner!(ProverGWC<_>, VerifierGWC<_>, k, acc_encoding, compress, rng)
+            }
+        }
+    }
+
+    macro_rules! create_testdata_inner {
+        ($p:ty, $v:ty, $k:ident, $acc_encoding:ident, $compress:ident, $rng:ident) => {{
+            let circuit = C::new($acc_encoding, $rng.clone());
+            let instances = circuit.instances();
+            let instance_slices = instances.iter().map(Vec::as_slice).collect_vec();
+
+            let params = ParamsKZG::<bn256::Bn256>::setup($k, &mut $rng);
+            let vk = keygen_vk_custom(&params, &circuit, $compress).unwrap();
+            let pk = keygen_pk(&params, vk.clone(), &circuit).unwrap();
+
+            let proof = {
+                let mut transcript = Keccak256Transcript::new(Vec::new());
+                create_proof::<_, $p, _, _, _, _>(
+                    &params,
+                    &pk,
+                    &[circuit],
+                    &[instance_slices.as_slice()],
+                    &mut $rng,
+                    &mut transcript,
+                )
+                .unwrap();
+                transcript.finalize()
+            };
+
+            let result = {
+                let mut transcript = Keccak256Transcript::from_proof(&proof);
+                verify_proof::<_, $v, _, _, SingleStrategy<_>>(
+                    &params,
+                    pk.get_vk(),
+                    SingleStrategy::new(&params),
+                    &[instance_slices.as_slice()],
+                    &mut transcript,
+                )
+            };
+            assert!(result.is_ok());
+
+            (params, vk, instances, proof)
+        }};
+    }
+
+    use create_testdata_inner;
+
+    /// Create proofs of many circuits of type `C` with the same verifying key, by `Bdfg21`.
+    #[allow(clippy::type_complexity)]
+    pub fn create_testdata_batch<C: TestCircuit<bn256::Fr>>(
+        k: u32,
+        num_proofs: usize,
+        mut rng: impl RngCore,
+    ) -> (
+        ParamsKZG<bn256::Bn256>,
+        VerifyingKey<bn256::G1Affine>,
+        Vec<(Vec<Vec<bn256::Fr>>, Vec<u8>)>,
+    ) {
+        let circuits = iter::repeat_with(|| C::new(None, &mut rng))
+            .take(num_proofs)
+            .collect_vec();
+
+        let params = ParamsKZG::<bn256::Bn256>::setup(k, &mut rng);
+        let (vk, testdata) = create_testdata_with_params(&params, circuits, rng);
+
+        (params, vk, testdata)
+    }
+
+    /// Create proofs of `circuits` of type `C` with the same verifying key by `Bdfg21`, with
+    /// given `params`, so proofs of different circuits can share the same setup.
+    #[allow(clippy::type_complexity)]
+    pub fn create_testdata_with_params<C: TestCircuit<bn256::Fr>>(
+        params: &ParamsKZG<bn256::Bn256>,
+        circuits: Vec<C>,
+        mut rng: impl RngCore,
+    ) -> (
+        VerifyingKey<bn256::G1Affine>,
+        Vec<(Vec<Vec<bn256::Fr>>, Vec<u8>)>,
+    ) {
+        let vk = keygen_vk(params, &circuits[0]).unwrap();
+        let pk = keygen_pk(params, vk.clone(), &circuits[0]).unwrap();
+
+        let testdata = circuits
+            .into_iter()
+            .map(|circuit| {
+                let instances = circuit.instances();
+                let instance_slices = instances.iter().map(Vec::as_slice).collect_vec();
+                let mut transcript = Keccak256Transcript::new(Vec::new());
+                create_proof::<_, ProverSHPLONK<_>, _, _, _, _>(
+                    params,
+                    &pk,
+                    &[circuit],
+                    &[instance_slices.as_slice()],
+                    &mut rng,
+                    &mut transcript,
+                )
+                .unwrap();
+                (instances, transcript.finalize())
+            })
+            .collect();
+
+        (vk, testdata)
+    }
+
+    /// Create one proof of `num_circuits` circuits of type `C` with the same verifying key, each
+    /// with its own instances.
+    #[allow(clippy::type_complexity)]
+    pub fn create_testdata_multi<C: TestCircuit<bn256::Fr>>(
+        k: u32,
+        scheme: BatchOpenScheme,
+        num_circuits: usize,
+        mut rng: impl RngCore,
+    ) -> (
+        ParamsKZG<bn256::Bn256>,
+        VerifyingKey<bn256::G1Affine>,
+        Vec<Vec<Vec<bn256::Fr>>>,
+        Vec<u8>,
+    ) {
+        let circuits = iter::repeat_with(|| C::new(None, &mut rng))
+            .take(num_circuits)
+            .collect_vec();
+        let instances = circuits.iter().map(C::instances).collect_vec();
+        let instance_slices = instances
+            .iter()
+            .map(|instances| instances.iter().map(Vec::as_slice).collect_vec())
+            .collect_vec();
+        let instance_slices = instance_slices.iter().map(Vec::as_slice).collect_vec();
+
+        let params = ParamsKZG::<bn256::Bn256>::setup(k, &mut rng);
+        let vk = keygen_vk(&params, &circuits[0]).unwrap();
+        let pk = keygen_pk(&params, vk.clone(), &circuits[0]).unwrap();
+
+        let proof = {
+            let mut transcript = Keccak256Transcript::new(Vec::new());
+            match scheme {
+                Bdfg21 => create_proof::<_, ProverSHPLONK<_>, _, _, _, _>(
+                    &params,
+                    &pk,
+                    &circuits,
+                    &instance_slices,
+                    &mut rng,
+                    &mut transcript,
+                ),
+                Gwc19 => create_proof::<_, ProverGWC<_>, _, _, _, _>(
+                    &params,
+                    &pk,
+                    &circuits,
+                    &instance_slices,
+                    &mut rng,
+                    &mut transcript,
+                ),
+            }
+            .unwrap();
+            transcript.finalize()
+        };
+
+        let result = {
+            let mut transcript = Keccak256Transcript::from_proof(&proof);
+            let strategy = SingleStrategy::new(&params);
+            match scheme {
+                Bdfg21 => verify_proof::<_, VerifierSHPLONK<_>, _, _, SingleStrategy<_>>(
+                    &params,
+                    pk.get_vk(),
+                    strategy,
+                    &instance_slices,
+                    &mut transcript,
+                ),
+                Gwc19 => verify_proof::<_, VerifierGWC<_>, _, _, SingleStrategy<_>>(
+                    &params,
+                    pk.get_vk(),
+                    strategy,
+                    &instance_slices,
+                    &mut transcript,
+                ),
+            }
+        };
+        assert!(result.is_ok());
+
+        (params, vk, instances, proof)
+    }
+
+    fn random_accumulator_limbs<M>(
         acc_encoding: AccumulatorEncoding,
         mut rng: impl RngCore,
     ) -> Vec<M::Fr>
@@ -268,377 +4228,1869 @@ mod halo2 {
         let rhs_scalar = lhs_scalar * s.invert().unwrap();
         let [lhs, rhs] = [lhs_scalar, rhs_scalar].map(|scalar| (g1 * scalar).to_affine());
 
-        assert!(bool::from(
-            M::multi_miller_loop(&[(&lhs, &g2.into()), (&rhs, &neg_s_g2.into())])
-                .final_exponentiation()
-                .is_identity()
-        ));
+        assert!(bool::from(
+            M::multi_miller_loop(&[(&lhs, &g2.into()), (&rhs, &neg_s_g2.into())])
+                .final_exponentiation()
+                .is_identity()
+        ));
+
+        [lhs, rhs]
+            .into_iter()
+            .flat_map(|ec_point| ec_point_to_limbs(ec_point, acc_encoding.num_limb_bits))
+            .collect()
+    }
+
+    /// Fold pairing lhs and rhs of proofs, each returned as 4 words by a verifier rendered with
+    /// deferred pairing, by powers of `challenge` into limbs of a single accumulator.
+    pub fn fold_pairings_into_limbs(
+        pairings: &[Vec<u8>],
+        challenge: bn256::Fr,
+        acc_encoding: AccumulatorEncoding,
+    ) -> Vec<bn256::Fr> {
+        let fq_from_word = |word: &[u8]| {
+            let mut repr = <[u8; 0x20]>::try_from(word).unwrap();
+            repr.reverse();
+            bn256::Fq::from_repr(repr).unwrap()
+        };
+        let ec_point_from_words = |words: &[u8]| {
+            let (x, y) = (
+                fq_from_word(&words[..0x20]),
+                fq_from_word(&words[0x20..0x40]),
+            );
+            bn256::G1::from(bn256::G1Affine::from_xy(x, y).unwrap())
+        };
+        let (lhs, rhs) = pairings.iter().rev().fold(
+            (bn256::G1::identity(), bn256::G1::identity()),
+            |(lhs, rhs), pairing| {
+                (
+                    lhs * challenge + ec_point_from_words(&pairing[..0x40]),
+                    rhs * challenge + ec_point_from_words(&pairing[0x40..0x80]),
+                )
+            },
+        );
+
+        [lhs, rhs]
+            .map(|ec_point| ec_point.to_affine())
+            .into_iter()
+            .flat_map(|ec_point| ec_point_to_limbs(ec_point, acc_encoding.num_limb_bits))
+            .collect()
+    }
+
+    fn ec_point_to_limbs<C>(ec_point: impl Borrow<C>, num_limb_bits: usize) -> Vec<C::Scalar>
+    where
+        C: CurveAffine,
+        C::Base: PrimeField<Repr = [u8; 0x20]>,
+        C::Scalar: PrimeField<Repr = [u8; 0x20]>,
+    {
+        let coords = ec_point.borrow().coordinates().unwrap();
+        [*coords.x(), *coords.y()]
+            .into_iter()
+            .flat_map(|coord| fe_to_limbs(coord, num_limb_bits))
+            .collect()
+    }
+
+    fn fe_to_limbs<F1, F2>(fe: impl Borrow<F1>, num_limb_bits: usize) -> Vec<F2>
+    where
+        F1: PrimeField<Repr = [u8; 0x20]>,
+        F2: PrimeField<Repr = [u8; 0x20]>,
+    {
+        let big = U256::from_le_bytes(fe.borrow().to_repr());
+        let mask = &((U256::from(1) << num_limb_bits) - U256::from(1));
+        (0usize..)
+            .step_by(num_limb_bits)
+            .map(|shift| fe_from_u256((big >> shift) & mask))
+            .take((F1::NUM_BITS as usize + num_limb_bits - 1) / num_limb_bits)
+            .collect_vec()
+    }
+
+    fn fe_from_u256<F>(u256: impl Borrow<U256>) -> F
+    where
+        F: PrimeField<Repr = [u8; 0x20]>,
+    {
+        let bytes = u256.borrow().to_le_bytes::<32>();
+        F::from_repr_vartime(bytes).unwrap()
+    }
+
+    pub mod huge {
+        use crate::{
+            codegen::AccumulatorEncoding,
+            test::halo2::{random_accumulator_limbs, TestCircuit},
+        };
+        use halo2_proofs::{
+            arithmetic::CurveAffine,
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::{
+                ff::{Field, PrimeField},
+                pairing::MultiMillerLoop,
+            },
+            plonk::{
+                self, Advice, Circuit, Column, ConstraintSystem, Expression, FirstPhase, Fixed,
+                Instance, SecondPhase, Selector, ThirdPhase,
+            },
+            poly::Rotation,
+        };
+        use itertools::{izip, Itertools};
+        use rand::RngCore;
+        use std::{array, fmt::Debug, iter, mem};
+
+        #[derive(Clone, Debug, Default)]
+        pub struct HugeCircuit<M: MultiMillerLoop>(Vec<M::Fr>);
+
+        impl<M: MultiMillerLoop> HugeCircuit<M> {
+            /// Circuit exposing given instances, e.g. limbs of an accumulator of other proofs.
+            pub fn from_instances(instances: Vec<M::Fr>) -> Self {
+                Self(instances)
+            }
+        }
+
+        impl<M: MultiMillerLoop> TestCircuit<M::Fr> for HugeCircuit<M>
+        where
+            M: MultiMillerLoop,
+            M::G1Affine: CurveAffine<ScalarExt = M::Fr>,
+            <M::G1Affine as CurveAffine>::Base: PrimeField<Repr = [u8; 0x20]>,
+            <M::G1Affine as CurveAffine>::ScalarExt: PrimeField<Repr = [u8; 0x20]>,
+        {
+            fn min_k() -> u32 {
+                6
+            }
+
+            fn new(acc_encoding: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                let instances = if let Some(acc_encoding) = acc_encoding {
+                    random_accumulator_limbs::<M>(acc_encoding, rng)
+                } else {
+                    iter::repeat_with(|| M::Fr::random(&mut rng))
+                        .take(10)
+                        .collect()
+                };
+                Self(instances)
+            }
+
+            fn instances(&self) -> Vec<Vec<M::Fr>> {
+                vec![self.0.clone()]
+            }
+        }
+
+        impl<M: MultiMillerLoop> Circuit<M::Fr> for HugeCircuit<M> {
+            type Config = (
+                [Selector; 10],
+                [Selector; 10],
+                [Column<Fixed>; 10],
+                [Column<Advice>; 10],
+                Column<Instance>,
+            );
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<M::Fr>) -> Self::Config {
+                let selectors = [(); 10].map(|_| meta.selector());
+                let complex_selectors = [(); 10].map(|_| meta.complex_selector());
+                let fixeds = [(); 10].map(|_| meta.fixed_column());
+                let (advices, challenges) = (0..10)
+                    .map(|idx| match idx % 3 {
+                        0 => (
+                            meta.advice_column_in(FirstPhase),
+                            meta.challenge_usable_after(FirstPhase),
+                        ),
+                        1 => (
+                            meta.advice_column_in(SecondPhase),
+                            meta.challenge_usable_after(SecondPhase),
+                        ),
+                        2 => (
+                            meta.advice_column_in(ThirdPhase),
+                            meta.challenge_usable_after(ThirdPhase),
+                        ),
+                        _ => unreachable!(),
+                    })
+                    .unzip::<_, _, Vec<_>, Vec<_>>();
+                let advices: [_; 10] = advices.try_into().unwrap();
+                let challenges: [_; 10] = challenges.try_into().unwrap();
+                let instance = meta.instance_column();
+
+                meta.create_gate("", |meta| {
+                    let selectors = selectors.map(|selector| meta.query_selector(selector));
+                    let advices: [Expression<M::Fr>; 10] = array::from_fn(|idx| {
+                        let rotation = Rotation((idx as i32 - advices.len() as i32) / 2);
+                        meta.query_advice(advices[idx], rotation)
+                    });
+                    let challenges = challenges.map(|challenge| meta.query_challenge(challenge));
+
+                    izip!(
+                        selectors,
+                        advices.iter().cloned(),
+                        advices[1..].iter().cloned(),
+                        advices[2..].iter().cloned(),
+                        challenges.iter().cloned(),
+                        challenges[1..].iter().cloned(),
+                        challenges[2..].iter().cloned(),
+                    )
+                    .map(|(q, a1, a2, a3, c1, c2, c3)| q * a1 * a2 * a3 * c1 * c2 * c3)
+                    .collect_vec()
+                });
+
+                for ((q1, q2, q3), (f1, f2, f3), (a1, a2, a3)) in izip!(
+                    complex_selectors.iter().tuple_windows(),
+                    fixeds.iter().tuple_windows(),
+                    advices.iter().tuple_windows()
+                ) {
+                    meta.lookup_any("", |meta| {
+                        izip!([q1, q2, q3], [f1, f2, f3], [a1, a2, a3])
+                            .map(|(q, f, a)| {
+                                let q = meta.query_selector(*q);
+                                let f = meta.query_fixed(*f, Rotation::cur());
+                                let a = meta.query_advice(*a, Rotation::cur());
+                                (q * a, f)
+                            })
+                            .collect_vec()
+                    });
+                }
+
+                fixeds.map(|column| meta.enable_equality(column));
+                advices.map(|column| meta.enable_equality(column));
+                meta.enable_equality(instance);
+
+                (selectors, complex_selectors, fixeds, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (selectors, complex_selectors, fixeds, advices, instance): Self::Config,
+                mut layouter: impl Layouter<M::Fr>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let offset = &mut 10;
+                        let mut next_offset = || mem::replace(offset, *offset + 1);
+
+                        for q in selectors {
+                            q.enable(&mut region, next_offset())?;
+                        }
+                        for q in complex_selectors {
+                            q.enable(&mut region, next_offset())?;
+                        }
+                        for (idx, column) in izip!(1.., fixeds) {
+                            let value = Value::known(M::Fr::from(idx));
+                            region.assign_fixed(|| "", column, next_offset(), || value)?;
+                        }
+                        izip!(advices, &self.0)
+                            .map(|(column, value)| {
+                                let value = Value::known(*value);
+                                region.assign_advice(|| "", column, next_offset(), || value)
+                            })
+                            .try_collect::<_, Vec<_>, _>()
+                    },
+                )?;
+                for (idx, assigned) in izip!(0.., assigneds) {
+                    layouter.constrain_instance(assigned.cell(), instance, idx)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod maingate {
+        use crate::{
+            codegen::AccumulatorEncoding,
+            test::halo2::{random_accumulator_limbs, TestCircuit},
+        };
+        use halo2_maingate::{
+            MainGate, MainGateConfig, MainGateInstructions, RangeChip, RangeConfig,
+            RangeInstructions, RegionCtx,
+        };
+        use halo2_proofs::{
+            arithmetic::CurveAffine,
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::{
+                ff::{Field, PrimeField},
+                pairing::MultiMillerLoop,
+            },
+            plonk::{Circuit, ConstraintSystem, Error},
+        };
+        use itertools::Itertools;
+        use rand::RngCore;
+        use std::iter;
+
+        #[derive(Clone)]
+        pub struct MainGateWithRangeConfig {
+            main_gate_config: MainGateConfig,
+            range_config: RangeConfig,
+        }
+
+        impl MainGateWithRangeConfig {
+            fn configure<F: PrimeField>(
+                meta: &mut ConstraintSystem<F>,
+                composition_bits: Vec<usize>,
+                overflow_bits: Vec<usize>,
+            ) -> Self {
+                let main_gate_config = MainGate::<F>::configure(meta);
+                let range_config = RangeChip::<F>::configure(
+                    meta,
+                    &main_gate_config,
+                    composition_bits,
+                    overflow_bits,
+                );
+                MainGateWithRangeConfig {
+                    main_gate_config,
+                    range_config,
+                }
+            }
+
+            fn main_gate<F: PrimeField>(&self) -> MainGate<F> {
+                MainGate::new(self.main_gate_config.clone())
+            }
+
+            fn range_chip<F: PrimeField>(&self) -> RangeChip<F> {
+                RangeChip::new(self.range_config.clone())
+            }
+        }
+
+        #[derive(Clone, Default)]
+        pub struct MainGateWithRange<M: MultiMillerLoop> {
+            instances: Vec<M::Fr>,
+        }
+
+        impl<M> TestCircuit<M::Fr> for MainGateWithRange<M>
+        where
+            M: MultiMillerLoop,
+            M::G1Affine: CurveAffine<ScalarExt = M::Fr>,
+            <M::G1Affine as CurveAffine>::Base: PrimeField<Repr = [u8; 0x20]>,
+            <M::G1Affine as CurveAffine>::ScalarExt: PrimeField<Repr = [u8; 0x20]>,
+        {
+            fn min_k() -> u32 {
+                9
+            }
+
+            fn new(acc_encoding: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                let instances = if let Some(acc_encoding) = acc_encoding {
+                    random_accumulator_limbs::<M>(acc_encoding, rng)
+                } else {
+                    iter::repeat_with(|| M::Fr::random(&mut rng))
+                        .take(10)
+                        .collect()
+                };
+                Self { instances }
+            }
+
+            fn instances(&self) -> Vec<Vec<M::Fr>> {
+                vec![self.instances.clone()]
+            }
+        }
+
+        impl<M: MultiMillerLoop> Circuit<M::Fr> for MainGateWithRange<M> {
+            type Config = MainGateWithRangeConfig;
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<M::Fr>) -> Self::Config {
+                MainGateWithRangeConfig::configure(meta, vec![8], vec![4, 7])
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl Layouter<M::Fr>,
+            ) -> Result<(), Error> {
+                let main_gate = config.main_gate();
+                let range_chip = config.range_chip();
+                range_chip.load_table(&mut layouter)?;
+
+                let advices = layouter.assign_region(
+                    || "",
+                    |region| {
+                        let mut ctx = RegionCtx::new(region, 0);
+
+                        let advices = self
+                            .instances
+                            .iter()
+                            .map(|value| main_gate.assign_value(&mut ctx, Value::known(*value)))
+                            .try_collect::<_, Vec<_>, _>()?;
+
+                        // Dummy gates to make all fixed column with values
+                        range_chip.decompose(
+                            &mut ctx,
+                            Value::known(M::Fr::from(u64::MAX)),
+                            8,
+                            64,
+                        )?;
+                        range_chip.decompose(
+                            &mut ctx,
+                            Value::known(M::Fr::from(u32::MAX as u64)),
+                            8,
+                            39,
+                        )?;
+                        let a = &advices[0];
+                        let b =
+                            main_gate.sub_sub_with_constant(&mut ctx, a, a, a, M::Fr::from(2))?;
+                        let cond = main_gate.assign_bit(&mut ctx, Value::known(M::Fr::ONE))?;
+                        main_gate.select(&mut ctx, a, &b, &cond)?;
+
+                        Ok(advices)
+                    },
+                )?;
+
+                for (offset, advice) in advices.into_iter().enumerate() {
+                    main_gate.expose_public(layouter.namespace(|| ""), advice, offset)?
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    pub mod two_instance_columns {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use itertools::izip;
+        use rand::RngCore;
+        use std::iter;
+
+        /// Circuit exposing different number of instances on 2 instance columns, which are both
+        /// copy constrained to advice cells and queried in gates.
+        #[derive(Clone, Debug, Default)]
+        pub struct TwoInstanceColumns<F>([Vec<F>; 2]);
+
+        impl<F: PrimeField> TestCircuit<F> for TwoInstanceColumns<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self([3, 5].map(|n| iter::repeat_with(|| F::random(&mut rng)).take(n).collect()))
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                self.0.to_vec()
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for TwoInstanceColumns<F> {
+            type Config = ([Selector; 2], [Column<Advice>; 2], [Column<Instance>; 2]);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selectors = [(); 2].map(|_| meta.selector());
+                let advices = [(); 2].map(|_| meta.advice_column());
+                let instances = [(); 2].map(|_| meta.instance_column());
+
+                for (selector, advice, instance) in izip!(selectors, advices, instances) {
+                    meta.enable_equality(advice);
+                    meta.enable_equality(instance);
+                    meta.create_gate("", |meta| {
+                        let q = meta.query_selector(selector);
+                        let a = meta.query_advice(advice, Rotation::cur());
+                        let i = meta.query_instance(instance, Rotation::cur());
+                        Some(q * (a - i))
+                    });
+                }
+
+                (selectors, advices, instances)
+            }
+
+            fn synthesize(
+                &self,
+                (selectors, advices, instances): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let mut assigneds = Vec::new();
+                        for (selector, advice, values) in izip!(selectors, advices, &self.0) {
+                            let mut column = Vec::new();
+                            for (offset, value) in values.iter().enumerate() {
+                                selector.enable(&mut region, offset)?;
+                                let value = Value::known(*value);
+                                column.push(region.assign_advice(
+                                    || "",
+                                    advice,
+                                    offset,
+                                    || value,
+                                )?);
+                            }
+                            assigneds.push(column);
+                        }
+                        Ok(assigneds)
+                    },
+                )?;
+                for (instance, assigneds) in izip!(instances, assigneds) {
+                    for (row, assigned) in assigneds.iter().enumerate() {
+                        layouter.constrain_instance(assigned.cell(), instance, row)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod hashed_instances {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+        use sha3::{Digest, Keccak256};
+        use std::iter;
+
+        /// Circuit exposing keccak256 of many public inputs as 2 instances, the higher and lower
+        /// 128 bits of the digest. The keccak256 itself is computed out of circuit for testing,
+        /// where a real circuit would constrain it by a keccak chip.
+        #[derive(Clone, Debug, Default)]
+        pub struct HashedInstances<F>(Vec<F>);
+
+        impl<F: PrimeField<Repr = [u8; 32]>> HashedInstances<F> {
+            pub fn public_inputs(&self) -> &[F] {
+                &self.0
+            }
+
+            fn digest(&self) -> [F; 2] {
+                let words = self
+                    .0
+                    .iter()
+                    .flat_map(|input| input.to_repr().into_iter().rev())
+                    .collect::<Vec<_>>();
+                let digest = Keccak256::digest(words);
+                let [hi, lo] = [&digest[..16], &digest[16..]]
+                    .map(|half| u128::from_be_bytes(half.try_into().unwrap()));
+                [hi, lo].map(F::from_u128)
+            }
+        }
+
+        impl<F: PrimeField<Repr = [u8; 32]>> TestCircuit<F> for HashedInstances<F> {
+            fn min_k() -> u32 {
+                4
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self(iter::repeat_with(|| F::random(&mut rng)).take(64).collect())
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.digest().to_vec()]
+            }
+        }
+
+        impl<F: PrimeField<Repr = [u8; 32]>> Circuit<F> for HashedInstances<F> {
+            type Config = (Selector, Column<Advice>, Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selector = meta.selector();
+                let advice = meta.advice_column();
+                let instance = meta.instance_column();
+                meta.enable_equality(advice);
+                meta.enable_equality(instance);
+                meta.create_gate("", |meta| {
+                    let q = meta.query_selector(selector);
+                    let a = meta.query_advice(advice, Rotation::cur());
+                    let i = meta.query_instance(instance, Rotation::cur());
+                    Some(q * (a - i))
+                });
+                (selector, advice, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (selector, advice, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let mut assigneds = Vec::new();
+                        for (offset, value) in self.digest().into_iter().enumerate() {
+                            selector.enable(&mut region, offset)?;
+                            let value = Value::known(value);
+                            assigneds.push(region.assign_advice(
+                                || "",
+                                advice,
+                                offset,
+                                || value,
+                            )?);
+                        }
+                        Ok(assigneds)
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod small_instances {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+
+        /// Bit width of each instance of [`SmallInstances`].
+        pub const BIT_WIDTHS: [usize; 8] = [64, 60, 60, 32, 8, 1, 128, 100];
+
+        /// Circuit exposing instances of small bit widths, where the second one is at the max
+        /// value of its width and the others are random.
+        #[derive(Clone, Debug, Default)]
+        pub struct SmallInstances<F>(Vec<F>);
+
+        impl<F: PrimeField> TestCircuit<F> for SmallInstances<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self(
+                    BIT_WIDTHS
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, bit_width)| {
+                            let max = u128::MAX >> (128 - bit_width);
+                            let random = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+                            F::from_u128(if idx == 1 { max } else { random & max })
+                        })
+                        .collect(),
+                )
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.0.clone()]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for SmallInstances<F> {
+            type Config = (Selector, Column<Advice>, Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selector = meta.selector();
+                let advice = meta.advice_column();
+                let instance = meta.instance_column();
+                meta.enable_equality(advice);
+                meta.enable_equality(instance);
+                meta.create_gate("", |meta| {
+                    let q = meta.query_selector(selector);
+                    let a = meta.query_advice(advice, Rotation::cur());
+                    let i = meta.query_instance(instance, Rotation::cur());
+                    Some(q * (a - i))
+                });
+                (selector, advice, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (selector, advice, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let mut assigneds = Vec::new();
+                        for (offset, value) in self.0.iter().enumerate() {
+                            selector.enable(&mut region, offset)?;
+                            let value = Value::known(*value);
+                            assigneds.push(region.assign_advice(
+                                || "",
+                                advice,
+                                offset,
+                                || value,
+                            )?);
+                        }
+                        Ok(assigneds)
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod two_phase_rlc {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{
+                self, Advice, Challenge, Circuit, Column, ConstraintSystem, FirstPhase, Instance,
+                SecondPhase, Selector,
+            },
+            poly::Rotation,
+        };
+        use rand::RngCore;
+        use std::iter;
+
+        /// Circuit accumulating random linear combination of values committed in first phase, by
+        /// challenge squeezed after first phase, into an advice column committed in second phase.
+        #[derive(Clone, Debug, Default)]
+        pub struct TwoPhaseRlc<F>(Vec<F>);
+
+        impl<F: PrimeField> TestCircuit<F> for TwoPhaseRlc<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self(iter::repeat_with(|| F::random(&mut rng)).take(8).collect())
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.0.clone()]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for TwoPhaseRlc<F> {
+            type Config = (
+                [Selector; 2],
+                [Column<Advice>; 2],
+                Challenge,
+                Column<Instance>,
+            );
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selectors = [(); 2].map(|_| meta.selector());
+                let value = meta.advice_column_in(FirstPhase);
+                let challenge = meta.challenge_usable_after(FirstPhase);
+                let acc = meta.advice_column_in(SecondPhase);
+                let instance = meta.instance_column();
+                meta.enable_equality(value);
+                meta.enable_equality(instance);
+                meta.create_gate("rlc", |meta| {
+                    let [q_first, q_rest] = selectors.map(|q| meta.query_selector(q));
+                    let value = meta.query_advice(value, Rotation::cur());
+                    let acc_prev = meta.query_advice(acc, Rotation::prev());
+                    let acc = meta.query_advice(acc, Rotation::cur());
+                    let challenge = meta.query_challenge(challenge);
+                    [
+                        q_first * (acc.clone() - value.clone()),
+                        q_rest * (acc - acc_prev * challenge - value),
+                    ]
+                });
+                (selectors, [value, acc], challenge, instance)
+            }
+
+            fn synthesize(
+                &self,
+                ([q_first, q_rest], [value_column, acc_column], challenge, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                // Unknown until first phase is committed
+                let challenge = layouter.get_challenge(challenge);
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let mut assigneds = Vec::new();
+                        let mut acc = Value::known(F::ZERO);
+                        for (offset, value) in self.0.iter().enumerate() {
+                            let q = if offset == 0 { q_first } else { q_rest };
+                            q.enable(&mut region, offset)?;
+                            let value = Value::known(*value);
+                            let assigned =
+                                region.assign_advice(|| "", value_column, offset, || value)?;
+                            assigneds.push(assigned);
+                            acc = acc * challenge + value;
+                            region.assign_advice(|| "", acc_column, offset, || acc)?;
+                        }
+                        Ok(assigneds)
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod dynamic_table {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+        use std::iter;
+
+        /// Circuit looking up pairs of key and value into a key-value table assigned in advice
+        /// columns by `lookup_any`, and exposing the values looked up as instances.
+        #[derive(Clone, Debug, Default)]
+        pub struct DynamicTable<F> {
+            table: Vec<F>,
+            keys: Vec<usize>,
+        }
+
+        impl<F: PrimeField> TestCircuit<F> for DynamicTable<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                let table = iter::repeat_with(|| F::random(&mut rng)).take(8).collect();
+                let keys = iter::repeat_with(|| rng.next_u32() as usize % 8)
+                    .take(4)
+                    .collect();
+                Self { table, keys }
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.keys.iter().map(|key| self.table[*key]).collect()]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for DynamicTable<F> {
+            type Config = (Selector, [Column<Advice>; 4], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.complex_selector();
+                let advices = [(); 4].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                meta.enable_equality(advices[1]);
+                meta.enable_equality(instance);
+                // Rows not in use are all zero, so the unselected inputs `(0, 0)` are in table
+                meta.lookup_any("key-value", |meta| {
+                    let q = meta.query_selector(q);
+                    let [key, value, table_key, table_value] =
+                        advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+                    vec![(q.clone() * key, table_key), (q * value, table_value)]
+                });
+                (q, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, [key, value, table_key, table_value], instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        for (offset, entry) in self.table.iter().enumerate() {
+                            let k = Value::known(F::from(offset as u64));
+                            region.assign_advice(|| "", table_key, offset, || k)?;
+                            let v = Value::known(*entry);
+                            region.assign_advice(|| "", table_value, offset, || v)?;
+                        }
+                        let mut assigneds = Vec::new();
+                        for (offset, k) in self.keys.iter().enumerate() {
+                            q.enable(&mut region, offset)?;
+                            let v = Value::known(self.table[*k]);
+                            let k = Value::known(F::from(*k as u64));
+                            region.assign_advice(|| "", key, offset, || k)?;
+                            assigneds.push(region.assign_advice(|| "", value, offset, || v)?);
+                        }
+                        Ok(assigneds)
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod high_degree {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+
+        /// Circuit constraining the second advice to be the 8-th power of the first one by a gate
+        /// of degree 9, so the quotient is split into 8 chunks, and exposing both as instances.
+        #[derive(Clone, Debug, Default)]
+        pub struct HighDegree<F>(F);
+
+        impl<F: PrimeField> TestCircuit<F> for HighDegree<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self(F::random(&mut rng))
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![vec![self.0, self.0.pow_vartime([8])]]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for HighDegree<F> {
+            type Config = (Selector, [Column<Advice>; 2], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.selector();
+                let advices = [(); 2].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                for advice in advices {
+                    meta.enable_equality(advice);
+                }
+                meta.enable_equality(instance);
+                meta.create_gate("pow_8", |meta| {
+                    let q = meta.query_selector(q);
+                    let [a, b] = advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+                    let pow_8 = (0..7).fold(a.clone(), |acc, _| acc * a.clone());
+                    Some(q * (pow_8 - b))
+                });
+                (q, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, advices, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        q.enable(&mut region, 0)?;
+                        let values = [self.0, self.0.pow_vartime([8])].map(Value::known);
+                        let a = region.assign_advice(|| "", advices[0], 0, || values[0])?;
+                        let b = region.assign_advice(|| "", advices[1], 0, || values[1])?;
+                        Ok([a, b])
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod shared_subexpressions {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{
+                self, Advice, Circuit, Column, ConstraintSystem, Expression, Instance, Selector,
+            },
+            poly::Rotation,
+        };
+        use rand::RngCore;
+
+        /// Circuit constraining the last advice to be the product of the others by 8 polynomials,
+        /// each of which multiplies the same subexpression by a different factor, and exposing the
+        /// product as instance.
+        #[derive(Clone, Debug, Default)]
+        pub struct SharedSubexpressions<F>([F; 3]);
+
+        impl<F: PrimeField> TestCircuit<F> for SharedSubexpressions<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self([(); 3].map(|_| F::random(&mut rng)))
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![vec![self.0.iter().product()]]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for SharedSubexpressions<F> {
+            type Config = (Selector, [Column<Advice>; 4], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.selector();
+                let advices = [(); 4].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                meta.enable_equality(advices[3]);
+                meta.enable_equality(instance);
+                meta.create_gate("shared", |meta| {
+                    let q = meta.query_selector(q);
+                    let [a, b, c, d] =
+                        advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+                    let shared = q * (a.clone() * b * c - d);
+                    (0..8)
+                        .map(|i| shared.clone() * (a.clone() + Expression::Constant(F::from(i))))
+                        .collect::<Vec<_>>()
+                });
+                (q, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, advices, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigned = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        q.enable(&mut region, 0)?;
+                        for (advice, value) in advices.iter().zip(self.0) {
+                            region.assign_advice(|| "", *advice, 0, || Value::known(value))?;
+                        }
+                        let product = Value::known(self.0.iter().product());
+                        region.assign_advice(|| "", advices[3], 0, || product)
+                    },
+                )?;
+                layouter.constrain_instance(assigned.cell(), instance, 0)
+            }
+        }
+    }
+
+    pub mod many_gates {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{
+                self, Advice, Circuit, Column, ConstraintSystem, Expression, Instance, Selector,
+            },
+            poly::Rotation,
+        };
+        use rand::RngCore;
+
+        /// Number of gates, each of which shares a subexpression between its 2 polynomials.
+        pub const NUM_GATES: u64 = 64;
+
+        /// Circuit constraining the last advice to be the product of the others by many gates,
+        /// each of which has 2 polynomials sharing a subexpression only used by that gate, and
+        /// exposing the product as instance.
+        #[derive(Clone, Debug, Default)]
+        pub struct ManyGates<F>([F; 2]);
+
+        impl<F: PrimeField> TestCircuit<F> for ManyGates<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self([(); 2].map(|_| F::random(&mut rng)))
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![vec![self.0[0] * self.0[1]]]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for ManyGates<F> {
+            type Config = (Selector, [Column<Advice>; 3], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.selector();
+                let advices = [(); 3].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                meta.enable_equality(advices[2]);
+                meta.enable_equality(instance);
+                for idx in 0..NUM_GATES {
+                    meta.create_gate("many", |meta| {
+                        let q = meta.query_selector(q);
+                        let [a, b, c] =
+                            advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+                        let shifted = a.clone() + Expression::Constant(F::from(idx));
+                        let shared = q * (a.clone() * b - c) * shifted;
+                        [shared.clone(), shared * a]
+                    });
+                }
+                (q, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, advices, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigned = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        q.enable(&mut region, 0)?;
+                        for (advice, value) in advices.iter().zip(self.0) {
+                            region.assign_advice(|| "", *advice, 0, || Value::known(value))?;
+                        }
+                        let product = Value::known(self.0[0] * self.0[1]);
+                        region.assign_advice(|| "", advices[2], 0, || product)
+                    },
+                )?;
+                layouter.constrain_instance(assigned.cell(), instance, 0)
+            }
+        }
+    }
+
+    pub mod unblinded_advice {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+
+        /// Circuit with an unblinded advice column `a` and a blinded one `b`, constraining
+        /// `b[0] = a[0] * a[1]` and `a[2] = a[0] + a[1]` by a gate, and exposing `a[0..3]` and
+        /// `b[0]` as instances.
+        #[derive(Clone, Debug, Default)]
+        pub struct UnblindedAdvice<F>([F; 2]);
+
+        impl<F: PrimeField> TestCircuit<F> for UnblindedAdvice<F> {
+            fn min_k() -> u32 {
+                4
+            }
 
-        [lhs, rhs]
-            .into_iter()
-            .flat_map(|ec_point| ec_point_to_limbs(ec_point, acc_encoding.num_limb_bits))
-            .collect()
-    }
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self([(); 2].map(|_| F::random(&mut rng)))
+            }
 
-    fn ec_point_to_limbs<C>(ec_point: impl Borrow<C>, num_limb_bits: usize) -> Vec<C::Scalar>
-    where
-        C: CurveAffine,
-        C::Base: PrimeField<Repr = [u8; 0x20]>,
-        C::Scalar: PrimeField<Repr = [u8; 0x20]>,
-    {
-        let coords = ec_point.borrow().coordinates().unwrap();
-        [*coords.x(), *coords.y()]
-            .into_iter()
-            .flat_map(|coord| fe_to_limbs(coord, num_limb_bits))
-            .collect()
-    }
+            fn instances(&self) -> Vec<Vec<F>> {
+                let [x, y] = self.0;
+                vec![vec![x, y, x + y, x * y]]
+            }
+        }
 
-    fn fe_to_limbs<F1, F2>(fe: impl Borrow<F1>, num_limb_bits: usize) -> Vec<F2>
-    where
-        F1: PrimeField<Repr = [u8; 0x20]>,
-        F2: PrimeField<Repr = [u8; 0x20]>,
-    {
-        let big = U256::from_le_bytes(fe.borrow().to_repr());
-        let mask = &((U256::from(1) << num_limb_bits) - U256::from(1));
-        (0usize..)
-            .step_by(num_limb_bits)
-            .map(|shift| fe_from_u256((big >> shift) & mask))
-            .take((F1::NUM_BITS as usize + num_limb_bits - 1) / num_limb_bits)
-            .collect_vec()
-    }
+        impl<F: PrimeField> Circuit<F> for UnblindedAdvice<F> {
+            type Config = (Selector, [Column<Advice>; 2], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
 
-    fn fe_from_u256<F>(u256: impl Borrow<U256>) -> F
-    where
-        F: PrimeField<Repr = [u8; 0x20]>,
-    {
-        let bytes = u256.borrow().to_le_bytes::<32>();
-        F::from_repr_vartime(bytes).unwrap()
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.selector();
+                let advices = [meta.unblinded_advice_column(), meta.advice_column()];
+                let instance = meta.instance_column();
+                for advice in advices {
+                    meta.enable_equality(advice);
+                }
+                meta.enable_equality(instance);
+                meta.create_gate("unblinded", |meta| {
+                    let q = meta.query_selector(q);
+                    let [a0, a1, a2] =
+                        [0, 1, 2].map(|rotation| meta.query_advice(advices[0], Rotation(rotation)));
+                    let b0 = meta.query_advice(advices[1], Rotation::cur());
+                    [
+                        q.clone() * (a0.clone() * a1.clone() - b0),
+                        q * (a0 + a1 - a2),
+                    ]
+                });
+                (q, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, [a, b], instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        q.enable(&mut region, 0)?;
+                        let [x, y] = self.0;
+                        let a_values = [x, y, x + y].map(Value::known);
+                        let mut assigneds = Vec::new();
+                        for (row, value) in a_values.into_iter().enumerate() {
+                            assigneds.push(region.assign_advice(|| "", a, row, || value)?);
+                        }
+                        let value = Value::known(x * y);
+                        assigneds.push(region.assign_advice(|| "", b, 0, || value)?);
+                        Ok(assigneds)
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
     }
 
-    pub mod huge {
-        use crate::{
-            codegen::AccumulatorEncoding,
-            test::halo2::{random_accumulator_limbs, TestCircuit},
-        };
+    pub mod long_rotations {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
         use halo2_proofs::{
-            arithmetic::CurveAffine,
             circuit::{Layouter, SimpleFloorPlanner, Value},
-            halo2curves::{
-                ff::{Field, PrimeField},
-                pairing::MultiMillerLoop,
-            },
+            halo2curves::ff::PrimeField,
             plonk::{
-                self, Advice, Circuit, Column, ConstraintSystem, Expression, FirstPhase, Fixed,
-                Instance, SecondPhase, Selector, ThirdPhase,
+                self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector, TableColumn,
             },
             poly::Rotation,
         };
-        use itertools::{izip, Itertools};
         use rand::RngCore;
-        use std::{array, fmt::Debug, iter, mem};
 
+        /// Circuit querying advice column at rotations `-5`, `-3` and `7` from the selected row,
+        /// by a lookup range checking `a[0]` into `0..16` and a gate enforcing
+        /// `a[12] = a[2] + b[5]`, and exposing the 4 cells as instances.
         #[derive(Clone, Debug, Default)]
-        pub struct HugeCircuit<M: MultiMillerLoop>(Vec<M::Fr>);
+        pub struct LongRotations<F>([F; 3]);
 
-        impl<M: MultiMillerLoop> TestCircuit<M::Fr> for HugeCircuit<M>
-        where
-            M: MultiMillerLoop,
-            M::G1Affine: CurveAffine<ScalarExt = M::Fr>,
-            <M::G1Affine as CurveAffine>::Base: PrimeField<Repr = [u8; 0x20]>,
-            <M::G1Affine as CurveAffine>::ScalarExt: PrimeField<Repr = [u8; 0x20]>,
-        {
+        impl<F: PrimeField> TestCircuit<F> for LongRotations<F> {
             fn min_k() -> u32 {
-                6
+                5
             }
 
-            fn new(acc_encoding: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
-                let instances = if let Some(acc_encoding) = acc_encoding {
-                    random_accumulator_limbs::<M>(acc_encoding, rng)
-                } else {
-                    iter::repeat_with(|| M::Fr::random(&mut rng))
-                        .take(10)
-                        .collect()
-                };
-                Self(instances)
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                let small = F::from(rng.next_u64() % 16);
+                Self([small, F::random(&mut rng), F::random(&mut rng)])
             }
 
-            fn instances(&self) -> Vec<M::Fr> {
-                self.0.clone()
+            fn instances(&self) -> Vec<Vec<F>> {
+                let [small, a, b] = self.0;
+                vec![vec![small, a, a + b, b]]
             }
         }
 
-        impl<M: MultiMillerLoop> Circuit<M::Fr> for HugeCircuit<M> {
-            type Config = (
-                [Selector; 10],
-                [Selector; 10],
-                [Column<Fixed>; 10],
-                [Column<Advice>; 10],
-                Column<Instance>,
-            );
+        impl<F: PrimeField> Circuit<F> for LongRotations<F> {
+            type Config = (Selector, [Column<Advice>; 2], Column<Instance>, TableColumn);
             type FloorPlanner = SimpleFloorPlanner;
-            #[cfg(feature = "halo2_circuit_params")]
+            #[cfg(feature = "circuit-params")]
             type Params = ();
 
             fn without_witnesses(&self) -> Self {
                 unimplemented!()
             }
 
-            fn configure(meta: &mut ConstraintSystem<M::Fr>) -> Self::Config {
-                let selectors = [(); 10].map(|_| meta.selector());
-                let complex_selectors = [(); 10].map(|_| meta.complex_selector());
-                let fixeds = [(); 10].map(|_| meta.fixed_column());
-                let (advices, challenges) = (0..10)
-                    .map(|idx| match idx % 3 {
-                        0 => (
-                            meta.advice_column_in(FirstPhase),
-                            meta.challenge_usable_after(FirstPhase),
-                        ),
-                        1 => (
-                            meta.advice_column_in(SecondPhase),
-                            meta.challenge_usable_after(SecondPhase),
-                        ),
-                        2 => (
-                            meta.advice_column_in(ThirdPhase),
-                            meta.challenge_usable_after(ThirdPhase),
-                        ),
-                        _ => unreachable!(),
-                    })
-                    .unzip::<_, _, Vec<_>, Vec<_>>();
-                let advices: [_; 10] = advices.try_into().unwrap();
-                let challenges: [_; 10] = challenges.try_into().unwrap();
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.complex_selector();
+                let advices = [(); 2].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                let table = meta.lookup_table_column();
+                for advice in advices {
+                    meta.enable_equality(advice);
+                }
+                meta.enable_equality(instance);
+                meta.lookup("range", |meta| {
+                    let q = meta.query_selector(q);
+                    let small = meta.query_advice(advices[0], Rotation(-5));
+                    vec![(q * small, table)]
+                });
+                meta.create_gate("add", |meta| {
+                    let q = meta.query_selector(q);
+                    let a = meta.query_advice(advices[0], Rotation(-3));
+                    let c = meta.query_advice(advices[0], Rotation(7));
+                    let b = meta.query_advice(advices[1], Rotation::cur());
+                    Some(q * (a + b - c))
+                });
+                (q, advices, instance, table)
+            }
+
+            fn synthesize(
+                &self,
+                (q, advices, instance, table): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                layouter.assign_table(
+                    || "",
+                    |mut table_layouter| {
+                        for offset in 0..16 {
+                            let value = Value::known(F::from(offset as u64));
+                            table_layouter.assign_cell(|| "", table, offset, || value)?;
+                        }
+                        Ok(())
+                    },
+                )?;
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let [small, a, b] = self.0;
+                        q.enable(&mut region, 5)?;
+                        // Rows in between are left unassigned as zero
+                        let cells = [(0, 0, small), (0, 2, a), (0, 12, a + b), (1, 5, b)];
+                        cells
+                            .map(|(column, offset, value)| {
+                                let value = Value::known(value);
+                                region.assign_advice(|| "", advices[column], offset, || value)
+                            })
+                            .into_iter()
+                            .collect::<Result<Vec<_>, _>>()
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod many_equalities {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+        use std::iter;
+
+        /// Circuit enabling equality on 14 advice columns and an instance column, with a gate of
+        /// degree 4, so permutation argument is split into chunks of 2 columns.
+        #[derive(Clone, Debug, Default)]
+        pub struct ManyEqualities<F>(Vec<F>);
+
+        impl<F: PrimeField> TestCircuit<F> for ManyEqualities<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                let mut values = iter::repeat_with(|| F::random(&mut rng))
+                    .take(14)
+                    .collect::<Vec<_>>();
+                values[3] = values[0] * values[1] * values[2];
+                Self(values)
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.0.clone()]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for ManyEqualities<F> {
+            type Config = (Selector, [Column<Advice>; 14], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.selector();
+                let advices = [(); 14].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                for advice in advices {
+                    meta.enable_equality(advice);
+                }
+                meta.enable_equality(instance);
+                meta.create_gate("product", |meta| {
+                    let q = meta.query_selector(q);
+                    let [a, b, c, d] =
+                        [0, 1, 2, 3].map(|idx| meta.query_advice(advices[idx], Rotation::cur()));
+                    Some(q * (a * b * c - d))
+                });
+                (q, advices, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, advices, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigneds = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        q.enable(&mut region, 0)?;
+                        advices
+                            .iter()
+                            .zip(&self.0)
+                            .map(|(advice, value)| {
+                                region.assign_advice(|| "", *advice, 0, || Value::known(*value))
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    },
+                )?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub mod rotated_instance {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+        use std::iter;
+
+        /// Circuit constraining advice column to equal instance column by a gate querying both at
+        /// previous, current and next rows.
+        #[derive(Clone, Debug, Default)]
+        pub struct RotatedInstance<F>(Vec<F>);
+
+        impl<F: PrimeField> TestCircuit<F> for RotatedInstance<F> {
+            fn min_k() -> u32 {
+                5
+            }
+
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self(iter::repeat_with(|| F::random(&mut rng)).take(8).collect())
+            }
+
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.0.clone()]
+            }
+        }
+
+        impl<F: PrimeField> Circuit<F> for RotatedInstance<F> {
+            type Config = (Selector, Column<Advice>, Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.selector();
+                let advice = meta.advice_column();
                 let instance = meta.instance_column();
+                meta.enable_equality(advice);
+                meta.enable_equality(instance);
+                meta.create_gate("advice equals instance", |meta| {
+                    let q = meta.query_selector(q);
+                    [Rotation::prev(), Rotation::cur(), Rotation::next()]
+                        .map(|rotation| {
+                            let a = meta.query_advice(advice, rotation);
+                            let i = meta.query_instance(instance, rotation);
+                            q.clone() * (a - i)
+                        })
+                        .to_vec()
+                });
+                (q, advice, instance)
+            }
+
+            fn synthesize(
+                &self,
+                (q, advice, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                let assigned = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        for offset in 1..self.0.len() - 1 {
+                            q.enable(&mut region, offset)?;
+                        }
+                        let mut assigneds = Vec::new();
+                        for (offset, value) in self.0.iter().enumerate() {
+                            let value = Value::known(*value);
+                            assigneds.push(region.assign_advice(
+                                || "",
+                                advice,
+                                offset,
+                                || value,
+                            )?);
+                        }
+                        Ok(assigneds[0].clone())
+                    },
+                )?;
+                layouter.constrain_instance(assigned.cell(), instance, 0)
+            }
+        }
+    }
+
+    pub mod shuffle {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+            poly::Rotation,
+        };
+        use rand::RngCore;
+        use std::iter;
+
+        /// Circuit constraining pairs of value and tag in the last two advice columns to be the
+        /// ones in the first two in reversed order by shuffle argument, where values are copied
+        /// from instances.
+        #[derive(Clone, Debug, Default)]
+        pub struct Shuffle<F>(Vec<F>);
+
+        impl<F: PrimeField> TestCircuit<F> for Shuffle<F> {
+            fn min_k() -> u32 {
+                5
+            }
 
-                meta.create_gate("", |meta| {
-                    let selectors = selectors.map(|selector| meta.query_selector(selector));
-                    let advices: [Expression<M::Fr>; 10] = array::from_fn(|idx| {
-                        let rotation = Rotation((idx as i32 - advices.len() as i32) / 2);
-                        meta.query_advice(advices[idx], rotation)
-                    });
-                    let challenges = challenges.map(|challenge| meta.query_challenge(challenge));
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self(iter::repeat_with(|| F::random(&mut rng)).take(8).collect())
+            }
 
-                    izip!(
-                        selectors,
-                        advices.iter().cloned(),
-                        advices[1..].iter().cloned(),
-                        advices[2..].iter().cloned(),
-                        challenges.iter().cloned(),
-                        challenges[1..].iter().cloned(),
-                        challenges[2..].iter().cloned(),
-                    )
-                    .map(|(q, a1, a2, a3, c1, c2, c3)| q * a1 * a2 * a3 * c1 * c2 * c3)
-                    .collect_vec()
-                });
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![self.0.clone()]
+            }
+        }
 
-                for ((q1, q2, q3), (f1, f2, f3), (a1, a2, a3)) in izip!(
-                    complex_selectors.iter().tuple_windows(),
-                    fixeds.iter().tuple_windows(),
-                    advices.iter().tuple_windows()
-                ) {
-                    meta.lookup_any("", |meta| {
-                        izip!([q1, q2, q3], [f1, f2, f3], [a1, a2, a3])
-                            .map(|(q, f, a)| {
-                                let q = meta.query_selector(*q);
-                                let f = meta.query_fixed(*f, Rotation::cur());
-                                let a = meta.query_advice(*a, Rotation::cur());
-                                (q * a, f)
-                            })
-                            .collect_vec()
-                    });
-                }
+        impl<F: PrimeField> Circuit<F> for Shuffle<F> {
+            type Config = (Selector, [Column<Advice>; 4], Column<Instance>);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
 
-                fixeds.map(|column| meta.enable_equality(column));
-                advices.map(|column| meta.enable_equality(column));
-                meta.enable_equality(instance);
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
 
-                (selectors, complex_selectors, fixeds, advices, instance)
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let q = meta.complex_selector();
+                let advices = [(); 4].map(|_| meta.advice_column());
+                let instance = meta.instance_column();
+                meta.enable_equality(advices[0]);
+                meta.enable_equality(instance);
+                meta.shuffle("reversed", |meta| {
+                    let q = meta.query_selector(q);
+                    let [value, tag, shuffled_value, shuffled_tag] =
+                        advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+                    vec![
+                        (q.clone() * value, q.clone() * shuffled_value),
+                        (q.clone() * tag, q * shuffled_tag),
+                    ]
+                });
+                (q, advices, instance)
             }
 
             fn synthesize(
                 &self,
-                (selectors, complex_selectors, fixeds, advices, instance): Self::Config,
-                mut layouter: impl Layouter<M::Fr>,
+                (q, advices, instance): Self::Config,
+                mut layouter: impl Layouter<F>,
             ) -> Result<(), plonk::Error> {
                 let assigneds = layouter.assign_region(
                     || "",
                     |mut region| {
-                        let offset = &mut 10;
-                        let mut next_offset = || mem::replace(offset, *offset + 1);
-
-                        for q in selectors {
-                            q.enable(&mut region, next_offset())?;
-                        }
-                        for q in complex_selectors {
-                            q.enable(&mut region, next_offset())?;
-                        }
-                        for (idx, column) in izip!(1.., fixeds) {
-                            let value = Value::known(M::Fr::from(idx));
-                            region.assign_fixed(|| "", column, next_offset(), || value)?;
+                        let mut assigneds = Vec::new();
+                        for offset in 0..self.0.len() {
+                            q.enable(&mut region, offset)?;
+                            let shuffled_offset = self.0.len() - 1 - offset;
+                            let [value, tag, shuffled_value, shuffled_tag] = [
+                                self.0[offset],
+                                F::from(offset as u64),
+                                self.0[shuffled_offset],
+                                F::from(shuffled_offset as u64),
+                            ]
+                            .map(Value::known);
+                            let assigned =
+                                region.assign_advice(|| "", advices[0], offset, || value)?;
+                            assigneds.push(assigned);
+                            region.assign_advice(|| "", advices[1], offset, || tag)?;
+                            region.assign_advice(|| "", advices[2], offset, || shuffled_value)?;
+                            region.assign_advice(|| "", advices[3], offset, || shuffled_tag)?;
                         }
-                        izip!(advices, &self.0)
-                            .map(|(column, value)| {
-                                let value = Value::known(*value);
-                                region.assign_advice(|| "", column, next_offset(), || value)
-                            })
-                            .try_collect::<_, Vec<_>, _>()
+                        Ok(assigneds)
                     },
                 )?;
-                for (idx, assigned) in izip!(0.., assigneds) {
-                    layouter.constrain_instance(assigned.cell(), instance, idx)?;
+                for (row, assigned) in assigneds.iter().enumerate() {
+                    layouter.constrain_instance(assigned.cell(), instance, row)?;
                 }
                 Ok(())
             }
         }
     }
 
-    pub mod maingate {
-        use crate::{
-            codegen::AccumulatorEncoding,
-            test::halo2::{random_accumulator_limbs, TestCircuit},
-        };
-        use halo2_maingate::{
-            MainGate, MainGateConfig, MainGateInstructions, RangeChip, RangeConfig,
-            RangeInstructions, RegionCtx,
-        };
+    pub mod no_instance {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
         use halo2_proofs::{
-            arithmetic::CurveAffine,
             circuit::{Layouter, SimpleFloorPlanner, Value},
-            halo2curves::{
-                ff::{Field, PrimeField},
-                pairing::MultiMillerLoop,
-            },
-            plonk::{Circuit, ConstraintSystem, Error},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Advice, Circuit, Column, ConstraintSystem, Selector},
+            poly::Rotation,
         };
-        use itertools::Itertools;
         use rand::RngCore;
-        use std::iter;
 
-        #[derive(Clone)]
-        pub struct MainGateWithRangeConfig {
-            main_gate_config: MainGateConfig,
-            range_config: RangeConfig,
-        }
+        /// Circuit without instance column, which proves knowledge of `a` and `b` with `c = a * b`
+        /// only bound inside the proof.
+        #[derive(Clone, Debug, Default)]
+        pub struct NoInstance<F>([F; 2]);
 
-        impl MainGateWithRangeConfig {
-            fn configure<F: PrimeField>(
-                meta: &mut ConstraintSystem<F>,
-                composition_bits: Vec<usize>,
-                overflow_bits: Vec<usize>,
-            ) -> Self {
-                let main_gate_config = MainGate::<F>::configure(meta);
-                let range_config = RangeChip::<F>::configure(
-                    meta,
-                    &main_gate_config,
-                    composition_bits,
-                    overflow_bits,
-                );
-                MainGateWithRangeConfig {
-                    main_gate_config,
-                    range_config,
-                }
+        impl<F: PrimeField> TestCircuit<F> for NoInstance<F> {
+            fn min_k() -> u32 {
+                4
             }
 
-            fn main_gate<F: PrimeField>(&self) -> MainGate<F> {
-                MainGate::new(self.main_gate_config.clone())
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                Self([(); 2].map(|_| F::random(&mut rng)))
             }
 
-            fn range_chip<F: PrimeField>(&self) -> RangeChip<F> {
-                RangeChip::new(self.range_config.clone())
+            fn instances(&self) -> Vec<Vec<F>> {
+                Vec::new()
             }
         }
 
-        #[derive(Clone, Default)]
-        pub struct MainGateWithRange<M: MultiMillerLoop> {
-            instances: Vec<M::Fr>,
+        impl<F: PrimeField> Circuit<F> for NoInstance<F> {
+            type Config = (Selector, [Column<Advice>; 3]);
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                unimplemented!()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selector = meta.selector();
+                let advices = [(); 3].map(|_| meta.advice_column());
+                meta.enable_equality(advices[2]);
+                meta.create_gate("", |meta| {
+                    let q = meta.query_selector(selector);
+                    let [a, b, c] =
+                        advices.map(|advice| meta.query_advice(advice, Rotation::cur()));
+                    Some(q * (a * b - c))
+                });
+                (selector, advices)
+            }
+
+            fn synthesize(
+                &self,
+                (selector, advices): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        let [a, b] = self.0;
+                        selector.enable(&mut region, 0)?;
+                        for (advice, value) in advices.into_iter().zip([a, b, a * b]) {
+                            region.assign_advice(|| "", advice, 0, || Value::known(value))?;
+                        }
+                        Ok(())
+                    },
+                )
+            }
         }
+    }
 
-        impl<M> TestCircuit<M::Fr> for MainGateWithRange<M>
-        where
-            M: MultiMillerLoop,
-            M::G1Affine: CurveAffine<ScalarExt = M::Fr>,
-            <M::G1Affine as CurveAffine>::Base: PrimeField<Repr = [u8; 0x20]>,
-            <M::G1Affine as CurveAffine>::ScalarExt: PrimeField<Repr = [u8; 0x20]>,
-        {
+    pub mod fixed_table {
+        use crate::{codegen::AccumulatorEncoding, test::halo2::TestCircuit};
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner, Value},
+            halo2curves::ff::PrimeField,
+            plonk::{
+                self, Advice, Circuit, Column, ConstraintSystem, Instance, Selector, TableColumn,
+            },
+            poly::Rotation,
+        };
+        use rand::RngCore;
+
+        /// Circuit looking up advice cells in a table of random values, and exposing one of them
+        /// as instance, so fixed commitments depend on the table but constraint system doesn't.
+        #[derive(Clone, Debug, Default)]
+        pub struct FixedTable<F>([F; 4]);
+
+        impl<F: PrimeField> TestCircuit<F> for FixedTable<F> {
             fn min_k() -> u32 {
-                9
+                5
             }
 
-            fn new(acc_encoding: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
-                let instances = if let Some(acc_encoding) = acc_encoding {
-                    random_accumulator_limbs::<M>(acc_encoding, rng)
-                } else {
-                    iter::repeat_with(|| M::Fr::random(&mut rng))
-                        .take(10)
-                        .collect()
-                };
-                Self { instances }
+            fn new(_: Option<AccumulatorEncoding>, mut rng: impl RngCore) -> Self {
+                // Table contains zero for unused rows
+                let [a, b, c] = [(); 3].map(|_| F::random(&mut rng));
+                Self([F::ZERO, a, b, c])
             }
 
-            fn instances(&self) -> Vec<M::Fr> {
-                self.instances.clone()
+            fn instances(&self) -> Vec<Vec<F>> {
+                vec![vec![self.0[1]]]
             }
         }
 
-        impl<M: MultiMillerLoop> Circuit<M::Fr> for MainGateWithRange<M> {
-            type Config = MainGateWithRangeConfig;
+        impl<F: PrimeField> Circuit<F> for FixedTable<F> {
+            type Config = (Selector, Column<Advice>, Column<Instance>, TableColumn);
             type FloorPlanner = SimpleFloorPlanner;
-            #[cfg(feature = "halo2_circuit_params")]
+            #[cfg(feature = "circuit-params")]
             type Params = ();
 
             fn without_witnesses(&self) -> Self {
                 unimplemented!()
             }
 
-            fn configure(meta: &mut ConstraintSystem<M::Fr>) -> Self::Config {
-                MainGateWithRangeConfig::configure(meta, vec![8], vec![4, 7])
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selector = meta.complex_selector();
+                let advice = meta.advice_column();
+                let instance = meta.instance_column();
+                let table = meta.lookup_table_column();
+                meta.enable_equality(advice);
+                meta.enable_equality(instance);
+                meta.lookup("", |meta| {
+                    let q = meta.query_selector(selector);
+                    let a = meta.query_advice(advice, Rotation::cur());
+                    vec![(q * a, table)]
+                });
+                (selector, advice, instance, table)
             }
 
             fn synthesize(
                 &self,
-                config: Self::Config,
-                mut layouter: impl Layouter<M::Fr>,
-            ) -> Result<(), Error> {
-                let main_gate = config.main_gate();
-                let range_chip = config.range_chip();
-                range_chip.load_table(&mut layouter)?;
-
-                let advices = layouter.assign_region(
+                (selector, advice, instance, table): Self::Config,
+                mut layouter: impl Layouter<F>,
+            ) -> Result<(), plonk::Error> {
+                layouter.assign_table(
                     || "",
-                    |region| {
-                        let mut ctx = RegionCtx::new(region, 0);
+                    |mut table_layouter| {
+                        for (offset, value) in self.0.into_iter().enumerate() {
+                            table_layouter.assign_cell(
+                                || "",
+                                table,
+                                offset,
+                                || Value::known(value),
+                            )?;
+                        }
+                        Ok(())
+                    },
+                )?;
+                let cells = layouter.assign_region(
+                    || "",
+                    |mut region| {
+                        self.0
+                            .into_iter()
+                            .enumerate()
+                            .map(|(offset, value)| {
+                                selector.enable(&mut region, offset)?;
+                                region.assign_advice(|| "", advice, offset, || Value::known(value))
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    },
+                )?;
+                layouter.constrain_instance(cells[1].cell(), instance, 0)
+            }
+        }
+    }
 
-                        let advices = self
-                            .instances
-                            .iter()
-                            .map(|value| main_gate.assign_value(&mut ctx, Value::known(*value)))
-                            .try_collect::<_, Vec<_>, _>()?;
+    pub mod unsupported {
+        use halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner},
+            halo2curves::ff::PrimeField,
+            plonk::{self, Circuit, ConstraintSystem},
+            poly::Rotation,
+        };
+        use std::marker::PhantomData;
 
-                        // Dummy gates to make all fixed column with values
-                        range_chip.decompose(
-                            &mut ctx,
-                            Value::known(M::Fr::from(u64::MAX)),
-                            8,
-                            64,
-                        )?;
-                        range_chip.decompose(
-                            &mut ctx,
-                            Value::known(M::Fr::from(u32::MAX as u64)),
-                            8,
-                            39,
-                        )?;
-                        let a = &advices[0];
-                        let b =
-                            main_gate.sub_sub_with_constant(&mut ctx, a, a, a, M::Fr::from(2))?;
-                        let cond = main_gate.assign_bit(&mut ctx, Value::known(M::Fr::ONE))?;
-                        main_gate.select(&mut ctx, a, &b, &cond)?;
+        /// Circuit without column with equality enabled, so it has no permutation argument.
+        #[derive(Clone, Debug, Default)]
+        pub struct NoEquality<F>(PhantomData<F>);
 
-                        Ok(advices)
-                    },
-                )?;
+        impl<F: PrimeField> Circuit<F> for NoEquality<F> {
+            type Config = ();
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
 
-                for (offset, advice) in advices.into_iter().enumerate() {
-                    main_gate.expose_public(layouter.namespace(|| ""), advice, offset)?
-                }
+            fn without_witnesses(&self) -> Self {
+                Self::default()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selector = meta.selector();
+                let advice = meta.advice_column();
+                meta.create_gate("no_equality", |meta| {
+                    let q = meta.query_selector(selector);
+                    let a = meta.query_advice(advice, Rotation::cur());
+                    Some(q * a)
+                });
+            }
+
+            fn synthesize(&self, _: (), _: impl Layouter<F>) -> Result<(), plonk::Error> {
+                Ok(())
+            }
+        }
+
+        /// Circuit querying instance column in a gate with rotation greater than the number of
+        /// blinding factors plus one, whose Lagrange basis is not computed by verifier.
+        #[derive(Clone, Debug, Default)]
+        pub struct RotatedInstanceQuery<F>(PhantomData<F>);
+
+        impl<F: PrimeField> Circuit<F> for RotatedInstanceQuery<F> {
+            type Config = ();
+            type FloorPlanner = SimpleFloorPlanner;
+            #[cfg(feature = "circuit-params")]
+            type Params = ();
+
+            fn without_witnesses(&self) -> Self {
+                Self::default()
+            }
+
+            fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+                let selector = meta.selector();
+                let advice = meta.advice_column();
+                let instance = meta.instance_column();
+                meta.enable_equality(advice);
+                meta.create_gate("rotated_instance", |meta| {
+                    let q = meta.query_selector(selector);
+                    let a = meta.query_advice(advice, Rotation::cur());
+                    let i = meta.query_instance(instance, Rotation(7));
+                    Some(q * (a - i))
+                });
+            }
 
+            fn synthesize(&self, _: (), _: impl Layouter<F>) -> Result<(), plonk::Error> {
                 Ok(())
             }
         }
@@ -9,7 +9,7 @@ use itertools::{chain, Itertools};
 use ruint::aliases::U256;
 use sha3::{Digest, Keccak256};
 use std::{
-    io::{self, Read, Write},
+    io::{self, Cursor, Read, Write},
     marker::PhantomData,
     mem,
 };
@@ -33,6 +33,55 @@ impl<C, S> Keccak256Transcript<C, S> {
     }
 }
 
+impl<C, S> Keccak256Transcript<C, S>
+where
+    C: CurveAffine,
+    C::Scalar: PrimeField<Repr = [u8; 0x20]>,
+{
+    /// Squeeze `n` challenges, which hashes the accumulated buffer by a single `keccak256`, then
+    /// derives each following challenge from the previous hash.
+    ///
+    /// Each following hash is `keccak256(previous_hash || 0x01)`, the same expansion as
+    /// `squeeze_challenge_cont` in the verifier, so the challenges are identical to calling
+    /// `squeeze_challenge` `n` times and remain compatible with proofs and the verifier.
+    pub fn squeeze_n_challenges(&mut self, n: usize) -> Vec<C::Scalar> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut hash = self.squeeze_hash();
+        let mut challenges = Vec::with_capacity(n);
+        challenges.push(u256_to_fe(U256::from_be_bytes(hash)));
+        for _ in 1..n {
+            hash = Keccak256::new()
+                .chain_update(hash)
+                .chain_update([1])
+                .finalize()
+                .into();
+            challenges.push(u256_to_fe(U256::from_be_bytes(hash)));
+        }
+        self.buf = hash.to_vec();
+        challenges
+    }
+
+    /// Hash the accumulated buffer, with an extra `0x01` if only the previous hash is in it.
+    fn squeeze_hash(&mut self) -> [u8; 0x20] {
+        let buf_len = self.buf.len();
+        let data = chain![
+            mem::take(&mut self.buf),
+            if buf_len == 0x20 { Some(1) } else { None }
+        ]
+        .collect_vec();
+        Keccak256::digest(data).into()
+    }
+}
+
+impl<'a, C> Keccak256Transcript<C, Cursor<&'a [u8]>> {
+    /// Return a `Keccak256Transcript` with empty buffer reading from start of `proof`.
+    pub fn from_proof(proof: &'a [u8]) -> Self {
+        Self::new(Cursor::new(proof))
+    }
+}
+
 #[derive(Debug)]
 pub struct ChallengeEvm<C>(C::Scalar)
 where
@@ -61,13 +110,7 @@ where
     C::Scalar: PrimeField<Repr = [u8; 0x20]>,
 {
     fn squeeze_challenge(&mut self) -> ChallengeEvm<C> {
-        let buf_len = self.buf.len();
-        let data = chain![
-            mem::take(&mut self.buf),
-            if buf_len == 0x20 { Some(1) } else { None }
-        ]
-        .collect_vec();
-        let hash: [u8; 0x20] = Keccak256::digest(data).into();
+        let hash = self.squeeze_hash();
         self.buf = hash.to_vec();
         ChallengeEvm::new(&hash)
     }
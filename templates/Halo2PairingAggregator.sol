@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: {{ license }}
+
+pragma solidity {{ pragma }};
+
+/// @title {{ aggregator_name }}
+/// @notice Aggregator of pairings returned by `{{ contract_name }}` rendered with deferred pairing,
+/// which combines them by random linear combination and performs a single pairing for all.
+contract {{ aggregator_name }} {
+    /// @notice Check `e(lhs, g2) * e(rhs, -s_g2) == 1` of all `pairings` at once, each as
+    /// `[lhs_x, lhs_y, rhs_x, rhs_y]` returned by `{{ contract_name }}.{{ fn_name }}`.
+    /// @dev Pairings are only meaningful if they are returned by the verifier, since any pair of
+    /// points satisfying the pairing passes, so callers are expected to record them only from
+    /// successful calls to the verifier.
+    /// @return success True if all pairings hold.
+    function verifyPairings(uint256[4][] calldata pairings) external view returns (bool success) {
+        success = pairings.length != 0;
+        assembly {
+            if success {
+                let r := 21888242871839275222246405745257275088548364400416034343698204186575808495617 // BN254 scalar field
+
+                // Random linear combine pairing lhs and rhs of all pairings by powers of challenge,
+                // which is derived from all of them, in Horner's method from the last pairing
+                let len := mul(pairings.length, 0x80)
+                let ptr := mload(0x40)
+                calldatacopy(ptr, pairings.offset, len)
+                let challenge := mod(keccak256(ptr, len), r)
+                let last := add(pairings.offset, sub(len, 0x80))
+                mstore(ptr, calldataload(last))
+                mstore(add(ptr, 0x20), calldataload(add(last, 0x20)))
+                mstore(add(ptr, 0x80), calldataload(add(last, 0x40)))
+                mstore(add(ptr, 0xa0), calldataload(add(last, 0x60)))
+                for
+                    { let i := sub(pairings.length, 1) }
+                    i
+                    { i := sub(i, 1) }
+                {
+                    let entry := add(pairings.offset, mul(sub(i, 1), 0x80))
+
+                    // [lhs] = challenge * [lhs] + [lhs_i]
+                    mstore(add(ptr, 0x40), challenge)
+                    success := and(success, staticcall(gas(), 0x07, ptr, 0x60, ptr, 0x40))
+                    mstore(add(ptr, 0x40), calldataload(entry))
+                    mstore(add(ptr, 0x60), calldataload(add(entry, 0x20)))
+                    success := and(success, staticcall(gas(), 0x06, ptr, 0x80, ptr, 0x40))
+
+                    // [rhs] = challenge * [rhs] + [rhs_i]
+                    let rhs_ptr := add(ptr, 0x80)
+                    mstore(add(rhs_ptr, 0x40), challenge)
+                    success := and(success, staticcall(gas(), 0x07, rhs_ptr, 0x60, rhs_ptr, 0x40))
+                    mstore(add(rhs_ptr, 0x40), calldataload(add(entry, 0x40)))
+                    mstore(add(rhs_ptr, 0x60), calldataload(add(entry, 0x60)))
+                    success := and(success, staticcall(gas(), 0x06, rhs_ptr, 0x80, rhs_ptr, 0x40))
+                }
+
+                // Perform pairing once for all
+                let rhs_x := mload(add(ptr, 0x80))
+                let rhs_y := mload(add(ptr, 0xa0))
+                {%- for (name, chunk) in g2_constants[..4] %}
+                mstore(add(ptr, {{ (64 + 32 * loop.index0)|hex() }}), {{ chunk|hex_padded(64) }}) // {{ name }}
+                {%- endfor %}
+                mstore(add(ptr, 0xc0), rhs_x)
+                mstore(add(ptr, 0xe0), rhs_y)
+                {%- for (name, chunk) in g2_constants[4..] %}
+                mstore(add(ptr, {{ (256 + 32 * loop.index0)|hex() }}), {{ chunk|hex_padded(64) }}) // {{ name }}
+                {%- endfor %}
+                success := and(success, staticcall(gas(), 0x08, ptr, 0x180, ptr, 0x20))
+                success := and(success, mload(ptr))
+            }
+        }
+    }
+}
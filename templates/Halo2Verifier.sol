@@ -1,568 +1,624 @@
-// SPDX-License-Identifier: MIT
-
-pragma solidity ^0.8.0;
-
-contract Halo2Verifier {
-    uint256 internal constant    PROOF_LEN_CPTR = {{ proof_cptr - 1 }};
-    uint256 internal constant        PROOF_CPTR = {{ proof_cptr }};
-    uint256 internal constant NUM_INSTANCE_CPTR = {{ proof_cptr + (proof_len / 32) }};
-    uint256 internal constant     INSTANCE_CPTR = {{ proof_cptr + (proof_len / 32) + 1 }};
-
-    uint256 internal constant FIRST_QUOTIENT_X_CPTR = {{ quotient_comm_cptr }};
-    uint256 internal constant  LAST_QUOTIENT_X_CPTR = {{ quotient_comm_cptr + 2 * (num_quotients - 1) }};
-
-    uint256 internal constant                VK_MPTR = {{ vk_mptr }};
-    uint256 internal constant         VK_DIGEST_MPTR = {{ vk_mptr }};
-    uint256 internal constant     NUM_INSTANCES_MPTR = {{ vk_mptr + 1 }};
-    uint256 internal constant                 K_MPTR = {{ vk_mptr + 2 }};
-    uint256 internal constant             N_INV_MPTR = {{ vk_mptr + 3 }};
-    uint256 internal constant             OMEGA_MPTR = {{ vk_mptr + 4 }};
-    uint256 internal constant         OMEGA_INV_MPTR = {{ vk_mptr + 5 }};
-    uint256 internal constant    OMEGA_INV_TO_L_MPTR = {{ vk_mptr + 6 }};
-    uint256 internal constant   HAS_ACCUMULATOR_MPTR = {{ vk_mptr + 7 }};
-    uint256 internal constant        ACC_OFFSET_MPTR = {{ vk_mptr + 8 }};
-    uint256 internal constant     NUM_ACC_LIMBS_MPTR = {{ vk_mptr + 9 }};
-    uint256 internal constant NUM_ACC_LIMB_BITS_MPTR = {{ vk_mptr + 10 }};
-    uint256 internal constant              G1_X_MPTR = {{ vk_mptr + 11 }};
-    uint256 internal constant              G1_Y_MPTR = {{ vk_mptr + 12 }};
-    uint256 internal constant            G2_X_1_MPTR = {{ vk_mptr + 13 }};
-    uint256 internal constant            G2_X_2_MPTR = {{ vk_mptr + 14 }};
-    uint256 internal constant            G2_Y_1_MPTR = {{ vk_mptr + 15 }};
-    uint256 internal constant            G2_Y_2_MPTR = {{ vk_mptr + 16 }};
-    uint256 internal constant      NEG_S_G2_X_1_MPTR = {{ vk_mptr + 17 }};
-    uint256 internal constant      NEG_S_G2_X_2_MPTR = {{ vk_mptr + 18 }};
-    uint256 internal constant      NEG_S_G2_Y_1_MPTR = {{ vk_mptr + 19 }};
-    uint256 internal constant      NEG_S_G2_Y_2_MPTR = {{ vk_mptr + 20 }};
-
-    uint256 internal constant CHALLENGE_MPTR = {{ challenge_mptr }};
-
-    uint256 internal constant THETA_MPTR = {{ theta_mptr }};
-    uint256 internal constant  BETA_MPTR = {{ theta_mptr + 1 }};
-    uint256 internal constant GAMMA_MPTR = {{ theta_mptr + 2 }};
-    uint256 internal constant     Y_MPTR = {{ theta_mptr + 3 }};
-    uint256 internal constant     X_MPTR = {{ theta_mptr + 4 }};
-    {%- match scheme %}
-    {%- when Bdfg21 %}
-    uint256 internal constant  ZETA_MPTR = {{ theta_mptr + 5 }};
-    uint256 internal constant    NU_MPTR = {{ theta_mptr + 6 }};
-    uint256 internal constant    MU_MPTR = {{ theta_mptr + 7 }};
-    {%- when Gwc19 %}
-    uint256 internal constant    NU_MPTR = {{ theta_mptr + 5 }};
-    uint256 internal constant    MU_MPTR = {{ theta_mptr + 6 }};
+// SPDX-License-Identifier: {{ license }}
+
+pragma solidity {{ pragma }};
+
+{% match natspec %}{% when Some with (natspec) %}/// @title {{ contract_name }}{% if on_verified_hook %}Base{% endif %}
+/// @notice Verifier of halo2 proof with KZG on BN254 for circuit with {{ natspec }}.
+{% when None %}{% endmatch %}{% if on_verified_hook %}abstract contract {{ contract_name }}Base{% else %}contract {{ contract_name }}{% endif %} {
+    {%- for (doc, constants) in self.constants() %}
+    {%- if !loop.first %}
+{% endif %}
+    {%- if natspec.is_some() %}
+    /// @dev {{ doc }}
+    {%- endif %}
+    {%- for (name, value) in constants %}
+    uint256 internal constant {{ self.pad_constant_name(name, constants) }} = {{ value }};
+    {%- endfor %}
+    {%- endfor %}
+    {%- match on_failure %}
+    {%- when CustomError %}
+
+    /// Stage is 0 for instances, 1 for transcript, 2 for quotient, 3 for pcs and 4 for pairing.
+    error ProofInvalid(uint256 stage);
+    {%- else %}
     {%- endmatch %}
+    {%- if vk_codehash.is_some() %}
 
-    uint256 internal constant       ACC_LHS_X_MPTR = {{ theta_mptr + 8 }};
-    uint256 internal constant       ACC_LHS_Y_MPTR = {{ theta_mptr + 9 }};
-    uint256 internal constant       ACC_RHS_X_MPTR = {{ theta_mptr + 10 }};
-    uint256 internal constant       ACC_RHS_Y_MPTR = {{ theta_mptr + 11 }};
-    uint256 internal constant             X_N_MPTR = {{ theta_mptr + 12 }};
-    uint256 internal constant X_N_MINUS_1_INV_MPTR = {{ theta_mptr + 13 }};
-    uint256 internal constant          L_LAST_MPTR = {{ theta_mptr + 14 }};
-    uint256 internal constant         L_BLIND_MPTR = {{ theta_mptr + 15 }};
-    uint256 internal constant             L_0_MPTR = {{ theta_mptr + 16 }};
-    uint256 internal constant   INSTANCE_EVAL_MPTR = {{ theta_mptr + 17 }};
-    uint256 internal constant   QUOTIENT_EVAL_MPTR = {{ theta_mptr + 18 }};
-    uint256 internal constant      QUOTIENT_X_MPTR = {{ theta_mptr + 19 }};
-    uint256 internal constant      QUOTIENT_Y_MPTR = {{ theta_mptr + 20 }};
-    uint256 internal constant       G1_SCALAR_MPTR = {{ theta_mptr + 21 }};
-    uint256 internal constant   PAIRING_LHS_X_MPTR = {{ theta_mptr + 22 }};
-    uint256 internal constant   PAIRING_LHS_Y_MPTR = {{ theta_mptr + 23 }};
-    uint256 internal constant   PAIRING_RHS_X_MPTR = {{ theta_mptr + 24 }};
-    uint256 internal constant   PAIRING_RHS_Y_MPTR = {{ theta_mptr + 25 }};
-
-    function verifyProof(
-        {%- match self.embedded_vk %}
-        {%- when None %}
-        address vk,
-        {%- else %}
-        {%- endmatch %}
-        bytes calldata proof,
-        uint256[] calldata instances
-    ) public returns (bool) {
-        assembly {
-            // Read EC point (x, y) at (proof_cptr, proof_cptr + 0x20),
-            // and check if the point is on affine plane,
-            // and store them in (hash_mptr, hash_mptr + 0x20).
-            // Return updated (success, proof_cptr, hash_mptr).
-            function read_ec_point(success, proof_cptr, hash_mptr, q) -> ret0, ret1, ret2 {
-                let x := calldataload(proof_cptr)
-                let y := calldataload(add(proof_cptr, 0x20))
-                ret0 := and(success, lt(x, q))
-                ret0 := and(ret0, lt(y, q))
-                ret0 := and(ret0, eq(mulmod(y, y, q), addmod(mulmod(x, mulmod(x, x, q), q), 3, q)))
-                mstore(hash_mptr, x)
-                mstore(add(hash_mptr, 0x20), y)
-                ret1 := add(proof_cptr, 0x40)
-                ret2 := add(hash_mptr, 0x40)
-            }
-
-            // Squeeze challenge by keccak256(memory[0..hash_mptr]),
-            // and store hash mod r as challenge in challenge_mptr,
-            // and push back hash in 0x00 as the first input for next squeeze.
-            // Return updated (challenge_mptr, hash_mptr).
-            function squeeze_challenge(challenge_mptr, hash_mptr, r) -> ret0, ret1 {
-                let hash := keccak256(0x00, hash_mptr)
-                mstore(challenge_mptr, mod(hash, r))
-                mstore(0x00, hash)
-                ret0 := add(challenge_mptr, 0x20)
-                ret1 := 0x20
-            }
-
-            // Squeeze challenge without absorbing new input from calldata,
-            // by putting an extra 0x01 in memory[0x20] and squeeze by keccak256(memory[0..21]),
-            // and store hash mod r as challenge in challenge_mptr,
-            // and push back hash in 0x00 as the first input for next squeeze.
-            // Return updated (challenge_mptr).
-            function squeeze_challenge_cont(challenge_mptr, r) -> ret {
-                mstore8(0x20, 0x01)
-                let hash := keccak256(0x00, 0x21)
-                mstore(challenge_mptr, mod(hash, r))
-                mstore(0x00, hash)
-                ret := add(challenge_mptr, 0x20)
-            }
-
-            // Batch invert values in memory[mptr_start..mptr_end] in place.
-            // Return updated (success).
-            function batch_invert(success, mptr_start, mptr_end, r) -> ret {
-                let gp_mptr := mptr_end
-                let gp := mload(mptr_start)
-                let mptr := add(mptr_start, 0x20)
-                for
-                    {}
-                    lt(mptr, sub(mptr_end, 0x20))
-                    {}
-                {
-                    gp := mulmod(gp, mload(mptr), r)
-                    mstore(gp_mptr, gp)
-                    mptr := add(mptr, 0x20)
-                    gp_mptr := add(gp_mptr, 0x20)
-                }
-                gp := mulmod(gp, mload(mptr), r)
-
-                mstore(gp_mptr, 0x20)
-                mstore(add(gp_mptr, 0x20), 0x20)
-                mstore(add(gp_mptr, 0x40), 0x20)
-                mstore(add(gp_mptr, 0x60), gp)
-                mstore(add(gp_mptr, 0x80), sub(r, 2))
-                mstore(add(gp_mptr, 0xa0), r)
-                ret := and(success, staticcall(gas(), 0x05, gp_mptr, 0xc0, gp_mptr, 0x20))
-                let all_inv := mload(gp_mptr)
-
-                let first_mptr := mptr_start
-                let second_mptr := add(first_mptr, 0x20)
-                gp_mptr := sub(gp_mptr, 0x20)
-                for
-                    {}
-                    lt(second_mptr, mptr)
-                    {}
-                {
-                    let inv := mulmod(all_inv, mload(gp_mptr), r)
-                    all_inv := mulmod(all_inv, mload(mptr), r)
-                    mstore(mptr, inv)
-                    mptr := sub(mptr, 0x20)
-                    gp_mptr := sub(gp_mptr, 0x20)
-                }
-                let inv_first := mulmod(all_inv, mload(second_mptr), r)
-                let inv_second := mulmod(all_inv, mload(first_mptr), r)
-                mstore(first_mptr, inv_first)
-                mstore(second_mptr, inv_second)
-            }
+    error VkCodehashMismatch();
+    {%- endif %}
+    {%- if emit_event %}
 
-            // Add (x, y) into point at (0x00, 0x20).
-            // Return updated (success).
-            function ec_add_acc(success, x, y) -> ret {
-                mstore(0x40, x)
-                mstore(0x60, y)
-                ret := and(success, staticcall(gas(), 0x06, 0x00, 0x80, 0x00, 0x40))
-            }
-
-            // Scale point at (0x00, 0x20) by scalar.
-            function ec_mul_acc(success, scalar) -> ret {
-                mstore(0x40, scalar)
-                ret := and(success, staticcall(gas(), 0x07, 0x00, 0x60, 0x00, 0x40))
-            }
-
-            // Add (x, y) into point at (0x80, 0xa0).
-            // Return updated (success).
-            function ec_add_tmp(success, x, y) -> ret {
-                mstore(0xc0, x)
-                mstore(0xe0, y)
-                ret := and(success, staticcall(gas(), 0x06, 0x80, 0x80, 0x80, 0x40))
-            }
+    event Verified(bool success);
+    {%- endif %}
+    {%- if verify_and_store %}
 
-            // Scale point at (0x80, 0xa0) by scalar.
-            // Return updated (success).
-            function ec_mul_tmp(success, scalar) -> ret {
-                mstore(0xc0, scalar)
-                ret := and(success, staticcall(gas(), 0x07, 0x80, 0x60, 0x80, 0x40))
-            }
-
-            // Perform pairing check.
-            // Return updated (success).
-            function ec_pairing(success, lhs_x, lhs_y, rhs_x, rhs_y) -> ret {
-                mstore(0x00, lhs_x)
-                mstore(0x20, lhs_y)
-                mstore(0x40, mload(G2_X_1_MPTR))
-                mstore(0x60, mload(G2_X_2_MPTR))
-                mstore(0x80, mload(G2_Y_1_MPTR))
-                mstore(0xa0, mload(G2_Y_2_MPTR))
-                mstore(0xc0, rhs_x)
-                mstore(0xe0, rhs_y)
-                mstore(0x100, mload(NEG_S_G2_X_1_MPTR))
-                mstore(0x120, mload(NEG_S_G2_X_2_MPTR))
-                mstore(0x140, mload(NEG_S_G2_Y_1_MPTR))
-                mstore(0x160, mload(NEG_S_G2_Y_2_MPTR))
-                ret := and(success, staticcall(gas(), 0x08, 0x00, 0x180, 0x00, 0x20))
-                ret := and(ret, mload(0x00))
-            }
+    mapping(bytes32 => bool) internal verifiedDigests;
+    {%- endif %}
+    {%- if self.vk_state() %}
+    {%- match vk_address_source %}
+    {%- when Immutable %}
 
-            // Modulus
-            let q := 21888242871839275222246405745257275088696311157297823662689037894645226208583 // BN254 base field
-            let r := 21888242871839275222246405745257275088548364400416034343698204186575808495617 // BN254 scalar field
+    address public immutable vkAddress;
 
-            // Initialize success as true
-            let success := true
-
-            {
-                {%- match self.embedded_vk %}
-                {%- when Some with (embedded_vk) %}
-                // Load vk_digest and num_instances of vk into memory
-                {%- for (name, chunk) in embedded_vk.constants[..2] %}
-                mstore({{ vk_mptr + loop.index0 }}, {{ chunk|hex_padded(64) }}) // {{ name }}
-                {%- endfor %}
-                {%- when None %}
-                // Copy vk_digest and num_instances of vk into memory
-                extcodecopy(vk, VK_MPTR, 0x00, 0x40)
-                {%- endmatch %}
-
-                // Check valid length of proof
-                success := and(success, eq({{ proof_len|hex() }}, calldataload(PROOF_LEN_CPTR)))
-
-                // Check valid length of instances
-                let num_instances := mload(NUM_INSTANCES_MPTR)
-                success := and(success, eq(num_instances, calldataload(NUM_INSTANCE_CPTR)))
-
-                // Absorb vk diegst
-                mstore(0x00, mload(VK_DIGEST_MPTR))
+    constructor(address _vkAddress) {
+        vkAddress = _vkAddress;
+    }
+    {%- when Ownable %}
 
-                // Read instances and witness commitments and generate challenges
-                let hash_mptr := 0x20
-                let instance_cptr := INSTANCE_CPTR
-                for
-                    { let instance_cptr_end := add(instance_cptr, mul(0x20, num_instances)) }
-                    lt(instance_cptr, instance_cptr_end)
-                    {}
-                {
-                    let instance := calldataload(instance_cptr)
-                    success := and(success, lt(instance, r))
-                    mstore(hash_mptr, instance)
-                    instance_cptr := add(instance_cptr, 0x20)
-                    hash_mptr := add(hash_mptr, 0x20)
-                }
+    address public vkAddress;
+    address public owner;
 
-                let proof_cptr := PROOF_CPTR
-                let challenge_mptr := CHALLENGE_MPTR
-                {%- for num_advices in num_advices %}
+    event VkAddressUpdated(address indexed previousVkAddress, address indexed newVkAddress);
+    event OwnershipTransferred(address indexed previousOwner, address indexed newOwner);
 
-                // Phase {{ loop.index }}
-                for
-                    { let proof_cptr_end := add(proof_cptr, {{ (2 * 32 * num_advices)|hex() }}) }
-                    lt(proof_cptr, proof_cptr_end)
-                    {}
-                {
-                    success, proof_cptr, hash_mptr := read_ec_point(success, proof_cptr, hash_mptr, q)
-                }
+    constructor(address _vkAddress) {
+        vkAddress = _vkAddress;
+        owner = msg.sender;
+    }
 
-                challenge_mptr, hash_mptr := squeeze_challenge(challenge_mptr, hash_mptr, r)
-                {%- for _ in 0..num_challenges[loop.index0] - 1 %}
-                challenge_mptr := squeeze_challenge_cont(challenge_mptr, r)
-                {%- endfor %}
-                {%- endfor %}
+    /// @notice Update verifying key address for circuit upgrades, which is only callable by owner.
+    function setVkAddress(address newVkAddress) external {
+        require(msg.sender == owner, "not owner");
+        emit VkAddressUpdated(vkAddress, newVkAddress);
+        vkAddress = newVkAddress;
+    }
 
-                // Read evaluations
-                for
-                    { let proof_cptr_end := add(proof_cptr, {{ (32 * num_evals)|hex() }}) }
-                    lt(proof_cptr, proof_cptr_end)
-                    {}
-                {
-                    let eval := calldataload(proof_cptr)
-                    success := and(success, lt(eval, r))
-                    mstore(hash_mptr, eval)
-                    proof_cptr := add(proof_cptr, 0x20)
-                    hash_mptr := add(hash_mptr, 0x20)
-                }
+    /// @notice Transfer ownership to `newOwner`, which is only callable by owner.
+    function transferOwnership(address newOwner) external {
+        require(msg.sender == owner, "not owner");
+        emit OwnershipTransferred(owner, newOwner);
+        owner = newOwner;
+    }
+    {%- when Calldata %}
+    {%- endmatch %}
+    {%- endif %}
+    {%- if embedded_vk.is_some() %}
 
-                // Read batch opening proof and generate challenges
-                {%- match scheme %}
-                {%- when Bdfg21 %}
-                challenge_mptr, hash_mptr := squeeze_challenge(challenge_mptr, hash_mptr, r)       // zeta
-                challenge_mptr := squeeze_challenge_cont(challenge_mptr, r)                        // nu
+    {%- if natspec.is_some() %}
 
-                success, proof_cptr, hash_mptr := read_ec_point(success, proof_cptr, hash_mptr, q) // W
+    /// @notice Digest of verifying key, which is its `transcript_repr` absorbed first by transcript.
+    {%- endif %}
+    bytes32 public constant VERIFYING_KEY_DIGEST = {{ vk_digest|hex_padded(64) }};
+    {%- else if self.vk_state() %}
 
-                challenge_mptr, hash_mptr := squeeze_challenge(challenge_mptr, hash_mptr, r)       // mu
+    {%- if natspec.is_some() %}
 
-                success, proof_cptr, hash_mptr := read_ec_point(success, proof_cptr, hash_mptr, q) // W'
-                {%- when Gwc19 %}
-                challenge_mptr, hash_mptr := squeeze_challenge(challenge_mptr, hash_mptr, r)       // nu
+    /// @notice Return digest of verifying key at `vkAddress`, which is its `transcript_repr` stored
+    /// as the first word of its runtime code.
+    function VERIFYING_KEY_DIGEST() public view returns (bytes32 digest) {
+    {%- else %}
 
-                for
-                    { let proof_cptr_end := add(proof_cptr, {{ (2 * 32 * num_rotations)|hex() }}) }
-                    lt(proof_cptr, proof_cptr_end)
-                    {}
-                {
-                    success, proof_cptr, hash_mptr := read_ec_point(success, proof_cptr, hash_mptr, q)
-                }
+    function VERIFYING_KEY_DIGEST() public view returns (bytes32 digest) {
+    {%- endif %}
+        address vk = vkAddress;
+        assembly {
+            extcodecopy(vk, 0x00, 0x00, 0x20)
+            digest := mload(0x00)
+        }
+    }
+    {%- else %}
 
-                challenge_mptr, hash_mptr := squeeze_challenge(challenge_mptr, hash_mptr, r)       // mu
-                {%- endmatch %}
+    {%- if natspec.is_some() %}
 
-                {%~ match self.embedded_vk %}
-                {%- when Some with (embedded_vk) %}
-                // Load full vk into memory
-                {%- for (name, chunk) in embedded_vk.constants %}
-                mstore({{ vk_mptr + loop.index0 }}, {{ chunk|hex_padded(64) }}) // {{ name }}
-                {%- endfor %}
-                {%- for (x, y) in embedded_vk.fixed_comms %}
-                {%- let offset = embedded_vk.constants.len() %}
-                mstore({{ vk_mptr + offset + 2 * loop.index0 }}, {{ x|hex_padded(64) }}) // fixed_comms[{{ loop.index0 }}].x
-                mstore({{ vk_mptr + offset + 2 * loop.index0 + 1 }}, {{ y|hex_padded(64) }}) // fixed_comms[{{ loop.index0 }}].y
-                {%- endfor %}
-                {%- for (x, y) in embedded_vk.permutation_comms %}
-                {%- let offset = embedded_vk.constants.len() + 2 * embedded_vk.fixed_comms.len() %}
-                mstore({{ vk_mptr + offset + 2 * loop.index0 }}, {{ x|hex_padded(64) }}) // permutation_comms[{{ loop.index0 }}].x
-                mstore({{ vk_mptr + offset + 2 * loop.index0 + 1 }}, {{ y|hex_padded(64) }}) // permutation_comms[{{ loop.index0 }}].y
-                {%- endfor %}
-                {%- when None %}
-                // Copy full vk into memory
-                extcodecopy(vk, VK_MPTR, 0x00, {{ vk_len|hex() }})
-                {%- endmatch %}
+    /// @notice Return digest of verifying key at `vk`, which is its `transcript_repr` stored as the
+    /// first word of its runtime code.
+    function VERIFYING_KEY_DIGEST(address vk) public view returns (bytes32 digest) {
+    {%- else %}
 
-                // Read accumulator from instances
-                if mload(HAS_ACCUMULATOR_MPTR) {
-                    let num_limbs := mload(NUM_ACC_LIMBS_MPTR)
-                    let num_limb_bits := mload(NUM_ACC_LIMB_BITS_MPTR)
-
-                    let cptr := add(INSTANCE_CPTR, mul(mload(ACC_OFFSET_MPTR), 0x20))
-                    let lhs_y_off := mul(num_limbs, 0x20)
-                    let rhs_x_off := mul(lhs_y_off, 2)
-                    let rhs_y_off := mul(lhs_y_off, 3)
-                    let lhs_x := calldataload(cptr)
-                    let lhs_y := calldataload(add(cptr, lhs_y_off))
-                    let rhs_x := calldataload(add(cptr, rhs_x_off))
-                    let rhs_y := calldataload(add(cptr, rhs_y_off))
-                    for
-                        {
-                            let cptr_end := add(cptr, mul(0x20, num_limbs))
-                            let shift := num_limb_bits
-                        }
-                        lt(cptr, cptr_end)
-                        {}
-                    {
-                        cptr := add(cptr, 0x20)
-                        lhs_x := add(lhs_x, shl(shift, calldataload(cptr)))
-                        lhs_y := add(lhs_y, shl(shift, calldataload(add(cptr, lhs_y_off))))
-                        rhs_x := add(rhs_x, shl(shift, calldataload(add(cptr, rhs_x_off))))
-                        rhs_y := add(rhs_y, shl(shift, calldataload(add(cptr, rhs_y_off))))
-                        shift := add(shift, num_limb_bits)
-                    }
-
-                    success := and(success, and(lt(lhs_x, q), lt(lhs_y, q)))
-                    success := and(success, eq(mulmod(lhs_y, lhs_y, q), addmod(mulmod(lhs_x, mulmod(lhs_x, lhs_x, q), q), 3, q)))
-                    success := and(success, and(lt(rhs_x, q), lt(rhs_y, q)))
-                    success := and(success, eq(mulmod(rhs_y, rhs_y, q), addmod(mulmod(rhs_x, mulmod(rhs_x, rhs_x, q), q), 3, q)))
-
-                    mstore(ACC_LHS_X_MPTR, lhs_x)
-                    mstore(ACC_LHS_Y_MPTR, lhs_y)
-                    mstore(ACC_RHS_X_MPTR, rhs_x)
-                    mstore(ACC_RHS_Y_MPTR, rhs_y)
+    function VERIFYING_KEY_DIGEST(address vk) public view returns (bytes32 digest) {
+    {%- endif %}
+        assembly {
+            extcodecopy(vk, 0x00, 0x00, 0x20)
+            digest := mload(0x00)
+        }
+    }
+    {%- endif %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Verify `proof` of the circuit against `instances`.
+    {%- if self.vk_param() %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    {%- if hashed_instances %}
+    /// @param instancesHash Keccak256 of instances, split into 2 instances of 128 bits.
+    {%- else if num_circuits > 1 %}
+    /// @param instances Instances of all {{ num_circuits }} circuits, concatenated circuit by circuit, each flattened column by column.
+    {%- else %}
+    /// @param instances Instances of all instance columns, flattened column by column.
+    {%- endif %}
+    {%- if deferred_pairing %}
+    /// @return Pairing lhs and rhs `[lhs_x, lhs_y, rhs_x, rhs_y]` to be checked by `{{ contract_name }}PairingAggregator`.
+    {%- else %}
+    /// @return True if the proof is valid.
+    {%- endif %}
+    function {{ fn_name }}(
+    {%- else %}
+
+    function {{ fn_name }}(
+    {%- endif %}
+        {%- if self.vk_param() %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        {%- if hashed_instances %}
+        bytes32 instancesHash
+        {%- else %}
+        uint256[] calldata instances
+        {%- endif %}
+    ) public {% if view %}view {% endif %}returns ({% if deferred_pairing %}uint256[4] memory{% else %}bool{% endif %}) {
+{% if on_verified_hook %}        // Verify by calling itself before calling `_onVerified`, since the verifier below takes over
+        // memory and returns directly
+        if (msg.sender != address(this)) {
+            (bool ok, bytes memory ret) = address(this).call(msg.data);
+            if (!ok) {
+                assembly {
+                    revert(add(ret, 0x20), mload(ret))
                 }
-
-                pop(q)
             }
-
-            // Revert earlier if anything from calldata is invalid
-            if iszero(success) {
-                revert(0, 0)
+            bool verified = abi.decode(ret, (bool));
+            if (verified) {
+                _onVerified(instances);
             }
+            return verified;
+        }
 
-            // Compute lagrange evaluations and instance evaluation
-            {
-                let k := mload(K_MPTR)
-                let x := mload(X_MPTR)
-                let x_n := x
-                for
-                    { let idx := 0 }
-                    lt(idx, k)
-                    { idx := add(idx, 1) }
-                {
-                    x_n := mulmod(x_n, x_n, r)
-                }
-
-                let omega := mload(OMEGA_MPTR)
-
-                let mptr := X_N_MPTR
-                let mptr_end := add(mptr, mul(0x20, add(mload(NUM_INSTANCES_MPTR), {{ num_neg_lagranges }})))
-                if iszero(mload(NUM_INSTANCES_MPTR)) {
-                    mptr_end := add(mptr_end, 0x20)
-                }
-                for
-                    { let pow_of_omega := mload(OMEGA_INV_TO_L_MPTR) }
-                    lt(mptr, mptr_end)
-                    { mptr := add(mptr, 0x20) }
-                {
-                    mstore(mptr, addmod(x, sub(r, pow_of_omega), r))
-                    pow_of_omega := mulmod(pow_of_omega, omega, r)
-                }
-                let x_n_minus_1 := addmod(x_n, sub(r, 1), r)
-                mstore(mptr_end, x_n_minus_1)
-                success := batch_invert(success, X_N_MPTR, add(mptr_end, 0x20), r)
+{% endif %}{% if self.vk_state() %}        address vk = vkAddress;
 
-                mptr := X_N_MPTR
-                let l_i_common := mulmod(x_n_minus_1, mload(N_INV_MPTR), r)
-                for
-                    { let pow_of_omega := mload(OMEGA_INV_TO_L_MPTR) }
-                    lt(mptr, mptr_end)
-                    { mptr := add(mptr, 0x20) }
-                {
-                    mstore(mptr, mulmod(l_i_common, mulmod(mload(mptr), pow_of_omega, r), r))
-                    pow_of_omega := mulmod(pow_of_omega, omega, r)
-                }
-
-                let l_blind := mload(add(X_N_MPTR, 0x20))
-                let l_i_cptr := add(X_N_MPTR, 0x40)
-                for
-                    { let l_i_cptr_end := add(X_N_MPTR, {{ (num_neg_lagranges * 32)|hex() }}) }
-                    lt(l_i_cptr, l_i_cptr_end)
-                    { l_i_cptr := add(l_i_cptr, 0x20) }
-                {
-                    l_blind := addmod(l_blind, mload(l_i_cptr), r)
-                }
+{% endif %}        assembly {
+{% include "Halo2VerifierAssembly.yul" %}
+        }
+    }
+    {%- match packed_instances_len %}
+    {%- when Some with (packed_instances_len) %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Verify `proof` against instances packed in `packedInstances`, which are unpacked
+    /// and checked in range of their bit widths before calling `{{ fn_name }}`.
+    {%- if self.vk_param() %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    /// @param packedInstances Instances flattened column by column, each in big-endian bytes of
+    /// its bit width.
+    /// @return True if the proof is valid.
+    function {{ fn_name }}Packed(
+    {%- else %}
+
+    function {{ fn_name }}Packed(
+    {%- endif %}
+        {%- if self.vk_param() %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        bytes calldata packedInstances
+    ) public {% if view %}view {% endif %}returns (bool) {
+        uint256[] memory instances = new uint256[]({{ num_instances }});
+        bool success = packedInstances.length == {{ packed_instances_len }};
 
-                let instance_eval := 0
-                for
-                    {
-                        let instance_cptr := INSTANCE_CPTR
-                        let instance_cptr_end := add(instance_cptr, mul(0x20, mload(NUM_INSTANCES_MPTR)))
-                    }
-                    lt(instance_cptr, instance_cptr_end)
-                    {
-                        instance_cptr := add(instance_cptr, 0x20)
-                        l_i_cptr := add(l_i_cptr, 0x20)
-                    }
-                {
-                    instance_eval := addmod(instance_eval, mulmod(mload(l_i_cptr), calldataload(instance_cptr), r), r)
-                }
+        // Unpack each instance from big-endian bytes of its bit width, and check it's in range
+        assembly {
+            let cptr := packedInstances.offset
+            let mptr := add(instances, 0x20)
+            {%- for line in packed_instance_computations %}
+            {{ line }}
+            {%- endfor %}
+        }
 
-                let x_n_minus_1_inv := mload(mptr_end)
-                let l_last := mload(X_N_MPTR)
-                let l_0 := mload(add(X_N_MPTR, {{ (num_neg_lagranges * 32)|hex() }}))
+        // Fail if length of packed instances mismatches or any instance is out of range
+        if (!success) {
+            {%- match on_failure %}
+            {%- when Revert %}
+            revert();
+            {%- when ReturnFalse %}
+            {%- if emit_event %}
+            emit Verified(false);
+            {%- endif %}
+            return false;
+            {%- when CustomError %}
+            revert ProofInvalid(0);
+            {%- endmatch %}
+        }
+        {%- if on_verified_hook %}
+        success = this.{{ fn_name }}(
+        {%- else %}
+        return this.{{ fn_name }}(
+        {%- endif %}
+            {%- if self.vk_param() %}
+            vk,
+            {%- endif %}
+            proof,
+            instances
+        );
+        {%- if on_verified_hook %}
+        if (success) {
+            _onVerified(instances);
+        }
+        return success;
+        {%- endif %}
+    }
+    {%- when None %}
+    {%- endmatch %}
+    {%- match nested_instances_lens %}
+    {%- when Some with (nested_instances_lens) %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Verify `proof` against instances of each instance column in `nestedInstances`,
+    /// which are checked to be of expected lengths and flattened column by column before calling
+    /// `{{ fn_name }}`.
+    {%- if self.vk_param() %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    /// @param nestedInstances Instances of each instance column.
+    /// @return True if the proof is valid.
+    function {{ fn_name }}Nested(
+    {%- else %}
+
+    function {{ fn_name }}Nested(
+    {%- endif %}
+        {%- if self.vk_param() %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        uint256[][] calldata nestedInstances
+    ) public {% if view %}view {% endif %}returns (bool) {
+        uint256[] memory instances = new uint256[]({{ num_instances }});
+        bool success = nestedInstances.length == {{ nested_instances_lens.len() }};
 
-                mstore(X_N_MPTR, x_n)
-                mstore(X_N_MINUS_1_INV_MPTR, x_n_minus_1_inv)
-                mstore(L_LAST_MPTR, l_last)
-                mstore(L_BLIND_MPTR, l_blind)
-                mstore(L_0_MPTR, l_0)
-                mstore(INSTANCE_EVAL_MPTR, instance_eval)
+        // Copy instances of each column after the previous one, and check it's of expected length
+        uint256 mptr;
+        assembly {
+            mptr := add(instances, 0x20)
+        }
+        {%- for len in nested_instances_lens %}
+        if (success) {
+            uint256[] calldata column = nestedInstances[{{ loop.index0 }}];
+            success = column.length == {{ len }};
+            assembly {
+                calldatacopy(mptr, column.offset, {{ len * 32 }})
+                mptr := add(mptr, {{ len * 32 }})
             }
+        }
+        {%- endfor %}
+
+        // Fail if number of columns or length of any column mismatches
+        if (!success) {
+            {%- match on_failure %}
+            {%- when Revert %}
+            revert();
+            {%- when ReturnFalse %}
+            {%- if emit_event %}
+            emit Verified(false);
+            {%- endif %}
+            return false;
+            {%- when CustomError %}
+            revert ProofInvalid(0);
+            {%- endmatch %}
+        }
+        {%- if on_verified_hook %}
+        success = this.{{ fn_name }}(
+        {%- else %}
+        return this.{{ fn_name }}(
+        {%- endif %}
+            {%- if self.vk_param() %}
+            vk,
+            {%- endif %}
+            proof,
+            instances
+        );
+        {%- if on_verified_hook %}
+        if (success) {
+            _onVerified(instances);
+        }
+        return success;
+        {%- endif %}
+    }
+    {%- when None %}
+    {%- endmatch %}
+    {%- if verify_and_store %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Verify `proof` against `instances` by calling `{{ fn_name }}`, and record the digest
+    /// of them once the proof is valid, so it can be re-checked cheaply by `isVerified`.
+    {%- if self.vk_param() %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    {%- if hashed_instances %}
+    /// @param instancesHash Keccak256 of instances, split into 2 instances of 128 bits.
+    {%- else %}
+    /// @param instances Instances of all instance columns, flattened column by column.
+    {%- endif %}
+    /// @return True if the proof is valid.
+    function verifyAndStore(
+    {%- else %}
+
+    function verifyAndStore(
+    {%- endif %}
+        {%- if self.vk_param() %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        {%- if hashed_instances %}
+        bytes32 instancesHash
+        {%- else %}
+        uint256[] calldata instances
+        {%- endif %}
+    ) public returns (bool) {
+        bool success = this.{{ fn_name }}(
+            {%- if self.vk_param() %}
+            vk,
+            {%- endif %}
+            proof,
+            {%- if hashed_instances %}
+            instancesHash
+            {%- else %}
+            instances
+            {%- endif %}
+        );
+
+        // Record digest of proof and instances, bound to the verifying key if it's passed in
+        if (success) {
+            bytes32 digest = keccak256(
+                abi.encodePacked(
+                    {%- if self.vk_param() %}
+                    vk,
+                    {%- endif %}
+                    proof,
+                    {%- if hashed_instances %}
+                    instancesHash
+                    {%- else %}
+                    instances
+                    {%- endif %}
+                )
+            );
+            verifiedDigests[digest] = true;
+            {%- if on_verified_hook %}
+            _onVerified(instances);
+            {%- endif %}
+        }
+        return success;
+    }
 
-            // Compute quotient evavluation
-            {
-                let quotient_eval_numer
-                let delta := 4131629893567559867359510883348571134090853742863529169391034518566172092834
-                let y := mload(Y_MPTR)
-
-                {%- for code_block in quotient_eval_numer_computations %}
-                {
-                    {%- for line in code_block %}
-                    {{ line }}
-                    {%- endfor %}
-                }
-                {%- endfor %}
+    {%- if natspec.is_some() %}
 
-                pop(y)
-                pop(delta)
+    /// @notice Return whether proof and instances of `digest` are verified by `verifyAndStore`.
+    /// @param digest Keccak256 of proof followed by instances, in the same encoding as calldata,
+    /// and prefixed by verifying key address if it's passed in.
+    /// @return True if verified.
+    function isVerified(bytes32 digest) public view returns (bool) {
+    {%- else %}
 
-                let quotient_eval := mulmod(quotient_eval_numer, mload(X_N_MINUS_1_INV_MPTR), r)
-                mstore(QUOTIENT_EVAL_MPTR, quotient_eval)
+    function isVerified(bytes32 digest) public view returns (bool) {
+    {%- endif %}
+        return verifiedDigests[digest];
+    }
+    {%- endif %}
+    {%- if batch_verify %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Verify each of `proofs` against `instances` of the same index, with pairings of
+    /// all proofs combined into one.
+    {%- if self.vk_param() %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proofs Proofs created with `Keccak256Transcript`.
+    {%- if hashed_instances %}
+    /// @param instances Keccak256 of instances of each proof.
+    {%- else %}
+    /// @param instances Instances of each proof, flattened column by column.
+    {%- endif %}
+    /// @return True if all proofs are valid.
+    function verifyProofs(
+    {%- else %}
+
+    function verifyProofs(
+    {%- endif %}
+        {%- if self.vk_param() %}
+        address vk,
+        {%- endif %}
+        bytes[] calldata proofs,
+        {%- if hashed_instances %}
+        bytes32[] calldata instances
+        {%- else %}
+        uint256[][] calldata instances
+        {%- endif %}
+    ) public {% if view %}view {% endif %}returns (bool) {
+{% if self.vk_state() %}        address vk = vkAddress;
+{% endif %}        bool success = proofs.length != 0 && proofs.length == instances.length;
+
+        // Verify each proof by calling `{{ fn_name }}` of itself, which returns pairing lhs and rhs
+        // instead of performing pairing, and collect them in `pairings`
+        bytes memory pairings = new bytes(0x80 * proofs.length);
+        for (uint256 i = 0; success && i < proofs.length; i++) {
+            (bool ok, bytes memory ret) = address(this).staticcall(
+                abi.encodeWithSelector(
+                    this.{{ fn_name }}.selector,
+                    {%- if self.vk_param() %}
+                    vk,
+                    {%- endif %}
+                    proofs[i],
+                    instances[i]
+                )
+            );
+            if (!ok) {
+                // Bubble up revert reason of the invalid proof
+                assembly {
+                    revert(add(ret, 0x20), mload(ret))
+                }
+            }
+            success = ret.length == 0x80;
+            assembly {
+                let src := add(ret, 0x20)
+                let dst := add(add(pairings, 0x20), mul(i, 0x80))
+                mstore(dst, mload(src))
+                mstore(add(dst, 0x20), mload(add(src, 0x20)))
+                mstore(add(dst, 0x40), mload(add(src, 0x40)))
+                mstore(add(dst, 0x60), mload(add(src, 0x60)))
             }
+        }
 
-            // Compute quotient commitment
-            {
-                mstore(0x00, calldataload(LAST_QUOTIENT_X_CPTR))
-                mstore(0x20, calldataload(add(LAST_QUOTIENT_X_CPTR, 0x20)))
-                let x_n := mload(X_N_MPTR)
+        assembly {
+            if success {
+                let r := 21888242871839275222246405745257275088548364400416034343698204186575808495617 // BN254 scalar field
+
+                // Random linear combine pairing lhs and rhs of all proofs by powers of challenge,
+                // which is derived from all of them, in Horner's method from the last proof
+                let challenge := mod(keccak256(add(pairings, 0x20), mload(pairings)), r)
+                let ptr := mload(0x40)
+                let entry := add(pairings, sub(mload(pairings), 0x60))
+                mstore(ptr, mload(entry))
+                mstore(add(ptr, 0x20), mload(add(entry, 0x20)))
+                mstore(add(ptr, 0x80), mload(add(entry, 0x40)))
+                mstore(add(ptr, 0xa0), mload(add(entry, 0x60)))
                 for
-                    {
-                        let cptr := sub(LAST_QUOTIENT_X_CPTR, 0x40)
-                        let cptr_end := sub(FIRST_QUOTIENT_X_CPTR, 0x40)
-                    }
-                    lt(cptr_end, cptr)
-                    {}
+                    { entry := sub(entry, 0x80) }
+                    lt(pairings, entry)
+                    { entry := sub(entry, 0x80) }
                 {
-                    success := ec_mul_acc(success, x_n)
-                    success := ec_add_acc(success, calldataload(cptr), calldataload(add(cptr, 0x20)))
-                    cptr := sub(cptr, 0x40)
+                    // [lhs] = challenge * [lhs] + [lhs_i]
+                    mstore(add(ptr, 0x40), challenge)
+                    success := and(success, staticcall(gas(), 0x07, ptr, 0x60, ptr, 0x40))
+                    mstore(add(ptr, 0x40), mload(entry))
+                    mstore(add(ptr, 0x60), mload(add(entry, 0x20)))
+                    success := and(success, staticcall(gas(), 0x06, ptr, 0x80, ptr, 0x40))
+
+                    // [rhs] = challenge * [rhs] + [rhs_i]
+                    let rhs_ptr := add(ptr, 0x80)
+                    mstore(add(rhs_ptr, 0x40), challenge)
+                    success := and(success, staticcall(gas(), 0x07, rhs_ptr, 0x60, rhs_ptr, 0x40))
+                    mstore(add(rhs_ptr, 0x40), mload(add(entry, 0x40)))
+                    mstore(add(rhs_ptr, 0x60), mload(add(entry, 0x60)))
+                    success := and(success, staticcall(gas(), 0x06, rhs_ptr, 0x80, rhs_ptr, 0x40))
                 }
-                mstore(QUOTIENT_X_MPTR, mload(0x00))
-                mstore(QUOTIENT_Y_MPTR, mload(0x20))
-            }
 
-            // Compute pairing lhs and rhs
-            {
-                {%- for code_block in pcs_computations %}
-                {
-                    {%- for line in code_block %}
-                    {{ line }}
-                    {%- endfor %}
-                }
+                // Perform pairing once for all proofs
+                let rhs_x := mload(add(ptr, 0x80))
+                let rhs_y := mload(add(ptr, 0xa0))
+                {%- match self.embedded_vk %}
+                {%- when Some with (embedded_vk) %}
+                {%- for (name, chunk) in embedded_vk.constants[13..17] %}
+                mstore(add(ptr, {{ (64 + 32 * loop.index0)|hex() }}), {{ chunk|hex_padded(64) }}) // {{ name }}
                 {%- endfor %}
+                mstore(add(ptr, 0xc0), rhs_x)
+                mstore(add(ptr, 0xe0), rhs_y)
+                {%- for (name, chunk) in embedded_vk.constants[17..21] %}
+                mstore(add(ptr, {{ (256 + 32 * loop.index0)|hex() }}), {{ chunk|hex_padded(64) }}) // {{ name }}
+                {%- endfor %}
+                {%- when None %}
+                extcodecopy(vk, add(ptr, 0x40), sub(G2_X_1_MPTR, VK_MPTR), 0x80)
+                mstore(add(ptr, 0xc0), rhs_x)
+                mstore(add(ptr, 0xe0), rhs_y)
+                extcodecopy(vk, add(ptr, 0x100), sub(NEG_S_G2_X_1_MPTR, VK_MPTR), 0x80)
+                {%- endmatch %}
+                success := and(success, staticcall(gas(), 0x08, ptr, 0x180, ptr, 0x20))
+                success := and(success, mload(ptr))
             }
+        }
 
-            // Random linear combine with accumulator
-            if mload(HAS_ACCUMULATOR_MPTR) {
-                mstore(0x00, mload(ACC_LHS_X_MPTR))
-                mstore(0x20, mload(ACC_LHS_Y_MPTR))
-                mstore(0x40, mload(ACC_RHS_X_MPTR))
-                mstore(0x60, mload(ACC_RHS_Y_MPTR))
-                mstore(0x80, mload(PAIRING_LHS_X_MPTR))
-                mstore(0xa0, mload(PAIRING_LHS_Y_MPTR))
-                mstore(0xc0, mload(PAIRING_RHS_X_MPTR))
-                mstore(0xe0, mload(PAIRING_RHS_Y_MPTR))
-                let challenge := mod(keccak256(0x00, 0x100), r)
-
-                // [pairing_lhs] += challenge * [acc_lhs]
-                success := ec_mul_acc(success, challenge)
-                success := ec_add_acc(success, mload(PAIRING_LHS_X_MPTR), mload(PAIRING_LHS_Y_MPTR))
-                mstore(PAIRING_LHS_X_MPTR, mload(0x00))
-                mstore(PAIRING_LHS_Y_MPTR, mload(0x20))
-
-                // [pairing_rhs] += challenge * [acc_rhs]
-                mstore(0x00, mload(ACC_RHS_X_MPTR))
-                mstore(0x20, mload(ACC_RHS_Y_MPTR))
-                success := ec_mul_acc(success, challenge)
-                success := ec_add_acc(success, mload(PAIRING_RHS_X_MPTR), mload(PAIRING_RHS_Y_MPTR))
-                mstore(PAIRING_RHS_X_MPTR, mload(0x00))
-                mstore(PAIRING_RHS_Y_MPTR, mload(0x20))
-            }
-
-            // Perform pairing
-            success := ec_pairing(
-                success,
-                mload(PAIRING_LHS_X_MPTR),
-                mload(PAIRING_LHS_Y_MPTR),
-                mload(PAIRING_RHS_X_MPTR),
-                mload(PAIRING_RHS_Y_MPTR)
-            )
-
-            // Revert if anything fails
-            if iszero(success) {
-                revert(0x00, 0x00)
+        // Fail if any proof is invalid
+        if (!success) {
+            {%- match on_failure %}
+            {%- when Revert %}
+            revert();
+            {%- when ReturnFalse %}
+            {%- if emit_event %}
+            emit Verified(false);
+            {%- endif %}
+            return false;
+            {%- when CustomError %}
+            revert ProofInvalid(4);
+            {%- endmatch %}
+        }
+        {%- if emit_event %}
+        emit Verified(true);
+        {%- endif %}
+        return true;
+    }
+    {%- match num_proofs %}
+    {%- when Some with (num_proofs) %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Verify {{ num_proofs }} proofs concatenated in `proofs` by calling `verifyProofs`.
+    {%- if self.vk_param() %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proofs Each proof prefixed by its length and followed by number of its instances
+    /// and instances flattened column by column, with lengths and instances in 32 bytes words.
+    /// @return True if all proofs are valid.
+    function verifyProofsV2(
+    {%- else %}
+
+    function verifyProofsV2(
+    {%- endif %}
+        {%- if self.vk_param() %}
+        address vk,
+        {%- endif %}
+        bytes calldata proofs
+    ) public {% if view %}view {% endif %}returns (bool) {
+        bytes[] memory proofItems = new bytes[]({{ num_proofs }});
+        uint256[][] memory instanceItems = new uint256[][]({{ num_proofs }});
+        bool success = proofs.length == {{ num_proofs * (proof_len + 32 * num_instances + 64) }};
+
+        // Split each proof and its instances, and check their length prefixes
+        for (uint256 i = 0; success && i < {{ num_proofs }}; i++) {
+            uint256 offset = i * {{ proof_len + 32 * num_instances + 64 }};
+            uint256 instancesOffset = offset + {{ proof_len + 64 }};
+            uint256[] memory instances = new uint256[]({{ num_instances }});
+            assembly {
+                let cptr := add(proofs.offset, offset)
+                success := eq(calldataload(cptr), {{ proof_len }})
+                cptr := add(proofs.offset, sub(instancesOffset, 0x20))
+                success := and(success, eq(calldataload(cptr), {{ num_instances }}))
+                calldatacopy(add(instances, 0x20), add(cptr, 0x20), {{ 32 * num_instances }})
             }
+            proofItems[i] = proofs[offset + 0x20:instancesOffset - 0x20];
+            instanceItems[i] = instances;
+        }
 
-            // Return 1 as result if everything succeeds
-            mstore(0x00, 1)
-            return(0x00, 0x20)
+        // Fail if length of proofs or any length prefix mismatches
+        if (!success) {
+            {%- match on_failure %}
+            {%- when Revert %}
+            revert();
+            {%- when ReturnFalse %}
+            {%- if emit_event %}
+            emit Verified(false);
+            {%- endif %}
+            return false;
+            {%- when CustomError %}
+            revert ProofInvalid(0);
+            {%- endmatch %}
         }
+        return this.verifyProofs(
+            {%- if self.vk_param() %}
+            vk,
+            {%- endif %}
+            proofItems,
+            instanceItems
+        );
     }
+    {%- when None %}
+    {%- endmatch %}
+    {%- endif %}
+    {%- if on_verified_hook %}
+
+    {%- if natspec.is_some() %}
+
+    /// @notice Called with `instances` once the proof is verified by any verify function, except
+    /// when called by the contract itself. Override it to act on verified instances.
+    /// @param instances Instances of all instance columns, flattened column by column.
+    function _onVerified(uint256[] memory instances) internal virtual;
+    {%- else %}
+
+    function _onVerified(uint256[] memory instances) internal virtual;
+    {%- endif %}
+    {%- endif %}
 }
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: {{ license }}
+
+pragma solidity {{ pragma }};
+
+/// @title {{ registry_name }}
+/// @notice Registry of verifying keys of `{{ contract_name }}`, which only dispatches proofs to the
+/// verifier with verifying key registered by owner, so a caller can't pass a verifying key of
+/// another circuit.
+contract {{ registry_name }} {
+    /// @notice Verifier rendered separately, which proofs are dispatched to.
+    address public immutable verifier;
+    /// @notice Owner who registers and unregisters verifying keys.
+    address public immutable owner;
+    /// @notice Code hash of each registered verifying key, or zero if unregistered.
+    mapping(address => bytes32) public codehashes;
+
+    event Registered(address indexed vk, bytes32 codehash);
+    event Unregistered(address indexed vk);
+
+    constructor(address _verifier) {
+        verifier = _verifier;
+        owner = msg.sender;
+    }
+
+    /// @notice Register `vk` if its code hash is `codehash`, which is expected to be computed
+    /// off-chain from the verifying key.
+    function register(address vk, bytes32 codehash) external {
+        require(msg.sender == owner, "not owner");
+        require(codehash != 0 && vk.codehash == codehash, "codehash mismatch");
+        codehashes[vk] = codehash;
+        emit Registered(vk, codehash);
+    }
+
+    /// @notice Unregister `vk`.
+    function unregister(address vk) external {
+        require(msg.sender == owner, "not owner");
+        delete codehashes[vk];
+        emit Unregistered(vk);
+    }
+
+{% if vk_param %}    /// @notice Verify proof against instances by verifier with registered `vk`, which takes the
+    /// same calldata as the verifier.
+{% else %}    /// @notice Verify proof against instances by verifier only if its current verifying key at
+    /// `vkAddress()` is registered, which takes the same calldata as the verifier.
+{% endif %}    function {{ fn_name }}(
+        {%- if vk_param %}
+        address vk,
+        {%- endif %}
+        bytes calldata, // proof
+        {%- if hashed_instances %}
+        bytes32 // instancesHash
+        {%- else %}
+        uint256[] calldata // instances
+        {%- endif %}
+    ) external {% if view %}view {% endif %}returns ({% if deferred_pairing %}uint256[4] memory{% else %}bool{% endif %}) {
+        {%- if !vk_param %}
+        (, bytes memory vkAddress) = verifier.staticcall(abi.encodeWithSignature("vkAddress()"));
+        address vk = abi.decode(vkAddress, (address));
+        {%- endif %}
+        bytes32 codehash = codehashes[vk];
+        require(codehash != 0 && vk.codehash == codehash, "vk not registered");
+
+        // Forward calldata to verifier as is, and return or revert with its return data
+        (bool success, bytes memory ret) = verifier.{% if view %}staticcall{% else %}call{% endif %}(msg.data);
+        assembly {
+            if iszero(success) {
+                revert(add(ret, 0x20), mload(ret))
+            }
+            return(add(ret, 0x20), mload(ret))
+        }
+    }
+}
@@ -1,8 +1,14 @@
-// SPDX-License-Identifier: MIT
+// SPDX-License-Identifier: {{ license }}
 
-pragma solidity ^0.8.0;
+pragma solidity {{ pragma }};
 
-contract Halo2VerifyingKey {
+{% match natspec %}{% when Some with (natspec) %}/// @title {{ contract_name }}
+/// @notice Verifying key of halo2 circuit with {{ natspec }}.
+/// @dev Runtime code is the verifying key itself, which is read by verifier with `extcodecopy`.
+{% when None %}{% endmatch %}contract {{ contract_name }} {
+    {%- if natspec.is_some() %}
+    /// @notice Return verifying key as runtime code.
+    {%- endif %}
     constructor() {
         assembly {
             {%- for (name, chunk) in constants %}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: {{ license }}
+
+pragma solidity {{ pragma }};
+
+/// @title {{ interface_name }}
+/// @notice Interface of `{{ contract_name }}`, which verifies halo2 proof with KZG on BN254.
+interface {{ interface_name }} {
+    /// @notice Verify `proof` against `instances`.
+    /// @dev Calldata is the standard ABI encoding, laid out in bytes as below:
+    {%- for (start, end, description) in calldata_layout %}
+    /// - `[{{ start|hex() }}, {{ end|hex() }})`: {{ description }}
+    {%- endfor %}
+    {%- if separate %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    {%- if hashed_instances %}
+    /// @param instancesHash Keccak256 of instances, split into 2 instances of 128 bits.
+    {%- else %}
+    /// @param instances Instances of all instance columns, flattened column by column.
+    {%- endif %}
+    {%- if deferred_pairing %}
+    /// @return Pairing lhs and rhs `[lhs_x, lhs_y, rhs_x, rhs_y]` to be checked by `{{ contract_name }}PairingAggregator`.
+    {%- else %}
+    /// @return True if the proof is valid.
+    {%- endif %}
+    function {{ fn_name }}(
+        {%- if separate %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        {%- if hashed_instances %}
+        bytes32 instancesHash
+        {%- else %}
+        uint256[] calldata instances
+        {%- endif %}
+    ) external {% if view %}view {% endif %}returns ({% if deferred_pairing %}uint256[4] memory{% else %}bool{% endif %});
+    {%- if packed_instances %}
+
+    /// @notice Verify `proof` against instances packed in `packedInstances`, which are unpacked
+    /// and checked in range of their bit widths before calling `{{ fn_name }}`.
+    {%- if separate %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    /// @param packedInstances Instances flattened column by column, each in big-endian bytes of
+    /// its bit width.
+    /// @return True if the proof is valid.
+    function {{ fn_name }}Packed(
+        {%- if separate %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        bytes calldata packedInstances
+    ) external {% if view %}view {% endif %}returns (bool);
+    {%- endif %}
+    {%- if nested_instances %}
+
+    /// @notice Verify `proof` against instances of each instance column in `nestedInstances`,
+    /// which are checked to be of expected lengths and flattened column by column before calling
+    /// `{{ fn_name }}`.
+    {%- if separate %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    /// @param nestedInstances Instances of each instance column.
+    /// @return True if the proof is valid.
+    function {{ fn_name }}Nested(
+        {%- if separate %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        uint256[][] calldata nestedInstances
+    ) external {% if view %}view {% endif %}returns (bool);
+    {%- endif %}
+    {%- if verify_and_store %}
+
+    /// @notice Verify `proof` against `instances` by calling `{{ fn_name }}`, and record the digest
+    /// of them once the proof is valid, so it can be re-checked cheaply by `isVerified`.
+    {%- if separate %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proof Proof created with `Keccak256Transcript`.
+    {%- if hashed_instances %}
+    /// @param instancesHash Keccak256 of instances, split into 2 instances of 128 bits.
+    {%- else %}
+    /// @param instances Instances of all instance columns, flattened column by column.
+    {%- endif %}
+    /// @return True if the proof is valid.
+    function verifyAndStore(
+        {%- if separate %}
+        address vk,
+        {%- endif %}
+        bytes calldata proof,
+        {%- if hashed_instances %}
+        bytes32 instancesHash
+        {%- else %}
+        uint256[] calldata instances
+        {%- endif %}
+    ) external returns (bool);
+
+    /// @notice Return whether proof and instances of `digest` are verified by `verifyAndStore`.
+    /// @param digest Keccak256 of proof followed by instances, in the same encoding as calldata,
+    /// and prefixed by verifying key address if it's passed in.
+    /// @return True if verified.
+    function isVerified(bytes32 digest) external view returns (bool);
+    {%- endif %}
+    {%- if batch_verify %}
+
+    /// @notice Verify each of `proofs` against `instances` of the same index, with pairings of
+    /// all proofs combined into one.
+    {%- if separate %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proofs Proofs created with `Keccak256Transcript`.
+    {%- if hashed_instances %}
+    /// @param instances Keccak256 of instances of each proof.
+    {%- else %}
+    /// @param instances Instances of each proof, flattened column by column.
+    {%- endif %}
+    /// @return True if all proofs are valid.
+    function verifyProofs(
+        {%- if separate %}
+        address vk,
+        {%- endif %}
+        bytes[] calldata proofs,
+        {%- if hashed_instances %}
+        bytes32[] calldata instances
+        {%- else %}
+        uint256[][] calldata instances
+        {%- endif %}
+    ) external {% if view %}view {% endif %}returns (bool);
+    {%- match num_proofs %}
+    {%- when Some with (num_proofs) %}
+
+    /// @notice Verify {{ num_proofs }} proofs concatenated in `proofs` by calling `verifyProofs`.
+    {%- if separate %}
+    /// @param vk Address of the deployed verifying key contract.
+    {%- endif %}
+    /// @param proofs Each proof prefixed by its length and followed by number of its instances
+    /// and instances flattened column by column, with lengths and instances in 32 bytes words.
+    /// @return True if all proofs are valid.
+    function verifyProofsV2(
+        {%- if separate %}
+        address vk,
+        {%- endif %}
+        bytes calldata proofs
+    ) external {% if view %}view {% endif %}returns (bool);
+    {%- when None %}
+    {%- endmatch %}
+    {%- endif %}
+    {%- match on_failure %}
+    {%- when CustomError %}
+
+    /// Stage is 0 for instances, 1 for transcript, 2 for quotient, 3 for pcs and 4 for pairing.
+    error ProofInvalid(uint256 stage);
+    {%- else %}
+    {%- endmatch %}
+    {%- if emit_event %}
+
+    /// @notice Emitted with the result when a proof is verified without reverting.
+    event Verified(bool success);
+    {%- endif %}
+}
@@ -0,0 +1,26 @@
+// Generated by halo2-solidity-verifier, deploying the verifying key first, then the verifier.
+module.exports = async ({ getNamedAccounts, deployments }) => {
+  const { deploy } = deployments;
+  const { deployer } = await getNamedAccounts();
+
+  const vk = await deploy("{{ vk_contract_name }}", {
+    from: deployer,
+    log: true,
+  });
+  const verifier = await deploy("{{ contract_name }}", {
+    from: deployer,
+    {%- match vk_address_source %}
+    {%- when Calldata %}
+    {%- else %}
+    args: [vk.address],
+    {%- endmatch %}
+    log: true,
+  });
+  {%- match vk_address_source %}
+  {%- when Calldata %}
+  console.log(`Pass ${vk.address} as the first argument of {{ fn_name }} of ${verifier.address}`);
+  {%- else %}
+  console.log(`{{ contract_name }} at ${verifier.address} verifies with ${vk.address}`);
+  {%- endmatch %}
+};
+module.exports.tags = ["{{ contract_name }}"];
@@ -0,0 +1,33 @@
+// Generated by halo2-solidity-verifier, expecting contracts rendered by `render_separately` at:
+// - contracts/{{ verifier_filename }}
+// - contracts/{{ vk_filename }}
+// and the deploy script rendered by `render_hardhat_deploy_script` at deploy/.
+require("hardhat-deploy");
+
+/** @type import('hardhat/config').HardhatUserConfig */
+module.exports = {
+  solidity: {
+    version: "{{ solc_version }}",
+    settings: {
+      optimizer: {
+        enabled: true,
+        runs: 200,
+      },
+    },
+  },
+  networks: {
+    {{ network }}: {
+      url: {{ rpc_url }},
+      accounts: process.env.PRIVATE_KEY ? [process.env.PRIVATE_KEY] : [],
+    },
+  },
+  namedAccounts: {
+    deployer: {
+      default: 0,
+    },
+  },
+  paths: {
+    sources: "contracts",
+    deploy: "deploy",
+  },
+};
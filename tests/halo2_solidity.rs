@@ -263,68 +263,20 @@ impl<F: halo2_proofs::arithmetic::Field> Circuit<F> for P8sTestCircuit<F> {
 #[test]
 fn test_pythagoras_solidity_verifier() {
     use halo2_proofs::poly::kzg::commitment::ParamsKZG;
-    use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine};
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
     use halo2_proofs::dev::MockProver;
-    use halo2_proofs::plonk::{keygen_vk, keygen_pk, ProvingKey, create_proof, verify_proof};
-    use halo2_proofs::transcript::TranscriptWriterBuffer;
+    use halo2_proofs::plonk::{keygen_vk, keygen_pk};
 
     use halo2_solidity_verifier::SolidityGenerator;
     use halo2_solidity_verifier::BatchOpenScheme::Bdfg21;
     use halo2_solidity_verifier::Evm;
     use halo2_solidity_verifier::compile_solidity;
     use halo2_solidity_verifier::encode_calldata;
-    use halo2_solidity_verifier::Keccak256Transcript;
 
-    use log::LevelFilter;
     use log::info;
     use colored::Colorize;
-    use rand::RngCore;
 
-    fn create_proof_checked(
-        params: &ParamsKZG<Bn256>,
-        pk: &ProvingKey<G1Affine>,
-        circuit: impl Circuit<Fr>,
-        instances: &[Fr],
-        mut rng: impl RngCore,
-    ) -> Vec<u8> {
-        use halo2_proofs::poly::kzg::{
-            multiopen::{ProverSHPLONK, VerifierSHPLONK},
-            strategy::SingleStrategy,
-        };
-    
-        let proof = {
-            let mut transcript = Keccak256Transcript::new(Vec::new());
-            create_proof::<_, ProverSHPLONK<_>, _, _, _, _>(
-                params,
-                pk,
-                &[circuit],
-                &[&[instances]],
-                &mut rng,
-                &mut transcript,
-            )
-            .unwrap();
-            transcript.finalize()
-        };
-    
-        let result = {
-            let mut transcript = Keccak256Transcript::new(proof.as_slice());
-            verify_proof::<_, VerifierSHPLONK<_>, _, _, SingleStrategy<_>>(
-                params,
-                pk.get_vk(),
-                SingleStrategy::new(params),
-                &[&[instances]],
-                &mut transcript,
-            )
-        };
-        assert!(result.is_ok());
-        proof
-    }
-
-    // env logger
-    let mut log_builder = env_logger::Builder::from_default_env();
-    log_builder.format_timestamp(None);
-    log_builder.filter_level(LevelFilter::Info);
-    log_builder.try_init().unwrap();
+    std_ops::init_logger();
 
     // start circuit
     let degree = 10;
@@ -345,8 +297,8 @@ fn test_pythagoras_solidity_verifier() {
 
     let vk = keygen_vk(&param, &p8s_circuit).unwrap();
     let pk = keygen_pk(&param, vk, &p8s_circuit).unwrap();
-    let generator = SolidityGenerator::new(&param, pk.get_vk(), Bdfg21, 1);     // num_instances: the number of public inputs
-    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+    let generator = SolidityGenerator::new(&param, pk.get_vk(), Bdfg21, &[1]);     // num_instances: the number of public inputs of each instance column
+    let (verifier_solidity, vk_solidity, meta) = generator.render_separately_with_meta().unwrap();
 
     // validate
     let mut evm = Evm::default();
@@ -357,10 +309,15 @@ fn test_pythagoras_solidity_verifier() {
     // generates SNARK proof and runs EVM verifier
     info!("{}", "Starting finalization phase".blue().bold());
     let now = std_ops::Instant::now();
-    let proof = create_proof_checked(&param, &pk, p8s_circuit.clone(), &vec![h], &mut rng);
+    let proof = std_ops::create_proof_checked(&param, &pk, p8s_circuit.clone(), &vec![h], &mut rng);
     info!("{}", "SNARK proof generated successfully!".green().bold());
     std_ops::report_elapsed(now);
-    let calldata = encode_calldata(Some(vk_address.into()), &proof, &vec![h]);
+    let calldata = encode_calldata(Some(vk_address.into()), &proof, &[vec![h]]);
+    // meta describes the layout of proof and calldata without creating a proof
+    assert_eq!(proof.len(), meta.proof_len);
+    assert_eq!(calldata.len(), meta.calldata_len);
+    assert_eq!(meta.num_instances, 1);
+    assert_eq!(meta.num_commitments * 0x40 + meta.num_evals * 0x20, meta.proof_len);
     let (gas_cost, _output) = evm.call(verifier_address, calldata);
     info!("{}", format!("Gas cost: {}", gas_cost).yellow().bold());
 
@@ -370,6 +327,205 @@ fn test_pythagoras_solidity_verifier() {
 
 }
 
+#[test]
+fn test_pythagoras_solidity_verifier_embedded_vk() {
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+    use halo2_proofs::plonk::{keygen_vk, keygen_pk};
+
+    use halo2_solidity_verifier::SolidityGenerator;
+    use halo2_solidity_verifier::BatchOpenScheme::Bdfg21;
+    use halo2_solidity_verifier::Evm;
+    use halo2_solidity_verifier::compile_solidity;
+    use halo2_solidity_verifier::encode_calldata;
+
+    use log::info;
+    use colored::Colorize;
+
+    std_ops::init_logger();
+
+    let degree = 10;
+
+    let side_a = Fr::from(2);
+    let side_b = Fr::from(3);
+    let h = side_a.square() + side_b.square();
+    let p8s_circuit = P8sTestCircuit::new(Value::known(side_a), Value::known(side_b));
+
+    let mut rng = rand::thread_rng();
+    let param = ParamsKZG::<Bn256>::setup(degree, &mut rng);
+
+    let vk = keygen_vk(&param, &p8s_circuit).unwrap();
+    let pk = keygen_pk(&param, vk, &p8s_circuit).unwrap();
+    // verifying key is embedded, so only a single contract needs to be deployed
+    let generator = SolidityGenerator::new(&param, pk.get_vk(), Bdfg21, &[1]);
+    let (verifier_solidity, meta) = generator.render_with_meta().unwrap();
+
+    let mut evm = Evm::default();
+    let verifier_creation_code = compile_solidity(&verifier_solidity);
+    let verifier_address = evm.create(verifier_creation_code);
+    info!("{}", format!("Verifier runtime code size: {}", evm.code_size(verifier_address)).yellow().bold());
+
+    let proof = std_ops::create_proof_checked(&param, &pk, p8s_circuit, &vec![h], &mut rng);
+    // no vk address in calldata
+    let calldata = encode_calldata(None, &proof, &[vec![h]]);
+    assert_eq!(proof.len(), meta.proof_len);
+    assert_eq!(calldata.len(), meta.calldata_len);
+    let (gas_cost, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    info!("{}", format!("Gas cost: {}", gas_cost).yellow().bold());
+
+    // estimation runs the same verifier in an ephemeral evm
+    assert_eq!(generator.estimate_gas(&proof, &[vec![h]]), gas_cost);
+    // runtime bytecode compiled alone is the same as deployed one, within EIP-170 limit
+    assert_eq!(generator.check_bytecode_size(), Ok(evm.code_size(verifier_address)));
+    // static estimation from circuit shape should be within 10% of the measured one
+    let static_gas_cost = generator.estimate_gas_statically();
+    info!("{}", format!("Static gas estimation: {}", static_gas_cost).yellow().bold());
+    assert!(static_gas_cost.abs_diff(gas_cost) * 10 <= gas_cost);
+
+    std_ops::save_solidity(format!("p8s_verifier_embedded_vk.sol"), &verifier_solidity);
+}
+
+#[test]
+fn test_pythagoras_yul_verifier() {
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+    use halo2_proofs::plonk::{keygen_vk, keygen_pk};
+
+    use halo2_solidity_verifier::SolidityGenerator;
+    use halo2_solidity_verifier::BatchOpenScheme::Bdfg21;
+    use halo2_solidity_verifier::Evm;
+    use halo2_solidity_verifier::compile_yul;
+    use halo2_solidity_verifier::encode_calldata;
+
+    use log::info;
+    use colored::Colorize;
+
+    std_ops::init_logger();
+
+    let degree = 10;
+
+    let side_a = Fr::from(2);
+    let side_b = Fr::from(3);
+    let h = side_a.square() + side_b.square();
+    let p8s_circuit = P8sTestCircuit::new(Value::known(side_a), Value::known(side_b));
+
+    let mut rng = rand::thread_rng();
+    let param = ParamsKZG::<Bn256>::setup(degree, &mut rng);
+
+    let vk = keygen_vk(&param, &p8s_circuit).unwrap();
+    let pk = keygen_pk(&param, vk, &p8s_circuit).unwrap();
+    let generator = SolidityGenerator::new(&param, pk.get_vk(), Bdfg21, &[1]);
+    let verifier_yul = generator.render_yul().unwrap();
+
+    let mut evm = Evm::default();
+    let verifier_creation_code = compile_yul(&verifier_yul);
+    let verifier_address = evm.create(verifier_creation_code);
+    info!("{}", format!("Yul verifier runtime code size: {}", evm.code_size(verifier_address)).yellow().bold());
+
+    // Yul verifier accepts the same calldata as the Solidity one
+    let proof = std_ops::create_proof_checked(&param, &pk, p8s_circuit, &vec![h], &mut rng);
+    let calldata = encode_calldata(None, &proof, &[vec![h]]);
+    let (gas_cost, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    info!("{}", format!("Gas cost (Yul): {}", gas_cost).yellow().bold());
+
+    // wrong instance is rejected
+    let calldata = encode_calldata(None, &proof, &[vec![h + Fr::from(1)]]);
+    assert!(evm.try_call(verifier_address, calldata).is_err());
+
+    std_ops::save_solidity(format!("p8s_verifier.yul"), &verifier_yul);
+}
+
+#[test]
+fn test_pythagoras_solidity_verifier_gwc19() {
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+    use halo2_proofs::plonk::{keygen_vk, keygen_pk};
+
+    use halo2_solidity_verifier::SolidityGenerator;
+    use halo2_solidity_verifier::BatchOpenScheme::{Bdfg21, Gwc19};
+    use halo2_solidity_verifier::Evm;
+    use halo2_solidity_verifier::compile_solidity;
+    use halo2_solidity_verifier::encode_calldata;
+
+    use log::info;
+    use colored::Colorize;
+
+    std_ops::init_logger();
+
+    let degree = 10;
+
+    let side_a = Fr::from(2);
+    let side_b = Fr::from(3);
+    let h = side_a.square() + side_b.square();
+    let p8s_circuit = P8sTestCircuit::new(Value::known(side_a), Value::known(side_b));
+
+    let mut rng = rand::thread_rng();
+    let param = ParamsKZG::<Bn256>::setup(degree, &mut rng);
+
+    let vk = keygen_vk(&param, &p8s_circuit).unwrap();
+    let pk = keygen_pk(&param, vk, &p8s_circuit).unwrap();
+    let generator = SolidityGenerator::new(&param, pk.get_vk(), Gwc19, &[1]);
+    let (verifier_solidity, vk_solidity) = generator.render_separately().unwrap();
+
+    let mut evm = Evm::default();
+    let verifier_creation_code = compile_solidity(&verifier_solidity);
+    let verifier_address = evm.create(verifier_creation_code);
+    let vk_creation_code = compile_solidity(&vk_solidity);
+    let vk_address = evm.create(vk_creation_code);
+
+    // proof is created by `ProverGWC` and checked by `VerifierGWC`
+    let proof = std_ops::create_proof_checked_with(Gwc19, &param, &pk, p8s_circuit.clone(), &vec![h], &mut rng);
+    let calldata = encode_calldata(Some(vk_address.into()), &proof, &[vec![h]]);
+    let (gas_cost, output) = evm.call(verifier_address, calldata);
+    assert_eq!(output, [vec![0; 31], vec![1]].concat());
+    info!("{}", format!("Gas cost (GWC19): {}", gas_cost).yellow().bold());
+
+    // GWC19 opening proof has one witness per distinct rotation, instead of 2 for BDFG21
+    let shplonk_proof = std_ops::create_proof_checked_with(Bdfg21, &param, &pk, p8s_circuit, &vec![h], &mut rng);
+    info!("{}", format!("Proof length: {} (GWC19), {} (BDFG21)", proof.len(), shplonk_proof.len()).yellow().bold());
+
+    std_ops::save_solidity(format!("p8s_verifier_gwc19.sol"), &verifier_solidity);
+    std_ops::save_solidity(format!("p8s_vk_gwc19.sol"), &vk_solidity);
+}
+
+#[test]
+fn test_pythagoras_solidity_verifier_from_vk_bytes() {
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
+    use halo2_proofs::plonk::keygen_vk;
+    use halo2_proofs::SerdeFormat;
+
+    use halo2_solidity_verifier::{SolidityGenerator, VerifierParams};
+    use halo2_solidity_verifier::BatchOpenScheme::Bdfg21;
+
+    let degree = 10;
+
+    let p8s_circuit = P8sTestCircuit::<Fr>::default();
+
+    let mut rng = rand::thread_rng();
+    let param = ParamsKZG::<Bn256>::setup(degree, &mut rng);
+    let vk = keygen_vk(&param, &p8s_circuit).unwrap();
+    let generator = SolidityGenerator::new(&param, &vk, Bdfg21, &[1]);
+
+    // contract-generation service only has serialized vk and the circuit shape
+    let vk_bytes = vk.to_bytes(SerdeFormat::RawBytes);
+    let generator_from_vk_bytes = SolidityGenerator::from_vk_bytes::<P8sTestCircuit<Fr>>(
+        VerifierParams::from(&param),
+        &vk_bytes,
+        SerdeFormat::RawBytes,
+        #[cfg(feature = "circuit-params")]
+        (),
+        Bdfg21,
+        &[1],
+    )
+    .unwrap();
+
+    assert_eq!(generator.render().unwrap(), generator_from_vk_bytes.render().unwrap());
+    assert_eq!(generator.render_separately().unwrap(), generator_from_vk_bytes.render_separately().unwrap());
+}
+
 mod std_ops {
     pub(crate) use std::{
         fs::{create_dir_all, File},
@@ -377,6 +533,85 @@ mod std_ops {
     };
     pub(crate) use std::time::Instant;
 
+    use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine};
+    use halo2_proofs::plonk::{create_proof, verify_proof, Circuit, ProvingKey};
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+    use halo2_proofs::transcript::TranscriptWriterBuffer;
+    use halo2_solidity_verifier::{
+        BatchOpenScheme::{self, Bdfg21, Gwc19},
+        Keccak256Transcript,
+    };
+    use log::LevelFilter;
+    use rand::RngCore;
+
+    pub(crate) fn init_logger() {
+        let mut log_builder = env_logger::Builder::from_default_env();
+        log_builder.format_timestamp(None);
+        log_builder.filter_level(LevelFilter::Info);
+        // Multiple tests in the same binary share the global logger.
+        let _ = log_builder.try_init();
+    }
+
+    pub(crate) fn create_proof_checked(
+        params: &ParamsKZG<Bn256>,
+        pk: &ProvingKey<G1Affine>,
+        circuit: impl Circuit<Fr>,
+        instances: &[Fr],
+        rng: impl RngCore,
+    ) -> Vec<u8> {
+        create_proof_checked_with(Bdfg21, params, pk, circuit, instances, rng)
+    }
+
+    pub(crate) fn create_proof_checked_with(
+        scheme: BatchOpenScheme,
+        params: &ParamsKZG<Bn256>,
+        pk: &ProvingKey<G1Affine>,
+        circuit: impl Circuit<Fr>,
+        instances: &[Fr],
+        mut rng: impl RngCore,
+    ) -> Vec<u8> {
+        use halo2_proofs::poly::kzg::{
+            multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        };
+
+        macro_rules! create_proof_checked_inner {
+            ($p:ty, $v:ty) => {{
+                let proof = {
+                    let mut transcript = Keccak256Transcript::new(Vec::new());
+                    create_proof::<_, $p, _, _, _, _>(
+                        params,
+                        pk,
+                        &[circuit],
+                        &[&[instances]],
+                        &mut rng,
+                        &mut transcript,
+                    )
+                    .unwrap();
+                    transcript.finalize()
+                };
+
+                let result = {
+                    let mut transcript = Keccak256Transcript::from_proof(&proof);
+                    verify_proof::<_, $v, _, _, SingleStrategy<_>>(
+                        params,
+                        pk.get_vk(),
+                        SingleStrategy::new(params),
+                        &[&[instances]],
+                        &mut transcript,
+                    )
+                };
+                assert!(result.is_ok());
+                proof
+            }};
+        }
+
+        match scheme {
+            Bdfg21 => create_proof_checked_inner!(ProverSHPLONK<_>, VerifierSHPLONK<_>),
+            Gwc19 => create_proof_checked_inner!(ProverGWC<_>, VerifierGWC<_>),
+        }
+    }
+
     pub(crate) fn save_solidity(name: impl AsRef<str>, solidity: &str) {
         const DIR_GENERATED: &str = "./generated-sc";
     